@@ -0,0 +1,40 @@
+//! Compare point lookups of every `NumericId` using the `BTreeMap` (`numeric_id_to_param`) and
+//! the `HashMap` index (`ParameterDatabase::get`).
+//!
+//! Run with `cargo bench -p grib_tables`.
+
+use std::{hint::black_box, time::Instant};
+
+use grib_tables::ParameterDatabase;
+
+const N_ROUNDS: usize = 1_000;
+
+fn main() -> anyhow::Result<()> {
+    let param_db = ParameterDatabase::new().populate()?;
+    let numeric_ids: Vec<_> = param_db.numeric_id_to_param().keys().copied().collect();
+    let n_lookups = N_ROUNDS * numeric_ids.len();
+
+    let start = Instant::now();
+    for _ in 0..N_ROUNDS {
+        for numeric_id in &numeric_ids {
+            black_box(param_db.numeric_id_to_param().get(black_box(numeric_id)));
+        }
+    }
+    let btree = start.elapsed();
+
+    let start = Instant::now();
+    for _ in 0..N_ROUNDS {
+        for numeric_id in &numeric_ids {
+            black_box(param_db.get(black_box(numeric_id)));
+        }
+    }
+    let hash = start.elapsed();
+
+    for (name, elapsed) in [("BTreeMap", btree), ("HashMap", hash)] {
+        println!(
+            "{name}: {n_lookups} lookups in {elapsed:?} ({:.1} ns per lookup)",
+            elapsed.as_nanos() as f64 / n_lookups as f64
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,108 @@
+use crate::originating_center_name;
+
+/// The GRIB missing value for the originating center.
+const MISSING: u16 = u16::MAX;
+
+/// An originating center (GRIB2 Code Table C-11). Only the most commonly-used centers have their
+/// own variant. All other centers are represented by `Center::Other(code)`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Center {
+    /// US National Centers for Environmental Prediction.
+    Ncep,
+    /// Japan Meteorological Agency.
+    Jma,
+    /// Canadian Meteorological Centre.
+    Cmc,
+    /// UK Met Office.
+    Ukmo,
+    /// Deutscher Wetterdienst.
+    Dwd,
+    /// Météo-France.
+    Meteofrance,
+    /// European Centre for Medium-Range Weather Forecasts.
+    Ecmwf,
+    /// Any other center, identified by its GRIB code.
+    Other(u16),
+    /// The GRIB missing value (65535).
+    Missing,
+}
+
+impl Center {
+    /// The GRIB code of this center. For example, `Center::Ncep.code()` is 7.
+    pub fn code(&self) -> u16 {
+        match *self {
+            Self::Ncep => 7,
+            Self::Jma => 34,
+            Self::Cmc => 54,
+            Self::Ukmo => 74,
+            Self::Dwd => 78,
+            Self::Meteofrance => 85,
+            Self::Ecmwf => 98,
+            Self::Other(code) => code,
+            Self::Missing => MISSING,
+        }
+    }
+
+    /// The name of this center in GDAL's GRIB tables. For example, `Center::Ncep.name()` is
+    /// "US-NCEP". Returns `None` if the center is unknown or missing.
+    pub fn name(&self) -> Option<&'static str> {
+        originating_center_name(self.code())
+    }
+}
+
+impl From<u16> for Center {
+    fn from(code: u16) -> Self {
+        match code {
+            7 => Self::Ncep,
+            34 => Self::Jma,
+            54 => Self::Cmc,
+            74 => Self::Ukmo,
+            78 => Self::Dwd,
+            85 => Self::Meteofrance,
+            98 => Self::Ecmwf,
+            MISSING => Self::Missing,
+            code => Self::Other(code),
+        }
+    }
+}
+
+impl From<Center> for u16 {
+    fn from(center: Center) -> Self {
+        center.code()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_conversions() {
+        for center in [
+            Center::Ncep,
+            Center::Jma,
+            Center::Cmc,
+            Center::Ukmo,
+            Center::Dwd,
+            Center::Meteofrance,
+            Center::Ecmwf,
+            Center::Other(1),
+            Center::Missing,
+        ] {
+            assert_eq!(Center::from(u16::from(center)), center);
+        }
+        assert_eq!(Center::from(7), Center::Ncep);
+        assert_eq!(u16::from(Center::Ecmwf), 98);
+        assert_eq!(Center::from(110), Center::Other(110));
+        assert_eq!(Center::from(u16::MAX), Center::Missing);
+        assert_eq!(u16::from(Center::Missing), u16::MAX);
+    }
+
+    #[test]
+    fn test_name() {
+        assert_eq!(Center::Ncep.name(), Some("US-NCEP"));
+        assert_eq!(Center::Ukmo.name(), Some("UK-Met-Exeter"));
+        assert_eq!(Center::Other(110).name(), Some("Hong-Kong, China"));
+        assert_eq!(Center::Missing.name(), None);
+    }
+}
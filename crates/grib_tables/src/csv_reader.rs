@@ -1,4 +1,7 @@
+pub(crate) mod read_abbrev_aliases;
+pub(crate) mod read_center;
 pub(crate) mod read_local_index;
+pub(crate) mod read_subcenter;
 pub(crate) mod read_table_4_2;
 
 use std::path::PathBuf;
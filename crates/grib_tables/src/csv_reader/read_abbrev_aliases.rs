@@ -0,0 +1,62 @@
+use super::csv_path;
+
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+use anyhow::Context;
+
+#[derive(Debug, serde::Deserialize)]
+struct AbbrevAlias {
+    alias: String,
+    prod: u8,
+    cat: u8,
+    subcat: u8,
+}
+
+fn get_abbrev_aliases() -> HashMap<String, (u8, u8, u8)> {
+    let path = csv_path().join("abbrev_aliases.csv");
+    let mut reader = csv::Reader::from_path(&path)
+        .with_context(|| format!("Failed: csv::Reader::from_path({path:?})"))
+        .unwrap();
+    let mut map = HashMap::new();
+    for row in reader.deserialize() {
+        let record: AbbrevAlias = row
+            .with_context(|| format!("Failed to deserialize row from {path:?}"))
+            .unwrap();
+        let alias = record.alias.to_ascii_uppercase();
+        if map
+            .insert(alias, (record.prod, record.cat, record.subcat))
+            .is_some()
+        {
+            panic!(
+                "{path:?} contains duplicate aliases! alias={}",
+                record.alias
+            );
+        }
+    }
+    map
+}
+
+/// The (product discipline, parameter category, parameter number) of the master table parameter
+/// which `abbrev` is an alias for. For example, ECMWF's `tp` is an alias for `APCP` (0, 1, 8).
+/// Aliases are matched case-insensitively.
+pub(crate) fn abbrev_alias(abbrev: &str) -> Option<(u8, u8, u8)> {
+    static ALIASES: OnceLock<HashMap<String, (u8, u8, u8)>> = OnceLock::new();
+    ALIASES
+        .get_or_init(get_abbrev_aliases)
+        .get(&abbrev.to_ascii_uppercase())
+        .copied()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_abbrev_alias() {
+        assert_eq!(abbrev_alias("tp"), Some((0, 1, 8)));
+        assert_eq!(abbrev_alias("TP"), Some((0, 1, 8)));
+        assert_eq!(abbrev_alias("MSLET"), abbrev_alias("msl"));
+        assert_eq!(abbrev_alias("TMP"), None);
+    }
+}
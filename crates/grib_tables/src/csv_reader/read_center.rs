@@ -0,0 +1,60 @@
+use super::csv_path;
+
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+use anyhow::Context;
+
+#[derive(Debug, serde::Deserialize)]
+struct GdalCenter {
+    code: u16,
+    name: String,
+}
+
+fn get_centers() -> HashMap<u16, String> {
+    let path = csv_path().join("grib2_center.csv");
+    let mut reader = csv::Reader::from_path(&path)
+        .with_context(|| format!("Failed: csv::Reader::from_path({path:?})"))
+        .unwrap();
+    let mut map = HashMap::new();
+    for row in reader.deserialize() {
+        let record: GdalCenter = row
+            .with_context(|| format!("Failed to deserialize row from {path:?}"))
+            .unwrap();
+        match map.insert(record.code, record.name) {
+            None => (),
+            Some(old_name) => panic!(
+                "{path:?} contains duplicate codes! code={}, old name={old_name}",
+                record.code
+            ),
+        }
+    }
+    map
+}
+
+/// The name of the originating center `center`. For example, `originating_center_name(7)` is
+/// "US-NCEP". Returns `None` if the center is unknown, or is the GRIB missing value (65535).
+pub fn originating_center_name(center: u16) -> Option<&'static str> {
+    static CENTERS: OnceLock<HashMap<u16, String>> = OnceLock::new();
+    if center == u16::MAX {
+        return None;
+    }
+    CENTERS
+        .get_or_init(get_centers)
+        .get(&center)
+        .map(String::as_str)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_originating_center_name() {
+        assert_eq!(originating_center_name(7), Some("US-NCEP"));
+        assert_eq!(originating_center_name(98), Some("ECMWF"));
+        assert_eq!(originating_center_name(110), Some("Hong-Kong, China"));
+        assert_eq!(originating_center_name(u16::MAX), None);
+        assert_eq!(originating_center_name(1000), None);
+    }
+}
@@ -2,6 +2,7 @@ use super::csv_path;
 
 use std::collections::HashMap;
 use std::num::IntErrorKind;
+use std::path::Path;
 
 use anyhow::Context;
 use serde::Deserialize;
@@ -39,15 +40,17 @@ where
 
 /// The values of the HashMap are the (center_code, subcenter_code).
 pub(crate) fn get_local_index() -> HashMap<String, (u16, u8)> {
-    let path = csv_path().join("grib2_table_4_2_local_index.csv");
-    let mut reader = csv::Reader::from_path(&path)
-        .with_context(|| format!("Failed: csv::Reader::from_path({path:?})"))
-        .unwrap();
+    read_local_index(&csv_path().join("grib2_table_4_2_local_index.csv")).unwrap()
+}
+
+/// Read a `grib2_table_4_2_local_index.csv` file. See `get_local_index`.
+pub(crate) fn read_local_index(path: &Path) -> anyhow::Result<HashMap<String, (u16, u8)>> {
+    let mut reader = csv::Reader::from_path(path)
+        .with_context(|| format!("Failed: csv::Reader::from_path({path:?})"))?;
     let mut map = HashMap::new();
     for row in reader.deserialize() {
-        let record: GdalLocalIndex = row
-            .with_context(|| format!("Failed to deserialize row from {path:?}"))
-            .unwrap();
+        let record: GdalLocalIndex =
+            row.with_context(|| format!("Failed to deserialize row from {path:?}"))?;
 
         // Skip duplicate:
         if map.contains_key(&record.filename)
@@ -59,15 +62,15 @@ pub(crate) fn get_local_index() -> HashMap<String, (u16, u8)> {
         }
 
         let center_and_subcenter_codes = (record.center_code, record.subcenter_code);
-        match map.insert(record.filename, center_and_subcenter_codes) {
-            None => (),
-            Some(old_value) => panic!(
+        if let Some(old_value) = map.insert(record.filename, center_and_subcenter_codes) {
+            return Err(anyhow::format_err!(
                 "{path:?} contains duplicate filenames! Old center_code={}, old subcenter_code={}",
-                old_value.0, old_value.1
-            ),
+                old_value.0,
+                old_value.1
+            ));
         }
     }
-    map
+    Ok(map)
 }
 
 #[cfg(test)]
@@ -0,0 +1,88 @@
+use super::csv_path;
+
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+use anyhow::Context;
+
+#[derive(Debug, serde::Deserialize)]
+struct GdalSubcenter {
+    center_code: u16,
+    subcenter_code: u8,
+    name: String,
+}
+
+/// The keys of the HashMap are the (center_code, subcenter_code).
+fn get_subcenters() -> HashMap<(u16, u8), String> {
+    let path = csv_path().join("grib2_subcenter.csv");
+    let mut reader = csv::Reader::from_path(&path)
+        .with_context(|| format!("Failed: csv::Reader::from_path({path:?})"))
+        .unwrap();
+    let mut map = HashMap::new();
+    for row in reader.deserialize() {
+        let record: GdalSubcenter = row
+            .with_context(|| format!("Failed to deserialize row from {path:?}"))
+            .unwrap();
+
+        // Skip duplicate. GDAL's CSV gives two names for subcenter 10 of center 254 (EUMETSAT).
+        // We keep the first name.
+        if record.center_code == 254
+            && record.subcenter_code == 10
+            && record.name == "Maspalomas (Spain)"
+        {
+            continue;
+        }
+
+        let codes = (record.center_code, record.subcenter_code);
+        match map.insert(codes, record.name) {
+            None => (),
+            Some(old_name) => panic!(
+                "{path:?} contains duplicate codes! center_code={}, subcenter_code={}, old name={old_name}",
+                codes.0, codes.1
+            ),
+        }
+    }
+    map
+}
+
+/// The name of the subcenter `subcenter` of the originating center `center`. For example,
+/// `subcenter_name(7, 4)` is "Environmental Modeling Center".
+///
+/// Subcenter 0 means that there is no subcenter, unless GDAL's CSV names subcenter 0 for this
+/// center. Returns `None` if the subcenter is unknown, or is the GRIB missing value (255).
+pub fn subcenter_name(center: u16, subcenter: u8) -> Option<&'static str> {
+    static SUBCENTERS: OnceLock<HashMap<(u16, u8), String>> = OnceLock::new();
+    if subcenter == u8::MAX {
+        return None;
+    }
+    match SUBCENTERS
+        .get_or_init(get_subcenters)
+        .get(&(center, subcenter))
+    {
+        Some(name) => Some(name),
+        None if subcenter == 0 => Some("No sub-center"),
+        None => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_subcenter_name() {
+        assert_eq!(subcenter_name(7, 4), Some("Environmental Modeling Center"));
+        assert_eq!(
+            subcenter_name(8, 0),
+            Some("National Digital Forecast Database")
+        );
+        assert_eq!(
+            subcenter_name(98, 231),
+            Some("CNRM, Meteo France Climate Centre (HIRETYCS)")
+        );
+        assert_eq!(subcenter_name(254, 10), Some("Tromso (Norway)"));
+        assert_eq!(subcenter_name(7, 0), Some("No sub-center"));
+        assert_eq!(subcenter_name(7, u8::MAX), None);
+        assert_eq!(subcenter_name(7, 200), None);
+    }
+}
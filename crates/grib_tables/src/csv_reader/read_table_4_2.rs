@@ -27,11 +27,13 @@ pub(crate) struct GdalTable4_2Record {
     pub(crate) unit: String,
 }
 
-impl From<GdalTable4_2Record> for (NumericIdBuilder, Parameter) {
-    fn from(record: GdalTable4_2Record) -> Self {
-        let numeric_id = (&record).into();
+impl TryFrom<GdalTable4_2Record> for (NumericIdBuilder, Parameter) {
+    type Error = anyhow::Error;
+
+    fn try_from(record: GdalTable4_2Record) -> anyhow::Result<Self> {
+        let numeric_id = (&record).try_into()?;
         let parameter = record.into();
-        (numeric_id, parameter)
+        Ok((numeric_id, parameter))
     }
 }
 
@@ -45,43 +47,81 @@ impl From<GdalTable4_2Record> for Parameter {
     }
 }
 
-impl From<&GdalTable4_2Record> for NumericIdBuilder {
-    fn from(record: &GdalTable4_2Record) -> Self {
-        NumericIdBuilder::new(
-            record.prod.unwrap(),
-            record.cat.unwrap(),
-            record.subcat.try_into().expect("subcat must be a u8"),
-        )
+/// Returns an error if `prod` or `cat` is missing, or if `subcat` isn't a valid parameter number
+/// (e.g. the negative `subcat` of the comment rows at the top of each GDAL CSV).
+impl TryFrom<&GdalTable4_2Record> for NumericIdBuilder {
+    type Error = anyhow::Error;
+
+    fn try_from(record: &GdalTable4_2Record) -> anyhow::Result<Self> {
+        let missing = |column| anyhow::format_err!("`{column}` is missing from {record:?}");
+        let subcat = u8::try_from(record.subcat)
+            .with_context(|| format!("subcat must be a u8 in {record:?}"))?;
+        Ok(NumericIdBuilder::new(
+            record.prod.ok_or_else(|| missing("prod"))?,
+            record.cat.ok_or_else(|| missing("cat"))?,
+            subcat,
+        ))
     }
 }
 
+/// Iterate over the parameters in the GDAL CSV at `path`. Yields an error for each row which can't
+/// be deserialized.
 pub(crate) fn gdal_table_4_2_iterator(
     path: &PathBuf,
-) -> anyhow::Result<impl Iterator<Item = GdalTable4_2Record>> {
+) -> anyhow::Result<impl Iterator<Item = anyhow::Result<GdalTable4_2Record>>> {
     let reader = csv::Reader::from_path(path)
         .with_context(|| format!("Error when calling csv::Reader::from_path({path:?})"))?;
-    let deser_error_msg = format!("deserialize result into GdalTable4_2Record for path {path:?}",);
+    let path = path.clone();
     let iter = reader
         .into_deserialize()
-        .map(move |row| -> GdalTable4_2Record { row.expect(&deser_error_msg) })
-        .filter(|record| {
-            let lc_name = record.name.to_lowercase();
-            record.subcat >= 0 && !lc_name.contains("reserved") && !lc_name.contains("missing")
-        });
+        .enumerate()
+        .map(move |(i, row)| -> anyhow::Result<GdalTable4_2Record> {
+            row.with_context(|| {
+                format!("Failed to deserialize row {i} into GdalTable4_2Record for path {path:?}")
+            })
+        })
+        .filter(|record| record.as_ref().map_or(true, is_parameter));
     Ok(iter)
 }
 
+/// Like `gdal_table_4_2_iterator`, but reads from any `reader` (e.g. a user-supplied table), and
+/// returns an error for the first row which can't be deserialized.
+pub(crate) fn read_gdal_table_4_2(
+    reader: impl std::io::Read,
+) -> anyhow::Result<Vec<GdalTable4_2Record>> {
+    let mut records = Vec::new();
+    for (i, row) in csv::Reader::from_reader(reader)
+        .into_deserialize()
+        .enumerate()
+    {
+        let record: GdalTable4_2Record =
+            row.with_context(|| format!("Failed to deserialize row {i} into GdalTable4_2Record"))?;
+        if is_parameter(&record) {
+            records.push(record);
+        }
+    }
+    Ok(records)
+}
+
+/// Returns false for the comment rows at the top of each GDAL CSV, and for "reserved" and
+/// "missing" rows.
+fn is_parameter(record: &GdalTable4_2Record) -> bool {
+    let lc_name = record.name.to_lowercase();
+    record.subcat >= 0 && !lc_name.contains("reserved") && !lc_name.contains("missing")
+}
+
 pub(crate) fn gdal_master_table_4_2_iterator(
     product_discipline: u8,
     parameter_category: u8,
-) -> anyhow::Result<impl Iterator<Item = (NumericIdBuilder, Parameter)>> {
+) -> anyhow::Result<impl Iterator<Item = anyhow::Result<(NumericIdBuilder, Parameter)>>> {
     let filename = format!("grib2_table_4_2_{product_discipline}_{parameter_category}.csv");
     let path = csv_path().join(filename);
     let iter = gdal_table_4_2_iterator(&path)?;
-    Ok(iter.map(move |mut record| {
+    Ok(iter.map(move |record| {
+        let mut record = record?;
         record.prod = Some(product_discipline);
         record.cat = Some(parameter_category);
-        record.into()
+        record.try_into()
     }))
 }
 
@@ -98,7 +138,7 @@ mod test {
     #[test]
     fn test_read_gdal_table_4_2_0_0() -> anyhow::Result<()> {
         let iterator = gdal_master_table_4_2_iterator(0, 0)?;
-        let vec: Vec<_> = iterator.collect();
+        let vec = iterator.collect::<anyhow::Result<Vec<_>>>()?;
         assert_eq!(vec.len(), 33);
 
         // Check first row of data:
@@ -127,7 +167,7 @@ mod test {
     #[test]
     fn test_read_gdal_table_4_2_0_191() -> anyhow::Result<()> {
         let iterator = gdal_master_table_4_2_iterator(0, 191)?;
-        let vec: Vec<_> = iterator.collect();
+        let vec = iterator.collect::<anyhow::Result<Vec<_>>>()?;
         assert_eq!(vec.len(), 4);
 
         // Check first row of data:
@@ -155,7 +195,7 @@ mod test {
     #[test]
     fn test_read_gdal_table_4_2_10_0() -> anyhow::Result<()> {
         let iterator = gdal_master_table_4_2_iterator(10, 0)?;
-        let vec: Vec<_> = iterator.collect();
+        let vec = iterator.collect::<anyhow::Result<Vec<_>>>()?;
         assert_eq!(vec.len(), 74);
 
         // Check first row of data:
@@ -177,9 +217,9 @@ mod test {
     fn test_read_gdal_table_4_2_local_NCEP() -> anyhow::Result<()> {
         let path = csv_path().join("grib2_table_4_2_local_NCEP.csv");
         let iterator = gdal_table_4_2_iterator(&path)?;
-        let vec: Vec<_> = iterator
-            .map(|record| -> (NumericIdBuilder, Parameter) { record.into() })
-            .collect();
+        let vec = iterator
+            .map(|record| -> anyhow::Result<(NumericIdBuilder, Parameter)> { record?.try_into() })
+            .collect::<anyhow::Result<Vec<_>>>()?;
         assert_eq!(vec.len(), 391);
 
         // Check first row of data:
@@ -200,6 +240,58 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    fn test_read_gdal_table_4_2() -> anyhow::Result<()> {
+        let csv = "subcat,short_name,name,unit,unit_conv\n\
+                   -1,\"#\",\"#\",\"#\",\"#\"\n\
+                   0,\"FOO\",\"Foo\",\"K\",UC_NONE\n\
+                   1,\"\",\"Reserved\",\"-\",UC_NONE\n";
+        let records = read_gdal_table_4_2(csv.as_bytes())?;
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].short_name, "FOO");
+        assert!(read_gdal_table_4_2("subcat,short_name\nfoo,FOO\n".as_bytes()).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_numeric_id_builder_try_from_record() {
+        let record = |prod, subcat| GdalTable4_2Record {
+            prod,
+            cat: Some(1),
+            subcat,
+            short_name: String::from("FOO"),
+            name: String::from("Foo"),
+            unit: String::from("K"),
+        };
+        assert_eq!(
+            NumericIdBuilder::try_from(&record(Some(0), 2)).unwrap(),
+            NumericIdBuilder::new(0, 1, 2)
+        );
+        // A comment row:
+        assert!(NumericIdBuilder::try_from(&record(Some(0), -1)).is_err());
+        assert!(NumericIdBuilder::try_from(&record(Some(0), 256)).is_err());
+        assert!(NumericIdBuilder::try_from(&record(None, 2)).is_err());
+        assert!(<(NumericIdBuilder, Parameter)>::try_from(record(Some(0), -4)).is_err());
+    }
+
+    #[test]
+    fn test_gdal_table_4_2_iterator_malformed_row() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("grib2_table_4_2_0_0.csv");
+        std::fs::write(
+            &path,
+            "subcat,short_name,name,unit,unit_conv\n\
+             0,\"FOO\",\"Foo\",\"K\",UC_NONE\n\
+             bar,\"BAR\",\"Bar\",\"K\",UC_NONE\n",
+        )?;
+        let records: Vec<_> = gdal_table_4_2_iterator(&path)?.collect();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].as_ref().unwrap().short_name, "FOO");
+        let err = records[1].as_ref().unwrap_err();
+        assert!(format!("{err:#}").contains("row 1"), "{err:#}");
+        Ok(())
+    }
+
     #[test]
     fn test_gdal_table_4_2_iterator_bad_path() {
         let result = gdal_table_4_2_iterator(&PathBuf::from("foo"));
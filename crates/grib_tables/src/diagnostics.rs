@@ -0,0 +1,86 @@
+//! Print how we resolve a parameter abbreviation, so that our decoding can be diffed against
+//! other GRIB readers (e.g. `wgrib2 -V` or `gdalinfo`). Comparisons like this are how we found
+//! abbreviations which are associated with multiple parameters. See
+//! <https://github.com/JackKelly/hypergrib/issues/20>.
+
+use std::fmt::Write;
+
+use crate::{Abbrev, ParameterDatabase};
+
+impl ParameterDatabase {
+    /// One line per parameter associated with `abbrev`, in ascending order of `NumericId`. For
+    /// example:
+    ///
+    /// `TMP: discipline=0 category=0 number=0 name="Temperature" unit="K" table=master v30`
+    ///
+    /// Parameters from local tables end with `table=local center=<center> subcenter=<subcenter>`
+    /// instead. Returns `"<abbrev>: not found"` if `abbrev` isn't in the database.
+    pub fn describe_abbrev_for_comparison(&self, abbrev: &str) -> String {
+        let params = self.abbrev_to_parameter(&Abbrev::from(abbrev));
+        if params.is_empty() {
+            return format!("{abbrev}: not found\n");
+        }
+        let mut s = String::new();
+        for (numeric_id, param) in params {
+            write!(
+                s,
+                "{abbrev}: discipline={} category={} number={} name={:?} unit={:?}",
+                numeric_id.product_discipline(),
+                numeric_id.parameter_category(),
+                numeric_id.parameter_number(),
+                param.name(),
+                param.unit(),
+            )
+            .expect("write");
+            if numeric_id.originating_center() == u16::MAX {
+                writeln!(s, " table=master v{}", numeric_id.master_table_version())
+            } else {
+                writeln!(
+                    s,
+                    " table=local center={} subcenter={}",
+                    numeric_id.originating_center(),
+                    numeric_id.subcenter()
+                )
+            }
+            .expect("writeln");
+        }
+        s
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_describe_abbrev_for_comparison() {
+        let param_db = ParameterDatabase::shared();
+        let tmp = param_db.describe_abbrev_for_comparison("TMP");
+        assert_eq!(tmp.lines().count(), 1);
+        assert!(
+            tmp.starts_with(
+                "TMP: discipline=0 category=0 number=0 name=\"Temperature\" unit=\"K\""
+            ),
+            "{tmp}"
+        );
+        assert!(tmp.contains("table=master"), "{tmp}");
+
+        // An abbreviation associated with multiple parameters has one line per parameter:
+        let abbrev = param_db
+            .abbrevs()
+            .find(|abbrev| param_db.abbrev_to_parameter(abbrev).len() > 1)
+            .expect("Some abbreviations are associated with multiple parameters");
+        assert_eq!(
+            param_db
+                .describe_abbrev_for_comparison(&abbrev.0)
+                .lines()
+                .count(),
+            param_db.abbrev_to_parameter(abbrev).len()
+        );
+
+        assert_eq!(
+            param_db.describe_abbrev_for_comparison("NOT_AN_ABBREV"),
+            "NOT_AN_ABBREV: not found\n"
+        );
+    }
+}
@@ -1,10 +1,23 @@
 #![doc = include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/README.md"))]
 
+mod center;
 mod csv_reader;
+#[cfg(feature = "diagnostics")]
+mod diagnostics;
 mod parameter;
+mod template;
 
-pub use parameter::database::ParameterDatabase;
+pub use center::Center;
+pub use csv_reader::read_center::originating_center_name;
+pub use csv_reader::read_subcenter::subcenter_name;
+pub use parameter::database::{DuplicatePolicy, ParameterDatabase, Table4_2};
+pub use parameter::decode::{DecodePolicy, DecodedAbbrev};
 pub use parameter::numeric_id::{NumericId, NumericIdBuilder};
+pub use parameter::unit::normalize_unit;
 pub use parameter::{Abbrev, Parameter};
+pub use template::{
+    AnalysisOrForecastAtHorizontalLevel, Ensemble, FixedSurface,
+    IndividualEnsembleForecastAtHorizontalLevel, ProductDefinition, ProductTemplateCommon,
+};
 
 pub const MASTER_TABLE_VERSION: u8 = 30; // from grib2_table_versions.csv
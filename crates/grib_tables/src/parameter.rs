@@ -1,5 +1,7 @@
 pub(crate) mod database;
+pub(crate) mod decode;
 pub(crate) mod numeric_id;
+pub(crate) mod unit;
 
 /// The human-readable description of a GRIB parameter.
 #[derive(Clone, Debug, derive_more::Display, PartialEq, Eq)]
@@ -36,6 +38,11 @@ impl Parameter {
     pub fn unit(&self) -> &str {
         &self.unit
     }
+
+    /// The unit, with its spelling canonicalized by `normalize_unit`.
+    pub fn normalized_unit(&self) -> String {
+        unit::normalize_unit(&self.unit)
+    }
 }
 
 /// The abbreviation (AKA "short_name") for a GRIB parameter.
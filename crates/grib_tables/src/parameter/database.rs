@@ -1,11 +1,13 @@
 use std::collections::BTreeSet;
 use std::fmt::Write;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
 
 use std::collections::HashMap;
 
-use crate::{csv_reader::{read_local_index::get_local_index, read_table_4_2::{gdal_master_table_4_2_iterator, gdal_table_4_2_iterator, list_gdal_table_4_2_csv_files}}, MASTER_TABLE_VERSION};
+use crate::{originating_center_name, subcenter_name, csv_reader::{read_local_index::{get_local_index, read_local_index}, read_table_4_2::{gdal_master_table_4_2_iterator, gdal_table_4_2_iterator, list_gdal_table_4_2_csv_files, read_gdal_table_4_2}}, MASTER_TABLE_VERSION};
 
-use super::{numeric_id::NumericId, Abbrev, Parameter};
+use super::{numeric_id::{NumericId, NumericIdBuilder}, Abbrev, Parameter};
 
 use std::collections::BTreeMap;
 use anyhow::Context;
@@ -14,11 +16,16 @@ use anyhow::Context;
 ///
 /// ## Example:
 /// ```
-/// use grib_tables::ParameterDatabase;
+/// use grib_tables::{NumericIdBuilder, ParameterDatabase};
 /// # fn main() -> anyhow::Result<()> {
 /// let param_db = ParameterDatabase::new().populate()?;
 /// assert_eq!(param_db.num_numeric_ids(), 1669);
 /// assert_eq!(param_db.num_abbrevs(), 1168);
+///
+/// // Look up a parameter using the numeric fields from a GRIB message:
+/// let numeric_id = NumericIdBuilder::new(0, 0, 0).set_master_table_version(30).build();
+/// let param = &param_db.numeric_id_to_param()[&numeric_id];
+/// assert_eq!(param.abbrev().to_string(), "TMP");
 /// # Ok(())
 /// # }
 /// ```
@@ -27,64 +34,248 @@ pub struct ParameterDatabase {
     /// using `BTreeMap.range`.
     numeric_id_to_param: BTreeMap<NumericId, Parameter>,
 
+    /// The same parameters as `numeric_id_to_param`, for O(1) point lookups (see `get`).
+    numeric_id_index: HashMap<NumericId, Parameter>,
+
     /// The value of this `HashMap` is a `BTreeSet` (instead of just a `NumericId`) because 
     /// some abbreviations are associated with multiple parameters.
     abbrev_to_numeric_id: HashMap<Abbrev, BTreeSet<NumericId>>,
+
+    /// The parameters which weren't inserted because of `DuplicatePolicy::SkipDuplicates`.
+    skipped_duplicates: Vec<(NumericId, Parameter)>,
 }
 
 impl ParameterDatabase {
     pub fn new() -> Self {
         Self {
             numeric_id_to_param: BTreeMap::new(),
+            numeric_id_index: HashMap::new(),
             abbrev_to_numeric_id: HashMap::new(),
+            skipped_duplicates: Vec::new(),
         }
     }
 
-    pub fn populate(mut self) -> anyhow::Result<Self> {
+    /// A database which is populated (using `populate`) the first time `shared` is called, and
+    /// then shared by every caller, on every thread. Use this instead of `populate` to avoid
+    /// re-reading the CSVs every time a database is needed.
+    ///
+    /// Panics if the bundled CSVs can't be read.
+    pub fn shared() -> &'static Self {
+        static SHARED: OnceLock<ParameterDatabase> = OnceLock::new();
+        SHARED.get_or_init(|| {
+            Self::new()
+                .populate()
+                .expect("Failed to populate the ParameterDatabase from the bundled CSVs")
+        })
+    }
+
+    /// Populate the database from the GDAL CSVs, using `MASTER_TABLE_VERSION`. Parameters whose
+    /// `NumericId` has already been inserted are skipped (see `skipped_duplicates`). See
+    /// `populate_with_options`.
+    pub fn populate(self) -> anyhow::Result<Self> {
+        self.populate_with_options(DuplicatePolicy::default(), MASTER_TABLE_VERSION)
+    }
+
+    /// Populate the database from the GDAL CSVs. `duplicate_policy` controls what happens when a
+    /// `NumericId` appears more than once in the CSVs.
+    pub fn populate_with_duplicate_policy(
+        self,
+        duplicate_policy: DuplicatePolicy,
+    ) -> anyhow::Result<Self> {
+        self.populate_with_options(duplicate_policy, MASTER_TABLE_VERSION)
+    }
+
+    /// Populate the database from the GDAL CSVs, setting the master table version of every
+    /// `NumericId` to `master_table_version`. Use this to decode GRIB messages which were
+    /// written with an older master table version than `MASTER_TABLE_VERSION`.
+    ///
+    /// Note that the GDAL CSVs only describe the latest master table, so the parameters
+    /// themselves are the same whichever `master_table_version` is used.
+    pub fn populate_with_master_table_version(
+        self,
+        master_table_version: u8,
+    ) -> anyhow::Result<Self> {
+        self.populate_with_options(DuplicatePolicy::default(), master_table_version)
+    }
+
+    /// Populate the database from the GDAL CSVs. See `populate_with_duplicate_policy` and
+    /// `populate_with_master_table_version`.
+    pub fn populate_with_options(
+        mut self,
+        duplicate_policy: DuplicatePolicy,
+        master_table_version: u8,
+    ) -> anyhow::Result<Self> {
         let local_index = get_local_index();
-        let re_master_table =
-            regex::Regex::new(r"^grib2_table_4_2_(?<discipline>\d{1,2})_(?<category>\d{1,3}).csv$")
-                .unwrap();
-        let re_local_table = regex::Regex::new(r"^grib2_table_4_2_local_[A-Z][A-Za-z]+.csv$").unwrap();
         for path in list_gdal_table_4_2_csv_files()? {
             let path = path?;
-            let file_name = path
+            match table_4_2_from_path(&path, &local_index)? {
+                None => continue,
+                Some(Table4_2::Master {
+                    product_discipline,
+                    parameter_category,
+                }) => {
+                    for record in gdal_master_table_4_2_iterator(product_discipline, parameter_category)? {
+                        let (mut numeric_id_builder, parameter) = record
+                            .with_context(|| format!("Invalid row in master table 4.2 path={path:?}"))?;
+                        numeric_id_builder.set_master_table_version(master_table_version);
+                        let numeric_id = numeric_id_builder.build();
+                        self.insert_with_duplicate_policy(numeric_id, parameter, duplicate_policy).with_context(|| 
+                            format!("Error when inserting into parameter database. Master table 4.2 path={path:?}")
+                        )?;
+                    }
+                }
+                Some(Table4_2::Local {
+                    originating_center,
+                    subcenter,
+                }) => {
+                    self.insert_local_table(
+                        &path,
+                        originating_center,
+                        subcenter,
+                        master_table_version,
+                        duplicate_policy,
+                    )?;
+                }
+            }
+        }
+        Ok(self)
+    }
+
+    /// Populate the database from a directory of CSVs which have the same format and file names
+    /// as the GDAL CSVs: `grib2_table_4_2_{discipline}_{category}.csv` for master tables, and
+    /// `grib2_table_4_2_local_{name}.csv` for local tables. If the directory contains any local
+    /// tables then it must also contain a `grib2_table_4_2_local_index.csv` which gives the
+    /// originating center and subcenter of each local table. Files whose names don't start with
+    /// `grib2_table_4_2_` are ignored.
+    ///
+    /// Use this to load center-specific tables, or tables which are newer than the bundled GDAL
+    /// CSVs. To use the bundled CSVs as well, call `populate` first. Parameters whose `NumericId`
+    /// has already been inserted are skipped (see `skipped_duplicates`). The master table version
+    /// of every `NumericId` is `MASTER_TABLE_VERSION`. See `populate_from_dir_with_options`.
+    pub fn populate_from_dir(self, dir: impl AsRef<Path>) -> anyhow::Result<Self> {
+        self.populate_from_dir_with_options(DuplicatePolicy::default(), MASTER_TABLE_VERSION, dir)
+    }
+
+    /// Like `populate_from_dir`, but with the same options as `populate_with_options`.
+    pub fn populate_from_dir_with_options(
+        self,
+        duplicate_policy: DuplicatePolicy,
+        master_table_version: u8,
+        dir: impl AsRef<Path>,
+    ) -> anyhow::Result<Self> {
+        let dir = dir.as_ref();
+        let local_index_path = dir.join("grib2_table_4_2_local_index.csv");
+        let local_index = if local_index_path.exists() {
+            read_local_index(&local_index_path)?
+        } else {
+            HashMap::new()
+        };
+        let mut paths = std::fs::read_dir(dir)
+            .with_context(|| format!("Failed to read directory {dir:?}"))?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<Result<Vec<_>, _>>()?;
+        paths.sort();
+        let mut readers = Vec::new();
+        for path in paths {
+            let is_table_4_2 = path
                 .file_name()
-                .with_context(|| format!("Failed to get file_name from path {path:?}"))?
-                .to_str()
-                .with_context(|| format!("Failed to convert file_stem to &str for path {path:?}"))?;
-            if file_name == "grib2_table_4_2_local_index.csv" {
+                .and_then(|file_name| file_name.to_str())
+                .is_some_and(|file_name| file_name.starts_with("grib2_table_4_2_"));
+            if !is_table_4_2 || path.extension() != Some("csv".as_ref()) {
                 continue;
-            } else if let Some(captures) = re_master_table.captures(file_name) {
-                let discipline = (&captures["discipline"]).parse().expect("parse discipline");
-                let category = (&captures["category"]).parse().expect("parse category");
-                for record in gdal_master_table_4_2_iterator(discipline, category)? {
-                    let (mut numeric_id_builder, parameter) = record;
-                    numeric_id_builder.set_master_table_version(MASTER_TABLE_VERSION);
-                    let numeric_id = numeric_id_builder.build();
-                    self.insert(numeric_id, parameter).with_context(|| 
-                        format!("Error when inserting into parameter database. Master table 4.2 path={path:?}")
-                    )?;
+            }
+            if let Some(table) = table_4_2_from_path(&path, &local_index)? {
+                let file = std::fs::File::open(&path)
+                    .with_context(|| format!("Failed to open {path:?}"))?;
+                readers.push((table, file));
+            }
+        }
+        self.populate_from_readers_with_options(duplicate_policy, master_table_version, readers)
+    }
+
+    /// Populate the database from CSVs which have the same format as the GDAL CSVs. Each item of
+    /// `readers` is a CSV, and the `Table4_2` which that CSV describes. Parameters whose
+    /// `NumericId` has already been inserted are skipped (see `skipped_duplicates`). The master
+    /// table version of every `NumericId` is `MASTER_TABLE_VERSION`. See
+    /// `populate_from_readers_with_options`.
+    pub fn populate_from_readers<R: std::io::Read>(
+        self,
+        readers: impl IntoIterator<Item = (Table4_2, R)>,
+    ) -> anyhow::Result<Self> {
+        self.populate_from_readers_with_options(
+            DuplicatePolicy::default(),
+            MASTER_TABLE_VERSION,
+            readers,
+        )
+    }
+
+    /// Like `populate_from_readers`, but with the same options as `populate_with_options`.
+    pub fn populate_from_readers_with_options<R: std::io::Read>(
+        mut self,
+        duplicate_policy: DuplicatePolicy,
+        master_table_version: u8,
+        readers: impl IntoIterator<Item = (Table4_2, R)>,
+    ) -> anyhow::Result<Self> {
+        for (i, (table, reader)) in readers.into_iter().enumerate() {
+            let records = read_gdal_table_4_2(reader)
+                .with_context(|| format!("Failed to read CSV {i} ({table:?})"))?;
+            for mut record in records {
+                if let Table4_2::Master {
+                    product_discipline,
+                    parameter_category,
+                } = table
+                {
+                    record.prod = Some(product_discipline);
+                    record.cat = Some(parameter_category);
                 }
-            } else if re_local_table.is_match(file_name) {
-                for record in gdal_table_4_2_iterator(&path)? {
-                    let (mut numeric_id_builder, parameter) = record.into();
-                    numeric_id_builder.set_master_table_version(MASTER_TABLE_VERSION);
-                    let (originating_center, subcenter) = local_index[file_name];
+                let (mut numeric_id_builder, parameter): (NumericIdBuilder, Parameter) = record
+                    .try_into()
+                    .with_context(|| format!("Invalid row in CSV {i} ({table:?})"))?;
+                numeric_id_builder.set_master_table_version(master_table_version);
+                if let Table4_2::Local {
+                    originating_center,
+                    subcenter,
+                } = table
+                {
                     numeric_id_builder.set_originating_center(originating_center);
                     numeric_id_builder.set_subcenter(subcenter);
-                    let numeric_id = numeric_id_builder.build();
-                    self.insert(numeric_id, parameter).with_context(||
-                        format!("Error when inserting into parameter database. Local table 4.2 path={path:?}")
-                    )?;
                 }
-            } else {
-                return Err(anyhow::format_err!("Failed to interpret CSV path {path:?}!"));
+                self.insert_with_duplicate_policy(
+                    numeric_id_builder.build(),
+                    parameter,
+                    duplicate_policy,
+                )
+                .with_context(|| {
+                    format!("Error when inserting into parameter database. CSV {i} ({table:?})")
+                })?;
             }
         }
         Ok(self)
     }
 
+    fn insert_local_table(
+        &mut self,
+        path: &PathBuf,
+        originating_center: u16,
+        subcenter: u8,
+        master_table_version: u8,
+        duplicate_policy: DuplicatePolicy,
+    ) -> anyhow::Result<()> {
+        for record in gdal_table_4_2_iterator(path)? {
+            let (mut numeric_id_builder, parameter): (NumericIdBuilder, Parameter) = record
+                .and_then(TryInto::try_into)
+                .with_context(|| format!("Invalid row in local table 4.2 path={path:?}"))?;
+            numeric_id_builder.set_master_table_version(master_table_version);
+            numeric_id_builder.set_originating_center(originating_center);
+            numeric_id_builder.set_subcenter(subcenter);
+            let numeric_id = numeric_id_builder.build();
+            self.insert_with_duplicate_policy(numeric_id, parameter, duplicate_policy).with_context(||
+                format!("Error when inserting into parameter database. Local table 4.2 path={path:?}")
+            )?;
+        }
+        Ok(())
+    }
+
     /// Returns a `Vec` because some abbreviations are associated with multiple parameters.
     /// See <https://github.com/JackKelly/hypergrib/issues/20>
     pub fn abbrev_to_parameter(&self, abbrev: &Abbrev) -> Vec<(&NumericId, &Parameter)> {
@@ -100,6 +291,91 @@ impl ParameterDatabase {
         }
     }
 
+    /// Decode a parameter abbreviation from a `.idx` file (e.g. "TMP"), using the originating
+    /// center and subcenter of the GRIB message to disambiguate.
+    ///
+    /// Parameters from the local table of `originating_center` and `subcenter` take precedence
+    /// over parameters from the master table (see `params_from_table` for how local tables are
+    /// matched). Returns `None` if the abbreviation isn't found, or
+    /// if the abbreviation is ambiguous (i.e. it's associated with multiple parameters in the
+    /// same table).
+    pub fn decode_idx_abbrev(
+        &self,
+        abbrev: &str,
+        originating_center: u16,
+        subcenter: u8,
+    ) -> Option<&Parameter> {
+        let candidates = self.abbrev_to_parameter(&Abbrev(abbrev.to_string()));
+        let mut params = params_from_table(&candidates, originating_center, subcenter);
+        if params.is_empty() {
+            params = params_from_table(&candidates, u16::MAX, u8::MAX);
+        }
+        match params[..] {
+            [param] => Some(param),
+            _ => None,
+        }
+    }
+
+    /// A one-line description of the parameter with `numeric_id`, for UIs and logging. For
+    /// example: "Temperature [TMP] (K), discipline 0 / category 0 / number 0, master table v30".
+    /// Parameters from local tables also include the originating center and subcenter.
+    pub fn describe(&self, numeric_id: &NumericId) -> Option<String> {
+        let param = self.numeric_id_to_param.get(numeric_id)?;
+        let mut s = format!(
+            "{} [{}] ({}), discipline {} / category {} / number {}, master table v{}",
+            param.name,
+            param.abbrev,
+            param.unit,
+            numeric_id.product_discipline(),
+            numeric_id.parameter_category(),
+            numeric_id.parameter_number(),
+            numeric_id.master_table_version(),
+        );
+        let center = numeric_id.originating_center();
+        if center != u16::MAX {
+            write!(s, ", center {center}").expect("write");
+            if let Some(name) = originating_center_name(center) {
+                write!(s, " ({name})").expect("write");
+            }
+            write!(s, " / subcenter {}", numeric_id.subcenter()).expect("write");
+            if let Some(name) = subcenter_name(center, numeric_id.subcenter()) {
+                write!(s, " ({name})").expect("write");
+            }
+        }
+        Some(s)
+    }
+
+    /// Returns every master table version of the parameter with this `product_discipline`,
+    /// `parameter_category`, and `parameter_number`, in ascending order of master table version.
+    /// This shows how the parameter's definition changed across table versions. Parameters from
+    /// local tables are excluded.
+    pub fn all_versions_of(
+        &self,
+        product_discipline: u8,
+        parameter_category: u8,
+        parameter_number: u8,
+    ) -> Vec<(&NumericId, &Parameter)> {
+        let bound = |master_table_version, originating_center, subcenter, local_table_version| {
+            NumericIdBuilder::new(product_discipline, parameter_category, parameter_number)
+                .set_master_table_version(master_table_version)
+                .set_originating_center(originating_center)
+                .set_subcenter(subcenter)
+                .set_local_table_version(local_table_version)
+                .build()
+        };
+        self.numeric_id_to_param
+            .range(bound(0, 0, 0, 0)..=bound(u8::MAX, u16::MAX, u8::MAX, u8::MAX))
+            .filter(|(numeric_id, _)| numeric_id.originating_center() == u16::MAX)
+            .collect()
+    }
+
+    /// The parameters which were skipped because their `NumericId` had already been inserted
+    /// (when populating with `DuplicatePolicy::SkipDuplicates`), in the order they were skipped.
+    /// The parameter with the same `NumericId` in the database was kept.
+    pub fn skipped_duplicates(&self) -> &[(NumericId, Parameter)] {
+        &self.skipped_duplicates
+    }
+
     pub fn num_numeric_ids(&self) -> usize {
         self.numeric_id_to_param.len()
     }
@@ -108,6 +384,25 @@ impl ParameterDatabase {
         self.abbrev_to_numeric_id.len()
     }
 
+    /// Iterate over every abbreviation in the database, in arbitrary order. Each abbreviation is
+    /// yielded once, even if it's associated with multiple parameters.
+    pub fn abbrevs(&self) -> impl Iterator<Item = &Abbrev> {
+        self.abbrev_to_numeric_id.keys()
+    }
+
+    /// Returns true if `abbrev` is associated with at least one parameter. The match is
+    /// case-sensitive.
+    pub fn contains_abbrev(&self, abbrev: &Abbrev) -> bool {
+        self.abbrev_to_numeric_id.contains_key(abbrev)
+    }
+
+    /// Look up the parameter with `numeric_id`. This is the same as
+    /// `numeric_id_to_param().get(numeric_id)`, but uses a `HashMap`, so it's faster for point
+    /// lookups. Use `numeric_id_to_param` for range queries.
+    pub fn get(&self, numeric_id: &NumericId) -> Option<&Parameter> {
+        self.numeric_id_index.get(numeric_id)
+    }
+
     pub fn numeric_id_to_param(&self) -> &BTreeMap<NumericId, Parameter> {
         &self.numeric_id_to_param
     }
@@ -116,6 +411,32 @@ impl ParameterDatabase {
         &self.abbrev_to_numeric_id
     }
 
+    /// Write the whole database as CSV, with one row per `NumericId` (in `NumericId` order). This
+    /// is useful for comparing the database against the source CSVs, or for inspecting the
+    /// database in a spreadsheet. Missing codes are written as `u8::MAX` or `u16::MAX`, and unknown
+    /// center and subcenter names are written as empty strings.
+    pub fn to_csv(&self, writer: impl std::io::Write) -> anyhow::Result<()> {
+        let mut csv_writer = csv::Writer::from_writer(writer);
+        for (numeric_id, param) in &self.numeric_id_to_param {
+            csv_writer.serialize(CsvRow {
+                discipline: numeric_id.product_discipline(),
+                category: numeric_id.parameter_category(),
+                number: numeric_id.parameter_number(),
+                master_table_version: numeric_id.master_table_version(),
+                center: numeric_id.originating_center(),
+                center_name: originating_center_name(numeric_id.originating_center()),
+                subcenter: numeric_id.subcenter(),
+                subcenter_name: subcenter_name(numeric_id.originating_center(), numeric_id.subcenter()),
+                local_table_version: numeric_id.local_table_version(),
+                abbrev: &param.abbrev.0,
+                name: &param.name,
+                unit: &param.unit,
+            })?;
+        }
+        csv_writer.flush()?;
+        Ok(())
+    }
+
     /// Silently skips insertion into `abbrev_to_numeric_id` if abbrev = "".
     fn insert(
         &mut self,
@@ -131,9 +452,12 @@ impl ParameterDatabase {
                 )),
             );
         };
-        // Insert into `numeric_id_to_param`:
-        match self.numeric_id_to_param.insert(numeric_id, parameter) {
-            None => Ok(()),
+        // Insert into `numeric_id_to_param` and `numeric_id_index`:
+        match self.numeric_id_to_param.insert(numeric_id, parameter.clone()) {
+            None => {
+                self.numeric_id_index.insert(numeric_id, parameter);
+                Ok(())
+            }
             Some(old_param) => Err(
                 ParameterInsertionError::NumericIdAlreadyExistsInNumericIdToParam((
                     numeric_id, old_param,
@@ -142,11 +466,60 @@ impl ParameterDatabase {
         }
     }
 
+    /// Like `insert`, but if `numeric_id` already exists then `duplicate_policy` decides what
+    /// happens.
+    fn insert_with_duplicate_policy(
+        &mut self,
+        numeric_id: NumericId,
+        parameter: Parameter,
+        duplicate_policy: DuplicatePolicy,
+    ) -> Result<(), ParameterInsertionError> {
+        let Some(old_param) = self.numeric_id_to_param.get(&numeric_id) else {
+            return self.insert(numeric_id, parameter);
+        };
+        match duplicate_policy {
+            DuplicatePolicy::Strict => Err(
+                ParameterInsertionError::NumericIdAlreadyExistsInNumericIdToParam((
+                    numeric_id,
+                    old_param.clone(),
+                )),
+            ),
+            DuplicatePolicy::SkipDuplicates => {
+                self.skipped_duplicates.push((numeric_id, parameter));
+                Ok(())
+            }
+            DuplicatePolicy::Overwrite => {
+                let old_abbrev = old_param.abbrev.clone();
+                self.numeric_id_to_param.remove(&numeric_id);
+                self.numeric_id_index.remove(&numeric_id);
+                if let Some(numeric_ids) = self.abbrev_to_numeric_id.get_mut(&old_abbrev) {
+                    numeric_ids.remove(&numeric_id);
+                    if numeric_ids.is_empty() {
+                        self.abbrev_to_numeric_id.remove(&old_abbrev);
+                    }
+                }
+                self.insert(numeric_id, parameter)
+            }
+        }
+    }
+
+    /// Returns the abbreviations which are associated with multiple parameters that genuinely
+    /// differ. Parameters whose names differ only in case, and whose units differ only in
+    /// spelling (e.g. "kg/(m^2)" and "kg/m^2"), are not considered to differ.
     fn abbrevs_with_multiple_numeric_ids(&self) -> Vec<(&Abbrev, &BTreeSet<NumericId>)> {
         let mut abbrevs_with_multiple_numeric_ids: Vec<_> = self
             .abbrev_to_numeric_id
             .iter()
-            .filter(|(_, numeric_ids)| numeric_ids.len() > 1)
+            .filter(|(_, numeric_ids)| {
+                let distinct_params: BTreeSet<_> = numeric_ids
+                    .iter()
+                    .map(|numeric_id| {
+                        let param = &self.numeric_id_to_param[numeric_id];
+                        (param.name.to_lowercase(), param.normalized_unit())
+                    })
+                    .collect();
+                distinct_params.len() > 1
+            })
             .collect();
         abbrevs_with_multiple_numeric_ids
             .sort_by(|(abbrev1, _), (abbrev2, _)| abbrev1.cmp(&abbrev2));
@@ -208,6 +581,115 @@ impl ParameterDatabase {
     }
 }
 
+/// A row of the CSV written by `ParameterDatabase::to_csv`.
+#[derive(serde::Serialize)]
+struct CsvRow<'a> {
+    discipline: u8,
+    category: u8,
+    number: u8,
+    master_table_version: u8,
+    center: u16,
+    center_name: Option<&'static str>,
+    subcenter: u8,
+    subcenter_name: Option<&'static str>,
+    local_table_version: u8,
+    abbrev: &'a str,
+    name: &'a str,
+    unit: &'a str,
+}
+
+/// Which GRIB2 code table 4.2 a CSV describes. See `ParameterDatabase::populate_from_readers`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Table4_2 {
+    /// The master table of one parameter category. The CSV has the same columns as GDAL's
+    /// `grib2_table_4_2_{discipline}_{category}.csv` files: `subcat,short_name,name,unit`.
+    Master {
+        product_discipline: u8,
+        parameter_category: u8,
+    },
+    /// The local table of an originating center and subcenter. The CSV has the same columns as
+    /// GDAL's `grib2_table_4_2_local_{name}.csv` files: `prod,cat,subcat,short_name,name,unit`.
+    Local { originating_center: u16, subcenter: u8 },
+}
+
+/// Identify which table a GDAL CSV describes, from its file name. Returns `None` for the local
+/// index. `local_index` maps the file name of each local table to its center and subcenter.
+fn table_4_2_from_path(
+    path: &Path,
+    local_index: &HashMap<String, (u16, u8)>,
+) -> anyhow::Result<Option<Table4_2>> {
+    let re_master_table =
+        regex::Regex::new(r"^grib2_table_4_2_(?<discipline>\d{1,2})_(?<category>\d{1,3}).csv$")
+            .unwrap();
+    let re_local_table = regex::Regex::new(r"^grib2_table_4_2_local_[A-Z][A-Za-z]+.csv$").unwrap();
+    let file_name = path
+        .file_name()
+        .with_context(|| format!("Failed to get file_name from path {path:?}"))?
+        .to_str()
+        .with_context(|| format!("Failed to convert file_stem to &str for path {path:?}"))?;
+    if file_name == "grib2_table_4_2_local_index.csv" {
+        Ok(None)
+    } else if let Some(captures) = re_master_table.captures(file_name) {
+        Ok(Some(Table4_2::Master {
+            product_discipline: captures["discipline"].parse().expect("parse discipline"),
+            parameter_category: captures["category"].parse().expect("parse category"),
+        }))
+    } else if re_local_table.is_match(file_name) {
+        let (originating_center, subcenter) = *local_index
+            .get(file_name)
+            .with_context(|| format!("{path:?} is not listed in the local index"))?;
+        Ok(Some(Table4_2::Local {
+            originating_center,
+            subcenter,
+        }))
+    } else {
+        Err(anyhow::format_err!("Failed to interpret CSV path {path:?}!"))
+    }
+}
+
+/// The parameters in `candidates` which are from the table of `originating_center` and
+/// `subcenter`. Use `u16::MAX` and `u8::MAX` to select the master table.
+///
+/// A local table whose subcenter is `u8::MAX` (i.e. blank in the local index, like NCEP's table)
+/// applies to every subcenter of its originating center. But a local table for `subcenter`
+/// itself (e.g. NCEP's HPC table, subcenter 5) takes precedence.
+pub(crate) fn params_from_table<'a>(
+    candidates: &[(&'a NumericId, &'a Parameter)],
+    originating_center: u16,
+    subcenter: u8,
+) -> Vec<&'a Parameter> {
+    let params_with_subcenter = |subcenter: u8| -> Vec<&'a Parameter> {
+        candidates
+            .iter()
+            .filter(|(numeric_id, _)| {
+                numeric_id.originating_center() == originating_center
+                    && numeric_id.subcenter() == subcenter
+            })
+            .map(|(_, param)| *param)
+            .collect()
+    };
+    let params = params_with_subcenter(subcenter);
+    if params.is_empty() {
+        params_with_subcenter(u8::MAX)
+    } else {
+        params
+    }
+}
+
+/// What `ParameterDatabase::populate_with_duplicate_policy` does when a `NumericId` appears more
+/// than once in the CSVs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DuplicatePolicy {
+    /// Return an error.
+    Strict,
+    /// Keep the first parameter, and record the skipped parameter (see
+    /// `ParameterDatabase::skipped_duplicates`).
+    #[default]
+    SkipDuplicates,
+    /// Replace the existing parameter with the new parameter.
+    Overwrite,
+}
+
 #[derive(thiserror::Error, Debug, derive_more::Display)]
 #[display("ParameterInsertionError! {_variant}")]
 pub(crate) enum ParameterInsertionError {
@@ -250,10 +732,466 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    fn test_decode_idx_abbrev() -> anyhow::Result<()> {
+        const NCEP: u16 = 7;
+        const ECMWF: u16 = 98;
+        let param_db = ParameterDatabase::new().populate()?;
+
+        // `TMP` is only in the master table:
+        for center in [NCEP, ECMWF] {
+            let param = param_db.decode_idx_abbrev("TMP", center, u8::MAX).unwrap();
+            assert_eq!(param, &Parameter::new("TMP", "Temperature", "K"));
+        }
+
+        // `SNOHF` is in the master table and in NCEP's local table:
+        assert_eq!(
+            param_db.decode_idx_abbrev("SNOHF", NCEP, u8::MAX).unwrap(),
+            &Parameter::new("SNOHF", "Snow Phase Change Heat Flux", "W/(m^2)")
+        );
+        assert_eq!(
+            param_db.decode_idx_abbrev("SNOHF", ECMWF, u8::MAX).unwrap(),
+            &Parameter::new("SNOHF", "Snow phase change heat flux", "W/m^2")
+        );
+
+        assert!(param_db.decode_idx_abbrev("NOT_AN_ABBREV", NCEP, u8::MAX).is_none());
+        Ok(())
+    }
+
+    /// NCEP's local table applies to every NCEP subcenter, but HPC's table (subcenter 5) only
+    /// applies to HPC.
+    #[test]
+    fn test_decode_idx_abbrev_subcenter() -> anyhow::Result<()> {
+        const NCEP: u16 = 7;
+        const HPC: u8 = 5;
+        let param_db = ParameterDatabase::new().populate()?;
+        let ncep_snohf = Parameter::new("SNOHF", "Snow Phase Change Heat Flux", "W/(m^2)");
+        for subcenter in [0, 2, HPC] {
+            assert_eq!(
+                param_db.decode_idx_abbrev("SNOHF", NCEP, subcenter),
+                Some(&ncep_snohf),
+                "subcenter={subcenter}"
+            );
+        }
+        assert_eq!(
+            param_db.decode_idx_abbrev("HPC-Wx", NCEP, HPC),
+            Some(&Parameter::new("HPC-Wx", "HPC Code", "-"))
+        );
+        assert_eq!(param_db.decode_idx_abbrev("HPC-Wx", NCEP, 0), None);
+
+        // A table for the subcenter itself takes precedence over the table for every subcenter:
+        let csv = "prod,cat,subcat,short_name,name,unit\n0,1,250,FOO,Foo,K\n";
+        let local_table = |subcenter| Table4_2::Local {
+            originating_center: NCEP,
+            subcenter,
+        };
+        let param_db = ParameterDatabase::new().populate_from_readers([
+            (local_table(u8::MAX), csv.as_bytes()),
+            (local_table(HPC), csv.replace("Foo", "Foo (HPC)").as_bytes()),
+        ])?;
+        assert_eq!(
+            param_db.decode_idx_abbrev("FOO", NCEP, 0).map(Parameter::name),
+            Some("Foo")
+        );
+        assert_eq!(
+            param_db.decode_idx_abbrev("FOO", NCEP, HPC).map(Parameter::name),
+            Some("Foo (HPC)")
+        );
+        Ok(())
+    }
+
+    /// Every meteorological parameter category in the master tables is decoded, not just
+    /// categories 0 and 1.
+    #[test]
+    fn test_meteorological_categories() {
+        let param_db = ParameterDatabase::shared();
+        let numeric_id = |category, number| {
+            NumericIdBuilder::new(0, category, number)
+                .set_master_table_version(MASTER_TABLE_VERSION)
+                .build()
+        };
+        // Momentum, mass, short-wave radiation, long-wave radiation, cloud, and thermodynamic
+        // stability:
+        for (category, abbrev) in [
+            (2, "WDIR"),
+            (3, "PRES"),
+            (4, "NSWRS"),
+            (5, "NLWRS"),
+            (6, "CICE"),
+            (7, "PLI"),
+        ] {
+            assert_eq!(
+                param_db.numeric_id_to_param()[&numeric_id(category, 0)].abbrev().0,
+                abbrev
+            );
+        }
+        assert_eq!(
+            param_db.numeric_id_to_param()[&numeric_id(7, 6)],
+            Parameter::new("CAPE", "Convective available potential energy", "J/kg")
+        );
+    }
+
+    #[test]
+    fn test_describe() -> anyhow::Result<()> {
+        let param_db = ParameterDatabase::new().populate()?;
+        let numeric_id = NumericIdBuilder::new(0, 0, 0)
+            .set_master_table_version(MASTER_TABLE_VERSION)
+            .build();
+        assert_eq!(
+            param_db.describe(&numeric_id).unwrap(),
+            "Temperature [TMP] (K), discipline 0 / category 0 / number 0, master table v30"
+        );
+
+        let numeric_id = NumericIdBuilder::new(0, 1, 199)
+            .set_master_table_version(MASTER_TABLE_VERSION)
+            .set_originating_center(7)
+            .set_subcenter(255)
+            .build();
+        assert_eq!(
+            param_db.describe(&numeric_id).unwrap(),
+            "Potential Evaporation [PEVAP] (kg/(m^2)), discipline 0 / category 1 / number 199, \
+             master table v30, center 7 (US-NCEP) / subcenter 255"
+        );
+
+        assert!(param_db.describe(&NumericIdBuilder::new(0, 0, 0).build()).is_none());
+        Ok(())
+    }
+
+    /// Local tables are found via `grib2_table_4_2_local_index.csv`, so supporting another center
+    /// only needs another CSV, and numeric IDs from centers (or local disciplines) without a
+    /// local table aren't found.
+    #[test]
+    fn test_local_tables_are_dispatched_by_center() {
+        const NCEP: u16 = 7;
+        const MRMS: u16 = 161;
+        let param_db = ParameterDatabase::shared();
+        let local_id = |center, discipline, category, number| {
+            NumericIdBuilder::new(discipline, category, number)
+                .set_master_table_version(MASTER_TABLE_VERSION)
+                .set_originating_center(center)
+                .set_subcenter(u8::MAX)
+                .build()
+        };
+
+        // A local category of NCEP's local table:
+        assert_eq!(
+            param_db.get(&local_id(NCEP, 0, 1, 199)).unwrap().abbrev().0,
+            "PEVAP"
+        );
+        // A local discipline (209) of the MRMS local table:
+        assert_eq!(
+            param_db.get(&local_id(MRMS, 209, 2, 0)).unwrap().abbrev().0,
+            "NLDN_CG_001min_AvgDensity"
+        );
+
+        // NCEP doesn't define discipline 209, and ECMWF doesn't have a local table:
+        assert!(param_db.get(&local_id(NCEP, 209, 2, 0)).is_none());
+        assert!(param_db.get(&local_id(98, 0, 1, 199)).is_none());
+        assert!(param_db.describe(&local_id(NCEP, 250, 0, 0)).is_none());
+    }
+
+    #[test]
+    fn test_populate_with_master_table_version() -> anyhow::Result<()> {
+        let v29 = ParameterDatabase::new().populate_with_master_table_version(29)?;
+        let v30 = ParameterDatabase::new().populate_with_master_table_version(30)?;
+        assert_eq!(v29.num_numeric_ids(), v30.num_numeric_ids());
+        assert!(v29
+            .numeric_id_to_param()
+            .keys()
+            .all(|numeric_id| numeric_id.master_table_version() == 29));
+        assert!(v30
+            .numeric_id_to_param()
+            .keys()
+            .all(|numeric_id| numeric_id.master_table_version() == 30));
+
+        // Look up TMP using each version:
+        let tmp = |master_table_version| {
+            NumericIdBuilder::new(0, 0, 0)
+                .set_master_table_version(master_table_version)
+                .build()
+        };
+        assert_eq!(v29.numeric_id_to_param()[&tmp(29)].abbrev().to_string(), "TMP");
+        assert!(v29.numeric_id_to_param().get(&tmp(30)).is_none());
+        assert_eq!(v30.numeric_id_to_param()[&tmp(30)].abbrev().to_string(), "TMP");
+        Ok(())
+    }
+
+    #[test]
+    fn test_all_versions_of() -> anyhow::Result<()> {
+        let mut param_db = ParameterDatabase::new();
+        let numeric_id = |master_table_version| {
+            NumericIdBuilder::new(0, 1, 2)
+                .set_master_table_version(master_table_version)
+                .build()
+        };
+        // Insert the newer version first, to check that the results are in version order:
+        let new_param = Parameter::new("FOO", "Foo", "kg/m^2");
+        let old_param = Parameter::new("FOO", "Foo", "kg/(m^2)");
+        param_db.insert(numeric_id(30), new_param.clone())?;
+        param_db.insert(numeric_id(2), old_param.clone())?;
+
+        // Other parameters which shouldn't be returned:
+        param_db.insert(
+            NumericIdBuilder::new(0, 1, 3).set_master_table_version(30).build(),
+            Parameter::new("BAR", "Bar", "K"),
+        )?;
+        param_db.insert(
+            NumericIdBuilder::new(0, 1, 2)
+                .set_master_table_version(30)
+                .set_originating_center(7)
+                .set_subcenter(255)
+                .build(),
+            Parameter::new("BAZ", "Baz", "K"),
+        )?;
+
+        assert_eq!(
+            param_db.all_versions_of(0, 1, 2),
+            [
+                (&numeric_id(2), &old_param),
+                (&numeric_id(30), &new_param)
+            ]
+        );
+        assert!(param_db.all_versions_of(0, 1, 4).is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_to_csv() -> anyhow::Result<()> {
+        let param_db = ParameterDatabase::new().populate()?;
+        let mut buf = Vec::new();
+        param_db.to_csv(&mut buf)?;
+        let csv = String::from_utf8(buf)?;
+        let mut lines = csv.lines();
+        assert_eq!(
+            lines.next(),
+            Some(
+                "discipline,category,number,master_table_version,center,center_name,\
+                 subcenter,subcenter_name,local_table_version,abbrev,name,unit"
+            )
+        );
+        let rows: Vec<_> = lines.collect();
+        assert_eq!(rows.len(), param_db.num_numeric_ids());
+        assert!(rows.contains(&"0,0,0,30,65535,,255,,255,TMP,Temperature,K"));
+        assert!(rows.contains(
+            &"0,1,199,30,7,US-NCEP,255,,255,PEVAP,Potential Evaporation,kg/(m^2)"
+        ));
+        Ok(())
+    }
+
     #[test]
     fn test_for_duplicate_abbreviations() -> anyhow::Result<()> {
         let  param_db = ParameterDatabase::new().populate()?;
         println!("{}", param_db.describe_abbrevs_with_multiple_params());
         Ok(())
     }
+
+    /// Insert a synthetic local table which contains a duplicate `NumericId`.
+    fn insert_duplicate(duplicate_policy: DuplicatePolicy) -> anyhow::Result<ParameterDatabase> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("grib2_table_4_2_local_Test.csv");
+        std::fs::write(
+            &path,
+            "prod,cat,subcat,short_name,name,unit,unit_conv\n\
+             0,0,192,\"FOO\",\"Foo\",\"K\",UC_NONE\n\
+             0,0,193,\"BAR\",\"Bar\",\"K\",UC_NONE\n\
+             0,0,192,\"BAZ\",\"Baz\",\"m\",UC_NONE\n",
+        )?;
+        let mut param_db = ParameterDatabase::new();
+        param_db.insert_local_table(&path, 7, 0, MASTER_TABLE_VERSION, duplicate_policy)?;
+        Ok(param_db)
+    }
+
+    #[test]
+    fn test_duplicate_policy_strict() {
+        assert!(insert_duplicate(DuplicatePolicy::Strict).is_err());
+    }
+
+    #[test]
+    fn test_duplicate_policy_skip_duplicates() -> anyhow::Result<()> {
+        let param_db = insert_duplicate(DuplicatePolicy::SkipDuplicates)?;
+        assert_eq!(param_db.num_numeric_ids(), 2);
+        assert_eq!(param_db.abbrev_to_parameter(&Abbrev("FOO".to_string())).len(), 1);
+        assert!(param_db.abbrev_to_parameter(&Abbrev("BAZ".to_string())).is_empty());
+        let (foo_numeric_id, _) = param_db.abbrev_to_parameter(&Abbrev("FOO".to_string()))[0];
+        assert_eq!(
+            param_db.skipped_duplicates(),
+            [(*foo_numeric_id, Parameter::new("BAZ", "Baz", "m"))]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_duplicate_policy_overwrite() -> anyhow::Result<()> {
+        let param_db = insert_duplicate(DuplicatePolicy::Overwrite)?;
+        assert_eq!(param_db.num_numeric_ids(), 2);
+        assert!(param_db.skipped_duplicates().is_empty());
+        assert_eq!(param_db.num_abbrevs(), 2);
+        assert!(param_db.abbrev_to_parameter(&Abbrev("FOO".to_string())).is_empty());
+        let params = param_db.abbrev_to_parameter(&Abbrev("BAZ".to_string()));
+        assert_eq!(params.len(), 1);
+        assert_eq!(params[0].1, &Parameter::new("BAZ", "Baz", "m"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_populate_from_dir() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        std::fs::write(
+            dir.path().join("grib2_table_4_2_0_0.csv"),
+            "subcat,short_name,name,unit,unit_conv\n\
+             -1,\"#\",\"#\",\"#\",\"#\"\n\
+             0,\"TMP\",\"Temperature\",\"K\",UC_NONE\n\
+             250,\"NEWTMP\",\"New temperature\",\"K\",UC_NONE\n",
+        )?;
+        std::fs::write(
+            dir.path().join("grib2_table_4_2_local_Test.csv"),
+            "prod,cat,subcat,short_name,name,unit,unit_conv\n\
+             0,1,192,\"FOO\",\"Foo\",\"kg/kg\",UC_NONE\n",
+        )?;
+        std::fs::write(
+            dir.path().join("grib2_table_4_2_local_index.csv"),
+            "center_code,subcenter_code,filename\n\
+             254,,grib2_table_4_2_local_Test.csv\n",
+        )?;
+        std::fs::write(dir.path().join("README.md"), "Not a table")?;
+
+        let param_db = ParameterDatabase::new().populate_from_dir(dir.path())?;
+        assert_eq!(param_db.num_numeric_ids(), 3);
+        let new_tmp = NumericIdBuilder::new(0, 0, 250)
+            .set_master_table_version(MASTER_TABLE_VERSION)
+            .build();
+        assert_eq!(
+            param_db.numeric_id_to_param()[&new_tmp],
+            Parameter::new("NEWTMP", "New temperature", "K")
+        );
+        assert_eq!(
+            param_db.decode_idx_abbrev("FOO", 254, u8::MAX),
+            Some(&Parameter::new("FOO", "Foo", "kg/kg"))
+        );
+
+        // The custom tables can extend the bundled tables:
+        let bundled = ParameterDatabase::new().populate()?;
+        let n_bundled = bundled.num_numeric_ids();
+        let param_db = bundled.populate_from_dir(dir.path())?;
+        assert_eq!(param_db.num_numeric_ids(), n_bundled + 2);
+
+        // Local tables must be listed in the local index:
+        std::fs::remove_file(dir.path().join("grib2_table_4_2_local_index.csv"))?;
+        assert!(ParameterDatabase::new().populate_from_dir(dir.path()).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_populate_from_readers() -> anyhow::Result<()> {
+        let master = "subcat,short_name,name,unit\n0,\"TMP\",\"Temperature\",\"K\"\n";
+        let param_db = ParameterDatabase::new().populate_from_readers([(
+            Table4_2::Master {
+                product_discipline: 0,
+                parameter_category: 0,
+            },
+            master.as_bytes(),
+        )])?;
+        assert_eq!(
+            param_db.decode_idx_abbrev("TMP", 7, u8::MAX),
+            Some(&Parameter::new("TMP", "Temperature", "K"))
+        );
+
+        // Local tables need the `prod` and `cat` columns:
+        let result = ParameterDatabase::new().populate_from_readers([(
+            Table4_2::Local {
+                originating_center: 7,
+                subcenter: u8::MAX,
+            },
+            master.as_bytes(),
+        )]);
+        assert!(result.is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_populate_from_readers_with_options() -> anyhow::Result<()> {
+        let master = "subcat,short_name,name,unit\n0,\"TMP\",\"Temperature\",\"K\"\n";
+        let table = Table4_2::Master {
+            product_discipline: 0,
+            parameter_category: 0,
+        };
+        let param_db = ParameterDatabase::new().populate_from_readers_with_options(
+            DuplicatePolicy::default(),
+            29,
+            [(table, master.as_bytes())],
+        )?;
+        let numeric_id = NumericIdBuilder::new(0, 0, 0)
+            .set_master_table_version(29)
+            .build();
+        assert_eq!(
+            param_db.get(&numeric_id),
+            Some(&Parameter::new("TMP", "Temperature", "K"))
+        );
+
+        // The same table twice:
+        let readers = [(table, master.as_bytes()), (table, master.as_bytes())];
+        assert!(ParameterDatabase::new()
+            .populate_from_readers_with_options(
+                DuplicatePolicy::Strict,
+                MASTER_TABLE_VERSION,
+                readers
+            )
+            .is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_shared() {
+        let param_db = ParameterDatabase::shared();
+        assert!(std::ptr::eq(param_db, ParameterDatabase::shared()));
+        assert_eq!(
+            param_db.num_numeric_ids(),
+            ParameterDatabase::new().populate().unwrap().num_numeric_ids()
+        );
+
+        let handles: Vec<_> = (0..8)
+            .map(|_| {
+                std::thread::spawn(|| {
+                    let param_db = ParameterDatabase::shared();
+                    let tmp = param_db.decode_idx_abbrev("TMP", 7, u8::MAX).unwrap().clone();
+                    (param_db as *const ParameterDatabase as usize, tmp)
+                })
+            })
+            .collect();
+        for handle in handles {
+            let (address, tmp) = handle.join().unwrap();
+            assert_eq!(address, param_db as *const ParameterDatabase as usize);
+            assert_eq!(tmp, Parameter::new("TMP", "Temperature", "K"));
+        }
+    }
+
+    #[test]
+    fn test_abbrevs() {
+        let param_db = ParameterDatabase::shared();
+        assert_eq!(param_db.abbrevs().count(), param_db.num_abbrevs());
+        assert!(param_db.abbrevs().any(|abbrev| abbrev.0 == "TMP"));
+        assert!(param_db.contains_abbrev(&Abbrev::from("TMP")));
+        assert!(!param_db.contains_abbrev(&Abbrev::from("tmp")));
+        assert!(!param_db.contains_abbrev(&Abbrev::from("")));
+    }
+
+    #[test]
+    fn test_get_matches_numeric_id_to_param() -> anyhow::Result<()> {
+        let param_db = ParameterDatabase::shared();
+        for (numeric_id, param) in param_db.numeric_id_to_param() {
+            assert_eq!(param_db.get(numeric_id), Some(param));
+        }
+        let missing = NumericIdBuilder::new(255, 255, 255).build();
+        assert_eq!(param_db.get(&missing), None);
+
+        // Overwritten parameters are replaced in both maps:
+        let param_db = ParameterDatabase::new()
+            .populate()?
+            .populate_with_options(DuplicatePolicy::Overwrite, MASTER_TABLE_VERSION)?;
+        assert_eq!(param_db.numeric_id_index.len(), param_db.num_numeric_ids());
+        for (numeric_id, param) in param_db.numeric_id_to_param() {
+            assert_eq!(param_db.get(numeric_id), Some(param));
+        }
+        Ok(())
+    }
 }
@@ -0,0 +1,322 @@
+use super::{
+    database::{params_from_table, ParameterDatabase},
+    Parameter,
+};
+use crate::csv_reader::read_abbrev_aliases::abbrev_alias;
+
+/// The result of `ParameterDatabase::decode_idx_abbrev_with_fallback`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DecodedAbbrev<'a> {
+    /// The abbreviation was found in the local table of the originating center and subcenter.
+    Local(&'a Parameter),
+    /// The abbreviation was found in the master table.
+    Master(&'a Parameter),
+    /// The abbreviation was found by a lenient match: Either a case-insensitive match of the
+    /// abbreviation, or a wgrib2 string like `var discipline=0 master_table=2 parmcat=1 parm=225`
+    /// (which wgrib2 writes for parameters it doesn't know).
+    Lenient(&'a Parameter),
+    /// The abbreviation is a synonym (listed in `csv/abbrev_aliases.csv`) for a parameter in the
+    /// master table. For example, ECMWF's `tp` is a synonym for `APCP`.
+    Alias(&'a Parameter),
+    /// The abbreviation couldn't be decoded (or is ambiguous). The raw abbreviation is kept so
+    /// that the message isn't dropped.
+    Unknown { abbrev: String },
+}
+
+impl<'a> DecodedAbbrev<'a> {
+    /// Returns `None` if the abbreviation is `Unknown`.
+    pub fn parameter(&self) -> Option<&'a Parameter> {
+        match *self {
+            Self::Local(param)
+            | Self::Master(param)
+            | Self::Lenient(param)
+            | Self::Alias(param) => Some(param),
+            Self::Unknown { .. } => None,
+        }
+    }
+}
+
+/// What `ParameterDatabase::decode_idx_abbrev_with_policy` does when an abbreviation can't be
+/// decoded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DecodePolicy {
+    /// Return `DecodedAbbrev::Unknown`, so that the message isn't dropped.
+    #[default]
+    Lenient,
+    /// Return an error. Use this to find gaps in the tables (e.g. when validating a dataset in
+    /// CI).
+    Strict,
+}
+
+impl ParameterDatabase {
+    /// Like `decode_idx_abbrev`, but never fails. Tries, in order:
+    /// 1. The local table of `originating_center` and `subcenter`.
+    /// 2. The master table.
+    /// 3. A lenient match (see `DecodedAbbrev::Lenient`).
+    /// 4. The alias table (see `DecodedAbbrev::Alias`).
+    /// 5. Otherwise, returns `DecodedAbbrev::Unknown`.
+    ///
+    /// Each step only succeeds if it finds exactly one parameter.
+    pub fn decode_idx_abbrev_with_fallback(
+        &self,
+        abbrev: &str,
+        originating_center: u16,
+        subcenter: u8,
+    ) -> DecodedAbbrev<'_> {
+        let from_table = |center: u16, subcenter: u8, abbrev: &str| -> Option<&Parameter> {
+            let candidates = self.abbrev_to_parameter(&super::Abbrev(abbrev.to_string()));
+            match params_from_table(&candidates, center, subcenter)[..] {
+                [param] => Some(param),
+                _ => None,
+            }
+        };
+        if let Some(param) = from_table(originating_center, subcenter, abbrev) {
+            return DecodedAbbrev::Local(param);
+        }
+        if let Some(param) = from_table(u16::MAX, u8::MAX, abbrev) {
+            return DecodedAbbrev::Master(param);
+        }
+        if let Some(param) = self.decode_wgrib2_var(abbrev, originating_center, subcenter) {
+            return DecodedAbbrev::Lenient(param);
+        }
+        // Case-insensitive match, trying the local table before the master table:
+        let matching_abbrevs: Vec<_> = self
+            .abbrev_to_numeric_id()
+            .keys()
+            .filter(|a| a.0 != abbrev && a.0.eq_ignore_ascii_case(abbrev))
+            .collect();
+        if let [matching_abbrev] = matching_abbrevs[..] {
+            if let Some(param) = from_table(originating_center, subcenter, &matching_abbrev.0)
+                .or_else(|| from_table(u16::MAX, u8::MAX, &matching_abbrev.0))
+            {
+                return DecodedAbbrev::Lenient(param);
+            }
+        }
+        if let Some(param) = self.decode_alias(abbrev) {
+            return DecodedAbbrev::Alias(param);
+        }
+        DecodedAbbrev::Unknown {
+            abbrev: abbrev.to_string(),
+        }
+    }
+
+    /// Like `decode_idx_abbrev_with_fallback`, but if `decode_policy` is `DecodePolicy::Strict`
+    /// then an abbreviation which can't be decoded is an error (which names the abbreviation).
+    pub fn decode_idx_abbrev_with_policy(
+        &self,
+        abbrev: &str,
+        originating_center: u16,
+        subcenter: u8,
+        decode_policy: DecodePolicy,
+    ) -> anyhow::Result<DecodedAbbrev<'_>> {
+        match (
+            self.decode_idx_abbrev_with_fallback(abbrev, originating_center, subcenter),
+            decode_policy,
+        ) {
+            (DecodedAbbrev::Unknown { abbrev }, DecodePolicy::Strict) => Err(anyhow::format_err!(
+                "Unknown parameter abbreviation '{abbrev}' \
+                 (originating_center={originating_center}, subcenter={subcenter})"
+            )),
+            (decoded, _) => Ok(decoded),
+        }
+    }
+
+    /// Look up `abbrev` in the alias table, and return the latest master table version of the
+    /// parameter it's an alias for.
+    fn decode_alias(&self, abbrev: &str) -> Option<&Parameter> {
+        let (discipline, category, number) = abbrev_alias(abbrev)?;
+        self.all_versions_of(discipline, category, number)
+            .last()
+            .map(|(_, param)| *param)
+    }
+
+    /// Decode wgrib2's name for parameters it doesn't know, for example
+    /// `var discipline=0 master_table=2 parmcat=1 parm=225` or
+    /// `var discipline=0 center=7 local_table=1 parmcat=1 parm=225`.
+    fn decode_wgrib2_var(
+        &self,
+        abbrev: &str,
+        originating_center: u16,
+        subcenter: u8,
+    ) -> Option<&Parameter> {
+        let mut discipline = None;
+        let mut category = None;
+        let mut number = None;
+        let mut is_local = false;
+        for field in abbrev.strip_prefix("var ")?.split_whitespace() {
+            let (key, value) = field.split_once('=')?;
+            match key {
+                "discipline" => discipline = Some(value.parse::<u8>().ok()?),
+                "parmcat" => category = Some(value.parse::<u8>().ok()?),
+                "parm" => number = Some(value.parse::<u8>().ok()?),
+                "center" | "local_table" => is_local = true,
+                _ => (),
+            }
+        }
+        let (discipline, category, number) = (discipline?, category?, number?);
+        let is_match = |numeric_id: &crate::NumericId| {
+            numeric_id.product_discipline() == discipline
+                && numeric_id.parameter_category() == category
+                && numeric_id.parameter_number() == number
+        };
+        if is_local {
+            let candidates: Vec<_> = self
+                .numeric_id_to_param()
+                .iter()
+                .filter(|(numeric_id, _)| is_match(numeric_id))
+                .collect();
+            params_from_table(&candidates, originating_center, subcenter)
+                .last()
+                .copied()
+        } else {
+            // Use the latest master table version.
+            self.all_versions_of(discipline, category, number)
+                .last()
+                .map(|(_, param)| *param)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const NCEP: u16 = 7;
+    const ECMWF: u16 = 98;
+
+    #[test]
+    fn test_decode_idx_abbrev_with_fallback() -> anyhow::Result<()> {
+        let param_db = ParameterDatabase::new().populate()?;
+        let tmp = Parameter::new("TMP", "Temperature", "K");
+
+        assert_eq!(
+            param_db.decode_idx_abbrev_with_fallback("TMP", NCEP, u8::MAX),
+            DecodedAbbrev::Master(&tmp)
+        );
+        assert!(matches!(
+            param_db.decode_idx_abbrev_with_fallback("SNOHF", NCEP, u8::MAX),
+            DecodedAbbrev::Local(_)
+        ));
+        assert!(matches!(
+            param_db.decode_idx_abbrev_with_fallback("SNOHF", ECMWF, u8::MAX),
+            DecodedAbbrev::Master(_)
+        ));
+        // NCEP's local table applies to every NCEP subcenter:
+        for subcenter in [0, 2] {
+            assert!(matches!(
+                param_db.decode_idx_abbrev_with_fallback("SNOHF", NCEP, subcenter),
+                DecodedAbbrev::Local(_)
+            ));
+            assert_eq!(
+                param_db
+                    .decode_idx_abbrev_with_fallback(
+                        "var discipline=0 center=7 local_table=1 parmcat=1 parm=199",
+                        NCEP,
+                        subcenter
+                    )
+                    .parameter()
+                    .map(|param| param.abbrev().to_string()),
+                Some(String::from("PEVAP"))
+            );
+        }
+
+        // Lenient matches:
+        assert_eq!(
+            param_db.decode_idx_abbrev_with_fallback("tmp", NCEP, u8::MAX),
+            DecodedAbbrev::Lenient(&tmp)
+        );
+        assert_eq!(
+            param_db.decode_idx_abbrev_with_fallback(
+                "var discipline=0 master_table=2 parmcat=0 parm=0",
+                ECMWF,
+                u8::MAX
+            ),
+            DecodedAbbrev::Lenient(&tmp)
+        );
+        assert_eq!(
+            param_db
+                .decode_idx_abbrev_with_fallback(
+                    "var discipline=0 center=7 local_table=1 parmcat=1 parm=199",
+                    NCEP,
+                    u8::MAX
+                )
+                .parameter()
+                .map(|param| param.abbrev().to_string()),
+            Some(String::from("PEVAP"))
+        );
+
+        // Aliases:
+        let apcp = param_db.decode_idx_abbrev_with_fallback("APCP", NCEP, u8::MAX);
+        assert_eq!(
+            apcp.parameter().map(Parameter::name),
+            Some("Total precipitation")
+        );
+        assert_eq!(
+            param_db.decode_idx_abbrev_with_fallback("tp", ECMWF, u8::MAX),
+            DecodedAbbrev::Alias(apcp.parameter().unwrap())
+        );
+        let prmsl = param_db.decode_idx_abbrev_with_fallback("PRMSL", ECMWF, u8::MAX);
+        assert_eq!(
+            param_db.decode_idx_abbrev_with_fallback("msl", ECMWF, u8::MAX),
+            DecodedAbbrev::Alias(prmsl.parameter().unwrap())
+        );
+        assert_eq!(
+            param_db.decode_idx_abbrev_with_fallback("MSLET", ECMWF, u8::MAX),
+            DecodedAbbrev::Alias(prmsl.parameter().unwrap())
+        );
+        // Aliases are only a fallback, so NCEP's own MSLET parameter takes precedence:
+        assert!(matches!(
+            param_db.decode_idx_abbrev_with_fallback("MSLET", NCEP, u8::MAX),
+            DecodedAbbrev::Local(param) if param.name() == "MSLP (Eta model reduction)"
+        ));
+
+        // Unknown abbreviations are kept:
+        let decoded = param_db.decode_idx_abbrev_with_fallback("NOT_AN_ABBREV", NCEP, u8::MAX);
+        assert_eq!(
+            decoded,
+            DecodedAbbrev::Unknown {
+                abbrev: String::from("NOT_AN_ABBREV")
+            }
+        );
+        assert_eq!(decoded.parameter(), None);
+        assert!(matches!(
+            param_db.decode_idx_abbrev_with_fallback(
+                "var discipline=255 master_table=2 parmcat=255 parm=255",
+                NCEP,
+                u8::MAX
+            ),
+            DecodedAbbrev::Unknown { .. }
+        ));
+        Ok(())
+    }
+    #[test]
+    fn test_decode_idx_abbrev_with_policy() -> anyhow::Result<()> {
+        let param_db = ParameterDatabase::new().populate()?;
+        for decode_policy in [DecodePolicy::Lenient, DecodePolicy::Strict] {
+            assert!(matches!(
+                param_db.decode_idx_abbrev_with_policy("TMP", NCEP, u8::MAX, decode_policy)?,
+                DecodedAbbrev::Master(param) if param.abbrev().0 == "TMP"
+            ));
+        }
+
+        // Lenient mode keeps unknown abbreviations:
+        assert_eq!(
+            param_db.decode_idx_abbrev_with_policy(
+                "NOT_AN_ABBREV",
+                NCEP,
+                u8::MAX,
+                DecodePolicy::Lenient
+            )?,
+            DecodedAbbrev::Unknown {
+                abbrev: String::from("NOT_AN_ABBREV")
+            }
+        );
+
+        // Strict mode doesn't:
+        let error = param_db
+            .decode_idx_abbrev_with_policy("NOT_AN_ABBREV", NCEP, u8::MAX, DecodePolicy::Strict)
+            .unwrap_err();
+        assert!(error.to_string().contains("'NOT_AN_ABBREV'"), "{error}");
+        Ok(())
+    }
+}
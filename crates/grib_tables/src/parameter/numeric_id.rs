@@ -3,6 +3,34 @@ use core::fmt;
 const N_BITS_PER_BYTE: u64 = 8;
 
 /// The only public way to create a [`NumericId`].
+///
+/// For example, to create the `NumericId` from the fields of a decoded GRIB message, and use it
+/// to look up the parameter in the [`ParameterDatabase`](crate::ParameterDatabase):
+///
+/// ```
+/// use grib_tables::{NumericIdBuilder, ParameterDatabase, MASTER_TABLE_VERSION};
+/// # fn main() -> anyhow::Result<()> {
+/// let param_db = ParameterDatabase::new().populate()?;
+///
+/// // Temperature is in the master table, so we don't set the center, subcenter, or
+/// // local table version:
+/// let numeric_id = NumericIdBuilder::new(0, 0, 0) // discipline, category, number
+///     .set_master_table_version(MASTER_TABLE_VERSION)
+///     .build();
+/// assert_eq!(param_db.numeric_id_to_param()[&numeric_id].name(), "Temperature");
+///
+/// // "Potential Evaporation" is in NCEP's local table:
+/// let numeric_id = NumericIdBuilder::new(0, 1, 199)
+///     .set_master_table_version(MASTER_TABLE_VERSION)
+///     .set_originating_center(7) // NCEP
+///     .set_subcenter(255)
+///     .build();
+/// assert_eq!(param_db.numeric_id_to_param()[&numeric_id].name(), "Potential Evaporation");
+/// # Ok(())
+/// # }
+/// ```
+///
+/// Fields which aren't set default to the GRIB "missing" value (`u8::MAX` or `u16::MAX`).
 #[derive(PartialEq, Debug)]
 pub struct NumericIdBuilder {
     product_discipline: u8,
@@ -15,11 +43,7 @@ pub struct NumericIdBuilder {
 }
 
 impl NumericIdBuilder {
-    pub(crate) fn new(
-        product_discipline: u8,
-        parameter_category: u8,
-        parameter_number: u8,
-    ) -> Self {
+    pub fn new(product_discipline: u8, parameter_category: u8, parameter_number: u8) -> Self {
         Self {
             product_discipline,
             parameter_category,
@@ -31,27 +55,27 @@ impl NumericIdBuilder {
         }
     }
 
-    pub(crate) fn set_master_table_version(&mut self, master_table_version: u8) -> &Self {
+    pub fn set_master_table_version(&mut self, master_table_version: u8) -> &mut Self {
         self.master_table_version = master_table_version;
         self
     }
 
-    pub(crate) fn set_originating_center(&mut self, originating_center: u16) -> &Self {
+    pub fn set_originating_center(&mut self, originating_center: u16) -> &mut Self {
         self.originating_center = originating_center;
         self
     }
 
-    pub(crate) fn set_subcenter(&mut self, subcenter: u8) -> &Self {
+    pub fn set_subcenter(&mut self, subcenter: u8) -> &mut Self {
         self.subcenter = subcenter;
         self
     }
 
-    pub(crate) fn set_local_table_version(&mut self, local_table_version: u8) -> &Self {
+    pub fn set_local_table_version(&mut self, local_table_version: u8) -> &mut Self {
         self.local_table_version = local_table_version;
         self
     }
 
-    pub(crate) fn build(self) -> NumericId {
+    pub fn build(&self) -> NumericId {
         NumericId::new(
             self.product_discipline,
             self.parameter_category,
@@ -96,7 +120,10 @@ impl NumericIdBuilder {
 /// [`BTreeMap::range`][std::collections::BTreeMap::range]
 /// from `0x<product_discipline>_<parameter_category>_00_00_00_00_00_00`
 /// to   `0x<product_discipline>_<parameter_category>_FF_FF_FF_FF_FF_FF`
-#[derive(PartialOrd, Ord, Eq, PartialEq, Copy, Clone)]
+///
+/// [`NumericId`] also implements [`Hash`], for point lookups in a `HashMap` (see
+/// [`ParameterDatabase::get`][crate::ParameterDatabase::get]).
+#[derive(PartialOrd, Ord, Eq, PartialEq, Hash, Copy, Clone)]
 pub struct NumericId(u64);
 
 impl NumericId {
@@ -155,6 +182,11 @@ impl NumericId {
         (left << N_BITS_PER_BYTE) | right
     }
 
+    /// The originating center as a typed [`Center`](crate::Center).
+    pub fn center(&self) -> crate::Center {
+        crate::Center::from(self.originating_center())
+    }
+
     pub fn subcenter(&self) -> u8 {
         self.extract_nth_byte(Self::SUBCENTER_BYTE)
     }
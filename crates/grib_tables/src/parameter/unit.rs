@@ -0,0 +1,161 @@
+use std::{collections::BTreeMap, iter::Peekable, str::Chars};
+
+/// Canonicalize the spelling of a unit, so that equivalent spellings compare equal. For example,
+/// `"kg/(m^2)"` and `"kg/m^2"` both become `"kg/m^2"`, and `"kg/(kg s)"` and `"kg/(kg*s)"` both
+/// become `"kg/(kg*s)"`.
+///
+/// The canonical form lists the numerator's factors, then the denominator's factors, each in
+/// alphabetical order, joined by `*`. Factors are not cancelled (so `"kg/kg"` stays as `"kg/kg"`).
+///
+/// Units which can't be parsed as products and quotients of base units (e.g. `"%"` or
+/// `"0=no; 1=yes"`) are returned with surrounding whitespace removed, but are otherwise unchanged.
+pub fn normalize_unit(unit: &str) -> String {
+    let unit = unit.trim();
+    match parse_unit(unit) {
+        Some(factors) => factors.to_string(),
+        None => unit.to_string(),
+    }
+}
+
+/// Maps from each base unit (e.g. "m") to its exponent.
+#[derive(Default, Debug, PartialEq)]
+struct Factors {
+    numerator: BTreeMap<String, i32>,
+    denominator: BTreeMap<String, i32>,
+}
+
+impl Factors {
+    fn multiply(&mut self, other: Factors) {
+        for (base, exponent) in other.numerator {
+            *self.numerator.entry(base).or_default() += exponent;
+        }
+        for (base, exponent) in other.denominator {
+            *self.denominator.entry(base).or_default() += exponent;
+        }
+    }
+
+    fn invert(self) -> Self {
+        Self {
+            numerator: self.denominator,
+            denominator: self.numerator,
+        }
+    }
+}
+
+impl std::fmt::Display for Factors {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let join = |factors: &BTreeMap<String, i32>| {
+            factors
+                .iter()
+                .map(|(base, exponent)| match exponent {
+                    1 => base.clone(),
+                    _ => format!("{base}^{exponent}"),
+                })
+                .collect::<Vec<_>>()
+                .join("*")
+        };
+        if self.numerator.is_empty() {
+            write!(f, "1")?;
+        } else {
+            write!(f, "{}", join(&self.numerator))?;
+        }
+        match self.denominator.len() {
+            0 => Ok(()),
+            1 => write!(f, "/{}", join(&self.denominator)),
+            _ => write!(f, "/({})", join(&self.denominator)),
+        }
+    }
+}
+
+fn parse_unit(unit: &str) -> Option<Factors> {
+    let mut chars = unit.chars().peekable();
+    let factors = parse_expr(&mut chars)?;
+    chars.peek().is_none().then_some(factors)
+}
+
+/// expr := term ([`*` | ` ` | `/`] term)*
+///
+/// Terms which are juxtaposed (e.g. `"(kg/m^3)(m/s)"`) are multiplied.
+fn parse_expr(chars: &mut Peekable<Chars>) -> Option<Factors> {
+    let mut factors = parse_term(chars)?;
+    loop {
+        match chars.peek() {
+            None | Some(')') => return Some(factors),
+            Some('/') => {
+                chars.next();
+                factors.multiply(parse_term(chars)?.invert());
+            }
+            Some('*' | ' ') => {
+                chars.next();
+                factors.multiply(parse_term(chars)?);
+            }
+            Some(_) => factors.multiply(parse_term(chars)?),
+        }
+    }
+}
+
+/// term := `(` expr `)` | `1` | base [`^` exponent]
+fn parse_term(chars: &mut Peekable<Chars>) -> Option<Factors> {
+    match chars.peek()? {
+        '(' => {
+            chars.next();
+            let factors = parse_expr(chars)?;
+            (chars.next()? == ')').then_some(factors)
+        }
+        '1' => {
+            chars.next();
+            Some(Factors::default())
+        }
+        c if c.is_ascii_alphabetic() => {
+            let mut base = String::new();
+            while let Some(c) = chars.next_if(|c| c.is_ascii_alphabetic()) {
+                base.push(c);
+            }
+            let mut exponent = 1;
+            if chars.next_if_eq(&'^').is_some() {
+                let mut s = String::new();
+                while let Some(c) = chars.next_if(|c| *c == '-' || c.is_ascii_digit()) {
+                    s.push(c);
+                }
+                exponent = s.parse().ok()?;
+            }
+            let mut factors = Factors::default();
+            if exponent > 0 {
+                factors.numerator.insert(base, exponent);
+            } else if exponent < 0 {
+                factors.denominator.insert(base, -exponent);
+            }
+            Some(factors)
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_equivalent_spellings_normalize_to_the_same_unit() {
+        for (spellings, expected) in [
+            (vec!["kg/(m^2)", "kg/m^2", "kg m^-2"], "kg/m^2"),
+            (vec!["W/(m^2)", "W/m^2", " W/m^2 "], "W/m^2"),
+            (vec!["kg/(kg s)", "kg/(kg*s)"], "kg/(kg*s)"),
+            (vec!["kg/(m^2*s)", "kg/m^2/s"], "kg/(m^2*s)"),
+            (vec!["(kg/(m^3))(m/s)"], "kg*m/(m^3*s)"),
+            (vec!["m/s", "m s^-1"], "m/s"),
+            (vec!["1/s", "s^-1"], "1/s"),
+        ] {
+            for spelling in spellings {
+                assert_eq!(normalize_unit(spelling), expected, "{spelling}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_unparsable_units_are_unchanged() {
+        for unit in ["%", "-", "0=no; 1=yes", "kg/(m^2", "K/"] {
+            assert_eq!(normalize_unit(unit), unit);
+        }
+    }
+}
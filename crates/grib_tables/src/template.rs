@@ -0,0 +1,388 @@
+//! Decode the product definition section (section 4) of a GRIB2 message.
+//!
+//! The product definition template says what the message contains (and when, and where in the
+//! vertical). Only the two most common templates are decoded:
+//! - Template 4.0: An analysis or forecast at a horizontal level (e.g. a deterministic model).
+//! - Template 4.1: An individual ensemble forecast at a horizontal level. This is template 4.0
+//!   followed by the type of ensemble forecast, the perturbation number, and the number of
+//!   forecasts in the ensemble.
+//!
+//! Reading the ensemble member from the GRIB message is more robust than parsing the ensemble
+//! strings in `.idx` files, which differ between producers.
+
+use anyhow::Context;
+
+/// The GRIB missing value for a one-octet code.
+const MISSING_U8: u8 = u8::MAX;
+
+/// The decoded product definition section of a GRIB2 message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProductDefinition {
+    /// Template 4.0.
+    AnalysisOrForecastAtHorizontalLevel(AnalysisOrForecastAtHorizontalLevel),
+    /// Template 4.1.
+    IndividualEnsembleForecastAtHorizontalLevel(IndividualEnsembleForecastAtHorizontalLevel),
+}
+
+impl ProductDefinition {
+    /// Decode `section`, which must start at the first octet of section 4 (the section length).
+    /// `section` may extend beyond the end of section 4 (e.g. it can be the rest of the GRIB
+    /// message). Returns an error if `section` isn't section 4, is truncated, or uses a template
+    /// other than 4.0 or 4.1.
+    pub fn from_section_bytes(section: &[u8]) -> anyhow::Result<Self> {
+        let octets = Octets(section);
+        let section_length = octets.u32(1)? as usize;
+        let section_number = octets.u8(5)?;
+        if section_number != 4 {
+            anyhow::bail!(
+                "Expected section 4 (the product definition section), not section {section_number}"
+            );
+        }
+        let section = section.get(..section_length).with_context(|| {
+            format!(
+                "Section 4 is {section_length} octets long, but only {} octets were given",
+                section.len()
+            )
+        })?;
+        let octets = Octets(section);
+        let template_number = octets.u16(8)?;
+        let product_definition = match template_number {
+            0 => Self::AnalysisOrForecastAtHorizontalLevel(AnalysisOrForecastAtHorizontalLevel {
+                common: ProductTemplateCommon::from_octets(&octets)?,
+            }),
+            1 => Self::IndividualEnsembleForecastAtHorizontalLevel(
+                IndividualEnsembleForecastAtHorizontalLevel {
+                    common: ProductTemplateCommon::from_octets(&octets)?,
+                    ensemble: Ensemble {
+                        type_of_ensemble_forecast: octets.u8(35)?,
+                        perturbation_number: octets.u8(36)?,
+                        number_of_forecasts_in_ensemble: octets.u8(37)?,
+                    },
+                },
+            ),
+            _ => anyhow::bail!(
+                "Product definition template 4.{template_number} is not supported (only 4.0 and \
+                 4.1 are supported)"
+            ),
+        };
+        Ok(product_definition)
+    }
+
+    /// The product definition template number. For example, 1 for template 4.1.
+    pub fn template_number(&self) -> u16 {
+        match self {
+            Self::AnalysisOrForecastAtHorizontalLevel(_) => 0,
+            Self::IndividualEnsembleForecastAtHorizontalLevel(_) => 1,
+        }
+    }
+
+    /// The fields which are common to every template.
+    pub fn common(&self) -> &ProductTemplateCommon {
+        match self {
+            Self::AnalysisOrForecastAtHorizontalLevel(forecast) => &forecast.common,
+            Self::IndividualEnsembleForecastAtHorizontalLevel(ensemble_forecast) => {
+                &ensemble_forecast.common
+            }
+        }
+    }
+
+    /// Returns `None` for deterministic (non-ensemble) messages.
+    pub fn ensemble(&self) -> Option<&Ensemble> {
+        match self {
+            Self::AnalysisOrForecastAtHorizontalLevel(_) => None,
+            Self::IndividualEnsembleForecastAtHorizontalLevel(ensemble_forecast) => {
+                Some(&ensemble_forecast.ensemble)
+            }
+        }
+    }
+}
+
+/// The fields at the start of the product definition templates (octets 10 to 34), which are
+/// shared by templates 4.0 and 4.1. New templates which start with the same fields should embed
+/// this struct, so that it's parsed once by `ProductTemplateCommon::from_octets`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProductTemplateCommon {
+    /// Code table 4.1. (The discipline is in section 0.)
+    pub parameter_category: u8,
+    /// Code table 4.2.
+    pub parameter_number: u8,
+    /// Code table 4.3. For example, 0 is an analysis, and 2 is a forecast.
+    pub type_of_generating_process: u8,
+    /// Defined by the originating center.
+    pub background_generating_process_identifier: u8,
+    /// Defined by the originating center (e.g. the model version).
+    pub analysis_or_forecast_generating_process_identifier: u8,
+    pub hours_after_data_cutoff: u16,
+    pub minutes_after_data_cutoff: u8,
+    /// Code table 4.4. For example, 1 is hours.
+    pub indicator_of_unit_of_time_range: u8,
+    /// The forecast step, in units of `indicator_of_unit_of_time_range`.
+    pub forecast_time: u32,
+    pub first_fixed_surface: FixedSurface,
+    pub second_fixed_surface: FixedSurface,
+}
+
+impl ProductTemplateCommon {
+    /// Octet numbers are relative to the start of section 4, as in the GRIB2 spec.
+    fn from_octets(octets: &Octets) -> anyhow::Result<Self> {
+        Ok(Self {
+            parameter_category: octets.u8(10)?,
+            parameter_number: octets.u8(11)?,
+            type_of_generating_process: octets.u8(12)?,
+            background_generating_process_identifier: octets.u8(13)?,
+            analysis_or_forecast_generating_process_identifier: octets.u8(14)?,
+            hours_after_data_cutoff: octets.u16(15)?,
+            minutes_after_data_cutoff: octets.u8(17)?,
+            indicator_of_unit_of_time_range: octets.u8(18)?,
+            forecast_time: octets.u32(19)?,
+            first_fixed_surface: FixedSurface::from_octets(octets, 23)?,
+            second_fixed_surface: FixedSurface::from_octets(octets, 29)?,
+        })
+    }
+}
+
+/// Product definition template 4.0: An analysis or forecast at a horizontal level or in a
+/// horizontal layer at a point in time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AnalysisOrForecastAtHorizontalLevel {
+    pub common: ProductTemplateCommon,
+}
+
+/// Product definition template 4.1: An individual ensemble forecast, control, or perturbed, at a
+/// horizontal level or in a horizontal layer at a point in time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IndividualEnsembleForecastAtHorizontalLevel {
+    pub common: ProductTemplateCommon,
+    pub ensemble: Ensemble,
+}
+
+/// The ensemble member of a message with product definition template 4.1.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Ensemble {
+    /// Code table 4.6. For example, 0 is the unperturbed high-resolution control forecast, and 3
+    /// is a positively perturbed forecast.
+    pub type_of_ensemble_forecast: u8,
+    /// For example, GEFS's `gep05` has perturbation number 5. The control member is usually 0.
+    pub perturbation_number: u8,
+    pub number_of_forecasts_in_ensemble: u8,
+}
+
+/// A fixed surface (code table 4.5), for example "isobaric surface at 50000 Pa".
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FixedSurface {
+    /// Code table 4.5. For example, 1 is the ground or water surface, 100 is an isobaric surface,
+    /// and 255 is missing.
+    pub type_of_surface: u8,
+    /// `None` if missing.
+    pub scale_factor: Option<i8>,
+    /// `None` if missing.
+    pub scaled_value: Option<i32>,
+}
+
+impl FixedSurface {
+    /// Decode the 6 octets starting at `octet` (type, scale factor, and scaled value).
+    fn from_octets(octets: &Octets, octet: usize) -> anyhow::Result<Self> {
+        let scale_factor = octets.u8(octet + 1)?;
+        let scaled_value = octets.u32(octet + 2)?;
+        Ok(Self {
+            type_of_surface: octets.u8(octet)?,
+            scale_factor: (scale_factor != u8::MAX).then(|| sign_magnitude_u8(scale_factor)),
+            scaled_value: (scaled_value != u32::MAX).then(|| sign_magnitude_u32(scaled_value)),
+        })
+    }
+
+    /// `scaled_value * 10^-scale_factor`, in the unit of `type_of_surface` (e.g. Pa). Returns
+    /// `None` if the surface, the scale factor, or the scaled value is missing. Surfaces without
+    /// a value (e.g. the ground) usually have a value of zero.
+    pub fn value(&self) -> Option<f64> {
+        if self.type_of_surface == MISSING_U8 {
+            return None;
+        }
+        let scale_factor = self.scale_factor?;
+        let scaled_value = self.scaled_value?;
+        Some(f64::from(scaled_value) * 10f64.powi(-i32::from(scale_factor)))
+    }
+}
+
+/// GRIB2 represents negative integers by setting the most significant bit, rather than by two's
+/// complement.
+fn sign_magnitude_u8(value: u8) -> i8 {
+    let magnitude = (value & 0x7f) as i8;
+    if value & 0x80 == 0 {
+        magnitude
+    } else {
+        -magnitude
+    }
+}
+
+/// See `sign_magnitude_u8`.
+fn sign_magnitude_u32(value: u32) -> i32 {
+    let magnitude = (value & 0x7fff_ffff) as i32;
+    if value & 0x8000_0000 == 0 {
+        magnitude
+    } else {
+        -magnitude
+    }
+}
+
+/// Big-endian reads from a section, using the 1-based octet numbers of the GRIB2 spec.
+struct Octets<'a>(&'a [u8]);
+
+impl Octets<'_> {
+    fn get<const N: usize>(&self, octet: usize) -> anyhow::Result<[u8; N]> {
+        self.0
+            .get(octet - 1..octet - 1 + N)
+            .and_then(|bytes| bytes.try_into().ok())
+            .with_context(|| {
+                format!(
+                    "Octet {octet} (and the following {} octets) is beyond the end of the section \
+                     ({} octets)",
+                    N - 1,
+                    self.0.len()
+                )
+            })
+    }
+
+    fn u8(&self, octet: usize) -> anyhow::Result<u8> {
+        Ok(self.get::<1>(octet)?[0])
+    }
+
+    fn u16(&self, octet: usize) -> anyhow::Result<u16> {
+        Ok(u16::from_be_bytes(self.get(octet)?))
+    }
+
+    fn u32(&self, octet: usize) -> anyhow::Result<u32> {
+        Ok(u32::from_be_bytes(self.get(octet)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Section 4 of the DWD ICON message in `hypergrib/src/datasets/icon/test_message.grib2.bz2`:
+    /// A 1-hour forecast at the surface.
+    const TEMPLATE_4_0: [u8; 34] = [
+        0, 0, 0, 34, // Section length
+        4,  // Section number
+        0, 0, // Number of coordinate values after the template
+        0, 0, // Template number
+        1, 34, // Parameter category and number
+        2, 0, 83, // Generating processes
+        0, 0, 0, // Data cutoff
+        1, 0, 0, 0, 1, // Forecast time: 1 hour
+        1, 0, 0, 0, 0, 0, // First fixed surface: The ground
+        255, 0, 0, 0, 0, 0, // Second fixed surface: Missing
+    ];
+
+    #[test]
+    fn test_template_4_0() -> anyhow::Result<()> {
+        // Trailing bytes (e.g. section 5) are ignored:
+        let bytes = [&TEMPLATE_4_0[..], &[0, 0, 0, 21, 5]].concat();
+        let product_definition = ProductDefinition::from_section_bytes(&bytes)?;
+        assert_eq!(product_definition.template_number(), 0);
+        assert_eq!(product_definition.ensemble(), None);
+        let forecast = product_definition.common();
+        assert_eq!(
+            (forecast.parameter_category, forecast.parameter_number),
+            (1, 34)
+        );
+        assert_eq!(forecast.type_of_generating_process, 2);
+        assert_eq!(
+            forecast.analysis_or_forecast_generating_process_identifier,
+            83
+        );
+        assert_eq!(
+            (
+                forecast.indicator_of_unit_of_time_range,
+                forecast.forecast_time
+            ),
+            (1, 1)
+        );
+        assert_eq!(forecast.first_fixed_surface.type_of_surface, 1);
+        assert_eq!(forecast.first_fixed_surface.value(), Some(0.0));
+        assert_eq!(forecast.second_fixed_surface.value(), None);
+
+        // Truncated:
+        assert!(ProductDefinition::from_section_bytes(&TEMPLATE_4_0[..33]).is_err());
+        // Not section 4:
+        let mut section_5 = TEMPLATE_4_0;
+        section_5[4] = 5;
+        assert!(ProductDefinition::from_section_bytes(&section_5).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_template_4_1() -> anyhow::Result<()> {
+        // Like a GEFS `gep05` message: Temperature at 850 hPa (a scaled value of 850 with a scale
+        // factor of -2), 6 hours into the forecast.
+        let mut bytes = TEMPLATE_4_0.to_vec();
+        bytes[3] = 37;
+        bytes[8] = 1;
+        bytes[9..11].copy_from_slice(&[0, 0]);
+        bytes[18..22].copy_from_slice(&6u32.to_be_bytes());
+        bytes[22..28].copy_from_slice(&[100, 0x82, 0, 0, 3, 82]);
+        bytes.extend([3, 5, 31]);
+
+        let product_definition = ProductDefinition::from_section_bytes(&bytes)?;
+        assert_eq!(product_definition.template_number(), 1);
+        assert_eq!(
+            product_definition.ensemble(),
+            Some(&Ensemble {
+                type_of_ensemble_forecast: 3,
+                perturbation_number: 5,
+                number_of_forecasts_in_ensemble: 31,
+            })
+        );
+        let forecast = product_definition.common();
+        assert_eq!(
+            (forecast.parameter_category, forecast.parameter_number),
+            (0, 0)
+        );
+        assert_eq!(forecast.forecast_time, 6);
+        let surface = forecast.first_fixed_surface;
+        assert_eq!(
+            (
+                surface.type_of_surface,
+                surface.scale_factor,
+                surface.scaled_value
+            ),
+            (100, Some(-2), Some(850))
+        );
+        assert_eq!(surface.value(), Some(85_000.0));
+
+        // Template 4.1 without the ensemble octets:
+        bytes.truncate(34);
+        bytes[3] = 34;
+        assert!(ProductDefinition::from_section_bytes(&bytes).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_common_fields_are_shared_by_4_0_and_4_1() -> anyhow::Result<()> {
+        // The same message as `TEMPLATE_4_0`, but as an ensemble member:
+        let mut bytes = TEMPLATE_4_0.to_vec();
+        bytes[3] = 37;
+        bytes[8] = 1;
+        bytes.extend([0, 0, 21]);
+        let deterministic = ProductDefinition::from_section_bytes(&TEMPLATE_4_0)?;
+        let ensemble_member = ProductDefinition::from_section_bytes(&bytes)?;
+        assert_eq!(ensemble_member.template_number(), 1);
+        assert_eq!(ensemble_member.common(), deterministic.common());
+        assert_eq!(
+            ensemble_member
+                .ensemble()
+                .map(|ensemble| ensemble.perturbation_number),
+            Some(0)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_unsupported_template() {
+        let mut bytes = TEMPLATE_4_0;
+        bytes[8] = 8;
+        let error = ProductDefinition::from_section_bytes(&bytes).unwrap_err();
+        assert!(error.to_string().contains("4.8"), "{error}");
+    }
+}
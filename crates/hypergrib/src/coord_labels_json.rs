@@ -0,0 +1,207 @@
+//! Serialize `CoordLabels` to (and from) human-readable JSON. This is useful for debugging, for
+//! diffing two indexing runs, and for reading the coordinate labels from other languages.
+//!
+//! The JSON looks like this:
+//!
+//! ```json
+//! {
+//!   "reference_datetime": ["2017-01-01T00:00:00Z", "2017-01-01T06:00:00Z"],
+//!   "ensemble_member": ["gec00", "gep01"],
+//!   "forecast_step": [0, {"start": 0, "end": 21600}, 21600],
+//!   "parameter": ["HGT", "TMP"],
+//!   "vertical_level": ["surface", "10 mb"]
+//! }
+//! ```
+//!
+//! `reference_datetime` is in RFC 3339 format. `forecast_step` is in seconds: A
+//! `ForecastStep::Instant` is a single number, and a `ForecastStep::Range` is an object with
+//! `start` and `end` fields.
+//!
+//! The `statistic` dimension (e.g. `[null, "10% level", "90% level"]`, where `null` is the label
+//! of deterministic fields) is omitted if the only label is `null`.
+
+use anyhow::Context;
+use chrono::{DateTime, SecondsFormat, TimeDelta, Utc};
+
+use crate::{CoordLabels, ForecastStep, Level, SortedVec, Statistic};
+
+#[derive(serde::Serialize, serde::Deserialize)]
+struct CoordLabelsJson {
+    reference_datetime: Vec<String>,
+    ensemble_member: Vec<String>,
+    forecast_step: Vec<ForecastStepJson>,
+    parameter: Vec<String>,
+    vertical_level: Vec<String>,
+    #[serde(
+        default = "deterministic_only",
+        skip_serializing_if = "is_deterministic_only"
+    )]
+    statistic: Vec<Option<String>>,
+}
+
+fn deterministic_only() -> Vec<Option<String>> {
+    vec![None]
+}
+
+fn is_deterministic_only(statistic: &[Option<String>]) -> bool {
+    statistic == [None]
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(untagged)]
+pub(crate) enum ForecastStepJson {
+    Instant(i64),
+    Range { start: i64, end: i64 },
+}
+
+impl From<&ForecastStep> for ForecastStepJson {
+    fn from(step: &ForecastStep) -> Self {
+        match *step {
+            ForecastStep::Instant(step) => Self::Instant(step.num_seconds()),
+            ForecastStep::Range { start, end } => Self::Range {
+                start: start.num_seconds(),
+                end: end.num_seconds(),
+            },
+        }
+    }
+}
+
+impl From<ForecastStepJson> for ForecastStep {
+    fn from(step: ForecastStepJson) -> Self {
+        match step {
+            ForecastStepJson::Instant(step) => Self::Instant(TimeDelta::seconds(step)),
+            ForecastStepJson::Range { start, end } => Self::Range {
+                start: TimeDelta::seconds(start),
+                end: TimeDelta::seconds(end),
+            },
+        }
+    }
+}
+
+impl CoordLabels {
+    /// Serialize to pretty-printed JSON. See the module-level docs for the format.
+    pub fn to_json(&self) -> anyhow::Result<String> {
+        let coord_labels_json = CoordLabelsJson {
+            reference_datetime: self
+                .reference_datetime
+                .iter()
+                .map(|dt| dt.to_rfc3339_opts(SecondsFormat::Secs, true))
+                .collect(),
+            ensemble_member: self.ensemble_member.to_vec(),
+            forecast_step: self.forecast_step.iter().map(Into::into).collect(),
+            parameter: self.parameter.to_vec(),
+            vertical_level: self.vertical_level.iter().map(Level::to_string).collect(),
+            statistic: self
+                .statistic
+                .iter()
+                .map(|statistic| statistic.as_ref().map(Statistic::to_string))
+                .collect(),
+        };
+        Ok(serde_json::to_string_pretty(&coord_labels_json)?)
+    }
+
+    /// Deserialize from JSON. Returns an error if the labels of any dimension are not sorted
+    /// and unique.
+    pub fn from_json(json: &str) -> anyhow::Result<Self> {
+        let coord_labels_json: CoordLabelsJson = serde_json::from_str(json)?;
+        let reference_datetime = coord_labels_json
+            .reference_datetime
+            .iter()
+            .map(|s| {
+                DateTime::parse_from_rfc3339(s)
+                    .map(|dt| dt.with_timezone(&Utc))
+                    .with_context(|| format!("Failed to parse reference_datetime '{s}'"))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        let forecast_step = coord_labels_json
+            .forecast_step
+            .into_iter()
+            .map(Into::into)
+            .collect();
+        let vertical_level = coord_labels_json
+            .vertical_level
+            .iter()
+            .map(|s| Level::from(s.as_str()))
+            .collect();
+        let statistic = coord_labels_json
+            .statistic
+            .iter()
+            .map(|s| match s {
+                Some(s) => Statistic::from_idx_str(s)
+                    .map(Some)
+                    .with_context(|| format!("Failed to parse statistic '{s}'")),
+                None => Ok(None),
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        Ok(Self::new(
+            SortedVec::try_from_sorted(reference_datetime).context("reference_datetime")?,
+            SortedVec::try_from_sorted(coord_labels_json.ensemble_member)
+                .context("ensemble_member")?,
+            SortedVec::try_from_sorted(forecast_step).context("forecast_step")?,
+            SortedVec::try_from_sorted(coord_labels_json.parameter).context("parameter")?,
+            SortedVec::try_from_sorted(vertical_level).context("vertical_level")?,
+        )
+        .with_statistic(SortedVec::try_from_sorted(statistic).context("statistic")?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ymdh_to_datetime;
+
+    use super::*;
+
+    #[test]
+    fn test_json_round_trip() -> anyhow::Result<()> {
+        let coord_labels = CoordLabels::new(
+            SortedVec::from(vec![
+                ymdh_to_datetime(2017, 1, 1, 0),
+                ymdh_to_datetime(2017, 1, 1, 6),
+            ]),
+            SortedVec::from(vec![String::from("gec00"), String::from("gep01")]),
+            SortedVec::from(vec![
+                ForecastStep::Instant(TimeDelta::zero()),
+                ForecastStep::Range {
+                    start: TimeDelta::zero(),
+                    end: TimeDelta::hours(6),
+                },
+                ForecastStep::Instant(TimeDelta::hours(6)),
+            ]),
+            SortedVec::from(vec![String::from("HGT"), String::from("TMP")]),
+            SortedVec::from(vec![Level::Surface, Level::Isobaric(1_000)]),
+        );
+        let json = coord_labels.to_json()?;
+        assert!(json.contains("\"2017-01-01T06:00:00Z\""));
+        assert!(json.contains("\"start\": 0"));
+        assert!(json.contains("\"end\": 21600"));
+
+        let round_tripped = CoordLabels::from_json(&json)?;
+        assert_eq!(round_tripped, coord_labels);
+        assert_eq!(round_tripped.to_json()?, json);
+
+        // The statistic dimension is only serialized for probabilistic products:
+        assert!(!json.contains("statistic"));
+        let coord_labels = coord_labels.with_statistic(SortedVec::from(vec![
+            None,
+            Some(Statistic::Percentile(10)),
+            Some(Statistic::ProbabilityAbove(254_000)),
+        ]));
+        let json = coord_labels.to_json()?;
+        assert!(json.contains("\"10% level\""));
+        assert!(json.contains("\"prob >0.254\""));
+        assert_eq!(CoordLabels::from_json(&json)?, coord_labels);
+        Ok(())
+    }
+
+    #[test]
+    fn test_from_json_with_unsorted_labels() {
+        let json = r#"{
+            "reference_datetime": [],
+            "ensemble_member": [],
+            "forecast_step": [],
+            "parameter": ["TMP", "HGT"],
+            "vertical_level": []
+        }"#;
+        assert!(CoordLabels::from_json(json).is_err());
+    }
+}
@@ -1 +1,5 @@
+pub mod ecmwf_ens;
 pub mod gefs;
+pub mod gefs_reforecast;
+pub mod gfs;
+pub mod icon;
@@ -0,0 +1,73 @@
+//! ECMWF's ensemble forecast (ENS).
+//! https://www.ecmwf.int/en/forecasts/datasets/open-data
+
+use chrono::TimeDelta;
+
+use crate::{EnsembleMember, EnsembleNaming, ForecastStepNaming};
+
+pub struct EcmwfEns;
+
+/// ECMWF numbers its ensemble members: `0` is the control member, and `1` to `50` are the
+/// perturbed members. The ensemble mean and spread aren't numbered, so aren't supported here.
+impl EnsembleNaming for EcmwfEns {
+    fn format_member(member: &EnsembleMember) -> Option<String> {
+        match *member {
+            EnsembleMember::Control => Some(String::from("0")),
+            EnsembleMember::Perturbed(i @ 1..=50) => Some(i.to_string()),
+            _ => None,
+        }
+    }
+
+    fn parse_member(s: &str) -> Option<EnsembleMember> {
+        let member = match s.parse::<u16>().ok()? {
+            0 => EnsembleMember::Control,
+            i => EnsembleMember::Perturbed(i),
+        };
+        // Only accept names which round-trip (e.g. reject "01" and "51").
+        (Self::format_member(&member).as_deref() == Some(s)).then_some(member)
+    }
+}
+
+/// ECMWF's open data formats forecast steps as the number of hours followed by `h`, without
+/// zero-padding (e.g. `0h`, `6h`, and `144h`). Steps are 3-hourly up to 144 hours, and 6-hourly
+/// after that.
+impl ForecastStepNaming for EcmwfEns {
+    fn format_forecast_step(forecast_step: &TimeDelta) -> String {
+        format!("{}h", forecast_step.num_hours())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ensemble_naming() {
+        for (s, member) in [
+            ("0", EnsembleMember::Control),
+            ("1", EnsembleMember::Perturbed(1)),
+            ("50", EnsembleMember::Perturbed(50)),
+        ] {
+            assert_eq!(EcmwfEns::parse_member(s), Some(member));
+            assert_eq!(EcmwfEns::format_member(&member).as_deref(), Some(s));
+        }
+        assert_eq!(EcmwfEns::format_member(&EnsembleMember::Mean), None);
+        assert_eq!(
+            EcmwfEns::format_member(&EnsembleMember::Perturbed(51)),
+            None
+        );
+        for s in ["01", "51", "-1", "gec00"] {
+            assert_eq!(EcmwfEns::parse_member(s), None, "{s}");
+        }
+    }
+
+    #[test]
+    fn test_format_forecast_step() {
+        assert_eq!(EcmwfEns::format_forecast_step(&TimeDelta::zero()), "0h");
+        assert_eq!(EcmwfEns::format_forecast_step(&TimeDelta::hours(3)), "3h");
+        assert_eq!(
+            EcmwfEns::format_forecast_step(&TimeDelta::hours(360)),
+            "360h"
+        );
+    }
+}
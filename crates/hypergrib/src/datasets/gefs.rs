@@ -1,77 +1,437 @@
 //! NOAA's Global Ensemble Forecast System (GEFS).
 //! https://registry.opendata.aws/noaa-gefs
 
+mod parameter_set;
 mod test_utils;
 mod version;
-pub(crate) use version::Version;
+pub use parameter_set::ParameterSets;
+pub use version::Version;
 
-use chrono::{TimeDelta, Timelike};
+use anyhow::Context;
+use chrono::{DateTime, NaiveDate, TimeDelta, Timelike, Utc};
 
-struct Gefs;
+use crate::{
+    forecast_step_naming::format_hours, EnsembleMember, EnsembleNaming, ForecastStepNaming,
+};
 
-impl crate::ToIdxPath for Gefs {
-    fn to_idx_path(
-        reference_datetime: &chrono::DateTime<chrono::Utc>,
-        _parameter: &str,
-        _vertical_level: &str,
+pub struct Gefs;
+
+impl Gefs {
+    /// The GRIB originating center code of GEFS (NCEP). Every message in a dataset shares the same
+    /// originating center, so it isn't stored in each `Key`. Use this (e.g. via
+    /// `grib_tables::Center::from`) to decode the `.idx` abbreviations of GEFS messages.
+    pub const ORIGINATING_CENTER: u16 = 7;
+
+    /// The names of directories which contain no GRIB data, and so should be skipped when
+    /// listing. See the docs for `Version::V3`.
+    pub const NON_GRIB_DIRS: &'static [&'static str] = &["bufr", "init", "station"];
+
+    /// Extract the coordinates from the path of an `atmos` `.idx` file. This is the inverse of
+    /// `Gefs::to_idx_path`, and works for all `Version`s. For example,
+    /// `gefs.20241008/00/atmos/pgrb2ap5/geavg.t00z.pgrb2a.0p50.f006.idx` has reference datetime
+    /// 2024-10-08T00, ensemble member `geavg`, forecast step 6 hours, parameter set `a`, and
+    /// resolution `0p50`.
+    ///
+    /// The path must start with the reference datetime (i.e. `gefs.%Y%m%d/%H`). Returns an error
+    /// if the structure of the path doesn't match the `Version` of the reference datetime.
+    pub fn from_idx_path(path: &object_store::path::Path) -> anyhow::Result<PathCoords> {
+        let error_context = |s| format!("{s} when parsing GEFS path: '{path}'");
+        let parts: Vec<_> = path.parts().collect();
+        let [date, init_hour, .., filename] = &parts[..] else {
+            anyhow::bail!(error_context("Too few parts"));
+        };
+        let date = NaiveDate::parse_from_str(date.as_ref(), "gefs.%Y%m%d")
+            .with_context(|| error_context("Failed to parse the date"))?;
+        let hour: u32 = init_hour
+            .as_ref()
+            .parse()
+            .with_context(|| error_context("Failed to parse the init hour"))?;
+        let reference_datetime = date
+            .and_hms_opt(hour, 0, 0)
+            .with_context(|| error_context("Invalid init hour"))?
+            .and_utc();
+        let version = Version::try_from_path(path)?;
+
+        let stem = filename
+            .as_ref()
+            .strip_suffix(".idx")
+            .with_context(|| error_context("Missing `.idx` extension"))?;
+        let (ensemble_member, init_hour_from_filename, product, resolution, forecast_step) =
+            match stem.split('.').collect::<Vec<_>>()[..] {
+                // V0, V1, and V2, e.g. `gec00.t00z.pgrb2af006`:
+                [member, init_hour, product_and_step] if version != Version::V3 => {
+                    let (product, step) = product_and_step
+                        .split_at_checked("pgrb2a".len())
+                        .unwrap_or((product_and_step, ""));
+                    (member, init_hour, product, None, step)
+                }
+                // V3 (and the V3-like paths of V2), e.g. `gec00.t00z.pgrb2a.0p50.f006`:
+                [member, init_hour, product, resolution, step]
+                    if matches!(version, Version::V2 | Version::V3) =>
+                {
+                    (member, init_hour, product, Some(resolution), step)
+                }
+                _ => anyhow::bail!(error_context(&format!(
+                    "Unrecognised filename for {version:?}"
+                ))),
+            };
+        if Self::parse_member(ensemble_member).is_none() {
+            anyhow::bail!(error_context("Unrecognised ensemble member"));
+        }
+        if init_hour_from_filename != format!("t{}z", init_hour.as_ref()) {
+            anyhow::bail!(error_context(
+                "The init hour in the filename doesn't match the directory"
+            ));
+        }
+        let parameter_set = match product.strip_prefix("pgrb2") {
+            Some(set) if set.len() == 1 => set.chars().next().unwrap(),
+            _ => anyhow::bail!(error_context("Unrecognised parameter set")),
+        };
+        let forecast_step = match forecast_step {
+            "anl" => TimeDelta::zero(),
+            _ => forecast_step
+                .strip_prefix('f')
+                .and_then(|hours| hours.parse().ok())
+                .map(TimeDelta::hours)
+                .with_context(|| error_context("Failed to parse the forecast step"))?,
+        };
+        Ok(PathCoords {
+            reference_datetime,
+            ensemble_member: ensemble_member.to_string(),
+            forecast_step,
+            parameter_set,
+            resolution: resolution.map(str::to_string),
+        })
+    }
+}
+
+/// The coordinates encoded in the path of a GEFS `.idx` file. See `Gefs::from_idx_path`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PathCoords {
+    pub reference_datetime: DateTime<Utc>,
+    pub ensemble_member: String,
+    pub forecast_step: TimeDelta,
+    /// The letter of the parameter set, e.g. `a` for `pgrb2a` files.
+    pub parameter_set: char,
+    /// The horizontal resolution, e.g. `0p50` for 0.5 degrees. `None` if the path doesn't
+    /// include the resolution (which is the case before `Version::V3`).
+    pub resolution: Option<String>,
+}
+
+/// GEFS names its ensemble members `gec00` (control), `gep01` to `gep30` (perturbed), `geavg`
+/// (mean), and `gespr` (spread).
+impl EnsembleNaming for Gefs {
+    fn format_member(member: &EnsembleMember) -> Option<String> {
+        match *member {
+            EnsembleMember::Control => Some(String::from("gec00")),
+            EnsembleMember::Perturbed(i @ 1..=30) => Some(format!("gep{i:02}")),
+            EnsembleMember::Perturbed(_) => None,
+            EnsembleMember::Mean => Some(String::from("geavg")),
+            EnsembleMember::Spread => Some(String::from("gespr")),
+        }
+    }
+
+    fn parse_member(s: &str) -> Option<EnsembleMember> {
+        match s {
+            "gec00" => Some(EnsembleMember::Control),
+            "geavg" => Some(EnsembleMember::Mean),
+            "gespr" => Some(EnsembleMember::Spread),
+            _ => {
+                let i: u16 = s.strip_prefix("gep")?.parse().ok()?;
+                let member = EnsembleMember::Perturbed(i);
+                // Only accept names which round-trip (e.g. reject "gep1" and "gep00").
+                (Self::format_member(&member).as_deref() == Some(s)).then_some(member)
+            }
+        }
+    }
+}
+
+/// GEFS formats forecast steps as `f006`. Note that, before GEFS v12, the analysis step is named
+/// `anl` rather than `f000` (see `Gefs::grib_path`).
+impl ForecastStepNaming for Gefs {
+    fn format_forecast_step(forecast_step: &TimeDelta) -> String {
+        format_hours(forecast_step, 3)
+    }
+}
+
+impl Gefs {
+    /// The path of the GRIB file of the parameter set `set` (e.g. `b` for `pgrb2b`), for example
+    /// `gefs.20241010/00/atmos/pgrb2bp5/gec00.t00z.pgrb2b.0p50.f006`. Use
+    /// `ParameterSets::to_grib_path` to find the parameter set of a parameter.
+    ///
+    /// TODO: For `Version::V3`, find whether the parameter belongs to 'atmos', 'chem', or
+    /// 'wave'. For now, we always return the path to an 'atmos' file.
+    pub fn grib_path(
+        reference_datetime: &DateTime<Utc>,
         forecast_step: &TimeDelta,
-        ensemble_member: Option<&str>,
-    ) -> object_store::path::Path {
-        // TODO: The code below only works for "old" (gefs::Version::V1) GEFS paths.
-        // Change this function to work with all gefs::Versions. And, for "Version::V3",
-        // have a `phf::Map` (or maybe just a `HashMap`) which tells us whether
-        // the  parameter belongs to 'atmos', 'chem', 'wave'; and 'pgrb2a' or 'pgrb2b' etc.
-        let mut parts = Vec::<object_store::path::PathPart>::with_capacity(3);
-
-        // First part of the Path:
-        parts.push(reference_datetime.format("gefs.%Y%m%d").to_string().into());
+        ensemble_member: &str,
+        set: char,
+    ) -> anyhow::Result<object_store::path::Path> {
+        let version = Version::try_from_reference_datetime(reference_datetime)?;
+        let mut parts = Vec::<object_store::path::PathPart>::with_capacity(5);
 
-        // Second part of the Path:
+        // The first two parts of the Path are the same for all versions:
+        parts.push(reference_datetime.format("gefs.%Y%m%d").to_string().into());
         let init_hour = format!("{:02}", reference_datetime.hour());
         parts.push(init_hour.as_str().into());
 
-        // Third part of the Path:
-        let ensemble_member = ensemble_member.expect("GEFS requires the ensemble member!");
-        let forecast_step = if *forecast_step == TimeDelta::zero() {
-            "anl".to_string()
-        } else {
-            format!("f{:03}", forecast_step.num_hours())
+        // The directories (if any) between the init hour and the filename:
+        match version {
+            Version::V0 => (),
+            Version::V1 | Version::V2 => parts.push(format!("pgrb2{set}").into()),
+            Version::V3 => {
+                parts.push("atmos".into());
+                parts.push(format!("pgrb2{set}p5").into());
+            }
+        }
+
+        // The filename:
+        let filename = match version {
+            Version::V0 | Version::V1 | Version::V2 => {
+                let forecast_step = if *forecast_step == TimeDelta::zero() {
+                    "anl".to_string()
+                } else {
+                    Self::format_forecast_step(forecast_step)
+                };
+                format!("{ensemble_member}.t{init_hour}z.pgrb2{set}{forecast_step}")
+            }
+            // Note that V3 `atmos` files have no file extension, whereas `chem` and `wave`
+            // files end with `.grib2`.
+            Version::V3 => format!(
+                "{ensemble_member}.t{init_hour}z.pgrb2{set}.0p50.{}",
+                Self::format_forecast_step(forecast_step)
+            ),
         };
-        parts.push(
-            format!(
-                "{ensemble_member}.t{init_hour}z.pgrb2a{forecast_step}",
-                ensemble_member = ensemble_member,
-                init_hour = init_hour,
-                forecast_step = forecast_step,
-            )
-            .into(),
-        );
-        object_store::path::Path::from_iter(parts)
+        parts.push(filename.into());
+        Ok(object_store::path::Path::from_iter(parts))
+    }
+
+    /// The path of the `.idx` file which describes the GRIB file at `Gefs::grib_path`.
+    pub fn idx_path(
+        reference_datetime: &DateTime<Utc>,
+        forecast_step: &TimeDelta,
+        ensemble_member: &str,
+        set: char,
+    ) -> anyhow::Result<object_store::path::Path> {
+        let grib_path = Self::grib_path(reference_datetime, forecast_step, ensemble_member, set)?;
+        Ok(object_store::path::Path::from(format!("{grib_path}.idx")))
+    }
+}
+
+impl crate::FromIdxPath for Gefs {
+    fn ensemble_member_from_idx_path(
+        idx_path: &object_store::path::Path,
+    ) -> anyhow::Result<String> {
+        Ok(Self::from_idx_path(idx_path)?.ensemble_member)
     }
 }
 
 #[cfg(test)]
 mod tests {
 
-    use crate::{ymdh_to_datetime, ToIdxPath};
+    use crate::ymdh_to_datetime;
+    use test_utils::load_gefs_test_paths_csv;
 
     use super::*;
 
     #[test]
-    fn test_to_idx_path() -> anyhow::Result<()> {
-        // TODO: Once `Gefs::to_idx_path` knows how to output different paths for different
-        // `GefsVersion`s, then update this test to use `GEFS_TEST_DATA`.
-        let p = Gefs::to_idx_path(
+    fn test_idx_path() -> anyhow::Result<()> {
+        let p = Gefs::idx_path(
             &ymdh_to_datetime(2017, 1, 1, 0),
-            "HGT",
-            "10 mb",
             &TimeDelta::hours(6),
-            Some("gec00"),
-        );
+            "gec00",
+            'a',
+        )?;
         assert_eq!(
             p,
-            object_store::path::Path::from("gefs.20170101/00/gec00.t00z.pgrb2af006")
+            object_store::path::Path::from("gefs.20170101/00/gec00.t00z.pgrb2af006.idx")
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_grib_path() -> anyhow::Result<()> {
+        let grib_path = |reference_datetime, forecast_hour, set| {
+            Gefs::grib_path(
+                &reference_datetime,
+                &TimeDelta::hours(forecast_hour),
+                "gec00",
+                set,
+            )
+        };
+
+        // V0:
+        assert_eq!(
+            grib_path(ymdh_to_datetime(2017, 1, 1, 0), 0, 'a')?,
+            object_store::path::Path::from("gefs.20170101/00/gec00.t00z.pgrb2aanl")
+        );
+
+        // V1:
+        assert_eq!(
+            grib_path(ymdh_to_datetime(2018, 7, 27, 6), 6, 'a')?,
+            object_store::path::Path::from("gefs.20180727/06/pgrb2a/gec00.t06z.pgrb2af006")
+        );
+
+        // V3:
+        assert_eq!(
+            grib_path(ymdh_to_datetime(2024, 10, 10, 0), 0, 'a')?,
+            object_store::path::Path::from(
+                "gefs.20241010/00/atmos/pgrb2ap5/gec00.t00z.pgrb2a.0p50.f000"
+            )
+        );
+        assert_eq!(
+            Gefs::idx_path(
+                &ymdh_to_datetime(2024, 10, 10, 0),
+                &TimeDelta::hours(840),
+                "gec00",
+                'a',
+            )?,
+            object_store::path::Path::from(
+                "gefs.20241010/00/atmos/pgrb2ap5/gec00.t00z.pgrb2a.0p50.f840.idx"
+            )
+        );
+
+        // Before the start of the dataset:
+        assert!(grib_path(ymdh_to_datetime(2000, 1, 1, 0), 0, 'a').is_err());
+
+        // The `pgrb2b` parameter set:
+        assert_eq!(
+            grib_path(ymdh_to_datetime(2017, 1, 1, 0), 330, 'b')?,
+            object_store::path::Path::from("gefs.20170101/00/gec00.t00z.pgrb2bf330")
+        );
+        assert_eq!(
+            grib_path(ymdh_to_datetime(2018, 7, 27, 6), 6, 'b')?,
+            object_store::path::Path::from("gefs.20180727/06/pgrb2b/gec00.t06z.pgrb2bf006")
+        );
+        assert_eq!(
+            grib_path(ymdh_to_datetime(2024, 10, 10, 0), 0, 'b')?,
+            object_store::path::Path::from(
+                "gefs.20241010/00/atmos/pgrb2bp5/gec00.t00z.pgrb2b.0p50.f000"
+            )
         );
         Ok(())
     }
+
+    #[test]
+    fn test_idx_path_matches_real_paths() -> anyhow::Result<()> {
+        // We treat `Version::V2` as if it were `Version::V1`, so skip the V2 paths. And skip
+        // the `pgrb2b` directory in the V1 path, whose filename contains `pgrb2a`.
+        let test_data = load_gefs_test_paths_csv();
+        let test_data = test_data.iter().filter(|t| {
+            t.gefs_version_enum_variant != Version::V2
+                && (t.path.contains(".pgrb2a") || t.path.contains(".pgrb2b"))
+                && !t.path.contains("/pgrb2b/")
+        });
+        let mut n_tested = 0;
+        for t in test_data {
+            let set = if t.path.contains(".pgrb2b") { 'b' } else { 'a' };
+            let p = Gefs::idx_path(
+                &t.reference_datetime,
+                &t.forecast_hour,
+                &t.ensemble_member,
+                set,
+            )?;
+            assert_eq!(format!("noaa-gefs-pds/{p}"), t.path);
+            n_tested += 1;
+        }
+        assert!(n_tested > 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_from_idx_path_round_trips() -> anyhow::Result<()> {
+        for (reference_datetime, forecast_hour, ensemble_member, set) in [
+            // V0:
+            (ymdh_to_datetime(2017, 1, 1, 0), 0, "gec00", 'a'),
+            (ymdh_to_datetime(2017, 1, 1, 0), 330, "gep20", 'b'),
+            // V1:
+            (ymdh_to_datetime(2018, 7, 27, 6), 6, "gec00", 'a'),
+            (ymdh_to_datetime(2018, 7, 27, 18), 0, "geavg", 'b'),
+            // V3:
+            (ymdh_to_datetime(2024, 10, 10, 0), 0, "gec00", 'a'),
+            (ymdh_to_datetime(2024, 10, 10, 12), 840, "gespr", 'b'),
+        ] {
+            let forecast_step = TimeDelta::hours(forecast_hour);
+            let path = Gefs::idx_path(&reference_datetime, &forecast_step, ensemble_member, set)?;
+            let coords = Gefs::from_idx_path(&path)?;
+            assert_eq!(coords.reference_datetime, reference_datetime, "{path}");
+            assert_eq!(coords.ensemble_member, ensemble_member, "{path}");
+            assert_eq!(coords.forecast_step, forecast_step, "{path}");
+            assert_eq!(coords.parameter_set, set, "{path}");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_from_idx_path() -> anyhow::Result<()> {
+        assert_eq!(
+            Gefs::from_idx_path(&object_store::path::Path::from(
+                "gefs.20241008/00/atmos/pgrb2ap5/geavg.t00z.pgrb2a.0p50.f006.idx"
+            ))?,
+            PathCoords {
+                reference_datetime: ymdh_to_datetime(2024, 10, 8, 0),
+                ensemble_member: String::from("geavg"),
+                forecast_step: TimeDelta::hours(6),
+                parameter_set: 'a',
+                resolution: Some(String::from("0p50")),
+            }
+        );
+
+        // The real paths:
+        let test_data = load_gefs_test_paths_csv();
+        let test_data = test_data.iter().filter(|t| t.path.contains(".pgrb2"));
+        let mut n_tested = 0;
+        for t in test_data {
+            let path = t.path.strip_prefix("noaa-gefs-pds/").unwrap();
+            let coords = Gefs::from_idx_path(&object_store::path::Path::from(path))?;
+            assert_eq!(coords.reference_datetime, t.reference_datetime, "{path}");
+            assert_eq!(coords.ensemble_member, t.ensemble_member, "{path}");
+            assert_eq!(coords.forecast_step, t.forecast_hour, "{path}");
+            n_tested += 1;
+        }
+        assert!(n_tested > 0);
+
+        for path in [
+            "gefs.20170101/00/gec00.t00z.pgrb2aanl",
+            "gefs.20170101/00/gec00.t06z.pgrb2aanl.idx",
+            "gefs.20170101/00/gexyz.t00z.pgrb2aanl.idx",
+            "gefs.20170101/00/gec00.t00z.pgrb3aanl.idx",
+            "gefs.20170101/00/gec00.t00z.pgrb2af.idx",
+            "gefs.20170101/gec00.t00z.pgrb2aanl.idx",
+            "gefs.20241008/00/chem/pgrb2ap25/gefs.chem.t00z.a2d_0p25.f000.grib2.idx",
+            // The filename doesn't match the version of the directory structure:
+            "gefs.20241008/00/atmos/pgrb2ap5/geavg.t00z.pgrb2af006.idx",
+            "gefs.20170101/00/gec00.t00z.pgrb2a.0p50.f006.idx",
+        ] {
+            assert!(
+                Gefs::from_idx_path(&object_store::path::Path::from(path)).is_err(),
+                "{path}"
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_format_forecast_step() {
+        assert_eq!(Gefs::format_forecast_step(&TimeDelta::hours(6)), "f006");
+        assert_eq!(Gefs::format_forecast_step(&TimeDelta::hours(240)), "f240");
+    }
+
+    #[test]
+    fn test_ensemble_naming() {
+        for (s, member) in [
+            ("gec00", EnsembleMember::Control),
+            ("gep01", EnsembleMember::Perturbed(1)),
+            ("gep30", EnsembleMember::Perturbed(30)),
+            ("geavg", EnsembleMember::Mean),
+            ("gespr", EnsembleMember::Spread),
+        ] {
+            assert_eq!(Gefs::parse_member(s), Some(member));
+            assert_eq!(Gefs::format_member(&member).as_deref(), Some(s));
+        }
+        assert_eq!(Gefs::format_member(&EnsembleMember::Perturbed(31)), None);
+        for s in ["gep00", "gep1", "gep31", "gec01", "foo"] {
+            assert_eq!(Gefs::parse_member(s), None, "{s}");
+        }
+    }
 }
@@ -0,0 +1,256 @@
+use std::collections::{BTreeSet, HashMap};
+
+use chrono::{DateTime, TimeDelta, Utc};
+use hypergrib_idx_parser::IdxRecord;
+
+use super::Gefs;
+use crate::{CoordLabels, Key, Level};
+
+/// The vertical levels of each parameter in each GEFS parameter set (identified by its letter,
+/// e.g. `b` for `pgrb2b`). GEFS splits its parameters into "parameter sets": The `pgrb2a` files
+/// contain the most commonly used parameters, and the `pgrb2b` files contain the rest. A
+/// parameter may be in several sets at different vertical levels (e.g. `TMP`), so the set depends
+/// on the vertical level as well as the parameter. And the product of all the parameters and all
+/// the vertical levels contains many combinations which don't exist in any set.
+///
+/// Populate `ParameterSets` from the `.idx` files (see `insert_idx`), and then use
+/// `ParameterSets::to_grib_path` to get the path of the GRIB file which contains a parameter.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ParameterSets {
+    sets: HashMap<char, HashMap<String, BTreeSet<Level>>>,
+}
+
+impl ParameterSets {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record that `parameter_set` contains `parameter` at `vertical_level`.
+    pub fn insert(&mut self, parameter_set: char, parameter: &str, vertical_level: Level) {
+        self.sets
+            .entry(parameter_set)
+            .or_default()
+            .entry(parameter.to_string())
+            .or_default()
+            .insert(vertical_level);
+    }
+
+    /// Record every parameter and vertical level in `records`, which were parsed from the `.idx`
+    /// file at `idx_path`. The parameter set is given by the path (see `Gefs::from_idx_path`).
+    pub fn insert_idx(
+        &mut self,
+        idx_path: &object_store::path::Path,
+        records: &[IdxRecord],
+    ) -> anyhow::Result<()> {
+        let parameter_set = Gefs::from_idx_path(idx_path)?.parameter_set;
+        for record in records {
+            self.insert(
+                parameter_set,
+                &record.parameter,
+                record.vertical_level.clone(),
+            );
+        }
+        Ok(())
+    }
+
+    /// The letter of the parameter set which contains `parameter` at `vertical_level`. If
+    /// several sets contain it then the first set in alphabetical order is used (i.e. `pgrb2a`
+    /// takes precedence over `pgrb2b`). Returns an error if no set contains it.
+    pub fn parameter_set(&self, parameter: &str, vertical_level: &Level) -> anyhow::Result<char> {
+        self.sets_containing(parameter, vertical_level)
+            .first()
+            .copied()
+            .ok_or_else(|| {
+                anyhow::format_err!(
+                    "'{parameter}' at '{vertical_level}' isn't in any GEFS parameter set"
+                )
+            })
+    }
+
+    /// The path of the GRIB file which contains `parameter` at `vertical_level`. See
+    /// `Gefs::grib_path` and `ParameterSets::parameter_set`.
+    pub fn to_grib_path(
+        &self,
+        reference_datetime: &DateTime<Utc>,
+        parameter: &str,
+        vertical_level: &Level,
+        forecast_step: &TimeDelta,
+        ensemble_member: &str,
+    ) -> anyhow::Result<object_store::path::Path> {
+        Gefs::grib_path(
+            reference_datetime,
+            forecast_step,
+            ensemble_member,
+            self.parameter_set(parameter, vertical_level)?,
+        )
+    }
+
+    /// The path of the `.idx` file which describes the GRIB file at `ParameterSets::to_grib_path`.
+    pub fn to_idx_path(
+        &self,
+        reference_datetime: &DateTime<Utc>,
+        parameter: &str,
+        vertical_level: &Level,
+        forecast_step: &TimeDelta,
+        ensemble_member: &str,
+    ) -> anyhow::Result<object_store::path::Path> {
+        let grib_path = self.to_grib_path(
+            reference_datetime,
+            parameter,
+            vertical_level,
+            forecast_step,
+            ensemble_member,
+        )?;
+        Ok(object_store::path::Path::from(format!("{grib_path}.idx")))
+    }
+
+    /// The letters of the parameter sets which contain `parameter` at `vertical_level`, in
+    /// alphabetical order.
+    pub fn sets_containing(&self, parameter: &str, vertical_level: &Level) -> Vec<char> {
+        let mut letters: Vec<char> = self
+            .sets
+            .iter()
+            .filter(|(_, parameters)| {
+                parameters
+                    .get(parameter)
+                    .is_some_and(|levels| levels.contains(vertical_level))
+            })
+            .map(|(letter, _)| *letter)
+            .collect();
+        letters.sort_unstable();
+        letters
+    }
+
+    /// Like `CoordLabels::iter_keys`, but only yields keys whose parameter and vertical level
+    /// are in the same parameter set.
+    pub fn iter_keys<'a>(
+        &'a self,
+        coord_labels: &'a CoordLabels,
+    ) -> impl Iterator<Item = Key> + 'a {
+        coord_labels.iter_keys().filter(|key| {
+            self.sets.values().any(|parameters| {
+                parameters
+                    .get(key.parameter())
+                    .is_some_and(|levels| levels.contains(key.vertical_level()))
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_grib_path() -> anyhow::Result<()> {
+        use hypergrib_idx_parser::parse_idx;
+
+        use crate::ymdh_to_datetime;
+
+        let mut parameter_sets = ParameterSets::new();
+        parameter_sets.insert_idx(
+            &object_store::path::Path::from(
+                "gefs.20241010/00/atmos/pgrb2ap5/gec00.t00z.pgrb2a.0p50.f000.idx",
+            ),
+            &parse_idx(
+                b"1:0:d=2024101000:HGT:10 mb:anl:ENS=low-res ctl\n\
+                  2:5000:d=2024101000:TMP:2 m above ground:anl:ENS=low-res ctl\n",
+            )?,
+        )?;
+        parameter_sets.insert_idx(
+            &object_store::path::Path::from(
+                "gefs.20241010/00/atmos/pgrb2bp5/gec00.t00z.pgrb2b.0p50.f000.idx",
+            ),
+            &parse_idx(
+                b"1:0:d=2024101000:O3MR:10 mb:anl:ENS=low-res ctl\n\
+                  2:5000:d=2024101000:TMP:70 mb:anl:ENS=low-res ctl\n",
+            )?,
+        )?;
+
+        let reference_datetime = ymdh_to_datetime(2024, 10, 10, 0);
+        let to_idx_path = |parameter, vertical_level| {
+            parameter_sets.to_idx_path(
+                &reference_datetime,
+                parameter,
+                &vertical_level,
+                &TimeDelta::hours(6),
+                "gec00",
+            )
+        };
+        let pgrb2a = object_store::path::Path::from(
+            "gefs.20241010/00/atmos/pgrb2ap5/gec00.t00z.pgrb2a.0p50.f006.idx",
+        );
+        let pgrb2b = object_store::path::Path::from(
+            "gefs.20241010/00/atmos/pgrb2bp5/gec00.t00z.pgrb2b.0p50.f006.idx",
+        );
+        assert_eq!(to_idx_path("HGT", Level::Isobaric(1_000))?, pgrb2a);
+        assert_eq!(to_idx_path("O3MR", Level::Isobaric(1_000))?, pgrb2b);
+
+        // `TMP` is in both sets, at different vertical levels:
+        assert_eq!(to_idx_path("TMP", Level::HeightAboveGround(2))?, pgrb2a);
+        assert_eq!(to_idx_path("TMP", Level::Isobaric(7_000))?, pgrb2b);
+
+        // `HGT` isn't in any set at 70 mb:
+        assert!(to_idx_path("HGT", Level::Isobaric(7_000)).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_iter_keys_only_yields_levels_of_each_parameter_set() {
+        use chrono::TimeDelta;
+
+        use crate::{ymdh_to_datetime, ForecastStep};
+
+        let mut parameter_sets = ParameterSets::new();
+        parameter_sets.insert('a', "HGT", Level::Isobaric(50_000));
+        parameter_sets.insert('a', "TMP", Level::HeightAboveGround(2));
+        // `O3MR` is only in set 'b', at a level which isn't in set 'a':
+        parameter_sets.insert('b', "O3MR", Level::Isobaric(7_000));
+        parameter_sets.insert('b', "TMP", Level::Isobaric(7_000));
+
+        let coord_labels = CoordLabels::new(
+            vec![ymdh_to_datetime(2017, 1, 1, 0)].into(),
+            vec![String::from("gec00")].into(),
+            vec![ForecastStep::Instant(TimeDelta::zero())].into(),
+            vec![
+                String::from("HGT"),
+                String::from("O3MR"),
+                String::from("TMP"),
+            ]
+            .into(),
+            vec![
+                Level::HeightAboveGround(2),
+                Level::Isobaric(50_000),
+                Level::Isobaric(7_000),
+            ]
+            .into(),
+        );
+        assert_eq!(coord_labels.iter_keys().count(), 9);
+        let keys: Vec<_> = parameter_sets
+            .iter_keys(&coord_labels)
+            .map(|key| {
+                (
+                    key.parameter().to_string(),
+                    key.vertical_level().to_string(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            keys,
+            [
+                ("HGT", "500 mb"),
+                ("O3MR", "70 mb"),
+                ("TMP", "2 m above ground"),
+                ("TMP", "70 mb"),
+            ]
+            .map(|(parameter, level)| (parameter.to_string(), level.to_string()))
+        );
+        assert_eq!(
+            parameter_sets.sets_containing("O3MR", &Level::Isobaric(7_000)),
+            ['b']
+        );
+        assert!(parameter_sets
+            .sets_containing("O3MR", &Level::Isobaric(50_000))
+            .is_empty());
+    }
+}
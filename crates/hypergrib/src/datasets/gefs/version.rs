@@ -1,4 +1,5 @@
-use chrono::{DateTime, Utc};
+use anyhow::Context;
+use chrono::{DateTime, NaiveDateTime, Utc};
 
 use crate::ymdh_to_datetime;
 
@@ -18,7 +19,7 @@ use crate::ymdh_to_datetime;
 /// TODO: Extract the *actual* GEFS model version numbers from the GRIB files and use those as the
 /// enum variant names.
 #[derive(Debug, PartialEq, Clone)]
-pub(crate) enum Version {
+pub enum Version {
     /// GEFS model version 11?
     ///
     /// Paths of the form `gefs.20170101/00/gec00.t00z.pgrb2aanl.idx`
@@ -73,7 +74,7 @@ impl Version {
         }
     }
 
-    fn try_from_reference_datetime(
+    pub(super) fn try_from_reference_datetime(
         query_datetime: &DateTime<Utc>,
     ) -> Result<&'static Self, BeforeStartOfDatasetError> {
         for i in 0..Self::N_VERSIONS - 1 {
@@ -94,13 +95,103 @@ impl Version {
         }
     }
 
+    /// Classify the version from the structure of `path` (i.e. the directories between the init
+    /// hour and the filename), without looking at the reference datetime. `path` may start with
+    /// the bucket name. Returns `None` if the structure isn't recognised.
+    ///
+    /// The structure of `V2` paths is the same as either `V1` or `V3` paths, so this never
+    /// returns `V2`. See `try_from_path`.
+    pub fn from_path(path: &object_store::path::Path) -> Option<Self> {
+        let parts: Vec<_> = path.parts().collect();
+        let date_i = parts
+            .iter()
+            .position(|part| part.as_ref().starts_with("gefs."))?;
+        match &parts[date_i + 1..] {
+            [_init_hour, _filename] => Some(Self::V0),
+            [_init_hour, dir, _filename] if matches!(dir.as_ref(), "pgrb2a" | "pgrb2b") => {
+                Some(Self::V1)
+            }
+            [_init_hour, dir, ..] if matches!(dir.as_ref(), "atmos" | "chem" | "wave") => {
+                Some(Self::V3)
+            }
+            _ => None,
+        }
+    }
+
+    /// Like `from_path`, but cross-checks the version classified from the structure of `path`
+    /// against the version of the reference datetime in `path`. Returns `V2` for `V1`-like and
+    /// `V3`-like paths of the two `V2` reference datetimes. Returns an error if the two versions
+    /// don't match, which probably means that NOAA has changed the structure of the paths again.
+    pub fn try_from_path(path: &object_store::path::Path) -> anyhow::Result<Self> {
+        let from_structure =
+            Self::from_path(path).with_context(|| format!("Unrecognised GEFS path: '{path}'"))?;
+        let from_reference_datetime =
+            Self::try_from_reference_datetime(&reference_datetime_from_path(path)?)?;
+        match (from_structure, from_reference_datetime) {
+            (a, b) if a == *b => Ok(a),
+            (Self::V1 | Self::V3, Self::V2) => Ok(Self::V2),
+            (from_structure, from_reference_datetime) => Err(VersionMismatchError {
+                from_structure,
+                from_reference_datetime: from_reference_datetime.clone(),
+            }
+            .into()),
+        }
+    }
+
     pub(crate) const fn all_versions() -> [Self; Self::N_VERSIONS] {
         Self::ALL_VERSIONS
     }
 }
 
+/// The reference datetime of a GEFS path, from the `gefs.%Y%m%d/%H` parts of the path.
+fn reference_datetime_from_path(path: &object_store::path::Path) -> anyhow::Result<DateTime<Utc>> {
+    let mut parts = path
+        .parts()
+        .skip_while(|part| !part.as_ref().starts_with("gefs."));
+    let (Some(date), Some(init_hour)) = (parts.next(), parts.next()) else {
+        anyhow::bail!("Failed to find the reference datetime in GEFS path: '{path}'");
+    };
+    NaiveDateTime::parse_from_str(
+        &format!("{}{}00", date.as_ref(), init_hour.as_ref()),
+        "gefs.%Y%m%d%H%M",
+    )
+    .map(|datetime| datetime.and_utc())
+    .with_context(|| format!("Failed to parse the reference datetime of GEFS path: '{path}'"))
+}
+
+/// The structure of a GEFS path doesn't match the `Version` of its reference datetime.
+#[derive(Debug)]
+pub(super) struct VersionMismatchError {
+    from_structure: Version,
+    from_reference_datetime: Version,
+}
+
+impl std::fmt::Display for VersionMismatchError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "The structure of the path looks like {:?}, but the reference datetime is in {:?}",
+            self.from_structure, self.from_reference_datetime
+        )
+    }
+}
+
+impl std::error::Error for VersionMismatchError {}
+
 #[derive(Debug)]
-struct BeforeStartOfDatasetError;
+pub(super) struct BeforeStartOfDatasetError;
+
+impl std::fmt::Display for BeforeStartOfDatasetError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "The reference datetime is before the start of the GEFS dataset ({})",
+            Version::V0.start_reference_datetime()
+        )
+    }
+}
+
+impl std::error::Error for BeforeStartOfDatasetError {}
 
 #[cfg(test)]
 mod tests {
@@ -121,4 +212,57 @@ mod tests {
                 )
             });
     }
+
+    #[test]
+    fn test_from_path() {
+        load_gefs_test_paths_csv()
+            .iter()
+            .for_each(|gefs_test_struct| {
+                let path = object_store::path::Path::from(gefs_test_struct.path.as_str());
+                let expected = &gefs_test_struct.gefs_version_enum_variant;
+                if *expected != Version::V2 {
+                    assert_eq!(Version::from_path(&path).as_ref(), Some(expected), "{path}");
+                }
+                assert_eq!(&Version::try_from_path(&path).unwrap(), expected, "{path}");
+            });
+
+        // V0, V1, and V3 paths, classified by structure alone:
+        for (path, expected) in [
+            ("gefs.20170101/00/gec00.t00z.pgrb2aanl.idx", Version::V0),
+            (
+                "gefs.20180727/00/pgrb2b/gec00.t00z.pgrb2banl.idx",
+                Version::V1,
+            ),
+            (
+                "gefs.20241008/00/atmos/pgrb2ap5/geavg.t00z.pgrb2a.0p50.f000.idx",
+                Version::V3,
+            ),
+            (
+                "gefs.20241008/00/chem/pgrb2ap25/gefs.chem.t00z.a2d_0p25.f000.grib2.idx",
+                Version::V3,
+            ),
+        ] {
+            let path = object_store::path::Path::from(path);
+            assert_eq!(Version::from_path(&path), Some(expected), "{path}");
+        }
+        assert_eq!(
+            Version::from_path(&object_store::path::Path::from("foo/bar.idx")),
+            None
+        );
+
+        // The V2 reference datetimes contain both V1-like and V3-like paths:
+        for path in [
+            "gefs.20200923/00/pgrb2a/gec00.t00z.pgrb2aanl.idx",
+            "gefs.20200923/00/atmos/pgrb2ap5/gec00.t00z.pgrb2a.0p50.f000.idx",
+        ] {
+            let path = object_store::path::Path::from(path);
+            assert_eq!(Version::try_from_path(&path).unwrap(), Version::V2);
+        }
+
+        // A V0-like path with a V3 reference datetime:
+        let path = object_store::path::Path::from("gefs.20241008/00/gec00.t00z.pgrb2aanl.idx");
+        assert_eq!(Version::from_path(&path), Some(Version::V0));
+        let error = Version::try_from_path(&path).unwrap_err();
+        assert!(error.downcast_ref::<VersionMismatchError>().is_some());
+    }
 }
@@ -0,0 +1,255 @@
+//! NOAA's GEFS Reforecast: 20 years (2000-2019) of GEFSv12 reforecasts.
+//! https://registry.opendata.aws/noaa-gefs-reforecast
+//!
+//! The layout is completely different to the operational GEFS. Each GRIB file contains one
+//! parameter at one "level type" (e.g. all the isobaric levels), for every forecast step in a
+//! range of days. For example:
+//!
+//! `GEFSv12/reforecast/2000/2000010100/c00/Days:1-10/tmp_2m_2000010100_c00.grib2`
+//!
+//! The forecast steps are split across two directories: `Days:1-10` holds steps up to and
+//! including 240 hours, and `Days:10-16` holds the steps after 240 hours (up to 384 hours).
+//!
+//! Reforecasts are only initialised at 00 UTC. There are 5 ensemble members (`c00` and `p01`
+//! to `p04`), except on Wednesdays, which have 11 members (`c00` and `p01` to `p10`).
+
+use chrono::{DateTime, NaiveDateTime, TimeDelta, Utc};
+
+use crate::{EnsembleMember, EnsembleNaming, Level};
+
+pub struct GefsReforecast;
+
+impl GefsReforecast {
+    /// The prefix of the reforecast within the `noaa-gefs-retrospective` bucket.
+    pub const BASE_PATH: &'static str = "GEFSv12/reforecast";
+
+    /// The maximum forecast step (in hours) in the `Days:1-10` directory.
+    pub const DAYS_1_TO_10_MAX_STEP_HOURS: i64 = 240;
+
+    /// The maximum forecast step (in hours) in the `Days:10-16` directory.
+    pub const DAYS_10_TO_16_MAX_STEP_HOURS: i64 = 384;
+
+    /// The name of the directory which holds `forecast_step`.
+    pub fn days_dir(forecast_step: &TimeDelta) -> anyhow::Result<&'static str> {
+        match forecast_step.num_hours() {
+            h if (0..=Self::DAYS_1_TO_10_MAX_STEP_HOURS).contains(&h) => Ok("Days:1-10"),
+            h if h <= Self::DAYS_10_TO_16_MAX_STEP_HOURS && h > 0 => Ok("Days:10-16"),
+            h => Err(anyhow::format_err!(
+                "The GEFS reforecast doesn't include forecast step {h} hours"
+            )),
+        }
+    }
+
+    /// The "level type" part of the filename for `vertical_level` (e.g. `2m` for
+    /// "2 m above ground").
+    ///
+    /// TODO: Some parameters split their isobaric levels across two files: `pres` and
+    /// `pres_abv700mb`. For now, isobaric levels always map to `pres`.
+    pub fn level_type(vertical_level: &str) -> anyhow::Result<&'static str> {
+        match Level::from(vertical_level) {
+            Level::Surface => Ok("sfc"),
+            Level::HeightAboveGround(2) => Ok("2m"),
+            Level::HeightAboveGround(_) => Ok("hgt"),
+            Level::Isobaric(_) => Ok("pres"),
+            Level::Other(s) if s == "mean sea level" => Ok("msl"),
+            Level::Other(s) if s.starts_with("entire atmosphere") => Ok("eatm"),
+            _ => Err(anyhow::format_err!(
+                "Unknown GEFS reforecast level type for '{vertical_level}'"
+            )),
+        }
+    }
+
+    /// Parse a GRIB (or `.idx`) filename like `tmp_2m_2000010100_c00.grib2`. Returns the
+    /// parameter (lowercase, as in the filename), the level type, the reference datetime, and
+    /// the ensemble member. Returns `None` if `filename` doesn't match the layout.
+    pub fn parse_filename(filename: &str) -> Option<ReforecastFilename> {
+        let stem = filename
+            .strip_suffix(".idx")
+            .unwrap_or(filename)
+            .strip_suffix(".grib2")?;
+        let (rest, ensemble_member) = stem.rsplit_once('_')?;
+        let (rest, init) = rest.rsplit_once('_')?;
+        let (parameter, level_type) = rest.split_once('_')?;
+        let reference_datetime = NaiveDateTime::parse_from_str(&format!("{init}00"), "%Y%m%d%H%M")
+            .ok()?
+            .and_utc();
+        Self::parse_member(ensemble_member)?;
+        Some(ReforecastFilename {
+            parameter: parameter.to_string(),
+            level_type: level_type.to_string(),
+            reference_datetime,
+            ensemble_member: ensemble_member.to_string(),
+        })
+    }
+}
+
+/// The coordinates encoded in a GEFS reforecast filename. See `GefsReforecast::parse_filename`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReforecastFilename {
+    pub parameter: String,
+    pub level_type: String,
+    pub reference_datetime: DateTime<Utc>,
+    pub ensemble_member: String,
+}
+
+/// The GEFS reforecast names its ensemble members `c00` (control) and `p01` to `p10`
+/// (perturbed). There is no ensemble mean or spread.
+impl EnsembleNaming for GefsReforecast {
+    fn format_member(member: &EnsembleMember) -> Option<String> {
+        match *member {
+            EnsembleMember::Control => Some(String::from("c00")),
+            EnsembleMember::Perturbed(i @ 1..=10) => Some(format!("p{i:02}")),
+            _ => None,
+        }
+    }
+
+    fn parse_member(s: &str) -> Option<EnsembleMember> {
+        if s == "c00" {
+            return Some(EnsembleMember::Control);
+        }
+        let i: u16 = s.strip_prefix('p')?.parse().ok()?;
+        let member = EnsembleMember::Perturbed(i);
+        // Only accept names which round-trip (e.g. reject "p1" and "p00").
+        (Self::format_member(&member).as_deref() == Some(s)).then_some(member)
+    }
+}
+
+impl crate::ToGribPath for GefsReforecast {
+    /// Note that each GRIB file contains many forecast steps, so `forecast_step` only selects
+    /// the `Days:*` directory.
+    fn to_grib_path(
+        reference_datetime: &DateTime<Utc>,
+        parameter: &str,
+        vertical_level: &str,
+        forecast_step: &TimeDelta,
+        ensemble_member: Option<&str>,
+    ) -> anyhow::Result<object_store::path::Path> {
+        let ensemble_member = ensemble_member
+            .ok_or_else(|| anyhow::format_err!("GEFS reforecast requires the ensemble member!"))?;
+        let init = reference_datetime.format("%Y%m%d%H").to_string();
+        let filename = format!(
+            "{}_{}_{init}_{ensemble_member}.grib2",
+            parameter.to_lowercase(),
+            Self::level_type(vertical_level)?
+        );
+        let base_path = object_store::path::Path::from(Self::BASE_PATH);
+        Ok(object_store::path::Path::from_iter(
+            base_path.parts().chain([
+                reference_datetime.format("%Y").to_string().into(),
+                init.into(),
+                ensemble_member.into(),
+                Self::days_dir(forecast_step)?.into(),
+                filename.into(),
+            ]),
+        ))
+    }
+}
+
+impl crate::ToIdxPath for GefsReforecast {}
+
+impl crate::FromIdxPath for GefsReforecast {
+    fn ensemble_member_from_idx_path(
+        idx_path: &object_store::path::Path,
+    ) -> anyhow::Result<String> {
+        idx_path
+            .filename()
+            .and_then(Self::parse_filename)
+            .map(|filename| filename.ensemble_member)
+            .ok_or_else(|| {
+                anyhow::format_err!("Unrecognised GEFS reforecast filename: '{idx_path}'")
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{ymdh_to_datetime, ToGribPath, ToIdxPath};
+
+    use super::*;
+
+    #[test]
+    fn test_to_idx_path() -> anyhow::Result<()> {
+        let reference_datetime = ymdh_to_datetime(2000, 1, 1, 0);
+        assert_eq!(
+            GefsReforecast::to_idx_path(
+                &reference_datetime,
+                "TMP",
+                "2 m above ground",
+                &TimeDelta::hours(6),
+                Some("c00"),
+            )?
+            .as_ref(),
+            "GEFSv12/reforecast/2000/2000010100/c00/Days:1-10/tmp_2m_2000010100_c00.grib2.idx"
+        );
+        assert_eq!(
+            GefsReforecast::to_grib_path(
+                &reference_datetime,
+                "APCP",
+                "surface",
+                &TimeDelta::hours(246),
+                Some("p03"),
+            )?
+            .as_ref(),
+            "GEFSv12/reforecast/2000/2000010100/p03/Days:10-16/apcp_sfc_2000010100_p03.grib2"
+        );
+        assert!(GefsReforecast::to_grib_path(
+            &reference_datetime,
+            "TMP",
+            "2 m above ground",
+            &TimeDelta::hours(6),
+            None
+        )
+        .is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_days_dir() -> anyhow::Result<()> {
+        assert_eq!(GefsReforecast::days_dir(&TimeDelta::hours(3))?, "Days:1-10");
+        assert_eq!(
+            GefsReforecast::days_dir(&TimeDelta::hours(240))?,
+            "Days:1-10"
+        );
+        assert_eq!(
+            GefsReforecast::days_dir(&TimeDelta::hours(246))?,
+            "Days:10-16"
+        );
+        assert_eq!(
+            GefsReforecast::days_dir(&TimeDelta::hours(384))?,
+            "Days:10-16"
+        );
+        assert!(GefsReforecast::days_dir(&TimeDelta::hours(390)).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_filename() {
+        assert_eq!(
+            GefsReforecast::parse_filename("hgt_pres_abv700mb_2019123100_p10.grib2.idx"),
+            Some(ReforecastFilename {
+                parameter: String::from("hgt"),
+                level_type: String::from("pres_abv700mb"),
+                reference_datetime: ymdh_to_datetime(2019, 12, 31, 0),
+                ensemble_member: String::from("p10"),
+            })
+        );
+        for filename in [
+            "tmp_2m_2000010100_c00",
+            "tmp_2000010100_c00.grib2",
+            "tmp_2m_2000010100_gec00.grib2",
+            "tmp_2m_20000101_c00.grib2",
+        ] {
+            assert_eq!(GefsReforecast::parse_filename(filename), None, "{filename}");
+        }
+    }
+
+    #[test]
+    fn test_ensemble_naming() {
+        for member in [EnsembleMember::Control, EnsembleMember::Perturbed(10)] {
+            let name = GefsReforecast::format_member(&member).unwrap();
+            assert_eq!(GefsReforecast::parse_member(&name), Some(member));
+        }
+        assert_eq!(GefsReforecast::format_member(&EnsembleMember::Mean), None);
+        assert_eq!(GefsReforecast::parse_member("p11"), None);
+    }
+}
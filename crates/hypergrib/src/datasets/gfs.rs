@@ -0,0 +1,223 @@
+//! NOAA's Global Forecast System (GFS).
+//! https://registry.opendata.aws/noaa-gfs-bdp-pds
+
+use chrono::{DateTime, TimeDelta, Timelike, Utc};
+
+use crate::{
+    forecast_step_naming::format_hours, ymdh_to_datetime, EnsembleMember, EnsembleNaming,
+    ForecastStepNaming,
+};
+
+pub struct Gfs;
+
+/// The horizontal resolution of a GFS grid. Each resolution is a separate set of GRIB files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Resolution {
+    /// 0.25 degrees. The only resolution with hourly forecast steps (for the first 120 hours).
+    Deg0p25,
+    /// 0.5 degrees.
+    Deg0p50,
+    /// 1 degree.
+    Deg1p00,
+}
+
+impl Resolution {
+    pub const ALL: [Self; 3] = [Self::Deg0p25, Self::Deg0p50, Self::Deg1p00];
+
+    /// The token used in GFS filenames, e.g. `0p25`.
+    pub fn token(&self) -> &'static str {
+        match self {
+            Self::Deg0p25 => "0p25",
+            Self::Deg0p50 => "0p50",
+            Self::Deg1p00 => "1p00",
+        }
+    }
+
+    /// The inverse of `token`.
+    pub fn from_token(token: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|resolution| resolution.token() == token)
+    }
+}
+
+/// GFS splits its parameters into two parameter sets: The `pgrb2` files contain the most commonly
+/// used parameters, and the secondary `pgrb2b` files contain the rest. Both sets are available at
+/// every `Resolution`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum ParameterSet {
+    Primary,
+    Secondary,
+}
+
+impl ParameterSet {
+    /// The token used in GFS filenames, e.g. `pgrb2b`.
+    pub fn token(&self) -> &'static str {
+        match self {
+            Self::Primary => "pgrb2",
+            Self::Secondary => "pgrb2b",
+        }
+    }
+
+    /// The inverse of `token`.
+    pub fn from_token(token: &str) -> Option<Self> {
+        [Self::Primary, Self::Secondary]
+            .into_iter()
+            .find(|parameter_set| parameter_set.token() == token)
+    }
+}
+
+impl Gfs {
+    /// The first reference datetime of GFSv16, which moved the GRIB files into an `atmos`
+    /// directory.
+    fn atmos_dir_start() -> DateTime<Utc> {
+        ymdh_to_datetime(2021, 3, 22, 12)
+    }
+
+    /// The path of the GRIB file of `parameter_set` at `resolution`, for example
+    /// `gfs.20241010/00/atmos/gfs.t00z.pgrb2b.0p50.f006`.
+    pub fn grib_path(
+        reference_datetime: &DateTime<Utc>,
+        forecast_step: &TimeDelta,
+        resolution: Resolution,
+        parameter_set: ParameterSet,
+    ) -> object_store::path::Path {
+        let init_hour = format!("{:02}", reference_datetime.hour());
+        let mut parts = Vec::<object_store::path::PathPart>::with_capacity(4);
+        parts.push(reference_datetime.format("gfs.%Y%m%d").to_string().into());
+        parts.push(init_hour.as_str().into());
+        if *reference_datetime >= Self::atmos_dir_start() {
+            parts.push("atmos".into());
+        }
+        let filename = format!(
+            "gfs.t{init_hour}z.{}.{}.{}",
+            parameter_set.token(),
+            resolution.token(),
+            Self::format_forecast_step(forecast_step)
+        );
+        parts.push(filename.into());
+        object_store::path::Path::from_iter(parts)
+    }
+
+    /// The path of the `.idx` file which describes the GRIB file at `Gfs::grib_path`.
+    pub fn idx_path(
+        reference_datetime: &DateTime<Utc>,
+        forecast_step: &TimeDelta,
+        resolution: Resolution,
+        parameter_set: ParameterSet,
+    ) -> object_store::path::Path {
+        let grib_path =
+            Self::grib_path(reference_datetime, forecast_step, resolution, parameter_set);
+        object_store::path::Path::from(format!("{grib_path}.idx"))
+    }
+}
+
+/// GFS is a deterministic model, so it has no ensemble members.
+impl EnsembleNaming for Gfs {
+    fn format_member(_member: &EnsembleMember) -> Option<String> {
+        None
+    }
+
+    fn parse_member(_s: &str) -> Option<EnsembleMember> {
+        None
+    }
+}
+
+/// GFS formats forecast steps as `f006`, including the analysis step (`f000`).
+impl ForecastStepNaming for Gfs {
+    fn format_forecast_step(forecast_step: &TimeDelta) -> String {
+        format_hours(forecast_step, 3)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ensemble_naming() {
+        assert_eq!(Gfs::format_member(&EnsembleMember::Control), None);
+        assert_eq!(Gfs::parse_member("gec00"), None);
+        assert_eq!(Gfs::parse_member("0"), None);
+    }
+
+    #[test]
+    fn test_grib_path() {
+        let reference_datetime = ymdh_to_datetime(2024, 10, 10, 0);
+        let step = TimeDelta::hours(6);
+        let expected = [
+            (
+                Resolution::Deg0p25,
+                ParameterSet::Primary,
+                "gfs.t00z.pgrb2.0p25.f006",
+            ),
+            (
+                Resolution::Deg0p50,
+                ParameterSet::Primary,
+                "gfs.t00z.pgrb2.0p50.f006",
+            ),
+            (
+                Resolution::Deg1p00,
+                ParameterSet::Primary,
+                "gfs.t00z.pgrb2.1p00.f006",
+            ),
+            (
+                Resolution::Deg0p25,
+                ParameterSet::Secondary,
+                "gfs.t00z.pgrb2b.0p25.f006",
+            ),
+            (
+                Resolution::Deg0p50,
+                ParameterSet::Secondary,
+                "gfs.t00z.pgrb2b.0p50.f006",
+            ),
+            (
+                Resolution::Deg1p00,
+                ParameterSet::Secondary,
+                "gfs.t00z.pgrb2b.1p00.f006",
+            ),
+        ];
+        for (resolution, parameter_set, filename) in expected {
+            assert_eq!(
+                Gfs::grib_path(&reference_datetime, &step, resolution, parameter_set),
+                object_store::path::Path::from(format!("gfs.20241010/00/atmos/{filename}"))
+            );
+            assert_eq!(
+                Gfs::idx_path(&reference_datetime, &step, resolution, parameter_set),
+                object_store::path::Path::from(format!("gfs.20241010/00/atmos/{filename}.idx"))
+            );
+        }
+
+        // Before GFSv16, there's no `atmos` directory:
+        assert_eq!(
+            Gfs::grib_path(
+                &ymdh_to_datetime(2021, 3, 22, 6),
+                &TimeDelta::hours(1),
+                Resolution::Deg0p25,
+                ParameterSet::Primary,
+            ),
+            object_store::path::Path::from("gfs.20210322/06/gfs.t06z.pgrb2.0p25.f001")
+        );
+    }
+
+    #[test]
+    fn test_tokens_round_trip() {
+        for resolution in Resolution::ALL {
+            assert_eq!(Resolution::from_token(resolution.token()), Some(resolution));
+        }
+        assert_eq!(Resolution::from_token("0p5"), None);
+        for parameter_set in [ParameterSet::Primary, ParameterSet::Secondary] {
+            assert_eq!(
+                ParameterSet::from_token(parameter_set.token()),
+                Some(parameter_set)
+            );
+        }
+        assert_eq!(ParameterSet::from_token("pgrb2a"), None);
+    }
+
+    #[test]
+    fn test_format_forecast_step() {
+        assert_eq!(Gfs::format_forecast_step(&TimeDelta::zero()), "f000");
+        assert_eq!(Gfs::format_forecast_step(&TimeDelta::hours(6)), "f006");
+    }
+}
@@ -0,0 +1,93 @@
+//! DWD's ICON global model.
+//! https://opendata.dwd.de/weather/nwp/icon/grib/
+//!
+//! ICON's GRIB files are bzip2-compressed, and there are no `.idx` files. So each GRIB file
+//! must be fetched in full and decompressed (see `crate::fetch`). Each GRIB file contains a single
+//! parameter at a single vertical level. Note that DWD only keeps the most recent ~24 hours of
+//! forecasts on its open data server.
+//!
+//! TODO: Implement `GetCoordLabels` by listing the GRIB files.
+
+use chrono::{DateTime, TimeDelta, Timelike, Utc};
+
+use crate::{EnsembleMember, EnsembleNaming, ForecastStepNaming, Level};
+
+pub struct Icon;
+
+/// ICON global is a deterministic model, so it has no ensemble members.
+impl EnsembleNaming for Icon {
+    fn format_member(_member: &EnsembleMember) -> Option<String> {
+        None
+    }
+
+    fn parse_member(_s: &str) -> Option<EnsembleMember> {
+        None
+    }
+}
+
+/// ICON formats forecast steps as the number of hours, zero-padded to 3 digits, without a prefix
+/// (e.g. `006`).
+impl ForecastStepNaming for Icon {
+    fn format_forecast_step(forecast_step: &TimeDelta) -> String {
+        format!("{:03}", forecast_step.num_hours())
+    }
+}
+
+impl crate::ToGribPath for Icon {
+    /// `parameter` is DWD's name for the parameter (e.g. `T_2M` or `T`). Isobaric levels are
+    /// stored in `pressure-level` files. All other levels are treated as `single-level`.
+    ///
+    /// TODO: Support `model-level` and `soil-level` files.
+    fn to_grib_path(
+        reference_datetime: &DateTime<Utc>,
+        parameter: &str,
+        vertical_level: &str,
+        forecast_step: &TimeDelta,
+        _ensemble_member: Option<&str>,
+    ) -> anyhow::Result<object_store::path::Path> {
+        let init_hour = format!("{:02}", reference_datetime.hour());
+        let init = reference_datetime.format("%Y%m%d%H");
+        let step = Self::format_forecast_step(forecast_step);
+        let level_part = match Level::from(vertical_level) {
+            Level::Isobaric(pa) => format!("pressure-level_{init}_{step}_{}", pa / 100),
+            _ => format!("single-level_{init}_{step}"),
+        };
+        let filename = format!("icon_global_icosahedral_{level_part}_{parameter}.grib2.bz2");
+        Ok(object_store::path::Path::from_iter([
+            init_hour.as_str(),
+            parameter.to_lowercase().as_str(),
+            filename.as_str(),
+        ]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{ymdh_to_datetime, ToGribPath};
+
+    use super::*;
+
+    #[test]
+    fn test_to_grib_path() -> anyhow::Result<()> {
+        let reference_datetime = ymdh_to_datetime(2024, 1, 1, 0);
+        assert_eq!(
+            Icon::to_grib_path(
+                &reference_datetime,
+                "T_2M",
+                "2 m above ground",
+                &TimeDelta::hours(6),
+                None
+            )?,
+            object_store::path::Path::from(
+                "00/t_2m/icon_global_icosahedral_single-level_2024010100_006_T_2M.grib2.bz2"
+            )
+        );
+        assert_eq!(
+            Icon::to_grib_path(&reference_datetime, "T", "500 mb", &TimeDelta::zero(), None)?,
+            object_store::path::Path::from(
+                "00/t/icon_global_icosahedral_pressure-level_2024010100_000_500_T.grib2.bz2"
+            )
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,22 @@
+/// An ensemble member, independent of how any particular dataset names its ensemble members.
+#[derive(PartialEq, Eq, Hash, PartialOrd, Ord, Clone, Copy, Debug)]
+pub enum EnsembleMember {
+    /// The control member, which is run from the unperturbed initial conditions.
+    Control,
+    /// A perturbed member, numbered from 1.
+    Perturbed(u16),
+    /// The mean of all the ensemble members.
+    Mean,
+    /// The spread (standard deviation) of the ensemble members.
+    Spread,
+}
+
+/// Convert between `EnsembleMember` and the names that a dataset uses for its ensemble members
+/// (e.g. in its paths).
+pub trait EnsembleNaming {
+    /// Returns `None` if the dataset doesn't contain `member`.
+    fn format_member(member: &EnsembleMember) -> Option<String>;
+
+    /// Returns `None` if `s` isn't a valid ensemble member name for the dataset.
+    fn parse_member(s: &str) -> Option<EnsembleMember>;
+}
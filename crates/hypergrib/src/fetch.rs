@@ -0,0 +1,303 @@
+//! Fetch GRIB messages from an `ObjectStore`, transparently decompressing compressed GRIB files.
+//!
+//! Some datasets (e.g. DWD's ICON) distribute bzip2-compressed GRIB files (`.grib2.bz2`). It's
+//! not possible to read a byte range from the middle of a bzip2 stream, so compressed GRIB files
+//! are always fetched in full and decompressed. The byte offsets of messages in compressed GRIB
+//! files refer to the *decompressed* stream.
+//!
+//! Some mirrors serve gzip-compressed `.idx` files (`.idx.gz`). Use [`maybe_decompress_gzip`] to
+//! transparently decompress them before parsing.
+//!
+//! When fetching many GRIB messages from the same GRIB file, use [`fetch_grib_messages`], which
+//! merges nearby byte ranges into a single GET (see [`coalesce_byte_ranges`]).
+
+use std::{io::Read, ops::Range};
+
+use anyhow::Context;
+use bytes::Bytes;
+use futures_util::future::try_join_all;
+use object_store::{path::Path, ObjectStore};
+
+/// The first four bytes of every GRIB message.
+pub const GRIB_MAGIC: &[u8; 4] = b"GRIB";
+
+/// The default maximum gap (in bytes) between two byte ranges which will be merged into a single
+/// GET. This is the same as `object_store`'s default.
+pub const DEFAULT_MAX_GAP_BYTES: u64 = 1024 * 1024;
+
+/// Returns true if the GRIB file at `path` is bzip2-compressed.
+///
+/// Note that `object_store::path::Path::extension` only returns the last extension, so
+/// `filter_by_ext(stream, "bz2")` selects compressed GRIB files like `foo.grib2.bz2`.
+pub fn is_bz2_compressed(path: &Path) -> bool {
+    path.extension() == Some("bz2")
+}
+
+pub fn decompress_bz2(compressed: &[u8]) -> anyhow::Result<Vec<u8>> {
+    let mut decompressed = Vec::new();
+    bzip2::read::BzDecoder::new(compressed)
+        .read_to_end(&mut decompressed)
+        .context("Failed to decompress bzip2 stream")?;
+    Ok(decompressed)
+}
+
+/// Returns true if the file at `path` is gzip-compressed (e.g. `foo.idx.gz`).
+pub fn is_gzip_compressed(path: &Path) -> bool {
+    path.extension() == Some("gz")
+}
+
+pub fn decompress_gzip(compressed: &[u8]) -> anyhow::Result<Vec<u8>> {
+    let mut decompressed = Vec::new();
+    flate2::read::GzDecoder::new(compressed)
+        .read_to_end(&mut decompressed)
+        .context("Failed to decompress gzip stream")?;
+    Ok(decompressed)
+}
+
+/// Decompress `bytes` if the file at `path` is gzip-compressed. Otherwise return `bytes`
+/// unchanged.
+pub fn maybe_decompress_gzip(path: &Path, bytes: Bytes) -> anyhow::Result<Bytes> {
+    if is_gzip_compressed(path) {
+        let decompressed =
+            decompress_gzip(&bytes).with_context(|| format!("Failed to decompress '{path}'"))?;
+        Ok(Bytes::from(decompressed))
+    } else {
+        Ok(bytes)
+    }
+}
+
+/// The byte range of a GRIB message extends beyond the end of a decompressed GRIB file.
+#[derive(Debug)]
+pub struct BeyondEndOfFileError {
+    pub path: Path,
+    pub byte_range: Range<usize>,
+    /// The length of the decompressed GRIB file, in bytes.
+    pub len: usize,
+}
+
+impl std::fmt::Display for BeyondEndOfFileError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Byte range {:?} is beyond the end of the decompressed '{}' ({} bytes)",
+            self.byte_range, self.path, self.len
+        )
+    }
+}
+
+impl std::error::Error for BeyondEndOfFileError {}
+
+/// Fetch the GRIB message at `byte_range` of the GRIB file at `path`.
+///
+/// If the GRIB file is bzip2-compressed then the whole file is fetched and decompressed, and
+/// `byte_range` refers to the decompressed file. Returns a `BeyondEndOfFileError` if
+/// `byte_range` extends beyond the end of the decompressed file.
+pub async fn fetch_grib_message(
+    store: &dyn ObjectStore,
+    path: &Path,
+    byte_range: Range<u64>,
+) -> anyhow::Result<Bytes> {
+    if !is_bz2_compressed(path) {
+        let range = usize::try_from(byte_range.start)?..usize::try_from(byte_range.end)?;
+        return Ok(store.get_range(path, range).await?);
+    }
+    let compressed = store.get(path).await?.bytes().await?;
+    let decompressed = Bytes::from(
+        decompress_bz2(&compressed).with_context(|| format!("Failed to decompress '{path}'"))?,
+    );
+    let range = usize::try_from(byte_range.start)?..usize::try_from(byte_range.end)?;
+    if range.end > decompressed.len() {
+        return Err(BeyondEndOfFileError {
+            path: path.clone(),
+            byte_range: range,
+            len: decompressed.len(),
+        }
+        .into());
+    }
+    Ok(decompressed.slice(range))
+}
+
+/// A single GET which covers one or more GRIB messages in the same GRIB file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CoalescedRange {
+    /// The byte range to GET.
+    pub byte_range: Range<u64>,
+    /// The indices (into the `byte_ranges` passed to `coalesce_byte_ranges`) of the messages
+    /// covered by this GET, in ascending order of byte offset.
+    pub msg_indices: Vec<usize>,
+}
+
+/// Plan the GETs required to fetch `byte_ranges` from a single GRIB file (e.g. the
+/// `MessageLocation::byte_range` of several messages which share a path). Byte ranges which
+/// overlap, or which are separated by at most `max_gap` bytes, are merged into a single GET.
+///
+/// Returns the `CoalescedRange`s in ascending order of byte offset.
+pub fn coalesce_byte_ranges(byte_ranges: &[Range<u64>], max_gap: u64) -> Vec<CoalescedRange> {
+    let mut order: Vec<usize> = (0..byte_ranges.len()).collect();
+    order.sort_by_key(|&i| (byte_ranges[i].start, byte_ranges[i].end));
+    let mut coalesced: Vec<CoalescedRange> = Vec::new();
+    for i in order {
+        let range = &byte_ranges[i];
+        match coalesced.last_mut() {
+            Some(last) if range.start <= last.byte_range.end.saturating_add(max_gap) => {
+                last.byte_range.end = last.byte_range.end.max(range.end);
+                last.msg_indices.push(i);
+            }
+            _ => coalesced.push(CoalescedRange {
+                byte_range: range.clone(),
+                msg_indices: vec![i],
+            }),
+        }
+    }
+    coalesced
+}
+
+/// Fetch several GRIB messages from the GRIB file at `path`, merging byte ranges which are
+/// separated by at most `max_gap` bytes into a single GET. The coalesced GETs are issued
+/// concurrently.
+///
+/// Returns one `Bytes` per element of `byte_ranges`, in the same order as `byte_ranges`.
+///
+/// If the GRIB file is bzip2-compressed then the whole file is fetched (once) and decompressed.
+pub async fn fetch_grib_messages(
+    store: &dyn ObjectStore,
+    path: &Path,
+    byte_ranges: &[Range<u64>],
+    max_gap: u64,
+) -> anyhow::Result<Vec<Bytes>> {
+    // Compressed GRIB files are always fetched in full, so only fetch them once.
+    let max_gap = if is_bz2_compressed(path) {
+        u64::MAX
+    } else {
+        max_gap
+    };
+    let coalesced = coalesce_byte_ranges(byte_ranges, max_gap);
+    let fetched = try_join_all(
+        coalesced
+            .iter()
+            .map(|c| fetch_grib_message(store, path, c.byte_range.clone())),
+    )
+    .await?;
+
+    let mut msgs = vec![Bytes::new(); byte_ranges.len()];
+    for (c, bytes) in coalesced.iter().zip(fetched) {
+        for &i in &c.msg_indices {
+            let start = usize::try_from(byte_ranges[i].start - c.byte_range.start)?;
+            let end = usize::try_from(byte_ranges[i].end - c.byte_range.start)?;
+            msgs[i] = bytes.slice(start..end);
+        }
+    }
+    Ok(msgs)
+}
+
+#[cfg(test)]
+mod tests {
+    use object_store::{memory::InMemory, PutPayload};
+
+    use super::*;
+
+    /// A single small GRIB message (from HRRR, because ICON's messages are large), compressed
+    /// with `bzip2` in the same way as ICON's GRIB files.
+    const COMPRESSED_MESSAGE: &[u8] =
+        include_bytes!("datasets/icon/test_message.grib2.bz2").as_slice();
+
+    #[test]
+    fn test_decompress_bz2() -> anyhow::Result<()> {
+        let decompressed = decompress_bz2(COMPRESSED_MESSAGE)?;
+        assert!(decompressed.starts_with(GRIB_MAGIC));
+        assert!(decompressed.ends_with(b"7777"));
+        assert!(decompress_bz2(b"GRIB").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_maybe_decompress_gzip() -> anyhow::Result<()> {
+        use std::io::Write;
+
+        let idx_text = "1:0:d=2017010100:HGT:10 mb:anl:ENS=low-res ctl\n";
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(idx_text.as_bytes())?;
+        let compressed = Bytes::from(encoder.finish()?);
+
+        let decompressed = maybe_decompress_gzip(&Path::from("foo.idx.gz"), compressed.clone())?;
+        assert_eq!(decompressed, idx_text.as_bytes());
+        // Uncompressed files are returned unchanged:
+        let unchanged = maybe_decompress_gzip(&Path::from("foo.idx"), compressed.clone())?;
+        assert_eq!(unchanged, compressed);
+        // Files which claim to be compressed, but aren't:
+        assert!(maybe_decompress_gzip(&Path::from("foo.idx.gz"), Bytes::from(idx_text)).is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_fetch_grib_message() -> anyhow::Result<()> {
+        let store = InMemory::new();
+        let compressed_path = Path::from("icon/foo.grib2.bz2");
+        let decompressed_path = Path::from("icon/foo.grib2");
+        let decompressed = decompress_bz2(COMPRESSED_MESSAGE)?;
+        store
+            .put(
+                &compressed_path,
+                PutPayload::from_static(COMPRESSED_MESSAGE),
+            )
+            .await?;
+        store
+            .put(&decompressed_path, PutPayload::from(decompressed.clone()))
+            .await?;
+        assert!(is_bz2_compressed(&compressed_path));
+        assert!(!is_bz2_compressed(&decompressed_path));
+
+        let len = decompressed.len() as u64;
+        for path in [&compressed_path, &decompressed_path] {
+            let msg = fetch_grib_message(&store, path, 0..len).await?;
+            assert_eq!(msg, decompressed);
+            let msg = fetch_grib_message(&store, path, 4..8).await?;
+            assert_eq!(msg, decompressed[4..8]);
+        }
+        assert!(fetch_grib_message(&store, &compressed_path, 0..len + 1)
+            .await
+            .is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_coalesce_byte_ranges() {
+        // Three near-adjacent messages (given out of order), and one distant message:
+        let byte_ranges = [200..300, 0..100, 110..190, 10_000..10_100];
+        let coalesced = coalesce_byte_ranges(&byte_ranges, 16);
+        assert_eq!(
+            coalesced,
+            [
+                CoalescedRange {
+                    byte_range: 0..300,
+                    msg_indices: vec![1, 2, 0]
+                },
+                CoalescedRange {
+                    byte_range: 10_000..10_100,
+                    msg_indices: vec![3]
+                }
+            ]
+        );
+        // A gap of 10 bytes is too large to merge when `max_gap` is 9:
+        assert_eq!(coalesce_byte_ranges(&byte_ranges, 9).len(), 4);
+        assert_eq!(coalesce_byte_ranges(&byte_ranges, u64::MAX).len(), 1);
+        assert!(coalesce_byte_ranges(&[], 16).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_fetch_grib_messages() -> anyhow::Result<()> {
+        let store = InMemory::new();
+        let path = Path::from("foo.grib2");
+        let file: Vec<u8> = (0..=255).collect();
+        store.put(&path, PutPayload::from(file.clone())).await?;
+
+        let byte_ranges = [200..250, 0..100, 110..190];
+        assert_eq!(coalesce_byte_ranges(&byte_ranges, 16).len(), 1);
+        let msgs = fetch_grib_messages(&store, &path, &byte_ranges, 16).await?;
+        assert_eq!(msgs.len(), byte_ranges.len());
+        for (msg, range) in msgs.iter().zip(&byte_ranges) {
+            assert_eq!(msg, &file[range.start as usize..range.end as usize]);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,43 @@
+use chrono::TimeDelta;
+
+/// Format forecast steps the way that a dataset does in its paths. Datasets use different
+/// conventions. For example, GEFS uses `f006`, some datasets use `f06`, ECMWF's open data uses
+/// `6h`, and sub-hourly datasets (like HRRR's sub-hourly files) also encode the minutes.
+pub trait ForecastStepNaming {
+    fn format_forecast_step(forecast_step: &TimeDelta) -> String;
+}
+
+/// Format `forecast_step` as `f` followed by the number of hours, zero-padded to `width` digits.
+/// For example, 6 hours is `f006` if `width` is 3, and `f06` if `width` is 2.
+pub fn format_hours(forecast_step: &TimeDelta, width: usize) -> String {
+    format!("f{:0width$}", forecast_step.num_hours())
+}
+
+/// Format `forecast_step` as `f{hours:02}m{minutes:02}`. For example, 15 minutes is `f00m15`,
+/// and 1 hour 45 minutes is `f01m45`.
+pub fn format_hours_and_minutes(forecast_step: &TimeDelta) -> String {
+    format!(
+        "f{:02}m{:02}",
+        forecast_step.num_hours(),
+        forecast_step.num_minutes() % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_hours() {
+        assert_eq!(format_hours(&TimeDelta::hours(6), 3), "f006");
+        assert_eq!(format_hours(&TimeDelta::hours(6), 2), "f06");
+        assert_eq!(format_hours(&TimeDelta::hours(384), 2), "f384");
+    }
+
+    #[test]
+    fn test_format_hours_and_minutes() {
+        assert_eq!(format_hours_and_minutes(&TimeDelta::minutes(15)), "f00m15");
+        assert_eq!(format_hours_and_minutes(&TimeDelta::minutes(105)), "f01m45");
+        assert_eq!(format_hours_and_minutes(&TimeDelta::hours(18)), "f18m00");
+    }
+}
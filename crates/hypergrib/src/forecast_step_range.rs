@@ -0,0 +1,119 @@
+use std::collections::BTreeSet;
+
+use chrono::TimeDelta;
+
+/// A regular sequence of forecast steps, produced by some of a dataset's daily cycles.
+///
+/// A dataset's forecast steps are described by a list of `ForecastStepRange`s, because many
+/// datasets change their step duration part way through the forecast (e.g. hourly steps for the
+/// first few days, then 3-hourly steps), and many datasets run longer forecasts for some cycles
+/// (e.g. GEFS's 00Z cycle runs for longer than its other cycles).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ForecastStepRange {
+    /// The hours of the day at which the NWP is initialised (e.g. `[0, 6, 12, 18]`).
+    daily_cycles: Vec<u8>,
+    start_hour: u32,
+    /// Inclusive.
+    end_hour: u32,
+    step_duration_in_hours: u32,
+}
+
+impl ForecastStepRange {
+    pub fn new(
+        daily_cycles: Vec<u8>,
+        start_hour: u32,
+        end_hour: u32,
+        step_duration_in_hours: u32,
+    ) -> anyhow::Result<Self> {
+        if step_duration_in_hours == 0 {
+            return Err(anyhow::format_err!("step_duration_in_hours must be > 0"));
+        }
+        if end_hour < start_hour {
+            return Err(anyhow::format_err!(
+                "end_hour ({end_hour}) must be >= start_hour ({start_hour})"
+            ));
+        }
+        Ok(Self {
+            daily_cycles,
+            start_hour,
+            end_hour,
+            step_duration_in_hours,
+        })
+    }
+
+    pub fn contains_cycle(&self, cycle: u8) -> bool {
+        self.daily_cycles.contains(&cycle)
+    }
+
+    /// The forecast steps, in ascending order.
+    pub fn steps(&self) -> impl Iterator<Item = TimeDelta> {
+        (self.start_hour..=self.end_hour)
+            .step_by(self.step_duration_in_hours as usize)
+            .map(|hour| TimeDelta::hours(hour.into()))
+    }
+}
+
+/// The forecast steps (in ascending order, without duplicates) produced by the cycle initialised
+/// at hour `cycle` of the day, according to `ranges`.
+pub fn forecast_steps_for_cycle(ranges: &[ForecastStepRange], cycle: u8) -> Vec<TimeDelta> {
+    ranges
+        .iter()
+        .filter(|range| range.contains_cycle(cycle))
+        .flat_map(ForecastStepRange::steps)
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_forecast_steps_for_cycle() -> anyhow::Result<()> {
+        // The 00Z cycle runs to 384 hours, whereas the other cycles stop at 240 hours.
+        let ranges = [
+            ForecastStepRange::new(vec![0, 6, 12, 18], 0, 240, 3)?,
+            ForecastStepRange::new(vec![0], 246, 384, 6)?,
+        ];
+        let hours = |cycle| -> Vec<i64> {
+            forecast_steps_for_cycle(&ranges, cycle)
+                .iter()
+                .map(TimeDelta::num_hours)
+                .collect()
+        };
+
+        let steps_00z = hours(0);
+        assert_eq!(steps_00z.len(), 81 + 24);
+        assert_eq!(steps_00z[..3], [0, 3, 6]);
+        assert_eq!(steps_00z[79..83], [237, 240, 246, 252]);
+        assert_eq!(steps_00z.last(), Some(&384));
+
+        let steps_06z = hours(6);
+        assert_eq!(steps_06z.len(), 81);
+        assert_eq!(steps_06z.last(), Some(&240));
+        assert_eq!(steps_06z, steps_00z[..81]);
+
+        // There's no 03Z cycle:
+        assert!(hours(3).is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_overlapping_ranges_are_deduplicated() -> anyhow::Result<()> {
+        let ranges = [
+            ForecastStepRange::new(vec![0], 0, 12, 1)?,
+            ForecastStepRange::new(vec![0], 12, 24, 3)?,
+        ];
+        let steps = forecast_steps_for_cycle(&ranges, 0);
+        assert_eq!(steps.len(), 13 + 4);
+        assert!(steps.is_sorted());
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalid_ranges() {
+        assert!(ForecastStepRange::new(vec![0], 0, 12, 0).is_err());
+        assert!(ForecastStepRange::new(vec![0], 12, 0, 1).is_err());
+    }
+}
@@ -0,0 +1,216 @@
+use std::fmt;
+
+use chrono::TimeDelta;
+
+use crate::{CoordLabels, ForecastStep};
+
+/// A concise description of a forecast step axis: The first and last step, and the spacing
+/// between steps. See `CoordLabels::forecast_step_summary`.
+///
+/// Each forecast step is summarised by the time at which it's valid (`ForecastStep::end`), so
+/// `Instant(6 hours)` and `Range { start: 0 hours, end: 6 hours }` are the same step.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ForecastStepSummary {
+    pub min: TimeDelta,
+    pub max: TimeDelta,
+    pub cadence: StepCadence,
+}
+
+/// The spacing between consecutive (distinct) forecast steps.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StepCadence {
+    /// Every step is the same duration after the previous step.
+    Regular(TimeDelta),
+    /// The duration between steps changes part way through the forecast (e.g. GEFS has 3-hourly
+    /// steps up to 240 hours, then 6-hourly steps). Each `Cadence` starts where the previous
+    /// `Cadence` ends, so the breakpoints are the `end` of each `Cadence` except the last.
+    MultiCadence(Vec<Cadence>),
+    /// No two consecutive durations between steps are the same, so there's no cadence to report.
+    /// Holds every distinct step, in ascending order. An axis with a single distinct step is also
+    /// `Irregular`.
+    Irregular(Vec<TimeDelta>),
+}
+
+/// A run of steps from `start` to `end` (inclusive), which are `interval` apart.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cadence {
+    pub start: TimeDelta,
+    pub end: TimeDelta,
+    pub interval: TimeDelta,
+}
+
+impl ForecastStepSummary {
+    /// Returns `None` if `forecast_steps` is empty. `forecast_steps` doesn't need to be sorted.
+    pub fn new(forecast_steps: &[ForecastStep]) -> Option<Self> {
+        let mut steps: Vec<_> = forecast_steps.iter().map(ForecastStep::end).collect();
+        steps.sort();
+        steps.dedup();
+        let (&min, &max) = (steps.first()?, steps.last()?);
+
+        let mut cadences: Vec<Cadence> = vec![];
+        for pair in steps.windows(2) {
+            let interval = pair[1] - pair[0];
+            match cadences.last_mut() {
+                Some(cadence) if cadence.interval == interval => cadence.end = pair[1],
+                _ => cadences.push(Cadence {
+                    start: pair[0],
+                    end: pair[1],
+                    interval,
+                }),
+            }
+        }
+        let is_irregular = cadences
+            .iter()
+            .all(|cadence| cadence.end - cadence.start == cadence.interval);
+        let cadence = match cadences[..] {
+            [cadence] => StepCadence::Regular(cadence.interval),
+            _ if is_irregular => StepCadence::Irregular(steps),
+            _ => StepCadence::MultiCadence(cadences),
+        };
+        Some(Self { min, max, cadence })
+    }
+}
+
+impl CoordLabels {
+    /// Summarise the forecast step axis (e.g. for a description of the dataset). Returns `None`
+    /// if there are no forecast steps.
+    pub fn forecast_step_summary(&self) -> Option<ForecastStepSummary> {
+        ForecastStepSummary::new(self.forecast_step())
+    }
+}
+
+/// For example: `0h to 384h, every 3h until 240h, then every 6h`.
+impl fmt::Display for ForecastStepSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} to {}", Hours(&self.min), Hours(&self.max))?;
+        match &self.cadence {
+            StepCadence::Regular(interval) => write!(f, ", every {}", Hours(interval)),
+            StepCadence::MultiCadence(cadences) => {
+                for (i, cadence) in cadences.iter().enumerate() {
+                    let then = if i == 0 { "" } else { "then " };
+                    write!(f, ", {then}every {}", Hours(&cadence.interval))?;
+                    if i + 1 < cadences.len() {
+                        write!(f, " until {}", Hours(&cadence.end))?;
+                    }
+                }
+                Ok(())
+            }
+            StepCadence::Irregular(steps) => {
+                let steps: Vec<_> = steps.iter().map(|step| Hours(step).to_string()).collect();
+                write!(f, ", at irregular steps: [{}]", steps.join(", "))
+            }
+        }
+    }
+}
+
+/// Formats a `TimeDelta` as a whole number of hours (e.g. `6h`), or as minutes (e.g. `15min`)
+/// for sub-hourly durations.
+struct Hours<'a>(&'a TimeDelta);
+
+impl fmt::Display for Hours<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.0.num_minutes() % 60 == 0 {
+            write!(f, "{}h", self.0.num_hours())
+        } else {
+            write!(f, "{}min", self.0.num_minutes())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hours(hours: impl IntoIterator<Item = i64>) -> Vec<ForecastStep> {
+        hours
+            .into_iter()
+            .map(|hour| ForecastStep::Instant(TimeDelta::hours(hour)))
+            .collect()
+    }
+
+    #[test]
+    fn test_regular_hourly_axis() {
+        let coord_labels = CoordLabels::new(
+            vec![].into(),
+            vec![].into(),
+            hours(0..=48).into(),
+            vec![].into(),
+            vec![].into(),
+        );
+        let summary = coord_labels.forecast_step_summary().unwrap();
+        assert_eq!(
+            summary,
+            ForecastStepSummary {
+                min: TimeDelta::zero(),
+                max: TimeDelta::hours(48),
+                cadence: StepCadence::Regular(TimeDelta::hours(1)),
+            }
+        );
+        assert_eq!(summary.to_string(), "0h to 48h, every 1h");
+        assert_eq!(CoordLabels::default().forecast_step_summary(), None);
+    }
+
+    #[test]
+    fn test_multi_cadence_axis() {
+        // Hourly, then 3-hourly, then 6-hourly (like GEFS's 0.25 degree output). The 0-6 hour
+        // accumulation is valid at 6 hours, so it doesn't add another step.
+        let mut steps = hours(
+            (0..=120)
+                .chain((123..=240).step_by(3))
+                .chain((246..=384).step_by(6)),
+        );
+        steps.push(ForecastStep::Range {
+            start: TimeDelta::zero(),
+            end: TimeDelta::hours(6),
+        });
+        let summary = ForecastStepSummary::new(&steps).unwrap();
+        assert_eq!(summary.min, TimeDelta::zero());
+        assert_eq!(summary.max, TimeDelta::hours(384));
+        let cadence = |start, end, interval| Cadence {
+            start: TimeDelta::hours(start),
+            end: TimeDelta::hours(end),
+            interval: TimeDelta::hours(interval),
+        };
+        assert_eq!(
+            summary.cadence,
+            StepCadence::MultiCadence(vec![
+                cadence(0, 120, 1),
+                cadence(120, 240, 3),
+                cadence(240, 384, 6)
+            ])
+        );
+        assert_eq!(
+            summary.to_string(),
+            "0h to 384h, every 1h until 120h, then every 3h until 240h, then every 6h"
+        );
+    }
+
+    #[test]
+    fn test_irregular_axis() {
+        let summary = ForecastStepSummary::new(&hours([7, 0, 1, 3])).unwrap();
+        assert_eq!(
+            summary.cadence,
+            StepCadence::Irregular([0, 1, 3, 7].map(TimeDelta::hours).to_vec())
+        );
+        assert_eq!(
+            summary.to_string(),
+            "0h to 7h, at irregular steps: [0h, 1h, 3h, 7h]"
+        );
+
+        // A single step:
+        let summary = ForecastStepSummary::new(&hours([6])).unwrap();
+        assert_eq!(
+            summary.cadence,
+            StepCadence::Irregular(vec![TimeDelta::hours(6)])
+        );
+
+        // Sub-hourly steps:
+        let steps: Vec<_> = (0..=4)
+            .map(|i| ForecastStep::Instant(TimeDelta::minutes(15 * i)))
+            .collect();
+        assert_eq!(
+            ForecastStepSummary::new(&steps).unwrap().to_string(),
+            "0h to 1h, every 15min"
+        );
+    }
+}
@@ -0,0 +1,244 @@
+//! Detect changes to the horizontal grid across a dataset.
+//!
+//! `hypergrib` assumes that every message in a dataset has the same `(rows, columns)` grid. But
+//! datasets sometimes change their grid part way through the archive (e.g. when the model's
+//! resolution is upgraded). Stacking messages with different grids into one array would be wrong,
+//! so [`Manifest::survey_grids`] decodes the grid of one representative message from each era
+//! (e.g. each model version) and records the distinct grids.
+
+use std::{collections::BTreeMap, fmt};
+
+use anyhow::Context;
+use futures_util::future::try_join_all;
+use object_store::{path::Path, ObjectStore};
+
+use crate::{
+    fetch::fetch_grib_message,
+    inspect::read_message,
+    manifest::{Key, Manifest, MessageLocation},
+};
+
+/// The horizontal grid of a GRIB message (from the grid definition section).
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct GridDefinition {
+    /// The grid definition template number (code table 3.1). For example, 0 is a regular
+    /// latitude/longitude grid, and 30 is a Lambert conformal grid.
+    pub template_number: u16,
+    /// `(rows, columns)`.
+    pub shape: (usize, usize),
+    /// `None` if `gribberish` can't describe the projection of this grid.
+    pub proj_string: Option<String>,
+}
+
+impl GridDefinition {
+    /// Decode the grid of the GRIB message at the start of `bytes`.
+    pub fn from_bytes(bytes: &[u8]) -> anyhow::Result<Self> {
+        let msg = read_message(bytes)?;
+        Ok(Self {
+            template_number: msg.grid_template_id()?,
+            shape: msg.grid_dimensions()?,
+            proj_string: msg.proj_string().ok(),
+        })
+    }
+}
+
+/// For example: `template 3.30, 1059 x 1799 (+proj=lcc ...)`.
+impl fmt::Display for GridDefinition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "template 3.{}, {} x {}",
+            self.template_number, self.shape.0, self.shape.1
+        )?;
+        if let Some(proj_string) = &self.proj_string {
+            write!(f, " ({proj_string})")?;
+        }
+        Ok(())
+    }
+}
+
+/// The result of [`Manifest::survey_grids`]: The distinct grids, and the eras which use each grid.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct GridSurvey {
+    /// Each distinct grid, and the eras (in ascending order) whose representative message uses
+    /// that grid. Sorted by the first era of each grid.
+    pub grids: Vec<(GridDefinition, Vec<String>)>,
+}
+
+impl GridSurvey {
+    pub fn n_distinct_grids(&self) -> usize {
+        self.grids.len()
+    }
+
+    /// Returns true if every era uses the same grid.
+    pub fn is_consistent(&self) -> bool {
+        self.n_distinct_grids() <= 1
+    }
+
+    /// Returns `None` if every era uses the same grid. Otherwise, describes the grids, because
+    /// messages with different grids can't be stacked into one array.
+    pub fn warning(&self) -> Option<String> {
+        (!self.is_consistent()).then(|| {
+            format!(
+                "Found {} distinct grids, so this isn't one logical dataset. Messages with \
+                 different grids can't be stacked into one array.\n{self}",
+                self.n_distinct_grids()
+            )
+        })
+    }
+}
+
+/// One line per grid. For example: `- template 3.0, 181 x 360: eras v1, v2`.
+impl fmt::Display for GridSurvey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, (grid, eras)) in self.grids.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "- {grid}: eras {}", eras.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+impl Manifest {
+    /// Decode the grid of one representative message from each era, and record the distinct
+    /// grids. `era_of` maps each message's key to its era (for example, the model version of the
+    /// reference datetime). The representative message of each era is the message with the
+    /// smallest path and byte offset, so the same manifest always gives the same survey.
+    ///
+    /// `store` must be the store which `Manifest::base_path` refers to.
+    pub async fn survey_grids(
+        &self,
+        store: &dyn ObjectStore,
+        era_of: impl Fn(&Key) -> String,
+    ) -> anyhow::Result<GridSurvey> {
+        let mut representatives: BTreeMap<String, &MessageLocation> = BTreeMap::new();
+        for (key, msg_loc) in self.iter() {
+            representatives
+                .entry(era_of(key))
+                .and_modify(|representative| {
+                    if (msg_loc.path(), msg_loc.byte_offset())
+                        < (representative.path(), representative.byte_offset())
+                    {
+                        *representative = msg_loc;
+                    }
+                })
+                .or_insert(msg_loc);
+        }
+
+        let grids = try_join_all(
+            representatives
+                .into_iter()
+                .map(|(era, msg_loc)| async move {
+                    let grid = fetch_grid(store, &self.resolve_path(msg_loc), msg_loc).await?;
+                    anyhow::Ok((era, grid))
+                }),
+        )
+        .await?;
+
+        let mut survey = GridSurvey::default();
+        for (era, grid) in grids {
+            match survey.grids.iter_mut().find(|(g, _)| *g == grid) {
+                Some((_, eras)) => eras.push(era),
+                None => survey.grids.push((grid, vec![era])),
+            }
+        }
+        Ok(survey)
+    }
+}
+
+async fn fetch_grid(
+    store: &dyn ObjectStore,
+    path: &Path,
+    msg_loc: &MessageLocation,
+) -> anyhow::Result<GridDefinition> {
+    let bytes = fetch_grib_message(store, path, msg_loc.byte_range()).await?;
+    GridDefinition::from_bytes(&bytes).with_context(|| {
+        format!(
+            "Failed to decode the grid of the message at byte offset {} of '{path}'",
+            msg_loc.byte_offset()
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Datelike, TimeDelta};
+    use object_store::{memory::InMemory, PutPayload};
+
+    use crate::{
+        fetch::decompress_bz2, manifest::KeyBuilder, ymdh_to_datetime, ForecastStep, Level,
+    };
+
+    use super::*;
+
+    /// The HRRR message used by the other tests (a 1059 x 1799 Lambert conformal grid), and a
+    /// copy of that message whose grid definition section says the grid is 530 x 900.
+    fn two_grids() -> anyhow::Result<(Vec<u8>, Vec<u8>)> {
+        let msg = decompress_bz2(include_bytes!("datasets/icon/test_message.grib2.bz2"))?;
+        let mut smaller = msg.clone();
+        // Section 3 starts after section 0 (16 octets) and section 1 (21 octets). Nx and Ny are
+        // octets 31-34 and 35-38 of grid definition template 3.30.
+        let section_3 = 16 + 21;
+        smaller[section_3 + 30..section_3 + 34].copy_from_slice(&900u32.to_be_bytes());
+        smaller[section_3 + 34..section_3 + 38].copy_from_slice(&530u32.to_be_bytes());
+        Ok((msg, smaller))
+    }
+
+    #[tokio::test]
+    async fn test_survey_grids() -> anyhow::Result<()> {
+        let (msg, smaller) = two_grids()?;
+        let msg_len = msg.len() as u64;
+        let store = InMemory::new();
+        let mut manifest = Manifest::new(Path::default());
+        let mut builder = KeyBuilder::new();
+        builder
+            .set_ensemble_member("control")
+            .set_forecast_step(ForecastStep::Instant(TimeDelta::hours(1)))
+            .set_parameter("CFRZR")
+            .set_vertical_level(Level::Surface);
+        for (year, bytes) in [(2022, &msg), (2023, &msg), (2024, &smaller)] {
+            let path = Path::from(format!("{year}/foo.grib2"));
+            store.put(&path, PutPayload::from(bytes.clone())).await?;
+            builder.set_reference_datetime(ymdh_to_datetime(year, 1, 1, 0));
+            manifest.insert(builder.build()?, &path, 0, msg_len);
+        }
+
+        // One era per year: The 2024 message has a different grid.
+        let survey = manifest
+            .survey_grids(&store, |key| key.reference_datetime().year().to_string())
+            .await?;
+        assert_eq!(survey.n_distinct_grids(), 2);
+        assert!(!survey.is_consistent());
+        let shapes: Vec<_> = survey
+            .grids
+            .iter()
+            .map(|(grid, eras)| (grid.template_number, grid.shape, eras.clone()))
+            .collect();
+        assert_eq!(
+            shapes,
+            [
+                (
+                    30,
+                    (1059, 1799),
+                    vec!["2022".to_string(), "2023".to_string()]
+                ),
+                (30, (530, 900), vec!["2024".to_string()]),
+            ]
+        );
+        let warning = survey.warning().unwrap();
+        assert!(warning.starts_with("Found 2 distinct grids"), "{warning}");
+        assert!(warning.contains("1059 x 1799"), "{warning}");
+        assert!(warning.contains("eras 2024"), "{warning}");
+
+        // A single era:
+        let survey = manifest
+            .survey_grids(&store, |_| String::from("all"))
+            .await?;
+        assert_eq!(survey.n_distinct_grids(), 1);
+        assert_eq!(survey.grids[0].0.shape, (1059, 1799));
+        assert_eq!(survey.warning(), None);
+        Ok(())
+    }
+}
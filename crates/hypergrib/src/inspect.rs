@@ -0,0 +1,135 @@
+//! Fetch and decode a single GRIB message, to help debug manifest entries which look wrong.
+
+use std::{fmt, ops::Range};
+
+use chrono::{DateTime, TimeDelta, Utc};
+use gribberish::message::Message;
+use object_store::{path::Path, ObjectStore};
+
+use crate::fetch::{fetch_grib_message, GRIB_MAGIC};
+
+/// A human-readable summary of a decoded GRIB message.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MessageSummary {
+    /// The parameter abbreviation, e.g. "TMP".
+    pub parameter: String,
+    pub parameter_name: String,
+    pub unit: String,
+    /// The first fixed surface, e.g. "HeightAboveGround 2".
+    pub vertical_level: String,
+    pub reference_datetime: DateTime<Utc>,
+    /// The time between the reference datetime and the (start of the) forecast.
+    pub forecast_step: TimeDelta,
+    /// `(rows, columns)`.
+    pub grid_shape: (usize, usize),
+    /// The first few data values of the message.
+    pub sample_values: Vec<f64>,
+}
+
+impl MessageSummary {
+    /// Decode `bytes` (which must contain exactly one GRIB message), and keep the first
+    /// `n_sample_values` data values.
+    pub fn from_bytes(bytes: &[u8], n_sample_values: usize) -> anyhow::Result<Self> {
+        let msg = read_message(bytes)?;
+        let (surface_type, surface_value) = msg.first_fixed_surface()?;
+        let vertical_level = match surface_value {
+            Some(value) => format!("{surface_type:?} {value}"),
+            None => format!("{surface_type:?}"),
+        };
+        let reference_datetime = msg.reference_date()?;
+        let mut sample_values = msg.data()?;
+        sample_values.truncate(n_sample_values);
+        Ok(Self {
+            parameter: msg.variable_abbrev()?,
+            parameter_name: msg.variable_name()?,
+            unit: msg.unit()?,
+            vertical_level,
+            reference_datetime,
+            forecast_step: msg.forecast_date()? - reference_datetime,
+            grid_shape: msg.grid_dimensions()?,
+            sample_values,
+        })
+    }
+}
+
+impl fmt::Display for MessageSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "parameter: {} ({}) [{}]",
+            self.parameter, self.parameter_name, self.unit
+        )?;
+        writeln!(f, "vertical_level: {}", self.vertical_level)?;
+        writeln!(f, "reference_datetime: {}", self.reference_datetime)?;
+        writeln!(f, "forecast_step: {} hours", self.forecast_step.num_hours())?;
+        writeln!(
+            f,
+            "grid_shape: {} x {}",
+            self.grid_shape.0, self.grid_shape.1
+        )?;
+        write!(f, "sample_values: {:?}", self.sample_values)
+    }
+}
+
+/// Read the GRIB message at the start of `bytes`.
+pub(crate) fn read_message(bytes: &[u8]) -> anyhow::Result<Message<'_>> {
+    // `gribberish` panics if the bytes don't start with a GRIB message.
+    if !bytes.starts_with(GRIB_MAGIC) {
+        return Err(anyhow::format_err!(
+            "The bytes don't start with 'GRIB'. Is the byte offset correct?"
+        ));
+    }
+    Message::from_data(bytes, 0)
+        .ok_or_else(|| anyhow::format_err!("Failed to read a GRIB message from the bytes"))
+}
+
+/// Fetch the GRIB message at `byte_range` of the GRIB file at `path`, and decode it.
+pub async fn inspect_grib_message(
+    store: &dyn ObjectStore,
+    path: &Path,
+    byte_range: Range<u64>,
+    n_sample_values: usize,
+) -> anyhow::Result<MessageSummary> {
+    let bytes = fetch_grib_message(store, path, byte_range).await?;
+    MessageSummary::from_bytes(&bytes, n_sample_values)
+}
+
+#[cfg(test)]
+mod tests {
+    use object_store::{memory::InMemory, PutPayload};
+
+    use crate::fetch::decompress_bz2;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_inspect_grib_message() -> anyhow::Result<()> {
+        let msg = decompress_bz2(include_bytes!("datasets/icon/test_message.grib2.bz2"))?;
+        let msg_len = msg.len() as u64;
+        // Put the message after some padding, to check that the byte range is respected:
+        let mut file = vec![0; 100];
+        file.extend_from_slice(&msg);
+        let store = InMemory::new();
+        let path = Path::from("hrrr/foo.grib2");
+        store.put(&path, PutPayload::from(file)).await?;
+
+        let summary = inspect_grib_message(&store, &path, 100..100 + msg_len, 5).await?;
+        assert_eq!(summary.parameter, "CFRZR");
+        assert_eq!(summary.unit, "BOOL");
+        assert_eq!(summary.vertical_level, "GroundOrWater 0");
+        assert_eq!(
+            summary.reference_datetime,
+            crate::ymdh_to_datetime(2023, 7, 22, 6)
+        );
+        assert_eq!(summary.forecast_step, TimeDelta::hours(1));
+        assert_eq!(summary.grid_shape, (1059, 1799));
+        assert_eq!(summary.sample_values, [0.0; 5]);
+        assert!(summary.to_string().contains("grid_shape: 1059 x 1799"));
+
+        // The wrong byte range doesn't start with a GRIB message:
+        assert!(inspect_grib_message(&store, &path, 0..msg_len, 5)
+            .await
+            .is_err());
+        Ok(())
+    }
+}
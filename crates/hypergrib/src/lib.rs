@@ -1,40 +1,173 @@
-use std::{future, sync::Arc};
+use std::future;
 
+mod coord_labels_json;
 pub mod datasets;
-use chrono::{DateTime, TimeDelta, TimeZone, Utc};
+mod ensemble_member;
+#[cfg(feature = "native")]
+pub mod fetch;
+pub mod forecast_step_naming;
+mod forecast_step_range;
+mod forecast_step_summary;
+#[cfg(feature = "native")]
+pub mod grid;
+#[cfg(feature = "native")]
+pub mod inspect;
+pub mod manifest;
+#[cfg(feature = "arrow")]
+pub mod manifest_arrow;
+pub mod manifest_jsonl;
+pub mod path_template;
+pub mod selection;
+#[cfg(feature = "native")]
+pub mod slab;
+mod sorted_vec;
+#[cfg(feature = "native")]
+pub mod store;
+#[cfg(feature = "native")]
+pub mod verify;
+pub mod zarr;
+use chrono::{DateTime, Datelike, NaiveDate, TimeDelta, Utc};
 use futures_util::{Stream, StreamExt};
 use object_store::ObjectMeta;
 
-// #[derive(PartialEq, Eq, Hash, Clone)] // PartialEq, Eq, and Hash are required for HashMap keys.
-// struct Key {
-//     reference_datetime: DateTime<Utc>,
-//     ensemble_member: String, // TODO: Convert to info from GDAL GRIB tables
-//     forecast_step: TimeDelta,
-//     parameter: String, //  TODO: Convert to info from GDAL GRIB tables
-//     vertical_level: String, // TODO: Convert to info from GDAL GRIB tables
-// }
-
-/// The location of a GRIB message.
-struct MessageLocation {
-    path: Arc<object_store::path::Path>,
-    byte_offset: u32,
-    msg_length: u32,
-    // TODO: Store a reference to coord labels for x and y?
-    // TODO: Maybe a ref to a struct which holds lots of metadata about this grib message such as:
-    // - coord labels for x and y
-    // - NWP model version
-    // - other metadata?
-}
+pub use ensemble_member::{EnsembleMember, EnsembleNaming};
+pub use forecast_step_naming::ForecastStepNaming;
+pub use forecast_step_range::{forecast_steps_for_cycle, ForecastStepRange};
+pub use forecast_step_summary::{Cadence, ForecastStepSummary, StepCadence};
+pub use hypergrib_idx_parser::{ForecastStep, Level, Statistic};
+pub use manifest::{Conflict, ConflictPolicy, Key, KeyBuilder, Manifest, MessageLocation};
+pub use sorted_vec::SortedVec;
 
-/// Each `Vec` must be sorted and contains unique values.
-// TODO: Consider implementing a `SortedVec` struct which guarantees
-// that elements are sorted and unique.
+/// The coordinate labels of each dimension. Each dimension is a `SortedVec`, so the labels
+/// are guaranteed to be sorted and unique, which allows `SortedVec::index_of` to use a binary
+/// search.
+#[derive(Debug, Clone, PartialEq)]
 pub struct CoordLabels {
-    pub reference_datetime: Vec<DateTime<Utc>>,
-    pub ensemble_member: Vec<String>,
-    pub forecast_step: Vec<TimeDelta>,
-    pub parameter: Vec<String>,
-    pub vertical_level: Vec<String>,
+    reference_datetime: SortedVec<DateTime<Utc>>,
+    ensemble_member: SortedVec<String>,
+    forecast_step: SortedVec<ForecastStep>,
+    parameter: SortedVec<String>,
+    vertical_level: SortedVec<Level>,
+    /// `None` is the label of deterministic fields. See `CoordLabels::with_statistic`.
+    statistic: SortedVec<Option<Statistic>>,
+}
+
+impl Default for CoordLabels {
+    /// No labels, except for the single `None` label of the `statistic` dimension (the same as
+    /// `CoordLabels::new`).
+    fn default() -> Self {
+        Self::new(
+            Default::default(),
+            Default::default(),
+            Default::default(),
+            Default::default(),
+            Default::default(),
+        )
+    }
+}
+
+impl CoordLabels {
+    pub fn new(
+        reference_datetime: SortedVec<DateTime<Utc>>,
+        ensemble_member: SortedVec<String>,
+        forecast_step: SortedVec<ForecastStep>,
+        parameter: SortedVec<String>,
+        vertical_level: SortedVec<Level>,
+    ) -> Self {
+        Self {
+            reference_datetime,
+            ensemble_member,
+            forecast_step,
+            parameter,
+            vertical_level,
+            statistic: SortedVec::from(vec![None]),
+        }
+    }
+
+    /// Set the labels of the `statistic` dimension (e.g. the percentiles of a probabilistic
+    /// product). `CoordLabels::new` sets this dimension to a single `None` label, which means
+    /// "deterministic fields only".
+    pub fn with_statistic(mut self, statistic: SortedVec<Option<Statistic>>) -> Self {
+        self.statistic = statistic;
+        self
+    }
+
+    pub fn reference_datetime(&self) -> &SortedVec<DateTime<Utc>> {
+        &self.reference_datetime
+    }
+
+    pub fn ensemble_member(&self) -> &SortedVec<String> {
+        &self.ensemble_member
+    }
+
+    pub fn forecast_step(&self) -> &SortedVec<ForecastStep> {
+        &self.forecast_step
+    }
+
+    pub fn parameter(&self) -> &SortedVec<String> {
+        &self.parameter
+    }
+
+    pub fn vertical_level(&self) -> &SortedVec<Level> {
+        &self.vertical_level
+    }
+
+    pub fn statistic(&self) -> &SortedVec<Option<Statistic>> {
+        &self.statistic
+    }
+
+    /// The union of the labels of `self` and `other`, along each dimension.
+    pub fn merge(self, other: Self) -> Self {
+        fn union<T: Ord>(a: SortedVec<T>, b: SortedVec<T>) -> SortedVec<T> {
+            a.into_vec().into_iter().chain(b.into_vec()).collect()
+        }
+        Self {
+            reference_datetime: union(self.reference_datetime, other.reference_datetime),
+            ensemble_member: union(self.ensemble_member, other.ensemble_member),
+            forecast_step: union(self.forecast_step, other.forecast_step),
+            parameter: union(self.parameter, other.parameter),
+            vertical_level: union(self.vertical_level, other.vertical_level),
+            statistic: union(self.statistic, other.statistic),
+        }
+    }
+
+    /// Find the most recent reference datetime which has a forecast valid at `valid_time`. For
+    /// example, this is the query for a "current best forecast" product.
+    ///
+    /// A forecast step is valid at `reference_datetime + forecast_step.end()`. Returns all the
+    /// `(reference_datetime, forecast_step)` pairs from the most recent matching reference
+    /// datetime. There may be more than one pair if `available_steps` contains `Range` steps
+    /// (e.g. a 0-6 hour accumulation, and a 3-6 hour accumulation). Returns an empty `Vec` if no
+    /// reference datetime has a forecast valid at `valid_time`.
+    ///
+    /// `available_steps` will often be `self.forecast_step()`, but doesn't need to be sorted.
+    pub fn latest_init_for_valid_time(
+        &self,
+        valid_time: DateTime<Utc>,
+        available_steps: &[ForecastStep],
+    ) -> Vec<(DateTime<Utc>, ForecastStep)> {
+        // Sorting the steps in ascending order means that we try the reference datetimes in
+        // descending order.
+        let available_steps = SortedVec::from(available_steps.to_vec());
+        let mut pairs = vec![];
+        for step in available_steps.iter() {
+            let reference_datetime = valid_time - step.end();
+            if pairs
+                .first()
+                .is_some_and(|(latest, _)| reference_datetime < *latest)
+            {
+                break;
+            }
+            if self
+                .reference_datetime
+                .index_of(&reference_datetime)
+                .is_some()
+            {
+                pairs.push((reference_datetime, *step));
+            }
+        }
+        pairs
+    }
 }
 
 /// Get the coordinate labels.
@@ -43,35 +176,422 @@ pub trait GetCoordLabels {
     async fn get_coord_labels(self) -> anyhow::Result<CoordLabels>;
 }
 
-trait ToIdxPath {
+/// Get the coordinate labels of several parts of an archive (e.g. one prefix per year)
+/// concurrently, and merge them. `make_dataset` creates the dataset for each prefix.
+pub async fn get_coord_labels_for_prefixes<T, F>(
+    prefixes: impl IntoIterator<Item = object_store::path::Path>,
+    make_dataset: F,
+) -> anyhow::Result<CoordLabels>
+where
+    T: GetCoordLabels,
+    F: Fn(object_store::path::Path) -> T,
+{
+    let coord_labels = futures_util::future::try_join_all(
+        prefixes
+            .into_iter()
+            .map(|prefix| make_dataset(prefix).get_coord_labels()),
+    )
+    .await?;
+    Ok(coord_labels
+        .into_iter()
+        .fold(CoordLabels::default(), CoordLabels::merge))
+}
+
+/// Get the path of the GRIB file which holds the message for the given coordinates.
+pub trait ToGribPath {
     // TODO: Pass in a struct instead of individual fields?
+    fn to_grib_path(
+        reference_datetime: &DateTime<Utc>,
+        parameter: &str,
+        vertical_level: &str,
+        forecast_step: &TimeDelta,
+        ensemble_member: Option<&str>,
+    ) -> anyhow::Result<object_store::path::Path>;
+}
+
+/// Get the path of the `.idx` file which describes the GRIB file for the given coordinates.
+///
+/// By default, the idx path is the GRIB path with `.idx` appended. Datasets which don't follow
+/// this convention should override `to_idx_path`.
+pub trait ToIdxPath: ToGribPath {
     fn to_idx_path(
         reference_datetime: &DateTime<Utc>,
         parameter: &str,
         vertical_level: &str,
         forecast_step: &TimeDelta,
         ensemble_member: Option<&str>,
-    ) -> object_store::path::Path;
+    ) -> anyhow::Result<object_store::path::Path> {
+        let grib_path = Self::to_grib_path(
+            reference_datetime,
+            parameter,
+            vertical_level,
+            forecast_step,
+            ensemble_member,
+        )?;
+        Ok(object_store::path::Path::from(format!("{grib_path}.idx")))
+    }
+}
+
+/// Get the coordinates which aren't recorded in the body of an `.idx` file from the path of the
+/// `.idx` file. Used by `Manifest::from_idx_stream`.
+pub trait FromIdxPath {
+    /// The ensemble member of every message described by the `.idx` file at `idx_path`, as
+    /// named by the dataset (e.g. `gec00` for GEFS).
+    fn ensemble_member_from_idx_path(idx_path: &object_store::path::Path)
+        -> anyhow::Result<String>;
 }
 
 /// Filter a stream of `object_store::Result<object_store::ObjectMeta>` to select only the items
-/// which have a file extension which matches `extension`.
+/// which have a file extension which matches `extension`. Gzip-compressed files also match (e.g.
+/// `foo.idx.gz` matches the extension `idx`).
 pub fn filter_by_ext<'a>(
     stream: impl Stream<Item = object_store::Result<ObjectMeta>> + 'a,
     extension: &'static str,
 ) -> impl Stream<Item = object_store::Result<ObjectMeta>> + 'a {
     stream.filter(move |list_result| {
-        future::ready(list_result.as_ref().is_ok_and(|meta| {
-            meta.location
-                .extension()
-                .is_some_and(|ext| ext == extension)
-        }))
+        future::ready(
+            list_result
+                .as_ref()
+                .is_ok_and(|meta| has_ext(&meta.location, extension)),
+        )
     })
 }
 
+/// Returns true if `path` has the file extension `extension`, ignoring any trailing `.gz`. For
+/// example, `foo.idx` and `foo.idx.gz` both have the extension `idx`.
+pub fn has_ext(path: &object_store::path::Path, extension: &str) -> bool {
+    path.filename().is_some_and(|filename| {
+        let filename = filename.strip_suffix(".gz").unwrap_or(filename);
+        filename
+            .rsplit_once('.')
+            .is_some_and(|(stem, ext)| !stem.is_empty() && ext == extension)
+    })
+}
+
+/// Get the path of the GRIB file described by the `.idx` file at `idx_path`, by removing the
+/// trailing `.idx` (or `.idx.gz`). This handles files where the GRIB file has no extension (e.g. older GEFS
+/// files like `gec00.t00z.pgrb2af006.idx`), and files where the GRIB file has its own extension
+/// (e.g. `gefs.chem.t00z.a2d_0p25.f000.grib2.idx`).
+pub fn idx_path_to_grib_path(
+    idx_path: &object_store::path::Path,
+) -> anyhow::Result<object_store::path::Path> {
+    let filename = idx_path
+        .filename()
+        .ok_or_else(|| anyhow::format_err!("idx path has no filename: '{idx_path}'"))?;
+    let grib_filename = filename
+        .strip_suffix(".gz")
+        .unwrap_or(filename)
+        .strip_suffix(".idx")
+        .filter(|grib_filename| !grib_filename.is_empty())
+        .ok_or_else(|| {
+            anyhow::format_err!("idx path must end with '<filename>.idx': '{idx_path}'")
+        })?;
+    let mut parts: Vec<object_store::path::PathPart> = idx_path.parts().collect();
+    parts.pop();
+    parts.push(grib_filename.into());
+    Ok(object_store::path::Path::from_iter(parts))
+}
+
+/// Create a NWP reference datetime from its year, month, day, and hour. NWP reference datetimes
+/// are always whole hours in UTC. Returns a descriptive error if any component is out of range
+/// (e.g. hour 24, month 0, or day 32).
+pub fn try_ymdh_to_datetime(
+    year: i32,
+    month: u32,
+    day: u32,
+    hour: u32,
+) -> anyhow::Result<DateTime<Utc>> {
+    let datetime_str = format!("{year}-{month:02}-{day:02}T{hour:02}");
+    if !(1..=12).contains(&month) {
+        anyhow::bail!(
+            "Invalid month {month} in {datetime_str}: The month must be between 1 and 12"
+        );
+    }
+    if hour >= 24 {
+        anyhow::bail!("Invalid hour {hour} in {datetime_str}: The hour must be between 0 and 23");
+    }
+    let Some(first_of_month) = NaiveDate::from_ymd_opt(year, month, 1) else {
+        anyhow::bail!("Year {year} is out of range in {datetime_str}");
+    };
+    let Some(date) = first_of_month.with_day(day) else {
+        anyhow::bail!(
+            "Invalid day {day} in {datetime_str}: {year}-{month:02} has {} days",
+            first_of_month.num_days_in_month()
+        );
+    };
+    Ok(date
+        .and_hms_opt(hour, 0, 0)
+        .expect("hour has already been checked")
+        .and_utc())
+}
+
+/// Like `try_ymdh_to_datetime`, but panics if the datetime is invalid. Only use this for
+/// datetimes which are known to be valid (e.g. literals).
 pub(crate) fn ymdh_to_datetime(year: i32, month: u32, day: u32, hour: u32) -> DateTime<Utc> {
-    match Utc.with_ymd_and_hms(year, month, day, hour, 0, 0) {
-        chrono::offset::LocalResult::Single(dt) => dt,
-        _ => panic!("Invalid datetime! {year}-{month}-{day}T{hour}"),
+    try_ymdh_to_datetime(year, month, day, hour).expect("Invalid datetime")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_try_ymdh_to_datetime() -> anyhow::Result<()> {
+        assert_eq!(
+            try_ymdh_to_datetime(2017, 1, 1, 18)?,
+            DateTime::parse_from_rfc3339("2017-01-01T18:00:00Z")?
+        );
+        assert_eq!(
+            try_ymdh_to_datetime(2016, 2, 29, 0)?,
+            DateTime::parse_from_rfc3339("2016-02-29T00:00:00Z")?
+        );
+        for ((year, month, day, hour), expected_error) in [
+            (
+                (2017, 1, 1, 24),
+                "Invalid hour 24 in 2017-01-01T24: The hour must be between 0 and 23",
+            ),
+            (
+                (2017, 0, 1, 0),
+                "Invalid month 0 in 2017-00-01T00: The month must be between 1 and 12",
+            ),
+            (
+                (2017, 1, 32, 0),
+                "Invalid day 32 in 2017-01-32T00: 2017-01 has 31 days",
+            ),
+            (
+                (2017, 2, 29, 0),
+                "Invalid day 29 in 2017-02-29T00: 2017-02 has 28 days",
+            ),
+            ((2017, 1, 0, 0), "Invalid day 0 in 2017-01-00T00"),
+            ((i32::MAX, 1, 1, 0), "is out of range"),
+        ] {
+            let error = try_ymdh_to_datetime(year, month, day, hour).unwrap_err();
+            assert!(
+                error.to_string().contains(expected_error),
+                "Unexpected error: {error}"
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_coord_labels_sorts_labels_and_finds_index() {
+        let coord_labels = CoordLabels::new(
+            SortedVec::from(vec![
+                ymdh_to_datetime(2017, 1, 2, 0),
+                ymdh_to_datetime(2017, 1, 1, 0),
+            ]),
+            SortedVec::from(vec![String::from("gep01"), String::from("gec00")]),
+            SortedVec::from(vec![
+                ForecastStep::Instant(TimeDelta::hours(6)),
+                ForecastStep::Instant(TimeDelta::zero()),
+            ]),
+            SortedVec::from(vec![
+                String::from("TMP"),
+                String::from("HGT"),
+                String::from("TMP"),
+            ]),
+            SortedVec::default(),
+        );
+        assert_eq!(
+            **coord_labels.reference_datetime(),
+            [
+                ymdh_to_datetime(2017, 1, 1, 0),
+                ymdh_to_datetime(2017, 1, 2, 0)
+            ]
+        );
+        assert_eq!(**coord_labels.parameter(), ["HGT", "TMP"]);
+        assert_eq!(
+            coord_labels
+                .ensemble_member()
+                .index_of(&String::from("gep01")),
+            Some(1)
+        );
+        assert_eq!(
+            coord_labels
+                .forecast_step()
+                .index_of(&ForecastStep::Instant(TimeDelta::hours(6))),
+            Some(1)
+        );
+        assert_eq!(
+            coord_labels.parameter().index_of(&String::from("UGRD")),
+            None
+        );
+        assert!(coord_labels.vertical_level().is_empty());
+        assert_eq!(**coord_labels.statistic(), [None]);
+        assert_eq!(CoordLabels::default().statistic(), coord_labels.statistic());
+    }
+
+    #[test]
+    fn test_has_ext() {
+        for (path, expected) in [
+            ("foo/bar.idx", true),
+            ("foo/bar.grib2.idx.gz", true),
+            ("foo/bar.grib2", false),
+            ("foo/bar.gz", false),
+            ("foo/.idx", false),
+            ("foo/idx", false),
+        ] {
+            assert_eq!(
+                has_ext(&object_store::path::Path::from(path), "idx"),
+                expected,
+                "{path}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_idx_path_to_grib_path() -> anyhow::Result<()> {
+        let check = |idx_path: &str, expected_grib_path: &str| -> anyhow::Result<()> {
+            assert_eq!(
+                idx_path_to_grib_path(&object_store::path::Path::from(idx_path))?,
+                object_store::path::Path::from(expected_grib_path)
+            );
+            Ok(())
+        };
+
+        // The GRIB file has no extension:
+        check(
+            "gefs.20170101/00/gec00.t00z.pgrb2af006.idx",
+            "gefs.20170101/00/gec00.t00z.pgrb2af006",
+        )?;
+        check(
+            "gefs.20241010/00/atmos/pgrb2ap5/gec00.t00z.pgrb2a.0p50.f000.idx",
+            "gefs.20241010/00/atmos/pgrb2ap5/gec00.t00z.pgrb2a.0p50.f000",
+        )?;
+
+        // The GRIB file has a `.grib2` extension:
+        check(
+            "gefs.20241008/00/chem/pgrb2ap25/gefs.chem.t00z.a2d_0p25.f000.grib2.idx",
+            "gefs.20241008/00/chem/pgrb2ap25/gefs.chem.t00z.a2d_0p25.f000.grib2",
+        )?;
+
+        // The GRIB file is compressed:
+        check("foo/bar.grib2.bz2.idx", "foo/bar.grib2.bz2")?;
+
+        // The idx file is compressed:
+        check("foo/bar.grib2.idx.gz", "foo/bar.grib2")?;
+
+        // Not idx paths:
+        for path in [
+            "gefs.20170101/00/gec00.t00z.pgrb2af006",
+            "foo/.idx",
+            "foo/bar.gz",
+            "",
+        ] {
+            assert!(idx_path_to_grib_path(&object_store::path::Path::from(path)).is_err());
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_latest_init_for_valid_time() {
+        // Hourly reference datetimes for 2017-01-01, and 3-hourly steps from 3 to 24 hours:
+        let coord_labels = CoordLabels::new(
+            (0..24).map(|h| ymdh_to_datetime(2017, 1, 1, h)).collect(),
+            SortedVec::default(),
+            (1..=8)
+                .map(|i| ForecastStep::Instant(TimeDelta::hours(i * 3)))
+                .collect(),
+            SortedVec::default(),
+            SortedVec::default(),
+        );
+        let steps = coord_labels.forecast_step();
+        let instant = |hours| ForecastStep::Instant(TimeDelta::hours(hours));
+
+        assert_eq!(
+            coord_labels.latest_init_for_valid_time(ymdh_to_datetime(2017, 1, 1, 10), steps),
+            [(ymdh_to_datetime(2017, 1, 1, 7), instant(3))]
+        );
+        assert_eq!(
+            coord_labels.latest_init_for_valid_time(ymdh_to_datetime(2017, 1, 2, 5), steps),
+            [(ymdh_to_datetime(2017, 1, 1, 23), instant(6))]
+        );
+        assert_eq!(
+            coord_labels.latest_init_for_valid_time(ymdh_to_datetime(2017, 1, 2, 23), steps),
+            [(ymdh_to_datetime(2017, 1, 1, 23), instant(24))]
+        );
+
+        // Too early or too late:
+        for valid_time in [
+            ymdh_to_datetime(2017, 1, 1, 2),
+            ymdh_to_datetime(2017, 1, 3, 0),
+        ] {
+            assert!(coord_labels
+                .latest_init_for_valid_time(valid_time, steps)
+                .is_empty());
+        }
+
+        // Multiple steps from the same reference datetime can be valid at the same time:
+        let steps = [
+            ForecastStep::Range {
+                start: TimeDelta::hours(3),
+                end: TimeDelta::hours(6),
+            },
+            instant(6),
+            ForecastStep::Range {
+                start: TimeDelta::zero(),
+                end: TimeDelta::hours(6),
+            },
+            instant(9),
+        ];
+        let valid_time = ymdh_to_datetime(2017, 1, 2, 5);
+        let init = ymdh_to_datetime(2017, 1, 1, 23);
+        assert_eq!(
+            coord_labels.latest_init_for_valid_time(valid_time, &steps),
+            [(init, steps[2]), (init, steps[0]), (init, steps[1])]
+        );
+    }
+
+    /// A mock dataset whose reference datetimes are the first hour of each day in `prefix`,
+    /// where `prefix` is the year.
+    struct MockDataset {
+        prefix: object_store::path::Path,
+    }
+
+    impl GetCoordLabels for MockDataset {
+        async fn get_coord_labels(self) -> anyhow::Result<CoordLabels> {
+            let year: i32 = self.prefix.as_ref().parse()?;
+            Ok(CoordLabels::new(
+                SortedVec::from(vec![
+                    ymdh_to_datetime(year, 1, 1, 0),
+                    ymdh_to_datetime(year, 1, 2, 0),
+                ]),
+                SortedVec::from(vec![String::from("gec00")]),
+                SortedVec::default(),
+                SortedVec::from(vec![format!("PARAM_{year}"), String::from("TMP")]),
+                SortedVec::default(),
+            ))
+        }
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[tokio::test]
+    async fn test_get_coord_labels_for_prefixes() -> anyhow::Result<()> {
+        let prefixes = ["2018", "2017"].map(object_store::path::Path::from);
+        let coord_labels =
+            get_coord_labels_for_prefixes(prefixes, |prefix| MockDataset { prefix }).await?;
+        assert_eq!(
+            **coord_labels.reference_datetime(),
+            [
+                ymdh_to_datetime(2017, 1, 1, 0),
+                ymdh_to_datetime(2017, 1, 2, 0),
+                ymdh_to_datetime(2018, 1, 1, 0),
+                ymdh_to_datetime(2018, 1, 2, 0),
+            ]
+        );
+        assert_eq!(**coord_labels.ensemble_member(), ["gec00"]);
+        assert_eq!(
+            **coord_labels.parameter(),
+            ["PARAM_2017", "PARAM_2018", "TMP"]
+        );
+
+        let prefixes = ["2017", "not a year"].map(object_store::path::Path::from);
+        assert!(
+            get_coord_labels_for_prefixes(prefixes, |prefix| MockDataset { prefix })
+                .await
+                .is_err()
+        );
+        Ok(())
     }
 }
@@ -5,32 +5,140 @@ use std::fs;
 use url::Url;
 
 use hypergrib::filter_by_ext;
+use hypergrib::inspect::inspect_grib_message;
+use hypergrib::store::{open_store, AccessMode, StoreConfig};
 
-/// Create a manifest from GRIB `.idx` files.
+/// Tools for working with GRIB datasets.
 #[derive(Parser, Debug)]
 #[command(version, about, long_about = None)]
-struct Args {
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(clap::Subcommand, Debug)]
+enum Command {
+    /// List the GRIB `.idx` files under a URL, and download the first few.
+    List(ListArgs),
+
+    /// Fetch and decode a single GRIB message, and print a summary of it.
+    Inspect(InspectArgs),
+
+    /// Compare two `.idx` files: Print the messages which are only in A, only in B, and the
+    /// messages whose byte offset or length changed. Messages are identified by their parameter,
+    /// vertical level, and forecast step.
+    DiffIdx(DiffIdxArgs),
+}
+
+#[derive(clap::Args, Debug)]
+struct ListArgs {
     /// The URL of the GRIB files. For example "s3://noaa-gefs-pds/gefs.20170101/00/"
     #[arg(long)]
     url: Url,
 
-    /// Set this flag if accessing a bucket that requires authentication.
+    /// Whether to sign requests. `auto` tries anonymous access first, and then falls back to
+    /// signed access if anonymous access is denied. Requests to `http://` and `https://` URLs are
+    /// never signed.
+    #[arg(long, value_enum, default_value_t = AccessMode::Auto)]
+    access_mode: AccessMode,
+
+    #[command(flatten)]
+    store_config: StoreConfig,
+}
+
+#[derive(clap::Args, Debug)]
+struct InspectArgs {
+    /// The URL of the GRIB file. For example
+    /// "s3://noaa-gefs-pds/gefs.20170101/00/gec00.t00z.pgrb2aanl"
+    #[arg(long)]
+    url: Url,
+
+    /// The byte offset of the GRIB message within the GRIB file.
     #[arg(long)]
-    sign: bool,
+    offset: u64,
+
+    /// The length of the GRIB message in bytes.
+    #[arg(long)]
+    length: u64,
+
+    /// The number of data values to print.
+    #[arg(long, default_value_t = 10)]
+    n_sample_values: usize,
+
+    /// Whether to sign requests. `auto` tries anonymous access first, and then falls back to
+    /// signed access if anonymous access is denied. Requests to `http://` and `https://` URLs are
+    /// never signed.
+    #[arg(long, value_enum, default_value_t = AccessMode::Auto)]
+    access_mode: AccessMode,
+
+    #[command(flatten)]
+    store_config: StoreConfig,
+}
+
+#[derive(clap::Args, Debug)]
+struct DiffIdxArgs {
+    /// The URL of the first `.idx` file (A).
+    url_a: Url,
+
+    /// The URL of the second `.idx` file (B).
+    url_b: Url,
+
+    /// Whether to sign requests. `auto` tries anonymous access first, and then falls back to
+    /// signed access if anonymous access is denied. Requests to `http://` and `https://` URLs are
+    /// never signed.
+    #[arg(long, value_enum, default_value_t = AccessMode::Auto)]
+    access_mode: AccessMode,
+
+    #[command(flatten)]
+    store_config: StoreConfig,
 }
 
 #[tokio::main]
 pub async fn main() {
-    let args = Args::parse();
+    match Cli::parse().command {
+        Command::List(args) => list(args).await,
+        Command::Inspect(args) => inspect(args).await,
+        Command::DiffIdx(args) => diff_idx(args).await,
+    }
+}
+
+async fn diff_idx(args: DiffIdxArgs) {
+    let mut records = vec![];
+    for url in [&args.url_a, &args.url_b] {
+        let (store, path, _) = open_store(url, args.access_mode, &args.store_config)
+            .await
+            .unwrap();
+        let bytes = store.get(&path).await.unwrap().bytes().await.unwrap();
+        records.push(hypergrib_idx_parser::parse_idx(&bytes).unwrap());
+    }
+    let diff = hypergrib_idx_parser::diff_idx(&records[0], &records[1]).unwrap();
+    println!("A: {}\nB: {}\n{diff}", args.url_a, args.url_b);
+}
 
+async fn inspect(args: InspectArgs) {
+    let (store, path, access_mode) = open_store(&args.url, args.access_mode, &args.store_config)
+        .await
+        .unwrap();
+    println!("Using {access_mode:?} access");
+    let summary = inspect_grib_message(
+        store.as_ref(),
+        &path,
+        args.offset..args.offset + args.length,
+        args.n_sample_values,
+    )
+    .await
+    .unwrap();
+    println!("{summary}");
+}
+
+async fn list(args: ListArgs) {
     println!("{}", args.url);
 
-    // Get options, store, and path:
-    let mut opts = vec![];
-    if !args.sign {
-        opts.push(("skip_signature", "true"));
-    }
-    let (store, path) = object_store::parse_url_opts(&args.url, opts).unwrap();
+    // Get store and path:
+    let (store, path, access_mode) = open_store(&args.url, args.access_mode, &args.store_config)
+        .await
+        .unwrap();
+    println!("Using {access_mode:?} access");
 
     // Get listing of .idx files:
     let mut list_stream = filter_by_ext(store.list(Some(&path)), "idx");
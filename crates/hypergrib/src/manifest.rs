@@ -0,0 +1,1658 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    fmt::{self, Write},
+    sync::Arc,
+};
+
+use anyhow::Context;
+use bytes::Bytes;
+use chrono::{DateTime, Utc};
+use futures_util::{Stream, StreamExt};
+use hypergrib_idx_parser::{msg_lengths, parse_idx};
+use object_store::ObjectStore;
+
+use crate::{idx_path_to_grib_path, CoordLabels, ForecastStep, FromIdxPath, Level, Statistic};
+
+/// The maximum number of concurrent HEAD requests sent by `Manifest::from_idx_stream_and_store`.
+const MAX_CONCURRENT_HEADS: usize = 256;
+
+/// The coordinates of a single GRIB message. Create a `Key` using [`KeyBuilder`].
+#[derive(PartialEq, Eq, Hash, Clone, Debug)] // PartialEq, Eq, and Hash are required for HashMap keys.
+pub struct Key {
+    reference_datetime: DateTime<Utc>,
+    ensemble_member: String, // TODO: Convert to info from GDAL GRIB tables
+    forecast_step: ForecastStep,
+    parameter: String, //  TODO: Convert to info from GDAL GRIB tables
+    vertical_level: Level,
+    /// `None` for deterministic fields.
+    statistic: Option<Statistic>,
+}
+
+impl Key {
+    pub fn reference_datetime(&self) -> &DateTime<Utc> {
+        &self.reference_datetime
+    }
+
+    pub fn ensemble_member(&self) -> &str {
+        &self.ensemble_member
+    }
+
+    pub fn forecast_step(&self) -> &ForecastStep {
+        &self.forecast_step
+    }
+
+    pub fn parameter(&self) -> &str {
+        &self.parameter
+    }
+
+    pub fn vertical_level(&self) -> &Level {
+        &self.vertical_level
+    }
+
+    /// The percentile or probability of a probabilistic product. `None` for deterministic fields.
+    pub fn statistic(&self) -> Option<&Statistic> {
+        self.statistic.as_ref()
+    }
+
+    /// The datetime at which this message's field is valid: The reference datetime plus the end
+    /// of the forecast step. For example, the 6-hour forecast from 00Z and the 0-hour forecast
+    /// from 06Z are both valid at 06Z.
+    pub fn valid_time(&self) -> DateTime<Utc> {
+        self.reference_datetime + self.forecast_step.end()
+    }
+}
+
+/// The only public way to create a [`Key`]. All the fields except `statistic` (which defaults to
+/// `None`) must be set before calling `build`.
+///
+/// ```
+/// use chrono::{TimeDelta, TimeZone, Utc};
+/// use hypergrib::{ForecastStep, KeyBuilder, Level};
+/// # fn main() -> anyhow::Result<()> {
+/// let key = KeyBuilder::new()
+///     .set_reference_datetime(Utc.with_ymd_and_hms(2017, 1, 1, 0, 0, 0).unwrap())
+///     .set_ensemble_member("gec00")
+///     .set_forecast_step(ForecastStep::Instant(TimeDelta::hours(6)))
+///     .set_parameter("TMP")
+///     .set_vertical_level(Level::Isobaric(1_000))
+///     .build()?;
+/// assert_eq!(key.parameter(), "TMP");
+/// assert_eq!(key.vertical_level(), &Level::Isobaric(1_000));
+///
+/// // Create a `Key` which differs from `key` only in its parameter:
+/// let hgt_key = KeyBuilder::from(key.clone()).set_parameter("HGT").build()?;
+/// assert_eq!(hgt_key.parameter(), "HGT");
+/// assert_eq!(hgt_key.forecast_step(), key.forecast_step());
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Default, Clone, Debug)]
+pub struct KeyBuilder {
+    reference_datetime: Option<DateTime<Utc>>,
+    ensemble_member: Option<String>,
+    forecast_step: Option<ForecastStep>,
+    parameter: Option<String>,
+    vertical_level: Option<Level>,
+    statistic: Option<Statistic>,
+}
+
+impl KeyBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set_reference_datetime(&mut self, reference_datetime: DateTime<Utc>) -> &mut Self {
+        self.reference_datetime = Some(reference_datetime);
+        self
+    }
+
+    pub fn set_ensemble_member(&mut self, ensemble_member: impl Into<String>) -> &mut Self {
+        self.ensemble_member = Some(ensemble_member.into());
+        self
+    }
+
+    pub fn set_forecast_step(&mut self, forecast_step: ForecastStep) -> &mut Self {
+        self.forecast_step = Some(forecast_step);
+        self
+    }
+
+    pub fn set_parameter(&mut self, parameter: impl Into<String>) -> &mut Self {
+        self.parameter = Some(parameter.into());
+        self
+    }
+
+    pub fn set_vertical_level(&mut self, vertical_level: Level) -> &mut Self {
+        self.vertical_level = Some(vertical_level);
+        self
+    }
+
+    pub fn set_statistic(&mut self, statistic: Option<Statistic>) -> &mut Self {
+        self.statistic = statistic;
+        self
+    }
+
+    /// Returns an error if any field (except `statistic`) hasn't been set.
+    pub fn build(&self) -> anyhow::Result<Key> {
+        let missing = |field| anyhow::format_err!("KeyBuilder: `{field}` must be set");
+        Ok(Key {
+            reference_datetime: self
+                .reference_datetime
+                .ok_or_else(|| missing("reference_datetime"))?,
+            ensemble_member: self
+                .ensemble_member
+                .clone()
+                .ok_or_else(|| missing("ensemble_member"))?,
+            forecast_step: self.forecast_step.ok_or_else(|| missing("forecast_step"))?,
+            parameter: self.parameter.clone().ok_or_else(|| missing("parameter"))?,
+            vertical_level: self
+                .vertical_level
+                .clone()
+                .ok_or_else(|| missing("vertical_level"))?,
+            statistic: self.statistic.clone(),
+        })
+    }
+}
+
+impl From<Key> for KeyBuilder {
+    fn from(key: Key) -> Self {
+        Self {
+            reference_datetime: Some(key.reference_datetime),
+            ensemble_member: Some(key.ensemble_member),
+            forecast_step: Some(key.forecast_step),
+            parameter: Some(key.parameter),
+            vertical_level: Some(key.vertical_level),
+            statistic: key.statistic,
+        }
+    }
+}
+
+impl CoordLabels {
+    /// Lazily iterate over every `Key` in the cartesian product of the coordinate labels, in
+    /// row-major order (i.e. `statistic` changes fastest, and `reference_datetime` changes
+    /// slowest). The product can be huge, so avoid collecting it into a `Vec`!
+    pub fn iter_keys(&self) -> impl Iterator<Item = Key> + '_ {
+        self.reference_datetime
+            .iter()
+            .flat_map(move |reference_datetime| {
+                self.ensemble_member
+                    .iter()
+                    .flat_map(move |ensemble_member| {
+                        self.forecast_step.iter().flat_map(move |forecast_step| {
+                            self.parameter.iter().flat_map(move |parameter| {
+                                self.vertical_level.iter().flat_map(move |vertical_level| {
+                                    self.statistic.iter().map(move |statistic| Key {
+                                        reference_datetime: *reference_datetime,
+                                        ensemble_member: ensemble_member.clone(),
+                                        forecast_step: *forecast_step,
+                                        parameter: parameter.clone(),
+                                        vertical_level: vertical_level.clone(),
+                                        statistic: statistic.clone(),
+                                    })
+                                })
+                            })
+                        })
+                    })
+            })
+    }
+}
+
+/// The location of a GRIB message.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct MessageLocation {
+    path: Arc<object_store::path::Path>,
+    byte_offset: u64,
+    msg_length: u64,
+    /// The ETag of the GRIB file when the manifest was built, if known.
+    etag: Option<String>,
+    // TODO: Store a reference to coord labels for x and y?
+    // TODO: Maybe a ref to a struct which holds lots of metadata about this grib message such as:
+    // - coord labels for x and y
+    // - NWP model version
+    // - other metadata?
+}
+
+/// What `Manifest::insert` does when the key is already in the manifest, but at a different
+/// location. This happens when the same message is listed in two overlapping folders (e.g. the
+/// GEFS `Version::V2` runs, which have both "V1-like" and "V3-like" folders).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum ConflictPolicy {
+    /// Replace the existing location.
+    #[default]
+    LastWins,
+    /// Keep the existing location.
+    FirstWins,
+    /// Keep the location whose path contains the earliest of these path parts. For example,
+    /// `vec!["pgrb2a".into(), "atmos".into()]` prefers `gefs.20200923/00/pgrb2a/` over
+    /// `gefs.20200923/00/atmos/pgrb2ap5/`. Paths without any of these parts come last. If both
+    /// locations are equally preferred then the existing location is kept.
+    PreferPathParts(Vec<String>),
+}
+
+impl ConflictPolicy {
+    /// Returns true if `new` should replace `existing`.
+    fn prefer_new(&self, existing: &MessageLocation, new: &MessageLocation) -> bool {
+        match self {
+            Self::LastWins => true,
+            Self::FirstWins => false,
+            Self::PreferPathParts(preferred_parts) => {
+                let rank = |msg_loc: &MessageLocation| {
+                    preferred_parts
+                        .iter()
+                        .position(|preferred| {
+                            msg_loc.path.parts().any(|part| part.as_ref() == preferred)
+                        })
+                        .unwrap_or(preferred_parts.len())
+                };
+                rank(new) < rank(existing)
+            }
+        }
+    }
+}
+
+/// The same key was inserted into a `Manifest` at two different locations. See `ConflictPolicy`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Conflict {
+    pub key: Key,
+    /// The location which is in the manifest.
+    pub kept: MessageLocation,
+    /// The location which was dropped.
+    pub discarded: MessageLocation,
+}
+
+impl fmt::Display for Conflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:?} is at byte range {:?} of '{}' and at byte range {:?} of '{}'. Keeping '{}'.",
+            self.key,
+            self.kept.byte_range(),
+            self.kept.path,
+            self.discarded.byte_range(),
+            self.discarded.path,
+            self.kept.path,
+        )
+    }
+}
+
+/// Maps from the coordinates of each GRIB message to the location of that message.
+pub struct Manifest {
+    /// The `path` of each `MessageLocation` is relative to `base_path`.
+    base_path: object_store::path::Path,
+
+    /// Each unique path is stored once, and shared between `MessageLocation`s using an `Arc`.
+    paths: HashSet<Arc<object_store::path::Path>>,
+
+    manifest: HashMap<Key, MessageLocation>,
+
+    conflict_policy: ConflictPolicy,
+
+    conflicts: Vec<Conflict>,
+}
+
+impl MessageLocation {
+    /// The path of the GRIB file, relative to `Manifest::base_path`.
+    pub fn path(&self) -> &object_store::path::Path {
+        &self.path
+    }
+
+    pub fn byte_offset(&self) -> u64 {
+        self.byte_offset
+    }
+
+    pub fn msg_length(&self) -> u64 {
+        self.msg_length
+    }
+
+    /// The byte range of the GRIB message within the GRIB file.
+    pub fn byte_range(&self) -> std::ops::Range<u64> {
+        self.byte_offset..self.byte_offset + self.msg_length
+    }
+
+    /// The ETag of the GRIB file when the manifest was built. `None` if the manifest was built
+    /// without reading the GRIB file's metadata (e.g. by `Manifest::from_idx_stream`). If the
+    /// current ETag of the GRIB file (e.g. from `ObjectStore::head`) differs then the GRIB file
+    /// has changed since the manifest was built, so anything cached from this message is stale.
+    pub fn etag(&self) -> Option<&str> {
+        self.etag.as_deref()
+    }
+}
+
+/// The serialized form of a `MessageLocation`. `etag` is omitted if it's `None`.
+#[derive(serde::Serialize, serde::Deserialize)]
+struct MessageLocationJson {
+    path: String,
+    byte_offset: u64,
+    msg_length: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    etag: Option<String>,
+}
+
+impl serde::Serialize for MessageLocation {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        MessageLocationJson {
+            path: self.path.to_string(),
+            byte_offset: self.byte_offset,
+            msg_length: self.msg_length,
+            etag: self.etag.clone(),
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for MessageLocation {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let json = MessageLocationJson::deserialize(deserializer)?;
+        let path = object_store::path::Path::parse(&json.path).map_err(serde::de::Error::custom)?;
+        Ok(Self {
+            path: Arc::new(path),
+            byte_offset: json.byte_offset,
+            msg_length: json.msg_length,
+            etag: json.etag,
+        })
+    }
+}
+
+/// The final message of a GRIB file, whose length isn't known until we know the size of the GRIB
+/// file.
+struct FinalMessage {
+    key: Key,
+    grib_path: object_store::path::Path,
+    byte_offset: u64,
+}
+
+impl Manifest {
+    pub fn new(base_path: object_store::path::Path) -> Self {
+        Self {
+            base_path,
+            paths: HashSet::new(),
+            manifest: HashMap::new(),
+            conflict_policy: ConflictPolicy::default(),
+            conflicts: Vec::new(),
+        }
+    }
+
+    /// How `insert` resolves a key which is inserted at two different locations. The default is
+    /// `ConflictPolicy::LastWins`.
+    pub fn with_conflict_policy(mut self, conflict_policy: ConflictPolicy) -> Self {
+        self.conflict_policy = conflict_policy;
+        self
+    }
+
+    /// Build a manifest from a stream of `.idx` files. Each item is the path of an `.idx` file
+    /// (relative to `base_path`) and the uncompressed body of that file. Each `.idx` file is
+    /// parsed and inserted as soon as it arrives, and then dropped, so memory use only grows with
+    /// the size of the manifest, not with the number of `.idx` files in flight.
+    ///
+    /// `D` gets the ensemble member from each `.idx` path. `grib_file_size` is given the path of
+    /// each GRIB file (relative to `base_path`), and should return the size of that GRIB file if
+    /// it's known. The length of the final message in each GRIB file can only be computed from the
+    /// size of the GRIB file, so the final message is skipped if `grib_file_size` returns `None`.
+    /// Records with an empty parameter or vertical level are also skipped. A message which is
+    /// listed in more than one `.idx` file is resolved with `ConflictPolicy::LastWins`, and
+    /// recorded in `Manifest::conflicts`.
+    ///
+    /// See `from_idx_stream_and_store` to get the size of each GRIB file from the object store.
+    pub async fn from_idx_stream<D: FromIdxPath>(
+        base_path: object_store::path::Path,
+        idx_files: impl Stream<Item = (object_store::path::Path, Bytes)>,
+        grib_file_size: impl Fn(&object_store::path::Path) -> Option<u64>,
+    ) -> anyhow::Result<Self> {
+        let mut manifest = Self::new(base_path);
+        let mut idx_files = std::pin::pin!(idx_files);
+        while let Some((idx_path, bytes)) = idx_files.next().await {
+            manifest
+                .insert_idx::<D>(&idx_path, &bytes, &grib_file_size)
+                .with_context(|| format!("Failed to insert the records of '{idx_path}'"))?;
+        }
+        Ok(manifest)
+    }
+
+    /// Like `from_idx_stream`, but the final message of each GRIB file isn't skipped: Instead, the
+    /// size of each GRIB file is read from `store` (which must be the store which `base_path`
+    /// refers to) using a HEAD request, and the length of the final message is the size of the
+    /// GRIB file minus the byte offset of the final message. The HEAD requests are sent
+    /// concurrently, after the stream of `.idx` files is exhausted.
+    ///
+    /// The ETag returned by each HEAD request (if any) is recorded in the `MessageLocation` of
+    /// every message in that GRIB file (see `MessageLocation::etag`).
+    pub async fn from_idx_stream_and_store<D: FromIdxPath>(
+        store: &dyn ObjectStore,
+        base_path: object_store::path::Path,
+        idx_files: impl Stream<Item = (object_store::path::Path, Bytes)>,
+    ) -> anyhow::Result<Self> {
+        let mut manifest = Self::new(base_path);
+        let mut final_messages = Vec::new();
+        let mut idx_files = std::pin::pin!(idx_files);
+        while let Some((idx_path, bytes)) = idx_files.next().await {
+            let final_message = manifest
+                .insert_idx::<D>(&idx_path, &bytes, |_| None)
+                .with_context(|| format!("Failed to insert the records of '{idx_path}'"))?;
+            final_messages.extend(final_message);
+        }
+
+        let base_path = manifest.base_path.clone();
+        let mut grib_file_sizes = futures_util::stream::iter(final_messages)
+            .map(|final_message| {
+                let path = object_store::path::Path::from_iter(
+                    base_path.parts().chain(final_message.grib_path.parts()),
+                );
+                async move {
+                    let meta = store
+                        .head(&path)
+                        .await
+                        .with_context(|| format!("Failed to get the size of '{path}'"))?;
+                    anyhow::Ok((final_message, meta.size as u64, meta.e_tag))
+                }
+            })
+            .buffer_unordered(MAX_CONCURRENT_HEADS);
+        let mut etags = HashMap::new();
+        while let Some(result) = grib_file_sizes.next().await {
+            let (final_message, file_size, etag) = result?;
+            let msg_length = file_size
+                .checked_sub(final_message.byte_offset)
+                .filter(|msg_length| *msg_length > 0)
+                .with_context(|| {
+                    format!(
+                        "The size of '{}' ({file_size}) must be larger than the byte offset of \
+                         its final message ({})",
+                        final_message.grib_path, final_message.byte_offset
+                    )
+                })?;
+            if let Some(etag) = etag {
+                etags.insert(final_message.grib_path.clone(), etag);
+            }
+            manifest.insert(
+                final_message.key,
+                &final_message.grib_path,
+                final_message.byte_offset,
+                msg_length,
+            );
+        }
+        for msg_loc in manifest.manifest.values_mut() {
+            if let Some(etag) = etags.get(msg_loc.path.as_ref()) {
+                msg_loc.etag = Some(etag.clone());
+            }
+        }
+        Ok(manifest)
+    }
+
+    /// Insert the records of one `.idx` file. If the length of the final message is unknown (and
+    /// the final record isn't skipped for other reasons) then the final message isn't inserted,
+    /// and is returned instead.
+    fn insert_idx<D: FromIdxPath>(
+        &mut self,
+        idx_path: &object_store::path::Path,
+        bytes: &[u8],
+        grib_file_size: impl Fn(&object_store::path::Path) -> Option<u64>,
+    ) -> anyhow::Result<Option<FinalMessage>> {
+        let grib_path = idx_path_to_grib_path(idx_path)?;
+        let ensemble_member = D::ensemble_member_from_idx_path(idx_path)?;
+        let records = parse_idx(bytes)?;
+        let msg_lengths = msg_lengths(&records, grib_file_size(&grib_path))?;
+        let mut final_message = None;
+        for (record, msg_length) in records.into_iter().zip(msg_lengths) {
+            if record.has_empty_field() {
+                continue;
+            }
+            let key = Key {
+                reference_datetime: record.reference_datetime,
+                ensemble_member: ensemble_member.clone(),
+                forecast_step: record.forecast_step,
+                parameter: record.parameter,
+                vertical_level: record.vertical_level,
+                statistic: record.statistic,
+            };
+            match msg_length {
+                Some(msg_length) => {
+                    self.insert(key, &grib_path, record.byte_offset, msg_length);
+                }
+                None => {
+                    final_message = Some(FinalMessage {
+                        key,
+                        grib_path: grib_path.clone(),
+                        byte_offset: record.byte_offset,
+                    })
+                }
+            }
+        }
+        Ok(final_message)
+    }
+
+    /// Returns `true` if `key` was not already present in the manifest.
+    ///
+    /// If `key` was already present at a different location then the `ConflictPolicy` decides
+    /// which location is kept, and the conflict is recorded (see `Manifest::conflicts`).
+    /// Inserting the same key at the same location again isn't a conflict.
+    pub fn insert(
+        &mut self,
+        key: Key,
+        path: &object_store::path::Path,
+        byte_offset: u64,
+        msg_length: u64,
+    ) -> bool {
+        let msg_loc = MessageLocation {
+            path: match self.paths.get(path) {
+                Some(path) => path.clone(),
+                None => Arc::new(path.clone()),
+            },
+            byte_offset,
+            msg_length,
+            etag: None,
+        };
+        self.insert_message_location(key, msg_loc)
+    }
+
+    /// Like `Manifest::insert`, but keeps the ETag of `msg_loc` (e.g. when reading a manifest
+    /// which was built by `Manifest::from_idx_stream_and_store`).
+    pub(crate) fn insert_message_location(
+        &mut self,
+        key: Key,
+        mut msg_loc: MessageLocation,
+    ) -> bool {
+        if let Some(path) = self.paths.get(&msg_loc.path) {
+            msg_loc.path = path.clone();
+        }
+        let existing = match self.manifest.get(&key) {
+            None => None,
+            Some(existing)
+                if existing.path == msg_loc.path
+                    && existing.byte_range() == msg_loc.byte_range() =>
+            {
+                return false;
+            }
+            Some(existing) => Some(existing.clone()),
+        };
+        let is_new_key = existing.is_none();
+        let msg_loc = match existing {
+            Some(existing) if !self.conflict_policy.prefer_new(&existing, &msg_loc) => {
+                self.conflicts.push(Conflict {
+                    key,
+                    kept: existing,
+                    discarded: msg_loc,
+                });
+                return false;
+            }
+            Some(existing) => {
+                self.conflicts.push(Conflict {
+                    key: key.clone(),
+                    kept: msg_loc.clone(),
+                    discarded: existing,
+                });
+                msg_loc
+            }
+            None => msg_loc,
+        };
+        self.paths.insert(msg_loc.path.clone());
+        self.manifest.insert(key, msg_loc);
+        if let Some(conflict) = self.conflicts.last().filter(|_| !is_new_key) {
+            // Forget the discarded path if only `paths` and the conflict still refer to it.
+            if Arc::strong_count(&conflict.discarded.path) == 2 {
+                self.paths.remove(&conflict.discarded.path);
+            }
+        }
+        is_new_key
+    }
+
+    /// The keys which were inserted at two different locations, in the order they were found.
+    /// Each conflict is a warning that the manifest may not point at the intended messages.
+    pub fn conflicts(&self) -> &[Conflict] {
+        &self.conflicts
+    }
+
+    pub fn get(&self, key: &Key) -> Option<&MessageLocation> {
+        self.manifest.get(key)
+    }
+
+    /// Iterate over every GRIB message in the manifest, in arbitrary order.
+    pub fn iter(&self) -> impl Iterator<Item = (&Key, &MessageLocation)> {
+        self.manifest.iter()
+    }
+
+    /// The sorted, unique valid times (see `Key::valid_time`) of all the keys in the manifest.
+    /// Several (reference datetime, forecast step) pairs usually share a valid time, so there are
+    /// usually fewer valid times than pairs.
+    pub fn valid_times(&self) -> Vec<DateTime<Utc>> {
+        let valid_times: BTreeSet<_> = self.iter().map(|(key, _)| key.valid_time()).collect();
+        valid_times.into_iter().collect()
+    }
+
+    /// Iterate over every GRIB message which is valid at `valid_time`, in arbitrary order. This
+    /// yields one message per (reference datetime, forecast step) pair which is valid at
+    /// `valid_time` (for each ensemble member, parameter, and vertical level), so use
+    /// `Key::reference_datetime` to pick (for example) the most recent forecast.
+    pub fn iter_valid_time(
+        &self,
+        valid_time: DateTime<Utc>,
+    ) -> impl Iterator<Item = (&Key, &MessageLocation)> {
+        self.iter()
+            .filter(move |(key, _)| key.valid_time() == valid_time)
+    }
+
+    pub fn base_path(&self) -> &object_store::path::Path {
+        &self.base_path
+    }
+
+    /// The full path of the GRIB file which holds the message at `msg_loc`: `base_path` joined
+    /// with `msg_loc.path()`. The paths are joined part by part (not by string concatenation),
+    /// so that percent-encoded parts aren't encoded twice.
+    pub fn resolve_path(&self, msg_loc: &MessageLocation) -> object_store::path::Path {
+        object_store::path::Path::from_iter(self.base_path.parts().chain(msg_loc.path().parts()))
+    }
+
+    /// Check that every path in the manifest is a relative path which stays under `base_path`
+    /// when resolved (see `Manifest::resolve_path`). Returns the offending paths (sorted) if any
+    /// path:
+    ///
+    /// - is empty (so it resolves to `base_path` itself),
+    /// - has a `.` or `..` part (which `object_store` escapes, so these paths would 404 rather
+    ///   than escape `base_path`),
+    /// - starts with a URL scheme (e.g. `s3://bucket/...`, which was meant to be absolute), or
+    /// - starts with the parts of `base_path` (so `base_path` would be repeated when resolved).
+    ///
+    /// All of these are bugs in the code which constructed the paths, which would otherwise only
+    /// be found when reading the messages.
+    pub fn validate_paths(&self) -> Result<(), Vec<object_store::path::Path>> {
+        let dot = object_store::path::PathPart::from(".");
+        let dot_dot = object_store::path::PathPart::from("..");
+        let base_parts: Vec<_> = self.base_path.parts().collect();
+        let mut invalid: Vec<_> = self
+            .paths
+            .iter()
+            .filter(|path| {
+                let parts: Vec<_> = path.parts().collect();
+                parts.is_empty()
+                    || parts.iter().any(|part| *part == dot || *part == dot_dot)
+                    || parts[0].as_ref().ends_with(':')
+                    || (!base_parts.is_empty() && parts.starts_with(&base_parts))
+            })
+            .map(|path| object_store::path::Path::clone(path))
+            .collect();
+        if invalid.is_empty() {
+            return Ok(());
+        }
+        invalid.sort();
+        Err(invalid)
+    }
+
+    /// The number of GRIB messages in the manifest.
+    pub fn len(&self) -> usize {
+        self.manifest.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.manifest.is_empty()
+    }
+
+    /// The number of unique paths in the manifest.
+    pub fn num_paths(&self) -> usize {
+        self.paths.len()
+    }
+
+    /// The total size of all the GRIB messages in the manifest.
+    pub fn total_bytes(&self) -> u64 {
+        self.bytes_for_selection(|_| true)
+    }
+
+    /// The total size of the GRIB messages whose `Key` matches `selector`. For example, to
+    /// estimate the cost of reading one parameter over one year:
+    ///
+    /// ```ignore
+    /// manifest.bytes_for_selection(|key| {
+    ///     key.parameter() == "TMP" && key.reference_datetime().year() == 2020
+    /// })
+    /// ```
+    pub fn bytes_for_selection(&self, selector: impl Fn(&Key) -> bool) -> u64 {
+        self.iter()
+            .filter(|(key, _)| selector(key))
+            .map(|(_, msg_loc)| msg_loc.msg_length())
+            .sum()
+    }
+
+    /// Lazily iterate over the keys in the cartesian product of `coord_labels` which have no
+    /// message in the manifest, in the order of `CoordLabels::iter_keys`. Use the dataset's
+    /// `CoordLabels` to find messages which haven't been indexed, or `self.coord_labels()` to
+    /// find the gaps in the manifest.
+    pub fn missing_keys<'a>(
+        &'a self,
+        coord_labels: &'a CoordLabels,
+    ) -> impl Iterator<Item = Key> + 'a {
+        coord_labels
+            .iter_keys()
+            .filter(|key| !self.manifest.contains_key(key))
+    }
+
+    /// Get the sorted, unique coordinate labels of all the keys in the manifest.
+    pub fn coord_labels(&self) -> CoordLabels {
+        let mut reference_datetime = BTreeSet::new();
+        let mut ensemble_member = BTreeSet::new();
+        let mut forecast_step = BTreeSet::new();
+        let mut parameter = BTreeSet::new();
+        let mut vertical_level = BTreeSet::new();
+        let mut statistic = BTreeSet::new();
+        for (key, _) in self.iter() {
+            reference_datetime.insert(key.reference_datetime);
+            ensemble_member.insert(key.ensemble_member.clone());
+            forecast_step.insert(key.forecast_step);
+            parameter.insert(key.parameter.clone());
+            vertical_level.insert(key.vertical_level.clone());
+            statistic.insert(key.statistic.clone());
+        }
+        CoordLabels::new(
+            reference_datetime.into(),
+            ensemble_member.into(),
+            forecast_step.into(),
+            parameter.into(),
+            vertical_level.into(),
+        )
+        .with_statistic(statistic.into())
+    }
+
+    /// Split the manifest into one manifest per reference datetime (e.g. to store each NWP init
+    /// in its own partition, or to serve different inits from different workers). Each shard has
+    /// the same `base_path` as `self`, and only holds the paths of its own messages. Those paths
+    /// are shared (using an `Arc`) with `self`, so splitting doesn't copy any paths.
+    pub fn split_by_reference_datetime(&self) -> BTreeMap<DateTime<Utc>, Manifest> {
+        let mut shards = BTreeMap::new();
+        for (key, msg_loc) in self.iter() {
+            let shard = shards.entry(key.reference_datetime).or_insert_with(|| {
+                Manifest::new(self.base_path.clone())
+                    .with_conflict_policy(self.conflict_policy.clone())
+            });
+            shard.paths.insert(msg_loc.path.clone());
+            shard.manifest.insert(key.clone(), msg_loc.clone());
+        }
+        shards
+    }
+
+    /// Remove every message whose `Key` doesn't match `selector` (e.g. to drop the "b-set"
+    /// parameters, or the reference datetimes before 2023, before serializing the manifest). Paths
+    /// which are no longer used by any message are removed too, and so are the conflicts of the
+    /// removed keys. `coord_labels` and `describe` then only describe the remaining messages.
+    pub fn retain(&mut self, selector: impl Fn(&Key) -> bool) {
+        self.manifest.retain(|key, _| selector(key));
+        self.conflicts.retain(|conflict| selector(&conflict.key));
+        let used_paths: HashSet<_> = self
+            .manifest
+            .values()
+            .map(|msg_loc| &msg_loc.path)
+            .collect();
+        self.paths.retain(|path| used_paths.contains(path));
+    }
+
+    /// Summarise the contents of the manifest: The range of each coordinate, the number of
+    /// unique paths, the number of messages, the total number of bytes, and the number of
+    /// combinations of coordinate labels which have no message (the "gaps").
+    pub fn describe(&self) -> String {
+        let coord_labels = self.coord_labels();
+        let mut s = String::new();
+        writeln!(s, "Manifest with base_path='{}':", self.base_path).expect("writeln");
+        writeln!(
+            s,
+            "- {} messages in {} unique paths, totalling {} bytes.",
+            self.len(),
+            self.num_paths(),
+            self.total_bytes(),
+        )
+        .expect("writeln");
+        writeln!(
+            s,
+            "{}",
+            describe_axis("reference_datetime", coord_labels.reference_datetime())
+        )
+        .expect("writeln");
+        writeln!(
+            s,
+            "{}",
+            describe_axis("ensemble_member", coord_labels.ensemble_member())
+        )
+        .expect("writeln");
+        writeln!(
+            s,
+            "{}",
+            describe_axis("forecast_step", coord_labels.forecast_step())
+        )
+        .expect("writeln");
+        writeln!(
+            s,
+            "{}",
+            describe_axis("parameter", coord_labels.parameter())
+        )
+        .expect("writeln");
+        writeln!(
+            s,
+            "{}",
+            describe_axis("vertical_level", coord_labels.vertical_level())
+        )
+        .expect("writeln");
+        // Only describe the statistics of probabilistic products.
+        if coord_labels.statistic().iter().any(Option::is_some) {
+            writeln!(
+                s,
+                "{}",
+                describe_axis("statistic", coord_labels.statistic())
+            )
+            .expect("writeln");
+        }
+        let n_combinations = coord_labels.reference_datetime().len()
+            * coord_labels.ensemble_member().len()
+            * coord_labels.forecast_step().len()
+            * coord_labels.parameter().len()
+            * coord_labels.vertical_level().len()
+            * coord_labels.statistic().len();
+        writeln!(
+            s,
+            "- {} of the {} combinations of coordinate labels have no message.",
+            n_combinations - self.len(),
+            n_combinations,
+        )
+        .expect("writeln");
+        s
+    }
+}
+
+impl fmt::Display for Manifest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.describe())
+    }
+}
+
+impl fmt::Debug for Manifest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Manifest")
+            .field("base_path", &self.base_path)
+            .field("num_paths", &self.num_paths())
+            .field("num_messages", &self.len())
+            .finish()
+    }
+}
+
+fn describe_axis<T: fmt::Debug>(name: &str, labels: &[T]) -> String {
+    format!(
+        "- {name}: {} labels. First: {:?}. Last: {:?}",
+        labels.len(),
+        labels.first(),
+        labels.last()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeDelta, Timelike};
+
+    use crate::ymdh_to_datetime;
+
+    use super::*;
+
+    #[test]
+    fn test_describe() {
+        let mut manifest = Manifest::new(object_store::path::Path::from("gefs"));
+        let key = Key {
+            reference_datetime: ymdh_to_datetime(2017, 1, 1, 0),
+            ensemble_member: String::from("gec00"),
+            forecast_step: ForecastStep::Instant(TimeDelta::zero()),
+            parameter: String::from("HGT"),
+            vertical_level: Level::Isobaric(1_000),
+            statistic: None,
+        };
+        let path = object_store::path::Path::from("gefs.20170101/00/gec00.t00z.pgrb2aanl");
+        assert!(manifest.insert(key.clone(), &path, 0, 50487));
+        assert!(manifest.insert(
+            KeyBuilder::from(key.clone())
+                .set_parameter("TMP")
+                .build()
+                .unwrap(),
+            &path,
+            50487,
+            20166
+        ));
+        assert!(manifest.insert(
+            KeyBuilder::from(key.clone())
+                .set_forecast_step(ForecastStep::Instant(TimeDelta::hours(6)))
+                .build()
+                .unwrap(),
+            &object_store::path::Path::from("gefs.20170101/00/gec00.t00z.pgrb2af006"),
+            0,
+            50000
+        ));
+        assert!(!manifest.insert(key, &path, 0, 50487));
+
+        let description = manifest.describe();
+        assert!(description.starts_with("Manifest with base_path='gefs':\n"));
+        assert!(description.contains("3 messages in 2 unique paths, totalling 120653 bytes."));
+        assert!(description.contains(
+            "- ensemble_member: 1 labels. First: Some(\"gec00\"). Last: Some(\"gec00\")"
+        ));
+        assert!(description.contains("- parameter: 2 labels."));
+        assert!(description.contains("- forecast_step: 2 labels."));
+        assert!(
+            description.contains("1 of the 4 combinations of coordinate labels have no message.")
+        );
+        // Deterministic manifests don't describe the statistic dimension:
+        assert!(!description.contains("statistic"));
+        assert_eq!(format!("{manifest}"), description);
+    }
+
+    #[test]
+    fn test_conflict_policy() {
+        let key = Key {
+            reference_datetime: ymdh_to_datetime(2020, 9, 23, 0),
+            ensemble_member: String::from("gec00"),
+            forecast_step: ForecastStep::Instant(TimeDelta::zero()),
+            parameter: String::from("HGT"),
+            vertical_level: Level::Isobaric(1_000),
+            statistic: None,
+        };
+        // The same message in the "V1-like" and the "V3-like" folders of a GEFS V2 run:
+        let v1_like =
+            object_store::path::Path::from("gefs.20200923/00/pgrb2a/gec00.t00z.pgrb2aanl");
+        let v3_like = object_store::path::Path::from(
+            "gefs.20200923/00/atmos/pgrb2ap5/gec00.t00z.pgrb2a.0p50.f000",
+        );
+        let insert_both = |conflict_policy: ConflictPolicy, first, second| {
+            let mut manifest = Manifest::new(object_store::path::Path::from("gefs"))
+                .with_conflict_policy(conflict_policy);
+            assert!(manifest.insert(key.clone(), first, 0, 100));
+            assert!(!manifest.insert(key.clone(), second, 200, 100));
+            // Inserting the kept location again isn't another conflict:
+            let kept = manifest.get(&key).unwrap().clone();
+            assert!(!manifest.insert(key.clone(), kept.path(), kept.byte_offset(), 100));
+            assert_eq!(manifest.len(), 1);
+            assert_eq!(manifest.num_paths(), 1);
+            assert_eq!(manifest.conflicts().len(), 1);
+            manifest
+        };
+
+        let manifest = insert_both(ConflictPolicy::LastWins, &v1_like, &v3_like);
+        assert_eq!(manifest.get(&key).unwrap().path(), &v3_like);
+        let conflict = &manifest.conflicts()[0];
+        assert_eq!(conflict.key, key);
+        assert_eq!(conflict.kept.path(), &v3_like);
+        assert_eq!(conflict.discarded.path(), &v1_like);
+        assert!(conflict
+            .to_string()
+            .ends_with(&format!("Keeping '{v3_like}'.")));
+
+        let manifest = insert_both(ConflictPolicy::FirstWins, &v1_like, &v3_like);
+        assert_eq!(manifest.get(&key).unwrap().path(), &v1_like);
+        assert_eq!(manifest.conflicts()[0].discarded.path(), &v3_like);
+
+        // Prefer the "V1-like" folders, whichever order the locations are inserted in:
+        let prefer_v1 = ConflictPolicy::PreferPathParts(vec![String::from("pgrb2a")]);
+        for (first, second, expected_byte_offset) in
+            [(&v1_like, &v3_like, 0), (&v3_like, &v1_like, 200)]
+        {
+            let manifest = insert_both(prefer_v1.clone(), first, second);
+            let msg_loc = manifest.get(&key).unwrap();
+            assert_eq!(msg_loc.path(), &v1_like);
+            assert_eq!(msg_loc.byte_offset(), expected_byte_offset);
+        }
+    }
+
+    #[test]
+    fn test_overlapping_accumulation_windows_are_distinct_keys() {
+        let mut manifest = Manifest::new(object_store::path::Path::from("gefs"));
+        let key_0_to_6 = Key {
+            reference_datetime: ymdh_to_datetime(2017, 1, 1, 0),
+            ensemble_member: String::from("gec00"),
+            forecast_step: ForecastStep::Range {
+                start: TimeDelta::zero(),
+                end: TimeDelta::hours(6),
+            },
+            parameter: String::from("APCP"),
+            vertical_level: Level::Surface,
+            statistic: None,
+        };
+        let key_3_to_6 = KeyBuilder::from(key_0_to_6.clone())
+            .set_forecast_step(ForecastStep::Range {
+                start: TimeDelta::hours(3),
+                end: TimeDelta::hours(6),
+            })
+            .build()
+            .unwrap();
+        assert_ne!(key_0_to_6, key_3_to_6);
+        let path = object_store::path::Path::from("gefs.20170101/00/gec00.t00z.pgrb2af006");
+        assert!(manifest.insert(key_0_to_6, &path, 0, 100));
+        assert!(manifest.insert(key_3_to_6, &path, 100, 100));
+        assert_eq!(manifest.len(), 2);
+        assert_eq!(manifest.coord_labels().forecast_step().len(), 2);
+    }
+
+    #[test]
+    fn test_iter() {
+        let mut manifest = Manifest::new(object_store::path::Path::from("gefs"));
+        assert_eq!(manifest.iter().count(), 0);
+        let key = Key {
+            reference_datetime: ymdh_to_datetime(2017, 1, 1, 0),
+            ensemble_member: String::from("gec00"),
+            forecast_step: ForecastStep::Instant(TimeDelta::zero()),
+            parameter: String::from("HGT"),
+            vertical_level: Level::Isobaric(1_000),
+            statistic: None,
+        };
+        let path = object_store::path::Path::from("gefs.20170101/00/gec00.t00z.pgrb2aanl");
+        manifest.insert(key.clone(), &path, 0, 50487);
+        manifest.insert(
+            KeyBuilder::from(key.clone())
+                .set_parameter("TMP")
+                .build()
+                .unwrap(),
+            &path,
+            50487,
+            20166,
+        );
+
+        let mut items: Vec<_> = manifest
+            .iter()
+            .map(|(key, msg_loc)| (key.parameter.as_str(), msg_loc.byte_range()))
+            .collect();
+        items.sort_by_key(|(parameter, _)| *parameter);
+        assert_eq!(items, [("HGT", 0..50487), ("TMP", 50487..70653)]);
+        assert_eq!(manifest.len(), items.len());
+        assert!(manifest.iter().all(|(_, msg_loc)| msg_loc.path() == &path));
+    }
+
+    #[test]
+    fn test_valid_times() {
+        let mut manifest = Manifest::new(object_store::path::Path::from("gefs"));
+        let mut builder = KeyBuilder::new();
+        builder
+            .set_ensemble_member("gec00")
+            .set_parameter("TMP")
+            .set_vertical_level(Level::Isobaric(1_000));
+        // Two overlapping forecasts: 00Z (steps 0, 6, and 12 hours) and 06Z (steps 0 and 6 hours),
+        // plus a 0-6 hour accumulation from 06Z, which is valid at the end of its window.
+        let steps = [
+            (0, ForecastStep::Instant(TimeDelta::hours(0))),
+            (0, ForecastStep::Instant(TimeDelta::hours(6))),
+            (0, ForecastStep::Instant(TimeDelta::hours(12))),
+            (6, ForecastStep::Instant(TimeDelta::hours(0))),
+            (6, ForecastStep::Instant(TimeDelta::hours(6))),
+            (
+                6,
+                ForecastStep::Range {
+                    start: TimeDelta::zero(),
+                    end: TimeDelta::hours(6),
+                },
+            ),
+        ];
+        for (i, (hour, step)) in steps.into_iter().enumerate() {
+            builder
+                .set_reference_datetime(ymdh_to_datetime(2017, 1, 1, hour))
+                .set_forecast_step(step);
+            let path = object_store::path::Path::from(format!("gefs.20170101/{hour:02}/{i}"));
+            manifest.insert(builder.build().unwrap(), &path, 0, 100);
+        }
+
+        assert_eq!(
+            manifest.valid_times(),
+            [0, 6, 12].map(|hour| ymdh_to_datetime(2017, 1, 1, hour))
+        );
+
+        let mut at_12z: Vec<_> = manifest
+            .iter_valid_time(ymdh_to_datetime(2017, 1, 1, 12))
+            .map(|(key, _)| (key.reference_datetime.hour(), *key.forecast_step()))
+            .collect();
+        at_12z.sort();
+        assert_eq!(
+            at_12z,
+            [
+                (0, ForecastStep::Instant(TimeDelta::hours(12))),
+                (
+                    6,
+                    ForecastStep::Range {
+                        start: TimeDelta::zero(),
+                        end: TimeDelta::hours(6)
+                    }
+                ),
+                (6, ForecastStep::Instant(TimeDelta::hours(6))),
+            ]
+        );
+        assert_eq!(
+            manifest
+                .iter_valid_time(ymdh_to_datetime(2017, 1, 1, 0))
+                .count(),
+            1
+        );
+        assert_eq!(
+            manifest
+                .iter_valid_time(ymdh_to_datetime(2017, 1, 1, 18))
+                .count(),
+            0
+        );
+    }
+
+    #[test]
+    fn test_retain() {
+        let mut manifest = Manifest::new(object_store::path::Path::from("gefs"));
+        let mut builder = KeyBuilder::new();
+        builder
+            .set_reference_datetime(ymdh_to_datetime(2017, 1, 1, 0))
+            .set_ensemble_member("gec00")
+            .set_forecast_step(ForecastStep::Instant(TimeDelta::zero()))
+            .set_vertical_level(Level::Isobaric(1_000));
+        let a_set = object_store::path::Path::from("gefs.20170101/00/gec00.t00z.pgrb2aanl");
+        let b_set = object_store::path::Path::from("gefs.20170101/00/gec00.t00z.pgrb2banl");
+        for (path, parameter, byte_offset) in [
+            (&a_set, "HGT", 0),
+            (&a_set, "TMP", 100),
+            (&b_set, "UGRD", 0),
+        ] {
+            let key = builder.set_parameter(parameter).build().unwrap();
+            manifest.insert(key, path, byte_offset, 100);
+        }
+        assert_eq!((manifest.len(), manifest.num_paths()), (3, 2));
+
+        manifest.retain(|key| key.parameter() == "HGT");
+        assert_eq!((manifest.len(), manifest.num_paths()), (1, 1));
+        let (key, msg_loc) = manifest.iter().next().unwrap();
+        assert_eq!(key.parameter(), "HGT");
+        assert_eq!(msg_loc.path(), &a_set);
+        assert_eq!(**manifest.coord_labels().parameter(), ["HGT"]);
+        assert_eq!(manifest.total_bytes(), 100);
+
+        manifest.retain(|_| false);
+        assert!(manifest.is_empty());
+        assert_eq!(manifest.num_paths(), 0);
+    }
+
+    #[test]
+    fn test_split_by_reference_datetime() {
+        let mut manifest = Manifest::new(object_store::path::Path::from("gefs"));
+        let mut builder = KeyBuilder::new();
+        builder
+            .set_ensemble_member("gec00")
+            .set_forecast_step(ForecastStep::Instant(TimeDelta::zero()))
+            .set_vertical_level(Level::Isobaric(1_000));
+        for hour in [0, 6] {
+            let path = object_store::path::Path::from(format!(
+                "gefs.20170101/{hour:02}/gec00.t{hour:02}z.pgrb2aanl"
+            ));
+            builder.set_reference_datetime(ymdh_to_datetime(2017, 1, 1, hour));
+            for (i, parameter) in ["HGT", "TMP"].into_iter().enumerate() {
+                let key = builder.set_parameter(parameter).build().unwrap();
+                manifest.insert(key, &path, i as u64 * 100, 100);
+            }
+        }
+
+        let shards = manifest.split_by_reference_datetime();
+        assert_eq!(
+            shards.keys().copied().collect::<Vec<_>>(),
+            [
+                ymdh_to_datetime(2017, 1, 1, 0),
+                ymdh_to_datetime(2017, 1, 1, 6)
+            ]
+        );
+        for (reference_datetime, shard) in &shards {
+            assert_eq!(shard.base_path(), manifest.base_path());
+            assert_eq!(shard.len(), 2);
+            assert_eq!(shard.num_paths(), 1);
+            assert_eq!(
+                **shard.coord_labels().reference_datetime(),
+                [*reference_datetime]
+            );
+            assert_eq!(**shard.coord_labels().parameter(), ["HGT", "TMP"]);
+            for (key, msg_loc) in shard.iter() {
+                assert_eq!(key.reference_datetime(), reference_datetime);
+                assert_eq!(manifest.get(key), Some(msg_loc));
+                // The path is shared with the other message in the shard, and with `manifest`:
+                assert!(shard
+                    .iter()
+                    .all(|(_, other)| Arc::ptr_eq(&other.path, &msg_loc.path)));
+                assert!(Arc::ptr_eq(&manifest.get(key).unwrap().path, &msg_loc.path));
+            }
+        }
+        assert!(Manifest::new(object_store::path::Path::default())
+            .split_by_reference_datetime()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_iter_keys_and_missing_keys() {
+        let coord_labels = CoordLabels::new(
+            vec![
+                ymdh_to_datetime(2017, 1, 1, 0),
+                ymdh_to_datetime(2017, 1, 1, 6),
+            ]
+            .into(),
+            vec![String::from("gec00")].into(),
+            vec![ForecastStep::Instant(TimeDelta::zero())].into(),
+            vec![String::from("HGT"), String::from("TMP")].into(),
+            vec![Level::Isobaric(1_000), Level::Surface].into(),
+        );
+        let keys: Vec<_> = coord_labels.iter_keys().collect();
+        assert_eq!(keys.len(), 8);
+        assert_eq!(keys.iter().collect::<HashSet<_>>().len(), 8);
+        assert_eq!(
+            keys[..3]
+                .iter()
+                .map(|key| (key.parameter(), key.vertical_level().to_string()))
+                .collect::<Vec<_>>(),
+            [
+                ("HGT", String::from("surface")),
+                ("HGT", String::from("10 mb")),
+                ("TMP", String::from("surface")),
+            ]
+        );
+
+        // A manifest which contains every key except those for TMP at the surface:
+        let mut manifest = Manifest::new(object_store::path::Path::from("gefs"));
+        let path = object_store::path::Path::from("gefs.grib2");
+        for key in coord_labels.iter_keys() {
+            if !(key.parameter() == "TMP" && key.vertical_level() == &Level::Surface) {
+                manifest.insert(key, &path, 0, 100);
+            }
+        }
+        let missing: Vec<_> = manifest.missing_keys(&coord_labels).collect();
+        assert_eq!(
+            missing
+                .iter()
+                .map(|key| key.reference_datetime().hour())
+                .collect::<Vec<_>>(),
+            [0, 6]
+        );
+        assert!(missing.iter().all(|key| key.parameter() == "TMP"));
+        assert_eq!(manifest.missing_keys(&manifest.coord_labels()).count(), 2);
+    }
+
+    #[test]
+    fn test_validate_paths() {
+        use object_store::path::Path;
+        let mut manifest = Manifest::new(Path::from("noaa-gefs-pds"));
+        let mut builder = KeyBuilder::new();
+        builder
+            .set_reference_datetime(ymdh_to_datetime(2017, 1, 1, 0))
+            .set_ensemble_member("gec00")
+            .set_forecast_step(ForecastStep::Instant(TimeDelta::zero()))
+            .set_vertical_level(Level::Isobaric(1_000));
+        let valid = Path::from("gefs.20170101/00/gec00.t00z.pgrb2aanl");
+        manifest.insert(
+            builder.set_parameter("HGT").build().unwrap(),
+            &valid,
+            0,
+            100,
+        );
+        assert_eq!(manifest.validate_paths(), Ok(()));
+
+        let escapes = Path::from("../noaa-gefs-backup/gefs.20170101/00/gec00.t00z.pgrb2aanl");
+        let repeats_base_path = Path::from("noaa-gefs-pds/gefs.20170101/00/gec00.t00z.pgrb2aanl");
+        let url = Path::from("s3://noaa-gefs-pds/gefs.20170101/00/gec00.t00z.pgrb2aanl");
+        for (parameter, path) in [
+            ("TMP", &escapes),
+            ("RH", &repeats_base_path),
+            ("UGRD", &url),
+        ] {
+            manifest.insert(
+                builder.set_parameter(parameter).build().unwrap(),
+                path,
+                0,
+                100,
+            );
+        }
+        let mut expected = vec![escapes, repeats_base_path, url];
+        expected.sort();
+        assert_eq!(manifest.validate_paths(), Err(expected));
+
+        // Dropping the invalid paths makes the manifest valid again:
+        manifest.retain(|key| key.parameter() == "HGT");
+        assert_eq!(manifest.validate_paths(), Ok(()));
+    }
+
+    #[test]
+    fn test_resolve_path() {
+        use object_store::path::{Path, PathPart};
+        let base_path = Path::from_iter(["noaa gefs", "GEFSv12"]);
+        let mut manifest = Manifest::new(base_path.clone());
+        let key = KeyBuilder::new()
+            .set_reference_datetime(ymdh_to_datetime(2000, 1, 1, 0))
+            .set_ensemble_member("c00")
+            .set_forecast_step(ForecastStep::Instant(TimeDelta::hours(3)))
+            .set_parameter("TMP")
+            .set_vertical_level(Level::HeightAboveGround(2))
+            .build()
+            .unwrap();
+        let path = Path::from_iter(["Days:1-10", "tmp 2m%.grib2"]);
+        manifest.insert(key.clone(), &path, 0, 100);
+        let resolved = manifest.resolve_path(manifest.get(&key).unwrap());
+        assert_eq!(
+            resolved.parts().collect::<Vec<_>>(),
+            [
+                PathPart::from("noaa gefs"),
+                PathPart::from("GEFSv12"),
+                PathPart::from("Days:1-10"),
+                PathPart::from("tmp 2m%.grib2"),
+            ]
+        );
+        assert_eq!(
+            resolved.as_ref(),
+            "noaa gefs/GEFSv12/Days:1-10/tmp 2m%25.grib2"
+        );
+        // Joining the strings would encode the percent-encoded parts again:
+        assert_ne!(Path::from(format!("{base_path}/{path}")), resolved);
+    }
+
+    #[test]
+    fn test_key_builder_requires_all_fields() {
+        let mut builder = KeyBuilder::new();
+        builder
+            .set_reference_datetime(ymdh_to_datetime(2017, 1, 1, 0))
+            .set_ensemble_member("gec00")
+            .set_forecast_step(ForecastStep::Instant(TimeDelta::zero()))
+            .set_parameter("HGT");
+        assert!(builder.build().is_err());
+        builder.set_vertical_level(Level::Surface);
+        assert!(builder.build().is_ok());
+    }
+
+    #[test]
+    fn test_key_builder_matches_struct_literal() -> anyhow::Result<()> {
+        let built = KeyBuilder::new()
+            .set_reference_datetime(ymdh_to_datetime(2017, 1, 1, 0))
+            .set_ensemble_member("gec00")
+            .set_forecast_step(ForecastStep::Instant(TimeDelta::zero()))
+            .set_parameter("HGT")
+            .set_vertical_level(Level::Isobaric(1_000))
+            .build()?;
+        let literal = Key {
+            reference_datetime: ymdh_to_datetime(2017, 1, 1, 0),
+            ensemble_member: String::from("gec00"),
+            forecast_step: ForecastStep::Instant(TimeDelta::zero()),
+            parameter: String::from("HGT"),
+            vertical_level: Level::Isobaric(1_000),
+            statistic: None,
+        };
+        assert_eq!(built, literal);
+        assert_eq!(KeyBuilder::from(literal.clone()).build()?, literal);
+        Ok(())
+    }
+
+    #[test]
+    fn test_total_bytes_and_bytes_for_selection() {
+        let mut manifest = Manifest::new(object_store::path::Path::from("gefs"));
+        assert_eq!(manifest.total_bytes(), 0);
+        let key = KeyBuilder::new()
+            .set_reference_datetime(ymdh_to_datetime(2017, 1, 1, 0))
+            .set_ensemble_member("gec00")
+            .set_forecast_step(ForecastStep::Instant(TimeDelta::zero()))
+            .set_parameter("HGT")
+            .set_vertical_level(Level::Isobaric(1_000))
+            .build()
+            .unwrap();
+        let path = object_store::path::Path::from("gefs.20170101/00/gec00.t00z.pgrb2aanl");
+        manifest.insert(key.clone(), &path, 0, 100);
+        manifest.insert(
+            KeyBuilder::from(key.clone())
+                .set_parameter("TMP")
+                .build()
+                .unwrap(),
+            &path,
+            100,
+            20,
+        );
+        manifest.insert(
+            KeyBuilder::from(key)
+                .set_reference_datetime(ymdh_to_datetime(2017, 1, 1, 6))
+                .set_parameter("TMP")
+                .build()
+                .unwrap(),
+            &object_store::path::Path::from("gefs.20170101/06/gec00.t06z.pgrb2aanl"),
+            100,
+            30,
+        );
+        assert_eq!(manifest.total_bytes(), 150);
+        assert_eq!(
+            manifest.bytes_for_selection(|key| key.parameter() == "TMP"),
+            50
+        );
+        assert_eq!(
+            manifest.bytes_for_selection(|key| key.parameter() == "TMP"
+                && *key.reference_datetime() < ymdh_to_datetime(2017, 1, 1, 6)),
+            20
+        );
+        assert_eq!(
+            manifest.bytes_for_selection(|key| key.parameter() == "UGRD"),
+            0
+        );
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[tokio::test]
+    async fn test_percentiles_are_distinct_keys() -> anyhow::Result<()> {
+        use crate::datasets::gefs::Gefs;
+
+        // The same parameter, level, and step, but different percentiles:
+        const IDX_TEXT: &str = "\
+1:0:d=2017010100:TMP:2 m above ground:6 hour fcst:10% level
+2:1000:d=2017010100:TMP:2 m above ground:6 hour fcst:90% level
+3:2000:d=2017010100:TMP:2 m above ground:6 hour fcst:ENS=low-res ctl
+";
+        let idx_files = futures_util::stream::iter([(
+            object_store::path::Path::from("gefs.20170101/00/gec00.t00z.pgrb2af006.idx"),
+            Bytes::from_static(IDX_TEXT.as_bytes()),
+        )]);
+        let manifest = Manifest::from_idx_stream::<Gefs>(
+            object_store::path::Path::from("noaa-gefs-pds"),
+            idx_files,
+            |_| Some(3000),
+        )
+        .await?;
+        assert_eq!(manifest.len(), 3);
+        assert!(manifest.conflicts().is_empty());
+        let coord_labels = manifest.coord_labels();
+        assert_eq!(
+            **coord_labels.statistic(),
+            [
+                None,
+                Some(Statistic::Percentile(10)),
+                Some(Statistic::Percentile(90))
+            ]
+        );
+        assert_eq!(coord_labels.iter_keys().count(), 3);
+        assert!(manifest.describe().contains("- statistic: 3 labels."));
+
+        let mut builder = KeyBuilder::new();
+        builder
+            .set_reference_datetime(ymdh_to_datetime(2017, 1, 1, 0))
+            .set_ensemble_member("gec00")
+            .set_forecast_step(ForecastStep::Instant(TimeDelta::hours(6)))
+            .set_parameter("TMP")
+            .set_vertical_level(Level::HeightAboveGround(2));
+        let p10 = builder
+            .set_statistic(Some(Statistic::Percentile(10)))
+            .build()?;
+        let p90 = builder
+            .set_statistic(Some(Statistic::Percentile(90)))
+            .build()?;
+        assert_ne!(p10, p90);
+        assert_eq!(manifest.get(&p10).unwrap().byte_offset(), 0);
+        assert_eq!(manifest.get(&p90).unwrap().byte_offset(), 1000);
+        let deterministic = builder.set_statistic(None).build()?;
+        assert_eq!(manifest.get(&deterministic).unwrap().byte_offset(), 2000);
+        Ok(())
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[tokio::test]
+    async fn test_from_idx_stream() -> anyhow::Result<()> {
+        use crate::datasets::gefs::Gefs;
+
+        const IDX_TEXT: &str = "\
+1:0:d=2017010100:HGT:10 mb:anl:ENS=low-res ctl
+2:50487:d=2017010100:TMP:10 mb:anl:ENS=low-res ctl
+3:70653:d=2017010100:RH:10 mb:anl:ENS=low-res ctl
+";
+        let idx_paths = [
+            "gefs.20170101/00/gec00.t00z.pgrb2aanl.idx",
+            "gefs.20170101/00/gep01.t00z.pgrb2aanl.idx",
+            "gefs.20170101/00/gep02.t00z.pgrb2aanl.idx",
+        ];
+        let idx_files = futures_util::stream::iter(idx_paths.map(|idx_path| {
+            (
+                object_store::path::Path::from(idx_path),
+                Bytes::from_static(IDX_TEXT.as_bytes()),
+            )
+        }));
+        // Only the size of the control member's GRIB file is known:
+        let control = object_store::path::Path::from("gefs.20170101/00/gec00.t00z.pgrb2aanl");
+        let manifest = Manifest::from_idx_stream::<Gefs>(
+            object_store::path::Path::from("noaa-gefs-pds"),
+            idx_files,
+            |grib_path| (grib_path == &control).then_some(80_000),
+        )
+        .await?;
+
+        // 3 messages from the control member, and 2 messages from each perturbed member:
+        assert_eq!(manifest.len(), 7);
+        assert_eq!(manifest.num_paths(), 3);
+        assert_eq!(
+            **manifest.coord_labels().ensemble_member(),
+            ["gec00", "gep01", "gep02"]
+        );
+        let rh_key = KeyBuilder::new()
+            .set_reference_datetime(ymdh_to_datetime(2017, 1, 1, 0))
+            .set_ensemble_member("gec00")
+            .set_forecast_step(ForecastStep::Instant(TimeDelta::zero()))
+            .set_parameter("RH")
+            .set_vertical_level(Level::Isobaric(1_000))
+            .build()?;
+        let msg_loc = manifest.get(&rh_key).unwrap();
+        assert_eq!(msg_loc.path(), &control);
+        assert_eq!(msg_loc.byte_range(), 70653..80_000);
+
+        // Paths which don't identify the ensemble member are an error:
+        let idx_files = futures_util::stream::iter([(
+            object_store::path::Path::from("foo.idx"),
+            Bytes::from_static(IDX_TEXT.as_bytes()),
+        )]);
+        assert!(Manifest::from_idx_stream::<Gefs>(
+            object_store::path::Path::default(),
+            idx_files,
+            |_| None
+        )
+        .await
+        .is_err());
+        Ok(())
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[tokio::test]
+    async fn test_from_idx_stream_and_store() -> anyhow::Result<()> {
+        use object_store::{memory::InMemory, PutPayload};
+
+        use crate::datasets::gefs::Gefs;
+
+        const IDX_TEXT: &str = "\
+1:0:d=2017010100:HGT:10 mb:anl:ENS=low-res ctl
+2:50487:d=2017010100:TMP:10 mb:anl:ENS=low-res ctl
+3:70653:d=2017010100:RH:10 mb:anl:ENS=low-res ctl
+";
+        let store = InMemory::new();
+        let base_path = object_store::path::Path::from("noaa-gefs-pds");
+        let grib_paths = [
+            ("gefs.20170101/00/gec00.t00z.pgrb2aanl", 80_000),
+            ("gefs.20170101/00/gep01.t00z.pgrb2aanl", 90_000),
+        ];
+        for (grib_path, file_size) in grib_paths {
+            store
+                .put(
+                    &object_store::path::Path::from(format!("noaa-gefs-pds/{grib_path}")),
+                    PutPayload::from(vec![0; file_size]),
+                )
+                .await?;
+        }
+        let idx_files = futures_util::stream::iter(grib_paths.map(|(grib_path, _)| {
+            (
+                object_store::path::Path::from(format!("{grib_path}.idx")),
+                Bytes::from_static(IDX_TEXT.as_bytes()),
+            )
+        }));
+        let manifest =
+            Manifest::from_idx_stream_and_store::<Gefs>(&store, base_path.clone(), idx_files)
+                .await?;
+        assert_eq!(manifest.len(), 6);
+
+        let mut builder = KeyBuilder::new();
+        builder
+            .set_reference_datetime(ymdh_to_datetime(2017, 1, 1, 0))
+            .set_forecast_step(ForecastStep::Instant(TimeDelta::zero()))
+            .set_parameter("RH")
+            .set_vertical_level(Level::Isobaric(1_000));
+        for (ensemble_member, file_size) in [("gec00", 80_000), ("gep01", 90_000)] {
+            let key = builder.set_ensemble_member(ensemble_member).build()?;
+            let msg_loc = manifest.get(&key).unwrap();
+            assert_eq!(msg_loc.msg_length(), file_size - 70653);
+        }
+        // Every message records the ETag of its GRIB file:
+        for (_key, msg_loc) in manifest.iter() {
+            let meta = store.head(&manifest.resolve_path(msg_loc)).await?;
+            assert!(msg_loc.etag().is_some());
+            assert_eq!(msg_loc.etag(), meta.e_tag.as_deref());
+        }
+
+        // A GRIB file which doesn't exist is an error:
+        let idx_files = futures_util::stream::iter([(
+            object_store::path::Path::from("gefs.20170101/00/gep02.t00z.pgrb2aanl.idx"),
+            Bytes::from_static(IDX_TEXT.as_bytes()),
+        )]);
+        assert!(
+            Manifest::from_idx_stream_and_store::<Gefs>(&store, base_path, idx_files)
+                .await
+                .is_err()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_message_location_serde_round_trip() -> anyhow::Result<()> {
+        let msg_loc = MessageLocation {
+            path: Arc::new(object_store::path::Path::from(
+                "gefs.20170101/00/gec00.t00z.pgrb2aanl",
+            )),
+            byte_offset: 50487,
+            msg_length: 20166,
+            etag: None,
+        };
+        let json = serde_json::to_string(&msg_loc)?;
+        assert_eq!(
+            json,
+            r#"{"path":"gefs.20170101/00/gec00.t00z.pgrb2aanl","byte_offset":50487,"msg_length":20166}"#
+        );
+        assert_eq!(serde_json::from_str::<MessageLocation>(&json)?, msg_loc);
+
+        let msg_loc = MessageLocation {
+            etag: Some(String::from("6f5902ac237024bdd0c176cb93063dc4")),
+            ..msg_loc
+        };
+        let json = serde_json::to_string(&msg_loc)?;
+        assert!(json.ends_with(r#","etag":"6f5902ac237024bdd0c176cb93063dc4"}"#));
+        let round_tripped: MessageLocation = serde_json::from_str(&json)?;
+        assert_eq!(round_tripped, msg_loc);
+        assert_eq!(
+            round_tripped.etag(),
+            Some("6f5902ac237024bdd0c176cb93063dc4")
+        );
+        Ok(())
+    }
+
+    /// Also runs on `wasm32-unknown-unknown` (with `--no-default-features`), to check that
+    /// constructing and querying a manifest doesn't need any IO.
+    #[cfg_attr(not(target_arch = "wasm32"), test)]
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+    fn test_get() {
+        let mut manifest = Manifest::new(object_store::path::Path::from("gefs"));
+        let key = KeyBuilder::new()
+            .set_reference_datetime(ymdh_to_datetime(2017, 1, 1, 0))
+            .set_ensemble_member("gec00")
+            .set_forecast_step(ForecastStep::Instant(TimeDelta::zero()))
+            .set_parameter("HGT")
+            .set_vertical_level(Level::Isobaric(1_000))
+            .build()
+            .unwrap();
+        let path = object_store::path::Path::from("gefs.20170101/00/gec00.t00z.pgrb2aanl");
+        manifest.insert(key.clone(), &path, 50487, 20166);
+        let msg_loc = manifest.get(&key).unwrap();
+        assert_eq!(msg_loc.path(), &path);
+        assert_eq!(msg_loc.byte_range(), 50487..70653);
+        let tmp_key = KeyBuilder::from(key).set_parameter("TMP").build().unwrap();
+        assert!(manifest.get(&tmp_key).is_none());
+    }
+}
@@ -0,0 +1,260 @@
+//! Export a [`Manifest`] as an Arrow `RecordBatch` (or a Parquet file), so that the manifest can
+//! be queried with tools like DuckDB and Polars. For example, "which messages are larger than
+//! 10 MB?" becomes `SELECT * FROM 'manifest.parquet' WHERE msg_length > 10_000_000`.
+//!
+//! Each row is one GRIB message. The columns are `MANIFEST_COLUMNS`:
+//!
+//! - `reference_datetime`: A UTC timestamp (in microseconds).
+//! - `ensemble_member`, `parameter`, and `vertical_level`: Strings. `vertical_level` is formatted
+//!   in the same way as `.idx` files (e.g. `10 mb`).
+//! - `statistic`: The percentile or probability of a probabilistic product, formatted in the same
+//!   way as `.idx` files (e.g. `10% level`), or null for deterministic fields.
+//! - `forecast_step_start` and `forecast_step_end`: Durations (in seconds) since the reference
+//!   datetime. `forecast_step_end` is the time at which the field is valid. `forecast_step_start`
+//!   is null for a `ForecastStep::Instant`, and is the start of the window for a
+//!   `ForecastStep::Range`.
+//! - `path`: The path of the GRIB file, relative to `Manifest::base_path`. The base path is
+//!   stored in the schema's metadata, under the key `base_path`.
+//! - `byte_offset` and `msg_length`: Unsigned 64-bit integers.
+//! - `etag`: The ETag of the GRIB file (see `MessageLocation::etag`), or null.
+//!
+//! Rows are sorted by `path` and then by `byte_offset`.
+
+use std::{collections::HashMap, sync::Arc};
+
+use arrow_array::{
+    ArrayRef, DurationSecondArray, RecordBatch, StringArray, TimestampMicrosecondArray, UInt64Array,
+};
+use arrow_schema::{DataType, Field, Schema, TimeUnit};
+
+use crate::{manifest::Manifest, ForecastStep};
+
+/// The names of the columns of `Manifest::to_arrow`, in order.
+pub const MANIFEST_COLUMNS: [&str; 11] = [
+    "reference_datetime",
+    "ensemble_member",
+    "forecast_step_start",
+    "forecast_step_end",
+    "parameter",
+    "vertical_level",
+    "statistic",
+    "path",
+    "byte_offset",
+    "msg_length",
+    "etag",
+];
+
+impl Manifest {
+    /// The Arrow schema of `Manifest::to_arrow`. See the module-level docs for the columns.
+    pub fn arrow_schema(&self) -> Schema {
+        let fields = vec![
+            Field::new(
+                "reference_datetime",
+                DataType::Timestamp(TimeUnit::Microsecond, Some("UTC".into())),
+                false,
+            ),
+            Field::new("ensemble_member", DataType::Utf8, false),
+            Field::new(
+                "forecast_step_start",
+                DataType::Duration(TimeUnit::Second),
+                true,
+            ),
+            Field::new(
+                "forecast_step_end",
+                DataType::Duration(TimeUnit::Second),
+                false,
+            ),
+            Field::new("parameter", DataType::Utf8, false),
+            Field::new("vertical_level", DataType::Utf8, false),
+            Field::new("statistic", DataType::Utf8, true),
+            Field::new("path", DataType::Utf8, false),
+            Field::new("byte_offset", DataType::UInt64, false),
+            Field::new("msg_length", DataType::UInt64, false),
+            Field::new("etag", DataType::Utf8, true),
+        ];
+        let metadata = HashMap::from([(String::from("base_path"), self.base_path().to_string())]);
+        Schema::new_with_metadata(fields, metadata)
+    }
+
+    /// Export the manifest as a single Arrow `RecordBatch`, with one row per GRIB message. See the
+    /// module-level docs for the columns.
+    pub fn to_arrow(&self) -> anyhow::Result<RecordBatch> {
+        let mut messages: Vec<_> = self.iter().collect();
+        messages.sort_by(|(_, a), (_, b)| {
+            (a.path(), a.byte_offset()).cmp(&(b.path(), b.byte_offset()))
+        });
+
+        let columns: Vec<ArrayRef> = vec![
+            Arc::new(
+                TimestampMicrosecondArray::from_iter_values(
+                    messages
+                        .iter()
+                        .map(|(key, _)| key.reference_datetime().timestamp_micros()),
+                )
+                .with_timezone("UTC"),
+            ),
+            Arc::new(StringArray::from_iter_values(
+                messages.iter().map(|(key, _)| key.ensemble_member()),
+            )),
+            Arc::new(DurationSecondArray::from_iter(messages.iter().map(
+                |(key, _)| match key.forecast_step() {
+                    ForecastStep::Instant(_) => None,
+                    ForecastStep::Range { start, .. } => Some(start.num_seconds()),
+                },
+            ))),
+            Arc::new(DurationSecondArray::from_iter_values(
+                messages
+                    .iter()
+                    .map(|(key, _)| key.forecast_step().end().num_seconds()),
+            )),
+            Arc::new(StringArray::from_iter_values(
+                messages.iter().map(|(key, _)| key.parameter()),
+            )),
+            Arc::new(StringArray::from_iter_values(
+                messages
+                    .iter()
+                    .map(|(key, _)| key.vertical_level().to_string()),
+            )),
+            Arc::new(StringArray::from_iter(messages.iter().map(|(key, _)| {
+                key.statistic().map(|statistic| statistic.to_string())
+            }))),
+            Arc::new(StringArray::from_iter_values(
+                messages.iter().map(|(_, msg_loc)| msg_loc.path().as_ref()),
+            )),
+            Arc::new(UInt64Array::from_iter_values(
+                messages.iter().map(|(_, msg_loc)| msg_loc.byte_offset()),
+            )),
+            Arc::new(UInt64Array::from_iter_values(
+                messages.iter().map(|(_, msg_loc)| msg_loc.msg_length()),
+            )),
+            Arc::new(StringArray::from_iter(
+                messages.iter().map(|(_, msg_loc)| msg_loc.etag()),
+            )),
+        ];
+        Ok(RecordBatch::try_new(
+            Arc::new(self.arrow_schema()),
+            columns,
+        )?)
+    }
+
+    /// Write the manifest to `writer` as a Parquet file. See `Manifest::to_arrow`.
+    pub fn to_parquet<W: std::io::Write + Send>(&self, writer: W) -> anyhow::Result<()> {
+        let batch = self.to_arrow()?;
+        let mut writer = parquet::arrow::ArrowWriter::try_new(writer, batch.schema(), None)?;
+        writer.write(&batch)?;
+        writer.close()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow_array::Array;
+    use chrono::TimeDelta;
+
+    use crate::{manifest::KeyBuilder, ymdh_to_datetime, Level};
+
+    use super::*;
+
+    fn create_manifest() -> Manifest {
+        let mut manifest = Manifest::new(object_store::path::Path::from("noaa-gefs-pds"));
+        let path = object_store::path::Path::from("gefs.20170101/00/gec00.t00z.pgrb2af006");
+        let mut builder = KeyBuilder::new();
+        builder
+            .set_reference_datetime(ymdh_to_datetime(2017, 1, 1, 0))
+            .set_ensemble_member("gec00")
+            .set_vertical_level(Level::Isobaric(1_000));
+        let steps = [
+            ("HGT", ForecastStep::Instant(TimeDelta::hours(6))),
+            (
+                "APCP",
+                ForecastStep::Range {
+                    start: TimeDelta::zero(),
+                    end: TimeDelta::hours(6),
+                },
+            ),
+            ("TMP", ForecastStep::Instant(TimeDelta::hours(6))),
+        ];
+        for (i, (parameter, forecast_step)) in steps.into_iter().enumerate() {
+            let key = builder
+                .set_parameter(parameter)
+                .set_forecast_step(forecast_step)
+                .build()
+                .unwrap();
+            manifest.insert(key, &path, i as u64 * 100, 100);
+        }
+        manifest
+    }
+
+    #[test]
+    fn test_to_arrow() -> anyhow::Result<()> {
+        let manifest = create_manifest();
+        let batch = manifest.to_arrow()?;
+        assert_eq!(batch.num_rows(), 3);
+        let schema = batch.schema();
+        let column_names: Vec<_> = schema.fields().iter().map(|f| f.name().as_str()).collect();
+        assert_eq!(column_names, MANIFEST_COLUMNS);
+        assert_eq!(
+            schema.field(0).data_type(),
+            &DataType::Timestamp(TimeUnit::Microsecond, Some("UTC".into()))
+        );
+        assert_eq!(
+            schema.metadata().get("base_path").map(String::as_str),
+            Some("noaa-gefs-pds")
+        );
+
+        let column = |name| batch.column_by_name(name).unwrap();
+        let reference_datetime = column("reference_datetime")
+            .as_any()
+            .downcast_ref::<TimestampMicrosecondArray>()
+            .unwrap();
+        assert_eq!(
+            reference_datetime.value_as_datetime(0),
+            Some(ymdh_to_datetime(2017, 1, 1, 0).naive_utc())
+        );
+        let parameter = column("parameter")
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .unwrap();
+        // Sorted by byte offset:
+        assert_eq!(
+            parameter.iter().collect::<Vec<_>>(),
+            [Some("HGT"), Some("APCP"), Some("TMP")]
+        );
+        let forecast_step_start = column("forecast_step_start")
+            .as_any()
+            .downcast_ref::<DurationSecondArray>()
+            .unwrap();
+        assert_eq!(
+            forecast_step_start.iter().collect::<Vec<_>>(),
+            [None, Some(0), None]
+        );
+        assert_eq!(
+            column("vertical_level")
+                .as_any()
+                .downcast_ref::<StringArray>()
+                .unwrap()
+                .value(0),
+            "10 mb"
+        );
+        assert_eq!(column("statistic").null_count(), 3);
+        assert_eq!(column("etag").null_count(), 3);
+        Ok(())
+    }
+
+    #[test]
+    fn test_to_parquet() -> anyhow::Result<()> {
+        let manifest = create_manifest();
+        let mut buffer = Vec::new();
+        manifest.to_parquet(&mut buffer)?;
+        assert!(buffer.starts_with(b"PAR1"));
+        let builder = parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder::try_new(
+            bytes::Bytes::from(buffer),
+        )?;
+        assert_eq!(builder.schema().as_ref(), &manifest.arrow_schema());
+        let batches = builder.build()?.collect::<Result<Vec<_>, _>>()?;
+        assert_eq!(batches.len(), 1);
+        assert_eq!(batches[0].columns(), manifest.to_arrow()?.columns());
+        Ok(())
+    }
+}
@@ -0,0 +1,198 @@
+//! Export a [`Manifest`] as JSON lines (one JSON object per GRIB message), and read it back. JSON
+//! lines can be streamed, grepped, and queried with `jq`. For example:
+//!
+//! ```json
+//! {"reference_datetime":"2017-01-01T00:00:00Z","ensemble_member":"gec00","forecast_step":21600,"parameter":"HGT","vertical_level":"10 mb","path":"gefs.20170101/00/gec00.t00z.pgrb2af006","byte_offset":0,"msg_length":100}
+//! ```
+//!
+//! `reference_datetime` is in RFC 3339 format, and `forecast_step` is in seconds (in the same
+//! format as the coord labels JSON). `vertical_level` and `statistic` are formatted in the same
+//! way as `.idx` files. `statistic` is omitted for deterministic fields, and `etag` is omitted if
+//! it's unknown. `path` is relative to `Manifest::base_path`, which isn't written.
+//!
+//! Lines are sorted by `path` and then by `byte_offset`.
+
+use std::io::{BufRead, Write};
+
+use anyhow::Context;
+use chrono::{DateTime, SecondsFormat, Utc};
+
+use crate::{
+    coord_labels_json::ForecastStepJson,
+    manifest::{Key, KeyBuilder, Manifest, MessageLocation},
+    Level, Statistic,
+};
+
+/// One line of JSON.
+#[derive(serde::Serialize, serde::Deserialize)]
+struct MessageJson {
+    reference_datetime: String,
+    ensemble_member: String,
+    forecast_step: ForecastStepJson,
+    parameter: String,
+    vertical_level: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    statistic: Option<String>,
+    #[serde(flatten)]
+    msg_loc: MessageLocation,
+}
+
+impl MessageJson {
+    fn new(key: &Key, msg_loc: &MessageLocation) -> Self {
+        Self {
+            reference_datetime: key
+                .reference_datetime()
+                .to_rfc3339_opts(SecondsFormat::Secs, true),
+            ensemble_member: key.ensemble_member().to_string(),
+            forecast_step: key.forecast_step().into(),
+            parameter: key.parameter().to_string(),
+            vertical_level: key.vertical_level().to_string(),
+            statistic: key.statistic().map(Statistic::to_string),
+            msg_loc: msg_loc.clone(),
+        }
+    }
+
+    fn into_key_and_msg_loc(self) -> anyhow::Result<(Key, MessageLocation)> {
+        let reference_datetime = DateTime::parse_from_rfc3339(&self.reference_datetime)
+            .with_context(|| {
+                format!(
+                    "Failed to parse reference_datetime '{}'",
+                    self.reference_datetime
+                )
+            })?
+            .with_timezone(&Utc);
+        let statistic = match &self.statistic {
+            Some(s) => Some(
+                Statistic::from_idx_str(s)
+                    .with_context(|| format!("Failed to parse statistic '{s}'"))?,
+            ),
+            None => None,
+        };
+        let key = KeyBuilder::new()
+            .set_reference_datetime(reference_datetime)
+            .set_ensemble_member(self.ensemble_member)
+            .set_forecast_step(self.forecast_step.into())
+            .set_parameter(self.parameter)
+            .set_vertical_level(Level::from(self.vertical_level.as_str()))
+            .set_statistic(statistic)
+            .build()?;
+        Ok((key, self.msg_loc))
+    }
+}
+
+impl Manifest {
+    /// Write one line of JSON per GRIB message to `writer`. See the module-level docs for the
+    /// format.
+    pub fn write_jsonl<W: Write>(&self, mut writer: W) -> anyhow::Result<()> {
+        let mut messages: Vec<_> = self.iter().collect();
+        messages.sort_by(|(_, a), (_, b)| {
+            (a.path(), a.byte_offset()).cmp(&(b.path(), b.byte_offset()))
+        });
+        for (key, msg_loc) in messages {
+            serde_json::to_writer(&mut writer, &MessageJson::new(key, msg_loc))?;
+            writeln!(writer)?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Read the output of `Manifest::write_jsonl`, one line at a time. The paths are relative to
+    /// `base_path`. Empty lines are skipped. Returns an error for the first line which can't be
+    /// parsed.
+    pub fn read_jsonl<R: BufRead>(
+        base_path: object_store::path::Path,
+        reader: R,
+    ) -> anyhow::Result<Self> {
+        let mut manifest = Self::new(base_path);
+        for (i, line) in reader.lines().enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let (key, msg_loc) = serde_json::from_str::<MessageJson>(&line)
+                .map_err(anyhow::Error::from)
+                .and_then(MessageJson::into_key_and_msg_loc)
+                .with_context(|| format!("Failed to parse line {} of JSON lines", i + 1))?;
+            manifest.insert_message_location(key, msg_loc);
+        }
+        Ok(manifest)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeDelta;
+
+    use crate::{ymdh_to_datetime, ForecastStep};
+
+    use super::*;
+
+    #[test]
+    fn test_jsonl_round_trip() -> anyhow::Result<()> {
+        let mut manifest = Manifest::new(object_store::path::Path::from("noaa-gefs-pds"));
+        let path = object_store::path::Path::from("gefs.20170101/00/gec00.t00z.pgrb2af006");
+        let mut builder = KeyBuilder::new();
+        builder
+            .set_reference_datetime(ymdh_to_datetime(2017, 1, 1, 0))
+            .set_ensemble_member("gec00")
+            .set_vertical_level(Level::Isobaric(1_000));
+        let messages = [
+            ("HGT", ForecastStep::Instant(TimeDelta::hours(6)), None),
+            (
+                "APCP",
+                ForecastStep::Range {
+                    start: TimeDelta::zero(),
+                    end: TimeDelta::hours(6),
+                },
+                Some(Statistic::ProbabilityAbove(254_000)),
+            ),
+            (
+                "TMP",
+                ForecastStep::Instant(TimeDelta::hours(6)),
+                Some(Statistic::Percentile(10)),
+            ),
+        ];
+        for (i, (parameter, forecast_step, statistic)) in messages.into_iter().enumerate() {
+            let key = builder
+                .set_parameter(parameter)
+                .set_forecast_step(forecast_step)
+                .set_statistic(statistic)
+                .build()?;
+            manifest.insert(key, &path, i as u64 * 100, 100);
+        }
+
+        let mut buffer = Vec::new();
+        manifest.write_jsonl(&mut buffer)?;
+        let jsonl = String::from_utf8(buffer)?;
+        let lines: Vec<_> = jsonl.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(
+            lines[0],
+            r#"{"reference_datetime":"2017-01-01T00:00:00Z","ensemble_member":"gec00","forecast_step":21600,"parameter":"HGT","vertical_level":"10 mb","path":"gefs.20170101/00/gec00.t00z.pgrb2af006","byte_offset":0,"msg_length":100}"#
+        );
+        assert!(lines[1].contains(r#""forecast_step":{"start":0,"end":21600}"#));
+        assert!(lines[1].contains(r#""statistic":"prob >0.254""#));
+
+        let round_tripped = Manifest::read_jsonl(manifest.base_path().clone(), jsonl.as_bytes())?;
+        assert_eq!(round_tripped.len(), manifest.len());
+        assert_eq!(round_tripped.num_paths(), 1);
+        for (key, msg_loc) in manifest.iter() {
+            assert_eq!(round_tripped.get(key), Some(msg_loc));
+        }
+
+        // The ETag is kept:
+        let line = lines[0].replace(r#""msg_length":100"#, r#""msg_length":100,"etag":"abc""#);
+        let with_etag = Manifest::read_jsonl(manifest.base_path().clone(), line.as_bytes())?;
+        let (_, msg_loc) = with_etag.iter().next().unwrap();
+        assert_eq!(msg_loc.etag(), Some("abc"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_jsonl_reports_line_number() {
+        let jsonl = "\n{\"reference_datetime\":\"not a datetime\"}\n";
+        let err = Manifest::read_jsonl(object_store::path::Path::default(), jsonl.as_bytes())
+            .unwrap_err();
+        assert!(format!("{err:#}").contains("line 2"), "{err:#}");
+    }
+}
@@ -0,0 +1,476 @@
+//! Infer the template of a dataset's paths from a sample of real paths.
+//!
+//! Each dataset names its files differently, and `datasets::gefs::Version` shows how much work it
+//! is to identify the structure of the paths by hand. [`infer_path_template`] automates the first
+//! step: Given a sample of paths which all follow the same convention, it returns a template like
+//! `gefs.{year}{month}{day}/{cycle}/{ensemble_member}.t{cycle}z.pgrb2af{forecast_hour}.idx`.
+//!
+//! The paths are aligned part by part. Each part is split into runs of digits, letters, and other
+//! characters, and the runs are compared across the sample. Runs which are the same in every path
+//! are literals. Runs which vary are named by (in order of precedence):
+//!
+//! 1. An earlier token with the same value in every path (e.g. the init hour often appears in
+//!    both a directory name and the filename).
+//! 2. A [`KnownValues`] which contains the value from every path (e.g. the ensemble members).
+//! 3. Dates and times: `YYYYMMDDHH`, `YYYYMMDD`, `YYYYMM`, `YYYY`, and two-digit hours (which are
+//!    assumed to be the `cycle`, i.e. the hour of the reference datetime).
+//!
+//! Anything else becomes `{unknown}`. The sample should vary every coordinate which appears in
+//! the paths: If every sampled path has the same ensemble member then the ensemble member will be
+//! inferred to be a literal!
+//!
+//! Mixed conventions (e.g. GEFS `Version::V0` and `Version::V1` paths in the same sample) are out
+//! of scope, and are an error.
+
+use std::{collections::BTreeSet, fmt};
+
+use anyhow::Context;
+use chrono::{NaiveDate, NaiveDateTime};
+
+/// The name given to varying runs which can't be identified.
+pub const UNKNOWN_TOKEN: &str = "unknown";
+
+/// A template for the paths of a dataset. Use `to_string` to get the template as a string.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PathTemplate {
+    parts: Vec<Vec<TemplateSegment>>,
+}
+
+/// Part of one component of a `PathTemplate`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TemplateSegment {
+    /// Text which is the same in every path.
+    Literal(String),
+    /// A coordinate (e.g. `year` or `ensemble_member`), written as `{year}` in the template.
+    Token(String),
+}
+
+impl PathTemplate {
+    /// The segments of each part of the path (i.e. each component between `/` delimiters).
+    pub fn parts(&self) -> &[Vec<TemplateSegment>] {
+        &self.parts
+    }
+
+    /// The unique names of the tokens in the template.
+    pub fn token_names(&self) -> BTreeSet<&str> {
+        self.parts
+            .iter()
+            .flatten()
+            .filter_map(|segment| match segment {
+                TemplateSegment::Token(name) => Some(name.as_str()),
+                TemplateSegment::Literal(_) => None,
+            })
+            .collect()
+    }
+}
+
+impl fmt::Display for PathTemplate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, part) in self.parts.iter().enumerate() {
+            if i > 0 {
+                write!(f, "/")?;
+            }
+            for segment in part {
+                match segment {
+                    TemplateSegment::Literal(s) => write!(f, "{s}")?,
+                    TemplateSegment::Token(name) => write!(f, "{{{name}}}")?,
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The values of a coordinate, formatted as they appear in the paths. For example, the GEFS
+/// ensemble members are `gec00`, `gep01`, ..., `gep30`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KnownValues {
+    token: String,
+    values: BTreeSet<String>,
+}
+
+impl KnownValues {
+    pub fn new(
+        token: impl Into<String>,
+        values: impl IntoIterator<Item = impl Into<String>>,
+    ) -> Self {
+        Self {
+            token: token.into(),
+            values: values.into_iter().map(Into::into).collect(),
+        }
+    }
+}
+
+/// Infer the template of `paths`. All of `paths` must follow the same convention, i.e. they must
+/// have the same number of parts, and each part must have the same sequence of runs of digits,
+/// letters, and other characters. See the module-level docs.
+pub fn infer_path_template(
+    paths: &[object_store::path::Path],
+    known_values: &[KnownValues],
+) -> anyhow::Result<PathTemplate> {
+    let first_path = paths
+        .first()
+        .context("At least one path is required to infer a path template")?;
+    let n_parts = first_path.parts().count();
+    let mut inference = Inference {
+        known_values,
+        tokens: vec![],
+    };
+    let mut parts = Vec::with_capacity(n_parts);
+    for i in 0..n_parts {
+        // The runs of part `i` of every path.
+        let mut runs: Vec<Vec<(CharClass, String)>> = Vec::with_capacity(paths.len());
+        for path in paths {
+            let part = path.parts().nth(i).with_context(|| {
+                format!(
+                    "The paths don't follow a single convention: '{path}' has fewer parts \
+                     than '{first_path}'"
+                )
+            })?;
+            let path_runs = split_runs(part.as_ref());
+            if let Some(first_runs) = runs.first() {
+                if !same_classes(first_runs, &path_runs) {
+                    return Err(anyhow::format_err!(
+                        "The paths don't follow a single convention: Part {i} of '{path}' doesn't \
+                         have the same structure as part {i} of '{first_path}'"
+                    ));
+                }
+            }
+            runs.push(path_runs);
+        }
+        // `columns[j][p]` is the value of run `j` in path `p`.
+        let columns: Vec<Vec<&str>> = (0..runs[0].len())
+            .map(|j| {
+                runs.iter()
+                    .map(|path_runs| path_runs[j].1.as_str())
+                    .collect()
+            })
+            .collect();
+        parts.push(inference.infer_part(&columns, &runs[0]));
+    }
+    if let Some(path) = paths.iter().find(|path| path.parts().count() != n_parts) {
+        return Err(anyhow::format_err!(
+            "The paths don't follow a single convention: '{path}' has more parts than \
+             '{first_path}'"
+        ));
+    }
+    Ok(PathTemplate { parts })
+}
+
+struct Inference<'a> {
+    known_values: &'a [KnownValues],
+    /// The name of each token found so far, and its value in each path.
+    tokens: Vec<(String, Vec<String>)>,
+}
+
+impl Inference<'_> {
+    /// Infer the segments of one part of the paths. `columns[j][p]` is the value of run `j` in
+    /// path `p`.
+    fn infer_part(
+        &mut self,
+        columns: &[Vec<&str>],
+        runs: &[(CharClass, String)],
+    ) -> Vec<TemplateSegment> {
+        let is_constant = |column: &[&str]| column.iter().all(|value| *value == column[0]);
+        let mut segments = vec![];
+        let mut j = 0;
+        while j < columns.len() {
+            if is_constant(&columns[j]) {
+                push_literal(&mut segments, columns[j][0]);
+                j += 1;
+                continue;
+            }
+            // Adjacent varying runs may be a single coordinate (e.g. GEFS's `gec00` and `gep01`).
+            let span_end = (j..columns.len())
+                .find(|&k| is_constant(&columns[k]))
+                .unwrap_or(columns.len());
+            let n_paths = columns[j].len();
+            let span: Vec<String> = (0..n_paths)
+                .map(|p| (j..span_end).map(|k| columns[k][p]).collect())
+                .collect();
+            if span_end - j > 1 {
+                if let Some(name) = self.name_of(&span) {
+                    segments.push(TemplateSegment::Token(name));
+                    j = span_end;
+                    continue;
+                }
+            }
+            for k in j..span_end {
+                self.infer_column(&mut segments, &columns[k], runs[k].0);
+            }
+            j = span_end;
+        }
+        segments
+    }
+
+    /// Infer the segments of a single run which varies between paths.
+    fn infer_column(
+        &mut self,
+        segments: &mut Vec<TemplateSegment>,
+        column: &[&str],
+        class: CharClass,
+    ) {
+        // Text which is common to every path is a literal (e.g. the `f` in GEFS's `af006` and
+        // `bf012`). Numbers are kept whole, so that (e.g.) `006` and `012` aren't split.
+        let (prefix, suffix) = match class {
+            CharClass::Digit => (0, 0),
+            CharClass::Alpha | CharClass::Other => common_prefix_and_suffix_len(column),
+        };
+        let middle: Vec<String> = column
+            .iter()
+            .map(|value| value[prefix..value.len() - suffix].to_string())
+            .collect();
+        push_literal(segments, &column[0][..prefix]);
+        if let Some(name) = self.name_of(&middle) {
+            segments.push(TemplateSegment::Token(name));
+        } else if let Some(components) = (class == CharClass::Digit)
+            .then(|| recognise_datetime(&middle))
+            .flatten()
+            .filter(|components| {
+                // Each name must only be used for one coordinate.
+                components.iter().all(|(name, range)| {
+                    self.tokens
+                        .iter()
+                        .filter(|(token_name, _)| token_name == name)
+                        .all(|(_, token_values)| {
+                            token_values
+                                .iter()
+                                .zip(&middle)
+                                .all(|(token_value, value)| *token_value == value[range.clone()])
+                        })
+                })
+            })
+        {
+            for (name, range) in components {
+                let values = middle.iter().map(|value| value[range.clone()].to_string());
+                self.tokens.push((name.to_string(), values.collect()));
+                segments.push(TemplateSegment::Token(name.to_string()));
+            }
+        } else {
+            segments.push(TemplateSegment::Token(UNKNOWN_TOKEN.to_string()));
+        }
+        push_literal(segments, &column[0][column[0].len() - suffix..]);
+    }
+
+    /// Name `values` (the value in each path) using an earlier token, or a `KnownValues`.
+    fn name_of(&mut self, values: &[String]) -> Option<String> {
+        if let Some((name, _)) = self
+            .tokens
+            .iter()
+            .find(|(_, token_values)| token_values == values)
+        {
+            return Some(name.clone());
+        }
+        let known = self.known_values.iter().find(|known| {
+            values
+                .iter()
+                .all(|value| known.values.contains(value.as_str()))
+        })?;
+        self.tokens.push((known.token.clone(), values.to_vec()));
+        Some(known.token.clone())
+    }
+}
+
+/// If every value is a date or time (with the same format), return the name and the byte range of
+/// each component.
+fn recognise_datetime(values: &[String]) -> Option<Vec<(&'static str, std::ops::Range<usize>)>> {
+    let len = values[0].len();
+    if values.iter().any(|value| value.len() != len) {
+        return None;
+    }
+    let all = |f: &dyn Fn(&str) -> bool| values.iter().all(|value| f(value));
+    match len {
+        10 if all(&|v| NaiveDateTime::parse_from_str(&format!("{v}00"), "%Y%m%d%H%M").is_ok()) => {
+            Some(vec![
+                ("year", 0..4),
+                ("month", 4..6),
+                ("day", 6..8),
+                ("cycle", 8..10),
+            ])
+        }
+        8 if all(&|v| NaiveDate::parse_from_str(v, "%Y%m%d").is_ok()) => {
+            Some(vec![("year", 0..4), ("month", 4..6), ("day", 6..8)])
+        }
+        6 if all(&|v| NaiveDate::parse_from_str(&format!("{v}01"), "%Y%m%d").is_ok()) => {
+            Some(vec![("year", 0..4), ("month", 4..6)])
+        }
+        4 if all(&|v| {
+            v.parse()
+                .is_ok_and(|year: u32| (1900..=2100).contains(&year))
+        }) =>
+        {
+            Some(vec![("year", 0..4)])
+        }
+        2 if all(&|v| v.parse().is_ok_and(|hour: u32| hour < 24)) => Some(vec![("cycle", 0..2)]),
+        _ => None,
+    }
+}
+
+fn push_literal(segments: &mut Vec<TemplateSegment>, s: &str) {
+    if s.is_empty() {
+        return;
+    }
+    match segments.last_mut() {
+        Some(TemplateSegment::Literal(literal)) => literal.push_str(s),
+        _ => segments.push(TemplateSegment::Literal(s.to_string())),
+    }
+}
+
+/// The length (in bytes) of the longest prefix and the longest suffix which are common to every
+/// value, without overlapping in the shortest value.
+fn common_prefix_and_suffix_len(values: &[&str]) -> (usize, usize) {
+    let first = values[0];
+    let min_len = values.iter().map(|value| value.len()).min().unwrap_or(0);
+    let common_len = |chars: &dyn Fn(&str) -> Vec<char>| -> usize {
+        let first_chars = chars(first);
+        values
+            .iter()
+            .map(|value| {
+                first_chars
+                    .iter()
+                    .zip(chars(value))
+                    .take_while(|(a, b)| **a == *b)
+                    .map(|(c, _)| c.len_utf8())
+                    .sum::<usize>()
+            })
+            .min()
+            .unwrap_or(0)
+    };
+    let prefix = common_len(&|s| s.chars().collect());
+    let suffix = common_len(&|s| s.chars().rev().collect()).min(min_len - prefix);
+    (prefix, suffix)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CharClass {
+    Digit,
+    Alpha,
+    Other,
+}
+
+impl From<char> for CharClass {
+    fn from(c: char) -> Self {
+        if c.is_ascii_digit() {
+            Self::Digit
+        } else if c.is_alphabetic() {
+            Self::Alpha
+        } else {
+            Self::Other
+        }
+    }
+}
+
+/// Split `s` into runs of characters of the same `CharClass`.
+fn split_runs(s: &str) -> Vec<(CharClass, String)> {
+    let mut runs: Vec<(CharClass, String)> = vec![];
+    for c in s.chars() {
+        let class = CharClass::from(c);
+        match runs.last_mut() {
+            Some((last_class, run)) if *last_class == class => run.push(c),
+            _ => runs.push((class, c.to_string())),
+        }
+    }
+    runs
+}
+
+fn same_classes(a: &[(CharClass, String)], b: &[(CharClass, String)]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).all(|(a, b)| a.0 == b.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn paths(paths: &[&str]) -> Vec<object_store::path::Path> {
+        paths
+            .iter()
+            .map(|path| object_store::path::Path::from(*path))
+            .collect()
+    }
+
+    #[test]
+    fn test_infer_gefs_v1_template() -> anyhow::Result<()> {
+        // Real paths from GEFS `Version::V1`.
+        let paths = paths(&[
+            "gefs.20180727/00/pgrb2a/gec00.t00z.pgrb2af000.idx",
+            "gefs.20180727/06/pgrb2b/gep01.t06z.pgrb2bf006.idx",
+            "gefs.20190101/12/pgrb2a/gep20.t12z.pgrb2af384.idx",
+            "gefs.20200923/18/pgrb2b/gec00.t18z.pgrb2bf120.idx",
+        ]);
+        let ensemble_members =
+            std::iter::once(String::from("gec00")).chain((1..=30).map(|i| format!("gep{i:02}")));
+        let forecast_hours = (0..=384).map(|hour| format!("{hour:03}"));
+        let known_values = [
+            KnownValues::new("ensemble_member", ensemble_members),
+            KnownValues::new("parameter_set", ["a", "b"]),
+            KnownValues::new("forecast_hour", forecast_hours),
+        ];
+        let template = infer_path_template(&paths, &known_values)?;
+        assert_eq!(
+            template.to_string(),
+            "gefs.{year}{month}{day}/{cycle}/pgrb2{parameter_set}/\
+             {ensemble_member}.t{cycle}z.pgrb2{parameter_set}f{forecast_hour}.idx"
+        );
+        assert_eq!(
+            template.token_names(),
+            BTreeSet::from([
+                "cycle",
+                "day",
+                "ensemble_member",
+                "forecast_hour",
+                "month",
+                "parameter_set",
+                "year"
+            ])
+        );
+        assert_eq!(
+            template.parts()[2],
+            [
+                TemplateSegment::Literal(String::from("pgrb2")),
+                TemplateSegment::Token(String::from("parameter_set"))
+            ]
+        );
+
+        // Without known values, the coordinates which aren't dates or times are unknown:
+        let template = infer_path_template(&paths, &[])?;
+        assert_eq!(
+            template.to_string(),
+            "gefs.{year}{month}{day}/{cycle}/pgrb2{unknown}/\
+             ge{unknown}{unknown}.t{cycle}z.pgrb2{unknown}f{unknown}.idx"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_infer_template_with_year_and_init_directories() -> anyhow::Result<()> {
+        // Like the GEFS reforecast:
+        let paths = paths(&[
+            "2000/2000010100/c00/Days:1-10/tmp_2m_2000010100_c00.grib2",
+            "2019/2019112212/c00/Days:1-10/tmp_2m_2019112212_c00.grib2",
+        ]);
+        assert_eq!(
+            infer_path_template(&paths, &[])?.to_string(),
+            "{year}/{year}{month}{day}{cycle}/c00/Days:1-10/tmp_2m_{year}{month}{day}{cycle}_c00.grib2"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_mixed_conventions_are_an_error() {
+        // GEFS `Version::V0` and `Version::V1`:
+        let mixed = paths(&[
+            "gefs.20170101/00/gec00.t00z.pgrb2af006.idx",
+            "gefs.20180727/00/pgrb2a/gec00.t00z.pgrb2af006.idx",
+        ]);
+        assert!(infer_path_template(&mixed, &[]).is_err());
+        let reversed: Vec<_> = mixed.into_iter().rev().collect();
+        assert!(infer_path_template(&reversed, &[]).is_err());
+        // The same number of parts, but a different structure (`anl` instead of `f006`):
+        let mixed = paths(&[
+            "gefs.20170101/00/gec00.t00z.pgrb2af006.idx",
+            "gefs.20170101/00/gec00.t00z.pgrb2aanl.idx",
+        ]);
+        assert!(infer_path_template(&mixed, &[]).is_err());
+        assert!(infer_path_template(&[], &[]).is_err());
+    }
+}
@@ -0,0 +1,351 @@
+//! Select a hyper-rectangle of GRIB messages from a [`Manifest`], before fetching any bytes.
+//!
+//! For example, "temperature at 850 hPa for all forecast steps of one reference datetime" spans
+//! many GRIB messages. [`Manifest::select`] finds the `MessageLocation` of each of those messages,
+//! and the chunk coordinates where each message belongs in the output array.
+
+use std::ops::RangeInclusive;
+
+use chrono::{DateTime, Utc};
+
+use crate::{
+    manifest::{Key, KeyBuilder, Manifest, MessageLocation},
+    ForecastStep, Level, SortedVec, Statistic,
+};
+
+/// Which labels to select along one dimension.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub enum AxisSelection<T> {
+    /// Every label present in the manifest.
+    #[default]
+    All,
+    /// Exactly these labels. Labels which aren't present in the manifest are reported in
+    /// [`Selection::missing`].
+    Labels(Vec<T>),
+    /// Every label present in the manifest which falls within this (inclusive) range.
+    Range(RangeInclusive<T>),
+}
+
+impl<T: Ord + Clone> AxisSelection<T> {
+    /// Returns the selected labels, and the index of each label in `labels` (or `None` if the
+    /// label isn't in `labels`).
+    fn resolve(&self, labels: &SortedVec<T>) -> Vec<(Option<usize>, T)> {
+        match self {
+            Self::All => labels
+                .iter()
+                .cloned()
+                .enumerate()
+                .map(|(i, label)| (Some(i), label))
+                .collect(),
+            Self::Labels(selected) => selected
+                .iter()
+                .map(|label| (labels.index_of(label), label.clone()))
+                .collect(),
+            Self::Range(range) => labels
+                .iter()
+                .enumerate()
+                .filter(|(_, label)| range.contains(label))
+                .map(|(i, label)| (Some(i), label.clone()))
+                .collect(),
+        }
+    }
+}
+
+/// A selection along each dimension. Dimensions which aren't set select all their labels.
+#[derive(Debug, Clone, Default)]
+pub struct Selector {
+    reference_datetime: AxisSelection<DateTime<Utc>>,
+    ensemble_member: AxisSelection<String>,
+    forecast_step: AxisSelection<ForecastStep>,
+    parameter: AxisSelection<String>,
+    vertical_level: AxisSelection<Level>,
+    statistic: AxisSelection<Option<Statistic>>,
+}
+
+impl Selector {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_reference_datetime(mut self, selection: AxisSelection<DateTime<Utc>>) -> Self {
+        self.reference_datetime = selection;
+        self
+    }
+
+    pub fn with_ensemble_member(mut self, selection: AxisSelection<String>) -> Self {
+        self.ensemble_member = selection;
+        self
+    }
+
+    pub fn with_forecast_step(mut self, selection: AxisSelection<ForecastStep>) -> Self {
+        self.forecast_step = selection;
+        self
+    }
+
+    pub fn with_parameter(mut self, selection: AxisSelection<String>) -> Self {
+        self.parameter = selection;
+        self
+    }
+
+    pub fn with_vertical_level(mut self, selection: AxisSelection<Level>) -> Self {
+        self.vertical_level = selection;
+        self
+    }
+
+    /// Select percentiles or probabilities of a probabilistic product. `None` is the label of
+    /// deterministic fields.
+    pub fn with_statistic(mut self, selection: AxisSelection<Option<Statistic>>) -> Self {
+        self.statistic = selection;
+        self
+    }
+}
+
+/// A GRIB message which matched a [`Selector`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct SelectedMessage<'a> {
+    /// The index of each coordinate label in `Manifest::coord_labels`, in the order
+    /// `reference_datetime`, `ensemble_member`, `forecast_step`, `parameter`, `vertical_level`,
+    /// `statistic`.
+    pub chunk_coords: [usize; 6],
+    /// The index of each coordinate label in the selected labels of each dimension (see
+    /// `Selection::shape`), in the same order as `chunk_coords`.
+    pub selection_coords: [usize; 6],
+    pub key: Key,
+    pub msg_loc: &'a MessageLocation,
+}
+
+/// The result of [`Manifest::select`].
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Selection<'a> {
+    pub found: Vec<SelectedMessage<'a>>,
+    /// The selected combinations of coordinate labels which have no GRIB message in the manifest.
+    pub missing: Vec<Key>,
+    /// The number of selected labels along each dimension, in the same order as `chunk_coords`.
+    pub shape: [usize; 6],
+}
+
+impl Manifest {
+    /// Find the GRIB messages in the hyper-rectangle described by `selector`. This is the planning
+    /// step before fetching the selected messages.
+    ///
+    /// Messages are returned in row-major order of their coordinates.
+    pub fn select(&self, selector: &Selector) -> Selection<'_> {
+        let coord_labels = self.coord_labels();
+        let reference_datetimes = selector
+            .reference_datetime
+            .resolve(coord_labels.reference_datetime());
+        let ensemble_members = selector
+            .ensemble_member
+            .resolve(coord_labels.ensemble_member());
+        let forecast_steps = selector.forecast_step.resolve(coord_labels.forecast_step());
+        let parameters = selector.parameter.resolve(coord_labels.parameter());
+        let vertical_levels = selector
+            .vertical_level
+            .resolve(coord_labels.vertical_level());
+        let statistics = selector.statistic.resolve(coord_labels.statistic());
+
+        let mut selection = Selection {
+            shape: [
+                reference_datetimes.len(),
+                ensemble_members.len(),
+                forecast_steps.len(),
+                parameters.len(),
+                vertical_levels.len(),
+                statistics.len(),
+            ],
+            ..Selection::default()
+        };
+        let mut builder = KeyBuilder::new();
+        for (s_ref, (i_ref, reference_datetime)) in reference_datetimes.iter().enumerate() {
+            builder.set_reference_datetime(*reference_datetime);
+            for (s_ens, (i_ens, ensemble_member)) in ensemble_members.iter().enumerate() {
+                builder.set_ensemble_member(ensemble_member.as_str());
+                for (s_step, (i_step, forecast_step)) in forecast_steps.iter().enumerate() {
+                    builder.set_forecast_step(*forecast_step);
+                    for (s_param, (i_param, parameter)) in parameters.iter().enumerate() {
+                        builder.set_parameter(parameter.as_str());
+                        for (s_level, (i_level, vertical_level)) in
+                            vertical_levels.iter().enumerate()
+                        {
+                            builder.set_vertical_level(vertical_level.clone());
+                            for (s_stat, (i_stat, statistic)) in statistics.iter().enumerate() {
+                                builder.set_statistic(statistic.clone());
+                                let key = builder.build().expect("All fields are set");
+                                let chunk_coords =
+                                    [*i_ref, *i_ens, *i_step, *i_param, *i_level, *i_stat];
+                                match (chunk_coords, self.get(&key)) {
+                                    (
+                                        [Some(a), Some(b), Some(c), Some(d), Some(e), Some(f)],
+                                        Some(msg_loc),
+                                    ) => selection.found.push(SelectedMessage {
+                                        chunk_coords: [a, b, c, d, e, f],
+                                        selection_coords: [
+                                            s_ref, s_ens, s_step, s_param, s_level, s_stat,
+                                        ],
+                                        key,
+                                        msg_loc,
+                                    }),
+                                    _ => selection.missing.push(key),
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        selection
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeDelta;
+
+    use crate::ymdh_to_datetime;
+
+    use super::*;
+
+    /// Two parameters (HGT & TMP), three forecast steps (0, 6, 12 hours), and two vertical levels
+    /// (850 & 1000 hPa). TMP at 850 hPa has no message at the 12 hour forecast step. Note that
+    /// isobaric levels are sorted by descending pressure, so `Level::Isobaric(100_000)` (1000 hPa)
+    /// sorts before `Level::Isobaric(85_000)` (850 hPa).
+    fn create_manifest() -> Manifest {
+        let mut manifest = Manifest::new(object_store::path::Path::from("gefs"));
+        let mut builder = KeyBuilder::new();
+        builder
+            .set_reference_datetime(ymdh_to_datetime(2017, 1, 1, 0))
+            .set_ensemble_member("gec00");
+        let mut byte_offset = 0;
+        for hours in [0, 6, 12] {
+            let path = object_store::path::Path::from(format!(
+                "gefs.20170101/00/gec00.t00z.pgrb2af{hours:03}"
+            ));
+            builder.set_forecast_step(ForecastStep::Instant(TimeDelta::hours(hours)));
+            for parameter in ["HGT", "TMP"] {
+                builder.set_parameter(parameter);
+                for pascals in [85_000, 100_000] {
+                    if (hours, parameter, pascals) == (12, "TMP", 85_000) {
+                        continue;
+                    }
+                    builder.set_vertical_level(Level::Isobaric(pascals));
+                    manifest.insert(builder.build().unwrap(), &path, byte_offset, 100);
+                    byte_offset += 100;
+                }
+            }
+        }
+        manifest
+    }
+
+    #[test]
+    fn test_select_1d_slice() {
+        let manifest = create_manifest();
+        let selector = Selector::new()
+            .with_parameter(AxisSelection::Labels(vec![String::from("TMP")]))
+            .with_vertical_level(AxisSelection::Labels(vec![Level::Isobaric(100_000)]));
+        let selection = manifest.select(&selector);
+        assert!(selection.missing.is_empty());
+        let chunk_coords: Vec<_> = selection.found.iter().map(|m| m.chunk_coords).collect();
+        assert_eq!(
+            chunk_coords,
+            [[0, 0, 0, 1, 0, 0], [0, 0, 1, 1, 0, 0], [0, 0, 2, 1, 0, 0]]
+        );
+        assert!(selection
+            .found
+            .iter()
+            .all(|m| manifest.get(&m.key) == Some(m.msg_loc)));
+    }
+
+    #[test]
+    fn test_select_2d_slab() {
+        let manifest = create_manifest();
+        let selector = Selector::new()
+            .with_forecast_step(AxisSelection::Range(
+                ForecastStep::Instant(TimeDelta::hours(6))
+                    ..=ForecastStep::Instant(TimeDelta::hours(12)),
+            ))
+            .with_parameter(AxisSelection::Labels(vec![String::from("TMP")]));
+        let selection = manifest.select(&selector);
+        let chunk_coords: Vec<_> = selection.found.iter().map(|m| m.chunk_coords).collect();
+        assert_eq!(
+            chunk_coords,
+            [[0, 0, 1, 1, 0, 0], [0, 0, 1, 1, 1, 0], [0, 0, 2, 1, 0, 0]]
+        );
+        let selection_coords: Vec<_> = selection.found.iter().map(|m| m.selection_coords).collect();
+        assert_eq!(
+            selection_coords,
+            [[0, 0, 0, 0, 0, 0], [0, 0, 0, 0, 1, 0], [0, 0, 1, 0, 0, 0]]
+        );
+        assert_eq!(selection.shape, [1, 1, 2, 1, 2, 1]);
+        assert_eq!(selection.missing.len(), 1);
+        let missing = &selection.missing[0];
+        assert_eq!(
+            missing.forecast_step(),
+            &ForecastStep::Instant(TimeDelta::hours(12))
+        );
+        assert_eq!(missing.vertical_level(), &Level::Isobaric(85_000));
+    }
+
+    #[test]
+    fn test_select_label_not_in_manifest() {
+        let manifest = create_manifest();
+        let selector = Selector::new()
+            .with_forecast_step(AxisSelection::Labels(vec![ForecastStep::Instant(
+                TimeDelta::zero(),
+            )]))
+            .with_parameter(AxisSelection::Labels(vec![String::from("UGRD")]))
+            .with_vertical_level(AxisSelection::Labels(vec![Level::Isobaric(100_000)]));
+        let selection = manifest.select(&selector);
+        assert!(selection.found.is_empty());
+        assert_eq!(selection.missing.len(), 1);
+        assert_eq!(selection.missing[0].parameter(), "UGRD");
+    }
+
+    #[test]
+    fn test_select_statistic() {
+        let mut manifest = create_manifest();
+        let mut builder = KeyBuilder::new();
+        builder
+            .set_reference_datetime(ymdh_to_datetime(2017, 1, 1, 0))
+            .set_ensemble_member("gec00")
+            .set_forecast_step(ForecastStep::Instant(TimeDelta::zero()))
+            .set_parameter("TMP")
+            .set_vertical_level(Level::Isobaric(100_000));
+        let path = object_store::path::Path::from("blend.20170101/00/blend.t00z.f000");
+        for (i, percentile) in [10, 90].into_iter().enumerate() {
+            builder.set_statistic(Some(Statistic::Percentile(percentile)));
+            manifest.insert(builder.build().unwrap(), &path, i as u64 * 100, 100);
+        }
+
+        // The percentiles aren't reported as missing, and have their own chunk coordinate:
+        let selector = Selector::new()
+            .with_forecast_step(AxisSelection::Labels(vec![ForecastStep::Instant(
+                TimeDelta::zero(),
+            )]))
+            .with_parameter(AxisSelection::Labels(vec![String::from("TMP")]))
+            .with_vertical_level(AxisSelection::Labels(vec![Level::Isobaric(100_000)]));
+        let selection = manifest.select(&selector);
+        assert!(selection.missing.is_empty());
+        assert_eq!(selection.shape, [1, 1, 1, 1, 1, 3]);
+        let statistics: Vec<_> = selection
+            .found
+            .iter()
+            .map(|m| (m.chunk_coords[5], m.key.statistic().cloned()))
+            .collect();
+        assert_eq!(
+            statistics,
+            [
+                (0, None),
+                (1, Some(Statistic::Percentile(10))),
+                (2, Some(Statistic::Percentile(90)))
+            ]
+        );
+
+        // Only the 90th percentile:
+        let selection = manifest.select(
+            &selector.with_statistic(AxisSelection::Labels(vec![Some(Statistic::Percentile(90))])),
+        );
+        assert_eq!(selection.found.len(), 1);
+        assert_eq!(selection.found[0].chunk_coords, [0, 0, 0, 1, 0, 2]);
+        assert_eq!(selection.found[0].selection_coords, [0; 6]);
+    }
+}
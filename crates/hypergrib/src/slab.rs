@@ -0,0 +1,176 @@
+//! Read a selection of GRIB messages into a single dense N-dimensional array.
+//!
+//! [`read_slab`] is the "just give me the data as an array" entry point: It plans the selection
+//! with [`Manifest::select`], fetches the selected messages (coalescing GETs to messages in the
+//! same GRIB file), decodes each message, and writes each message's grid into its place in the
+//! output array.
+
+use std::collections::BTreeMap;
+
+use anyhow::Context;
+use futures_util::StreamExt;
+use ndarray::{Array2, ArrayD, Axis, IxDyn};
+use object_store::{path::Path, ObjectStore};
+
+use crate::{
+    fetch::{fetch_grib_messages, DEFAULT_MAX_GAP_BYTES},
+    inspect::read_message,
+    manifest::Manifest,
+    selection::{SelectedMessage, Selector},
+};
+
+/// The maximum number of GRIB files fetched concurrently by `read_slab`. Note that the store may
+/// impose a lower limit on the number of concurrent requests (e.g. if the store is wrapped in a
+/// `LimitStore`).
+const MAX_CONCURRENT_FILES: usize = 64;
+
+/// Fetch and decode every GRIB message selected by `selector`, and assemble them into a single
+/// array with the shape `[reference_datetime, ensemble_member, forecast_step, parameter,
+/// vertical_level, statistic, rows, columns]`. The first six dimensions have the shape
+/// `Selection::shape`, and each message is written at its `SelectedMessage::selection_coords`.
+///
+/// Selected messages which aren't in the manifest are filled with NaN. Returns an error if none
+/// of the selected messages are in the manifest (because the grid shape is then unknown), or if
+/// the selected messages don't all have the same grid shape.
+///
+/// `store` must be the store which `Manifest::base_path` refers to.
+pub async fn read_slab(
+    manifest: &Manifest,
+    store: &dyn ObjectStore,
+    selector: &Selector,
+) -> anyhow::Result<ArrayD<f32>> {
+    let selection = manifest.select(selector);
+    if selection.found.is_empty() {
+        return Err(anyhow::format_err!(
+            "None of the selected GRIB messages are in the manifest"
+        ));
+    }
+
+    // Group the messages by GRIB file, so that nearby messages can be fetched in a single GET.
+    let mut msgs_by_path: BTreeMap<Path, Vec<&SelectedMessage>> = BTreeMap::new();
+    for msg in &selection.found {
+        msgs_by_path
+            .entry(manifest.resolve_path(msg.msg_loc))
+            .or_default()
+            .push(msg);
+    }
+    let mut fetched = futures_util::stream::iter(msgs_by_path)
+        .map(|(path, msgs)| async move {
+            let byte_ranges: Vec<_> = msgs.iter().map(|msg| msg.msg_loc.byte_range()).collect();
+            let bytes = fetch_grib_messages(store, &path, &byte_ranges, DEFAULT_MAX_GAP_BYTES)
+                .await
+                .with_context(|| format!("Failed to fetch GRIB messages from '{path}'"))?;
+            anyhow::Ok(msgs.into_iter().zip(bytes).collect::<Vec<_>>())
+        })
+        .buffer_unordered(MAX_CONCURRENT_FILES);
+
+    let mut slab: Option<ArrayD<f32>> = None;
+    while let Some(fetched_msgs) = fetched.next().await {
+        for (msg, bytes) in fetched_msgs? {
+            let grid = decode_grid(&bytes)
+                .with_context(|| format!("Failed to decode the GRIB message for {:?}", msg.key))?;
+            let slab = slab.get_or_insert_with(|| {
+                let shape: Vec<_> = selection
+                    .shape
+                    .iter()
+                    .chain(grid.shape())
+                    .copied()
+                    .collect();
+                ArrayD::from_elem(IxDyn(&shape), f32::NAN)
+            });
+            let mut chunk = slab.view_mut();
+            for &i in &msg.selection_coords {
+                chunk = chunk.index_axis_move(Axis(0), i);
+            }
+            if chunk.shape() != grid.shape() {
+                return Err(anyhow::format_err!(
+                    "The grid shape of the GRIB message for {:?} is {:?}, but the grid shape of \
+                     other selected messages is {:?}",
+                    msg.key,
+                    grid.shape(),
+                    chunk.shape()
+                ));
+            }
+            chunk.assign(&grid);
+        }
+    }
+    Ok(slab.expect("At least one message was found"))
+}
+
+/// Decode the values of the GRIB message at the start of `bytes`, with the shape
+/// `[rows, columns]`.
+fn decode_grid(bytes: &[u8]) -> anyhow::Result<Array2<f32>> {
+    let msg = read_message(bytes)?;
+    let values: Vec<f32> = msg.data()?.into_iter().map(|value| value as f32).collect();
+    Ok(Array2::from_shape_vec(msg.grid_dimensions()?, values)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeDelta;
+    use object_store::{memory::InMemory, PutPayload};
+
+    use crate::{
+        fetch::decompress_bz2, manifest::KeyBuilder, selection::AxisSelection, ymdh_to_datetime,
+        ForecastStep, Level,
+    };
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_read_slab() -> anyhow::Result<()> {
+        // A GRIB file containing the same message twice, which we pretend are two forecast steps:
+        let msg = decompress_bz2(include_bytes!("datasets/icon/test_message.grib2.bz2"))?;
+        let msg_len = msg.len() as u64;
+        let store = InMemory::new();
+        let grib_path = Path::from("hrrr/foo.grib2");
+        store
+            .put(
+                &grib_path,
+                PutPayload::from([msg.clone(), msg.clone()].concat()),
+            )
+            .await?;
+
+        let mut manifest = Manifest::new(Path::default());
+        let mut builder = KeyBuilder::new();
+        builder
+            .set_reference_datetime(ymdh_to_datetime(2023, 7, 22, 6))
+            .set_ensemble_member("control")
+            .set_parameter("CFRZR")
+            .set_vertical_level(Level::Surface);
+        for (hours, byte_offset) in [(1, 0), (2, msg_len)] {
+            builder.set_forecast_step(ForecastStep::Instant(TimeDelta::hours(hours)));
+            manifest.insert(builder.build()?, &grib_path, byte_offset, msg_len);
+        }
+
+        let expected = decode_grid(&msg)?;
+        let slab = read_slab(&manifest, &store, &Selector::new()).await?;
+        assert_eq!(slab.shape(), [1, 1, 2, 1, 1, 1, 1059, 1799]);
+        let same = |a: &f32, b: &f32| a == b || (a.is_nan() && b.is_nan());
+        for step in 0..2 {
+            let grid = slab.slice(ndarray::s![0, 0, step, 0, 0, 0, .., ..]);
+            assert!(grid.iter().zip(expected.iter()).all(|(a, b)| same(a, b)));
+        }
+
+        // A forecast step which isn't in the manifest is filled with NaN:
+        let steps = [1, 3].map(|hours| ForecastStep::Instant(TimeDelta::hours(hours)));
+        let selector = Selector::new().with_forecast_step(AxisSelection::Labels(steps.to_vec()));
+        let slab = read_slab(&manifest, &store, &selector).await?;
+        assert_eq!(slab.shape(), [1, 1, 2, 1, 1, 1, 1059, 1799]);
+        assert!(slab
+            .slice(ndarray::s![0, 0, 0, 0, 0, 0, .., ..])
+            .iter()
+            .zip(expected.iter())
+            .all(|(a, b)| same(a, b)));
+        assert!(slab
+            .slice(ndarray::s![0, 0, 1, 0, 0, 0, .., ..])
+            .iter()
+            .all(|value| value.is_nan()));
+
+        // Nothing selected:
+        let selector =
+            Selector::new().with_parameter(AxisSelection::Labels(vec![String::from("TMP")]));
+        assert!(read_slab(&manifest, &store, &selector).await.is_err());
+        Ok(())
+    }
+}
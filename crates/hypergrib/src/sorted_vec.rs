@@ -0,0 +1,106 @@
+use std::{collections::BTreeSet, ops::Deref};
+
+/// A `Vec` whose elements are guaranteed to be sorted and unique.
+///
+/// `SortedVec` only allows immutable access to its elements (via `Deref<Target = [T]>`),
+/// so the invariant can't be broken after construction.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SortedVec<T>(Vec<T>);
+
+impl<T: Ord> SortedVec<T> {
+    /// Returns an error if `vec` is not sorted, or if `vec` contains duplicates.
+    /// Use `SortedVec::from(vec)` to sort and de-duplicate `vec`.
+    pub fn try_from_sorted(vec: Vec<T>) -> anyhow::Result<Self> {
+        match vec.windows(2).position(|pair| pair[0] >= pair[1]) {
+            None => Ok(Self(vec)),
+            Some(i) => Err(anyhow::format_err!(
+                "Elements must be sorted and unique, but element {i} >= element {}",
+                i + 1
+            )),
+        }
+    }
+
+    /// Find the index of `value` using a binary search.
+    pub fn index_of(&self, value: &T) -> Option<usize> {
+        self.0.binary_search(value).ok()
+    }
+
+    pub fn into_vec(self) -> Vec<T> {
+        self.0
+    }
+}
+
+impl<T> Default for SortedVec<T> {
+    fn default() -> Self {
+        Self(vec![])
+    }
+}
+
+/// Sorts and de-duplicates `vec`.
+impl<T: Ord> From<Vec<T>> for SortedVec<T> {
+    fn from(mut vec: Vec<T>) -> Self {
+        vec.sort();
+        vec.dedup();
+        Self(vec)
+    }
+}
+
+impl<T: Ord> From<BTreeSet<T>> for SortedVec<T> {
+    fn from(set: BTreeSet<T>) -> Self {
+        // `BTreeSet` iterates in sorted order, and its elements are unique.
+        Self(set.into_iter().collect())
+    }
+}
+
+impl<T: Ord> FromIterator<T> for SortedVec<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        Self::from(iter.into_iter().collect::<Vec<T>>())
+    }
+}
+
+impl<T> Deref for SortedVec<T> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        &self.0
+    }
+}
+
+impl<'a, T> IntoIterator for &'a SortedVec<T> {
+    type Item = &'a T;
+    type IntoIter = std::slice::Iter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_unsorted_vec() {
+        let sorted_vec = SortedVec::from(vec![3, 1, 2, 3, 1]);
+        assert_eq!(*sorted_vec, [1, 2, 3]);
+    }
+
+    #[test]
+    fn test_try_from_sorted() {
+        assert_eq!(
+            *SortedVec::try_from_sorted(vec![1, 2, 3]).unwrap(),
+            [1, 2, 3]
+        );
+        assert!(SortedVec::try_from_sorted(vec![1, 3, 2]).is_err());
+        assert!(SortedVec::try_from_sorted(vec![1, 2, 2]).is_err());
+    }
+
+    #[test]
+    fn test_index_of() {
+        let sorted_vec = SortedVec::from(vec!["c", "a", "b"]);
+        assert_eq!(sorted_vec.index_of(&"a"), Some(0));
+        assert_eq!(sorted_vec.index_of(&"b"), Some(1));
+        assert_eq!(sorted_vec.index_of(&"c"), Some(2));
+        assert_eq!(sorted_vec.index_of(&"d"), None);
+    }
+}
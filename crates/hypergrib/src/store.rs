@@ -0,0 +1,466 @@
+//! Construct an `ObjectStore` from a URL, with or without signing requests.
+//!
+//! Public buckets (like NOAA's buckets on AWS) require anonymous access (i.e. requests must not be
+//! signed), whereas private mirrors require signed requests. `AccessMode::Auto` tries anonymous
+//! access first and falls back to signed access if the anonymous request is denied.
+//!
+//! Plain `http://` and `https://` URLs (e.g. university mirrors) are also supported. Requests to
+//! these URLs are never signed. Listing only works if the server supports WebDAV.
+//!
+//! `StoreConfig` configures the timeouts, retries, and concurrency of the `ObjectStore`.
+//!
+//! The TLS backend for `https://` requests is chosen at compile time by the `rustls` (the default)
+//! and `native-tls` features. `native-tls` takes precedence if both are enabled. See
+//! [`TLS_BACKEND`].
+
+use std::time::Duration;
+
+use anyhow::Context;
+use object_store::{
+    aws::AmazonS3Builder, http::HttpBuilder, limit::LimitStore, path::Path, ClientOptions,
+    ObjectStore, ObjectStoreScheme, RetryConfig,
+};
+use url::{Position, Url};
+
+/// The name of the TLS backend which was selected at compile time: `"rustls"` or `"native-tls"`.
+/// `reqwest` uses `native-tls` whenever the `native-tls` feature is enabled.
+#[cfg(not(feature = "native-tls"))]
+pub const TLS_BACKEND: &str = "rustls";
+/// The name of the TLS backend which was selected at compile time: `"rustls"` or `"native-tls"`.
+/// `reqwest` uses `native-tls` whenever the `native-tls` feature is enabled.
+#[cfg(feature = "native-tls")]
+pub const TLS_BACKEND: &str = "native-tls";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum AccessMode {
+    /// Don't sign requests. Use this for public buckets.
+    Anonymous,
+    /// Sign requests. Use this for buckets which require authentication.
+    Signed,
+    /// Try `Anonymous` first. If that is denied then use `Signed`.
+    Auto,
+}
+
+/// Configure the timeouts, retries, and concurrency of an `ObjectStore`. Large indexing jobs
+/// against flaky endpoints may need longer timeouts and more retries than `object_store`'s
+/// defaults. Fields which are `None` use `object_store`'s defaults.
+#[derive(Debug, Clone, Default, PartialEq, Eq, clap::Args)]
+pub struct StoreConfig {
+    /// The timeout (in seconds) for each request, from connecting until the response body has
+    /// finished.
+    #[arg(long = "timeout", value_parser = parse_secs)]
+    pub timeout: Option<Duration>,
+
+    /// The timeout (in seconds) for connecting to the server.
+    #[arg(long = "connect-timeout", value_parser = parse_secs)]
+    pub connect_timeout: Option<Duration>,
+
+    /// The maximum number of times to retry a failed request.
+    #[arg(long)]
+    pub max_retries: Option<usize>,
+
+    /// The maximum number of concurrent requests.
+    #[arg(long)]
+    pub concurrency_limit: Option<usize>,
+}
+
+fn parse_secs(s: &str) -> Result<Duration, String> {
+    let secs: f64 = s.parse().map_err(|e| format!("{e}"))?;
+    Duration::try_from_secs_f64(secs).map_err(|e| format!("{e}"))
+}
+
+impl StoreConfig {
+    pub fn client_options(&self) -> ClientOptions {
+        let mut client_options = ClientOptions::new();
+        if let Some(timeout) = self.timeout {
+            client_options = client_options.with_timeout(timeout);
+        }
+        if let Some(connect_timeout) = self.connect_timeout {
+            client_options = client_options.with_connect_timeout(connect_timeout);
+        }
+        client_options
+    }
+
+    pub fn retry_config(&self) -> RetryConfig {
+        let mut retry_config = RetryConfig::default();
+        if let Some(max_retries) = self.max_retries {
+            retry_config.max_retries = max_retries;
+        }
+        retry_config
+    }
+
+    /// Construct an `ObjectStore` (and the path within that store) from `url`, without checking
+    /// whether the store is accessible.
+    ///
+    /// The client options and retry config only apply to S3 and HTTP stores. (They're irrelevant
+    /// for local and in-memory stores, and `hypergrib` doesn't enable `object_store`'s other
+    /// backends). `skip_signature` is ignored for HTTP stores, whose requests are never signed.
+    pub fn make_store(
+        &self,
+        url: &Url,
+        skip_signature: bool,
+    ) -> anyhow::Result<(Box<dyn ObjectStore>, Path)> {
+        let (scheme, path) = ObjectStoreScheme::parse(url)?;
+        let store: Box<dyn ObjectStore> = match scheme {
+            ObjectStoreScheme::AmazonS3 => Box::new(
+                AmazonS3Builder::from_env()
+                    .with_url(url.as_str())
+                    .with_skip_signature(skip_signature)
+                    .with_client_options(self.client_options())
+                    .with_retry(self.retry_config())
+                    .build()?,
+            ),
+            // The returned `path` is the whole path of the URL, so the store's URL is the origin.
+            ObjectStoreScheme::Http => Box::new(
+                HttpBuilder::new()
+                    .with_url(&url[..Position::BeforePath])
+                    .with_client_options(self.client_options().with_allow_http(true))
+                    .with_retry(self.retry_config())
+                    .build()?,
+            ),
+            _ => object_store::parse_url(url)?.0,
+        };
+        let store = match self.concurrency_limit {
+            Some(concurrency_limit) => Box::new(LimitStore::new(store, concurrency_limit)),
+            None => store,
+        };
+        Ok((store, path))
+    }
+}
+
+/// Construct an `ObjectStore` (and the path within that store) from `url`.
+///
+/// For `AccessMode::Auto`, this lists `path` anonymously to check whether anonymous access is
+/// allowed. The returned `AccessMode` is the mode which was actually used (i.e. `Anonymous` or
+/// `Signed`, never `Auto`).
+///
+/// `http://` and `https://` URLs are always accessed anonymously, without listing `path` (because
+/// most HTTP servers don't support listing).
+pub async fn open_store(
+    url: &Url,
+    access_mode: AccessMode,
+    config: &StoreConfig,
+) -> anyhow::Result<(Box<dyn ObjectStore>, Path, AccessMode)> {
+    if let (ObjectStoreScheme::Http, _) = ObjectStoreScheme::parse(url)? {
+        let (store, path) = config.make_store(url, true)?;
+        return Ok((store, path, AccessMode::Anonymous));
+    }
+    open_store_with(access_mode, |access_mode| {
+        config.make_store(url, access_mode == AccessMode::Anonymous)
+    })
+    .await
+}
+
+/// `make_store` is only ever called with `AccessMode::Anonymous` or `AccessMode::Signed`.
+async fn open_store_with<F>(
+    access_mode: AccessMode,
+    make_store: F,
+) -> anyhow::Result<(Box<dyn ObjectStore>, Path, AccessMode)>
+where
+    F: Fn(AccessMode) -> anyhow::Result<(Box<dyn ObjectStore>, Path)>,
+{
+    if access_mode != AccessMode::Auto {
+        let (store, path) = make_store(access_mode)?;
+        return Ok((store, path, access_mode));
+    }
+
+    let (store, path) = make_store(AccessMode::Anonymous)?;
+    match store.list_with_delimiter(Some(&path)).await {
+        Ok(_) => Ok((store, path, AccessMode::Anonymous)),
+        Err(
+            object_store::Error::PermissionDenied { .. }
+            | object_store::Error::Unauthenticated { .. },
+        ) => {
+            let (store, path) = make_store(AccessMode::Signed)?;
+            store
+                .list_with_delimiter(Some(&path))
+                .await
+                .with_context(|| {
+                    format!("Anonymous access to '{path}' was denied, and so was signed access")
+                })?;
+            Ok((store, path, AccessMode::Signed))
+        }
+        Err(e) => Err(e).with_context(|| format!("Failed to list '{path}'")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fmt,
+        sync::{Arc, Mutex},
+    };
+
+    use async_trait::async_trait;
+    use futures_util::stream::BoxStream;
+    use object_store::{
+        memory::InMemory, ClientConfigKey, GetOptions, GetResult, ListResult, MultipartUpload,
+        ObjectMeta, PutMultipartOpts, PutOptions, PutPayload, PutResult,
+    };
+
+    use super::*;
+
+    /// A store which denies every request, like a private bucket accessed anonymously.
+    #[derive(Debug)]
+    struct DenyAllStore;
+
+    impl fmt::Display for DenyAllStore {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "DenyAllStore")
+        }
+    }
+
+    fn denied(path: &Path) -> object_store::Error {
+        object_store::Error::PermissionDenied {
+            path: path.to_string(),
+            source: "Access denied".into(),
+        }
+    }
+
+    #[async_trait]
+    impl ObjectStore for DenyAllStore {
+        async fn put_opts(
+            &self,
+            location: &Path,
+            _payload: PutPayload,
+            _opts: PutOptions,
+        ) -> object_store::Result<PutResult> {
+            Err(denied(location))
+        }
+
+        async fn put_multipart_opts(
+            &self,
+            location: &Path,
+            _opts: PutMultipartOpts,
+        ) -> object_store::Result<Box<dyn MultipartUpload>> {
+            Err(denied(location))
+        }
+
+        async fn get_opts(
+            &self,
+            location: &Path,
+            _options: GetOptions,
+        ) -> object_store::Result<GetResult> {
+            Err(denied(location))
+        }
+
+        async fn delete(&self, location: &Path) -> object_store::Result<()> {
+            Err(denied(location))
+        }
+
+        fn list(&self, prefix: Option<&Path>) -> BoxStream<'_, object_store::Result<ObjectMeta>> {
+            let err = denied(prefix.unwrap_or(&Path::default()));
+            Box::pin(futures_util::stream::once(async { Err(err) }))
+        }
+
+        async fn list_with_delimiter(
+            &self,
+            prefix: Option<&Path>,
+        ) -> object_store::Result<ListResult> {
+            Err(denied(prefix.unwrap_or(&Path::default())))
+        }
+
+        async fn copy(&self, _from: &Path, to: &Path) -> object_store::Result<()> {
+            Err(denied(to))
+        }
+
+        async fn copy_if_not_exists(&self, _from: &Path, to: &Path) -> object_store::Result<()> {
+            Err(denied(to))
+        }
+    }
+
+    /// Returns a `DenyAllStore` for anonymous access if `anonymous_is_denied`, and records
+    /// which `AccessMode`s were requested.
+    fn make_store_fn(
+        anonymous_is_denied: bool,
+        requested_modes: Arc<Mutex<Vec<AccessMode>>>,
+    ) -> impl Fn(AccessMode) -> anyhow::Result<(Box<dyn ObjectStore>, Path)> {
+        move |access_mode| {
+            requested_modes.lock().unwrap().push(access_mode);
+            let store: Box<dyn ObjectStore> =
+                if anonymous_is_denied && access_mode == AccessMode::Anonymous {
+                    Box::new(DenyAllStore)
+                } else {
+                    Box::new(InMemory::new())
+                };
+            Ok((store, Path::from("bucket")))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_auto_falls_back_to_signed() -> anyhow::Result<()> {
+        let requested_modes = Arc::new(Mutex::new(vec![]));
+        let (_, _, access_mode) = open_store_with(
+            AccessMode::Auto,
+            make_store_fn(true, requested_modes.clone()),
+        )
+        .await?;
+        assert_eq!(access_mode, AccessMode::Signed);
+        assert_eq!(
+            *requested_modes.lock().unwrap(),
+            [AccessMode::Anonymous, AccessMode::Signed]
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_auto_uses_anonymous_if_allowed() -> anyhow::Result<()> {
+        let requested_modes = Arc::new(Mutex::new(vec![]));
+        let (_, _, access_mode) = open_store_with(
+            AccessMode::Auto,
+            make_store_fn(false, requested_modes.clone()),
+        )
+        .await?;
+        assert_eq!(access_mode, AccessMode::Anonymous);
+        assert_eq!(*requested_modes.lock().unwrap(), [AccessMode::Anonymous]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_explicit_access_mode_does_not_probe() -> anyhow::Result<()> {
+        let requested_modes = Arc::new(Mutex::new(vec![]));
+        let (_, _, access_mode) = open_store_with(
+            AccessMode::Anonymous,
+            make_store_fn(true, requested_modes.clone()),
+        )
+        .await?;
+        assert_eq!(access_mode, AccessMode::Anonymous);
+        assert_eq!(*requested_modes.lock().unwrap(), [AccessMode::Anonymous]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_store_config_client_options() {
+        let default_options = ClientOptions::new();
+        let config = StoreConfig {
+            timeout: Some(Duration::from_secs(120)),
+            connect_timeout: Some(Duration::from_millis(2_500)),
+            max_retries: Some(20),
+            concurrency_limit: Some(10),
+        };
+        let expected_options = ClientOptions::new()
+            .with_timeout(Duration::from_secs(120))
+            .with_connect_timeout(Duration::from_millis(2_500));
+        for key in [ClientConfigKey::Timeout, ClientConfigKey::ConnectTimeout] {
+            let value = config.client_options().get_config_value(&key);
+            assert_eq!(value, expected_options.get_config_value(&key), "{key:?}");
+            assert_ne!(value, default_options.get_config_value(&key), "{key:?}");
+        }
+        assert_eq!(config.retry_config().max_retries, 20);
+
+        // Fields which are `None` use `object_store`'s defaults:
+        let config = StoreConfig::default();
+        for key in [ClientConfigKey::Timeout, ClientConfigKey::ConnectTimeout] {
+            assert_eq!(
+                config.client_options().get_config_value(&key),
+                default_options.get_config_value(&key)
+            );
+        }
+        assert_eq!(
+            config.retry_config().max_retries,
+            RetryConfig::default().max_retries
+        );
+    }
+
+    /// A minimal HTTP server, which serves `body` at `path` and returns 404 for every other
+    /// request. Each request line (e.g. `GET /foo HTTP/1.1`) is recorded in `requests`. Returns
+    /// the URL of the server.
+    async fn serve_http(
+        path: &'static str,
+        body: &'static [u8],
+        requests: Arc<Mutex<Vec<String>>>,
+    ) -> anyhow::Result<Url> {
+        use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+        let url = Url::parse(&format!("http://{}", listener.local_addr()?))?;
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let mut stream = BufReader::new(stream);
+                let mut request_line = String::new();
+                stream.read_line(&mut request_line).await?;
+                // Skip the headers:
+                let mut line = String::new();
+                while stream.read_line(&mut line).await? > 2 {
+                    line.clear();
+                }
+                let request_line = request_line.trim_end().to_string();
+                let response = if request_line == format!("GET {path} HTTP/1.1") {
+                    format!("HTTP/1.1 200 OK\r\nContent-Length: {}\r\n", body.len())
+                } else {
+                    String::from("HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\n")
+                };
+                requests.lock().unwrap().push(request_line);
+                let mut stream = stream.into_inner();
+                stream
+                    .write_all(format!("{response}Connection: close\r\n\r\n").as_bytes())
+                    .await?;
+                if response.starts_with("HTTP/1.1 200") {
+                    stream.write_all(body).await?;
+                }
+                stream.shutdown().await?;
+            }
+            std::io::Result::Ok(())
+        });
+        Ok(url)
+    }
+
+    #[tokio::test]
+    async fn test_http_url() -> anyhow::Result<()> {
+        const IDX: &[u8] = b"1:0:d=2017010100:HGT:10 mb:anl:ENS=low-res ctl\n";
+        let requests = Arc::new(Mutex::new(vec![]));
+        let base_url =
+            serve_http("/mirror/gefs.20170101/00/gec00.idx", IDX, requests.clone()).await?;
+        let url = base_url.join("mirror/gefs.20170101/00/")?;
+
+        // `Auto` doesn't try to list the (non-WebDAV) server, and doesn't sign requests:
+        let (store, path, access_mode) =
+            open_store(&url, AccessMode::Auto, &StoreConfig::default()).await?;
+        assert_eq!(access_mode, AccessMode::Anonymous);
+        assert_eq!(path, Path::from("mirror/gefs.20170101/00"));
+        let bytes = store.get(&path.child("gec00.idx")).await?.bytes().await?;
+        assert_eq!(bytes.as_ref(), IDX);
+        assert!(matches!(
+            store.get(&path.child("missing.idx")).await,
+            Err(object_store::Error::NotFound { .. })
+        ));
+        assert_eq!(
+            *requests.lock().unwrap(),
+            [
+                "GET /mirror/gefs.20170101/00/gec00.idx HTTP/1.1",
+                "GET /mirror/gefs.20170101/00/missing.idx HTTP/1.1"
+            ]
+        );
+
+        // `https://` URLs are supported too:
+        let url = Url::parse("https://example.com/mirror/gefs.20170101/00/gec00.idx")?;
+        let (_store, path) = StoreConfig::default().make_store(&url, false)?;
+        assert_eq!(path, Path::from("mirror/gefs.20170101/00/gec00.idx"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_https_clients_construct_with_tls_backend() -> anyhow::Result<()> {
+        // Building a store builds its HTTP client, which initialises the TLS backend:
+        let config = StoreConfig {
+            timeout: Some(Duration::from_secs(5)),
+            ..Default::default()
+        };
+        for url in [
+            "https://example.com/mirror/gefs.20170101/00/gec00.idx",
+            "s3://noaa-gefs-pds/gefs.20170101/00/",
+        ] {
+            config
+                .make_store(&Url::parse(url)?, true)
+                .with_context(|| format!("Failed to construct the store for '{url}'"))?;
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_secs() {
+        assert_eq!(parse_secs("2.5"), Ok(Duration::from_millis(2_500)));
+        assert!(parse_secs("-1").is_err());
+        assert!(parse_secs("foo").is_err());
+    }
+}
@@ -0,0 +1,255 @@
+//! Check that a [`Manifest`] still matches the GRIB files in the bucket.
+//!
+//! Manifests go stale when a bucket is reorganised (e.g. files are moved or re-uploaded).
+//! [`Manifest::verify_sample`] checks a random sample of messages, which is much cheaper than
+//! checking every message.
+
+use futures_util::{StreamExt, TryStreamExt};
+use object_store::{path::Path, ObjectStore};
+use rand::{rngs::StdRng, seq::IteratorRandom, SeedableRng};
+
+use crate::{
+    fetch::{fetch_grib_message, is_bz2_compressed, BeyondEndOfFileError, GRIB_MAGIC},
+    manifest::{Key, Manifest, MessageLocation},
+};
+
+/// The maximum number of messages verified concurrently by `Manifest::verify_sample`. Note that
+/// the store may impose a lower limit on the number of concurrent requests (e.g. if the store is
+/// wrapped in a `LimitStore`).
+const MAX_CONCURRENT_MESSAGES: usize = 64;
+
+/// Why a sampled message failed verification.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VerifyFailure {
+    /// The GRIB file doesn't exist.
+    NotFound,
+    /// The message's byte range extends beyond the end of the GRIB file.
+    BeyondEndOfFile { file_size: u64 },
+    /// The message's bytes don't start with "GRIB".
+    NotGrib,
+}
+
+/// A sampled message which failed verification.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FailedMessage {
+    pub key: Key,
+    pub msg_loc: MessageLocation,
+    pub failure: VerifyFailure,
+}
+
+/// The result of [`Manifest::verify_sample`].
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct VerifyReport {
+    /// The number of messages which were checked.
+    pub n_checked: usize,
+    pub failed: Vec<FailedMessage>,
+}
+
+impl VerifyReport {
+    pub fn is_ok(&self) -> bool {
+        self.failed.is_empty()
+    }
+}
+
+impl Manifest {
+    /// Check a random sample of `n` messages (or every message, if the manifest contains fewer
+    /// than `n` messages): Each GRIB file must exist, and each message's byte range must be
+    /// within the GRIB file and start with "GRIB". The same `seed` always samples the same
+    /// messages from the same manifest.
+    ///
+    /// `store` must be the store which `Manifest::base_path` refers to. Errors other than a
+    /// missing GRIB file (e.g. network errors) are returned as `Err`.
+    pub async fn verify_sample(
+        &self,
+        store: &dyn ObjectStore,
+        n: usize,
+        seed: u64,
+    ) -> anyhow::Result<VerifyReport> {
+        // `Manifest::iter` is in arbitrary order, so sort before sampling to make the sample
+        // reproducible.
+        let mut messages: Vec<_> = self.iter().collect();
+        messages.sort_by(|(_, a), (_, b)| {
+            (a.path(), a.byte_offset()).cmp(&(b.path(), b.byte_offset()))
+        });
+        let mut rng = StdRng::seed_from_u64(seed);
+        let sample = messages.into_iter().choose_multiple(&mut rng, n);
+        let n_checked = sample.len();
+
+        let failures: Vec<_> = futures_util::stream::iter(sample)
+            .map(|(key, msg_loc)| async move {
+                let failure = verify_message(store, &self.resolve_path(msg_loc), msg_loc).await?;
+                anyhow::Ok(failure.map(|failure| FailedMessage {
+                    key: key.clone(),
+                    msg_loc: msg_loc.clone(),
+                    failure,
+                }))
+            })
+            // `buffered` (rather than `buffer_unordered`) keeps the failures in the order of the
+            // sample.
+            .buffered(MAX_CONCURRENT_MESSAGES)
+            .try_collect()
+            .await?;
+
+        Ok(VerifyReport {
+            n_checked,
+            failed: failures.into_iter().flatten().collect(),
+        })
+    }
+}
+
+/// Returns `None` if the message passes verification.
+async fn verify_message(
+    store: &dyn ObjectStore,
+    path: &Path,
+    msg_loc: &MessageLocation,
+) -> anyhow::Result<Option<VerifyFailure>> {
+    let file_size = match store.head(path).await {
+        Ok(meta) => meta.size as u64,
+        Err(object_store::Error::NotFound { .. }) => return Ok(Some(VerifyFailure::NotFound)),
+        Err(e) => return Err(e.into()),
+    };
+    // The byte offsets of messages in compressed GRIB files refer to the decompressed stream, so
+    // the compressed file size can't be checked against the byte range.
+    let bytes = if is_bz2_compressed(path) {
+        match fetch_grib_message(store, path, msg_loc.byte_range()).await {
+            Ok(bytes) => bytes,
+            Err(e) if e.is::<BeyondEndOfFileError>() => {
+                return Ok(Some(VerifyFailure::BeyondEndOfFile { file_size }))
+            }
+            Err(e) => return Err(e),
+        }
+    } else {
+        if msg_loc.byte_range().end > file_size {
+            return Ok(Some(VerifyFailure::BeyondEndOfFile { file_size }));
+        }
+        // Only fetch the first few bytes of the message.
+        let start = usize::try_from(msg_loc.byte_offset())?;
+        let end = start + GRIB_MAGIC.len().min(usize::try_from(msg_loc.msg_length())?);
+        store.get_range(path, start..end).await?
+    };
+    Ok((!bytes.starts_with(GRIB_MAGIC)).then_some(VerifyFailure::NotGrib))
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeDelta;
+    use object_store::{memory::InMemory, PutPayload};
+
+    use crate::{manifest::KeyBuilder, ymdh_to_datetime, ForecastStep, Level};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_verify_sample() -> anyhow::Result<()> {
+        let store = InMemory::new();
+        let base_path = Path::from("gefs");
+        // A GRIB file containing two (fake) 100-byte messages:
+        let mut file = vec![0; 200];
+        file[..4].copy_from_slice(GRIB_MAGIC);
+        file[100..104].copy_from_slice(GRIB_MAGIC);
+        let grib_path = Path::from("gefs.20170101/00/gec00.t00z.pgrb2aanl");
+        store
+            .put(
+                &Path::from_iter(base_path.parts().chain(grib_path.parts())),
+                PutPayload::from(file),
+            )
+            .await?;
+
+        let mut manifest = Manifest::new(base_path);
+        let mut builder = KeyBuilder::new();
+        builder
+            .set_reference_datetime(ymdh_to_datetime(2017, 1, 1, 0))
+            .set_ensemble_member("gec00")
+            .set_forecast_step(ForecastStep::Instant(TimeDelta::zero()))
+            .set_vertical_level(Level::Isobaric(1_000));
+        for (parameter, byte_offset) in [("HGT", 0), ("TMP", 100)] {
+            let key = builder.set_parameter(parameter).build()?;
+            manifest.insert(key, &grib_path, byte_offset, 100);
+        }
+        let report = manifest.verify_sample(&store, 10, 42).await?;
+        assert_eq!(report.n_checked, 2);
+        assert!(report.is_ok());
+
+        // An entry which points past EOF, an entry which doesn't start with "GRIB", and an entry
+        // whose GRIB file doesn't exist:
+        let past_eof = builder.set_parameter("UGRD").build()?;
+        manifest.insert(past_eof.clone(), &grib_path, 150, 100);
+        let not_grib = builder.set_parameter("VGRD").build()?;
+        manifest.insert(not_grib.clone(), &grib_path, 50, 50);
+        let not_found = builder.set_parameter("RH").build()?;
+        manifest.insert(not_found.clone(), &Path::from("foo.grib2"), 0, 100);
+
+        let report = manifest.verify_sample(&store, 10, 42).await?;
+        assert_eq!(report.n_checked, 5);
+        let mut failures: Vec<_> = report
+            .failed
+            .iter()
+            .map(|f| (f.key.parameter(), f.failure.clone()))
+            .collect();
+        failures.sort_by_key(|(parameter, _)| *parameter);
+        assert_eq!(
+            failures,
+            [
+                ("RH", VerifyFailure::NotFound),
+                ("UGRD", VerifyFailure::BeyondEndOfFile { file_size: 200 }),
+                ("VGRD", VerifyFailure::NotGrib),
+            ]
+        );
+
+        // The same seed samples the same messages:
+        let sample_keys = |report: VerifyReport| -> Vec<Key> {
+            report.failed.into_iter().map(|f| f.key).collect()
+        };
+        let a = sample_keys(manifest.verify_sample(&store, 3, 7).await?);
+        let b = sample_keys(manifest.verify_sample(&store, 3, 7).await?);
+        assert_eq!(a, b);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_verify_sample_bz2() -> anyhow::Result<()> {
+        let compressed: &[u8] = include_bytes!("datasets/icon/test_message.grib2.bz2");
+        let decompressed_len = crate::fetch::decompress_bz2(compressed)?.len() as u64;
+        let store = InMemory::new();
+        let grib_path = Path::from("icon.grib2.bz2");
+        store
+            .put(&grib_path, PutPayload::from(compressed.to_vec()))
+            .await?;
+        let corrupt_path = Path::from("corrupt.grib2.bz2");
+        store
+            .put(&corrupt_path, PutPayload::from(b"GRIB".to_vec()))
+            .await?;
+
+        let mut manifest = Manifest::new(Path::default());
+        let mut builder = KeyBuilder::new();
+        builder
+            .set_reference_datetime(ymdh_to_datetime(2017, 1, 1, 0))
+            .set_ensemble_member("")
+            .set_forecast_step(ForecastStep::Instant(TimeDelta::zero()))
+            .set_vertical_level(Level::Surface);
+        manifest.insert(
+            builder.set_parameter("TMP").build()?,
+            &grib_path,
+            0,
+            decompressed_len,
+        );
+        let past_eof = builder.set_parameter("UGRD").build()?;
+        manifest.insert(past_eof.clone(), &grib_path, 1, decompressed_len);
+        let report = manifest.verify_sample(&store, 10, 42).await?;
+        assert_eq!(report.n_checked, 2);
+        assert_eq!(report.failed.len(), 1);
+        assert_eq!(report.failed[0].key, past_eof);
+        assert_eq!(
+            report.failed[0].failure,
+            VerifyFailure::BeyondEndOfFile {
+                file_size: compressed.len() as u64
+            }
+        );
+
+        // Errors other than the byte range being beyond the end of the file are returned:
+        let mut manifest = Manifest::new(Path::default());
+        manifest.insert(builder.build()?, &corrupt_path, 0, 4);
+        assert!(manifest.verify_sample(&store, 10, 42).await.is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,640 @@
+//! Present a `Manifest` as a read-only [Zarr v3](https://zarr-specs.readthedocs.io/en/latest/v3/core/v3.0.html)
+//! store, so that tools like `xarray` and `zarr-python` can open a `hypergrib` dataset.
+//!
+//! The store is a group which contains one array per parameter (e.g. `TMP`). Each array has the
+//! dimensions `DIMENSION_NAMES`, and each chunk is one GRIB message (a single horizontal plane).
+//! So, for example, the chunk key `TMP/c/0/1/2/3/0/0/0` is the GRIB message for the 0th
+//! reference datetime, the 1st ensemble member, the 2nd forecast step, the 3rd vertical level, and
+//! the 0th statistic.
+//!
+//! The store also contains a one-dimensional coordinate array for each of the `reference_datetime`,
+//! `ensemble_member`, `forecast_step`, `vertical_level` and `statistic` dimensions, so `xarray` can
+//! label the axes. Each coordinate array is stored as a single chunk (e.g. `forecast_step/c/0`).
+//!
+//! An instantaneous forecast step and an accumulation which ends at the same step (e.g. 6 hours,
+//! and 0-6 hours) are different labels, so the `forecast_step` coordinate array holds a unique
+//! string label for each step (see `forecast_step_label`). The start and end of each step (in
+//! seconds) are in the `forecast_step_start` and `forecast_step_end` coordinate arrays, which are
+//! also indexed by the `forecast_step` dimension.
+//!
+//! The `statistic` dimension has a single empty label for deterministic datasets. Probabilistic
+//! datasets (e.g. percentiles) have one label per statistic (see `statistic_coord_array`).
+//!
+//! This module only generates the metadata and coordinate arrays, and resolves chunk keys to
+//! `MessageLocation`s. Fetching and decoding the GRIB message is left to the caller.
+
+use std::collections::BTreeMap;
+
+use anyhow::Context;
+use chrono::TimeDelta;
+use serde_json::json;
+
+use crate::{
+    manifest::{KeyBuilder, Manifest, MessageLocation},
+    CoordLabels, ForecastStep, Statistic,
+};
+
+/// The dimension names of each array, in order.
+pub const DIMENSION_NAMES: [&str; 7] = [
+    "reference_datetime",
+    "ensemble_member",
+    "forecast_step",
+    "vertical_level",
+    "statistic",
+    "y",
+    "x",
+];
+
+/// The CF units of the `reference_datetime` coordinate array.
+pub const REFERENCE_DATETIME_UNITS: &str = "nanoseconds since 1970-01-01T00:00:00Z";
+
+/// A one-dimensional Zarr array which is stored as a single chunk.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CoordArray {
+    /// The contents of the array's `zarr.json`.
+    pub metadata: serde_json::Value,
+    /// The encoded contents of the array's only chunk.
+    pub chunk: Vec<u8>,
+}
+
+impl CoordArray {
+    fn new(
+        dimension_name: &str,
+        len: usize,
+        data_type: serde_json::Value,
+        fill_value: serde_json::Value,
+        bytes_codec: serde_json::Value,
+        attributes: serde_json::Value,
+        chunk: Vec<u8>,
+    ) -> Self {
+        // Zarr requires each dimension of the chunk shape to be at least 1.
+        let chunk_len = len.max(1);
+        let metadata = json!({
+            "zarr_format": 3,
+            "node_type": "array",
+            "shape": [len],
+            "data_type": data_type,
+            "chunk_grid": {
+                "name": "regular",
+                "configuration": {"chunk_shape": [chunk_len]},
+            },
+            "chunk_key_encoding": {
+                "name": "default",
+                "configuration": {"separator": "/"},
+            },
+            "fill_value": fill_value,
+            "codecs": [bytes_codec],
+            "attributes": attributes,
+            "dimension_names": [dimension_name],
+        });
+        Self { metadata, chunk }
+    }
+
+    fn int64(dimension_name: &str, values: &[i64], attributes: serde_json::Value) -> Self {
+        let chunk = values.iter().flat_map(|v| v.to_le_bytes()).collect();
+        Self::new(
+            dimension_name,
+            values.len(),
+            json!("int64"),
+            json!(0),
+            json!({"name": "bytes", "configuration": {"endian": "little"}}),
+            attributes,
+            chunk,
+        )
+    }
+}
+
+/// The reference datetimes, as `int64` nanoseconds since the Unix epoch. This is compatible with
+/// numpy's `datetime64[ns]`, and the CF `units` attribute lets `xarray` decode the values.
+pub fn reference_datetime_coord_array(labels: &CoordLabels) -> anyhow::Result<CoordArray> {
+    let values = labels
+        .reference_datetime()
+        .iter()
+        .map(|dt| {
+            dt.timestamp_nanos_opt()
+                .with_context(|| format!("{dt} can't be represented as datetime64[ns]"))
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    Ok(CoordArray::int64(
+        "reference_datetime",
+        &values,
+        json!({
+            "units": REFERENCE_DATETIME_UNITS,
+            "calendar": "proleptic_gregorian",
+        }),
+    ))
+}
+
+/// The names of the coordinate arrays which hold the start and end of each forecast step.
+pub const FORECAST_STEP_BOUNDS: [&str; 2] = ["forecast_step_start", "forecast_step_end"];
+
+/// A unique label for `step`, in seconds. For example, `ForecastStep::Instant(6 hours)` is `21600`
+/// and `ForecastStep::Range { start: 0 hours, end: 6 hours }` is `0-21600`.
+pub fn forecast_step_label(step: &ForecastStep) -> String {
+    match step {
+        ForecastStep::Instant(step) => step.num_seconds().to_string(),
+        ForecastStep::Range { start, end } => {
+            format!("{}-{}", start.num_seconds(), end.num_seconds())
+        }
+    }
+}
+
+/// The forecast steps, as unique string labels (see `forecast_step_label`), in the same order as
+/// `labels.forecast_step()`.
+pub fn forecast_step_coord_array(labels: &CoordLabels) -> CoordArray {
+    let step_labels: Vec<String> = labels
+        .forecast_step()
+        .iter()
+        .map(forecast_step_label)
+        .collect();
+    string_coord_array("forecast_step", &step_labels)
+}
+
+/// The start and end of each forecast step (in the order of `FORECAST_STEP_BOUNDS`), as `int64`
+/// seconds. The start and end of a `ForecastStep::Instant` are the same. The end is the step at
+/// which the field is valid.
+pub fn forecast_step_bounds_coord_arrays(labels: &CoordLabels) -> [CoordArray; 2] {
+    let seconds = |f: fn(&ForecastStep) -> TimeDelta| -> Vec<i64> {
+        labels
+            .forecast_step()
+            .iter()
+            .map(|step| f(step).num_seconds())
+            .collect()
+    };
+    [
+        CoordArray::int64(
+            "forecast_step",
+            &seconds(ForecastStep::start),
+            json!({"units": "seconds"}),
+        ),
+        CoordArray::int64(
+            "forecast_step",
+            &seconds(ForecastStep::end),
+            json!({"units": "seconds"}),
+        ),
+    ]
+}
+
+/// The statistics, formatted in the same way as `.idx` files (e.g. "10% level"). Deterministic
+/// fields (whose statistic is `None`) have an empty label.
+pub fn statistic_coord_array(labels: &CoordLabels) -> CoordArray {
+    let statistic_labels: Vec<String> = labels
+        .statistic()
+        .iter()
+        .map(|statistic| {
+            statistic
+                .as_ref()
+                .map(Statistic::to_string)
+                .unwrap_or_default()
+        })
+        .collect();
+    string_coord_array("statistic", &statistic_labels)
+}
+
+/// Labels (e.g. `labels.parameter()` or `labels.vertical_level()`) as fixed-length UTF-8 strings.
+/// Each string is encoded as UTF-8 and padded with null bytes to the length of the longest string.
+/// This is the `null_terminated_bytes` Zarr data type (numpy's `S` dtype).
+pub fn string_coord_array<T: ToString>(dimension_name: &str, labels: &[T]) -> CoordArray {
+    let strings: Vec<String> = labels.iter().map(ToString::to_string).collect();
+    // Zarr requires `length_bytes` to be at least 1.
+    let length_bytes = strings.iter().map(String::len).max().unwrap_or(0).max(1);
+    let mut chunk = Vec::with_capacity(strings.len() * length_bytes);
+    for s in &strings {
+        chunk.extend_from_slice(s.as_bytes());
+        chunk.resize(chunk.len() + length_bytes - s.len(), 0);
+    }
+    CoordArray::new(
+        dimension_name,
+        strings.len(),
+        json!({"name": "null_terminated_bytes", "configuration": {"length_bytes": length_bytes}}),
+        json!(""),
+        json!({"name": "bytes"}),
+        json!({}),
+        chunk,
+    )
+}
+
+pub struct ZarrStoreView<'a> {
+    manifest: &'a Manifest,
+    coord_labels: CoordLabels,
+    /// The coordinate arrays, keyed by dimension name.
+    coord_arrays: BTreeMap<&'static str, CoordArray>,
+    /// The number of pixels in the `y` and `x` dimensions of each GRIB message.
+    // TODO: Get the horizontal grid from the GRIB messages. See issue #25.
+    grid_shape: [usize; 2],
+}
+
+impl<'a> ZarrStoreView<'a> {
+    pub fn new(manifest: &'a Manifest, grid_shape: [usize; 2]) -> anyhow::Result<Self> {
+        let coord_labels = manifest.coord_labels();
+        let [forecast_step_start, forecast_step_end] =
+            forecast_step_bounds_coord_arrays(&coord_labels);
+        let coord_arrays = BTreeMap::from([
+            (
+                "reference_datetime",
+                reference_datetime_coord_array(&coord_labels)?,
+            ),
+            (
+                "ensemble_member",
+                string_coord_array("ensemble_member", coord_labels.ensemble_member()),
+            ),
+            ("forecast_step", forecast_step_coord_array(&coord_labels)),
+            (FORECAST_STEP_BOUNDS[0], forecast_step_start),
+            (FORECAST_STEP_BOUNDS[1], forecast_step_end),
+            (
+                "vertical_level",
+                string_coord_array("vertical_level", coord_labels.vertical_level()),
+            ),
+            ("statistic", statistic_coord_array(&coord_labels)),
+        ]);
+        Ok(Self {
+            manifest,
+            coord_labels,
+            coord_arrays,
+            grid_shape,
+        })
+    }
+
+    pub fn coord_labels(&self) -> &CoordLabels {
+        &self.coord_labels
+    }
+
+    /// The shape of each parameter's array, in the order of `DIMENSION_NAMES`.
+    pub fn shape(&self) -> [usize; 7] {
+        [
+            self.coord_labels.reference_datetime().len(),
+            self.coord_labels.ensemble_member().len(),
+            self.coord_labels.forecast_step().len(),
+            self.coord_labels.vertical_level().len(),
+            self.coord_labels.statistic().len(),
+            self.grid_shape[0],
+            self.grid_shape[1],
+        ]
+    }
+
+    /// Get the metadata document for `key`. The valid keys are `zarr.json` (for the root group),
+    /// `<parameter>/zarr.json` (for each parameter's array), and `<dimension>/zarr.json` (for each
+    /// coordinate array). Returns `None` for all other keys.
+    pub fn get_metadata(&self, key: &str) -> Option<String> {
+        if key == "zarr.json" {
+            return Some(self.group_metadata().to_string());
+        }
+        let parameter = key.strip_suffix("/zarr.json")?;
+        if let Some(coord_array) = self.coord_arrays.get(parameter) {
+            return Some(coord_array.metadata.to_string());
+        }
+        self.coord_labels
+            .parameter()
+            .index_of(&parameter.to_string())
+            .map(|_| self.array_metadata().to_string())
+    }
+
+    /// Get the encoded chunk of a coordinate array (e.g. `forecast_step/c/0`). Returns `None` if
+    /// `chunk_key` isn't the chunk of a coordinate array.
+    pub fn get_coord_chunk(&self, chunk_key: &str) -> Option<&[u8]> {
+        let dimension_name = chunk_key.strip_suffix("/c/0")?;
+        self.coord_arrays
+            .get(dimension_name)
+            .map(|coord_array| coord_array.chunk.as_slice())
+    }
+
+    fn group_metadata(&self) -> serde_json::Value {
+        json!({
+            "zarr_format": 3,
+            "node_type": "group",
+            "attributes": {},
+        })
+    }
+
+    fn array_metadata(&self) -> serde_json::Value {
+        let [.., y, x] = self.shape();
+        json!({
+            "zarr_format": 3,
+            "node_type": "array",
+            "shape": self.shape(),
+            "data_type": "float32",
+            "chunk_grid": {
+                "name": "regular",
+                "configuration": {"chunk_shape": [1, 1, 1, 1, 1, y, x]},
+            },
+            "chunk_key_encoding": {
+                "name": "default",
+                "configuration": {"separator": "/"},
+            },
+            "fill_value": "NaN",
+            "codecs": [{"name": "bytes", "configuration": {"endian": "little"}}],
+            // Tell `xarray` about the coordinate arrays which don't share a dimension's name.
+            "attributes": {"coordinates": FORECAST_STEP_BOUNDS.join(" ")},
+            "dimension_names": DIMENSION_NAMES,
+        })
+    }
+
+    /// Resolve a chunk key (e.g. `TMP/c/0/1/2/3/0/0/0`) to the location of its GRIB message.
+    ///
+    /// Returns `Ok(None)` if the chunk key is valid but there's no GRIB message for that chunk
+    /// (in which case the chunk should be filled with NaNs). Returns an error if the chunk key
+    /// is invalid.
+    pub fn resolve_chunk_key(&self, chunk_key: &str) -> anyhow::Result<Option<&MessageLocation>> {
+        let error = || format!("Invalid chunk key '{chunk_key}'");
+        let (parameter, indices) = chunk_key.split_once("/c/").with_context(error)?;
+        if self
+            .coord_labels
+            .parameter()
+            .index_of(&parameter.to_string())
+            .is_none()
+        {
+            return Err(anyhow::format_err!("Unknown parameter '{parameter}'")).with_context(error);
+        }
+        let indices = indices
+            .split('/')
+            .map(|i| i.parse::<usize>())
+            .collect::<Result<Vec<_>, _>>()
+            .with_context(error)?;
+        let [i_ref, i_ens, i_step, i_level, i_stat, 0, 0] = indices[..] else {
+            return Err(anyhow::format_err!(
+                "Expected {} chunk indices, and the last two must be 0",
+                DIMENSION_NAMES.len()
+            ))
+            .with_context(error);
+        };
+        let labels = &self.coord_labels;
+        let out_of_bounds = || anyhow::format_err!("Chunk index out of bounds in '{chunk_key}'");
+        let key = KeyBuilder::new()
+            .set_reference_datetime(
+                *labels
+                    .reference_datetime()
+                    .get(i_ref)
+                    .ok_or_else(out_of_bounds)?,
+            )
+            .set_ensemble_member(
+                labels
+                    .ensemble_member()
+                    .get(i_ens)
+                    .ok_or_else(out_of_bounds)?
+                    .clone(),
+            )
+            .set_forecast_step(
+                *labels
+                    .forecast_step()
+                    .get(i_step)
+                    .ok_or_else(out_of_bounds)?,
+            )
+            .set_parameter(parameter)
+            .set_vertical_level(
+                labels
+                    .vertical_level()
+                    .get(i_level)
+                    .ok_or_else(out_of_bounds)?
+                    .clone(),
+            )
+            .set_statistic(
+                labels
+                    .statistic()
+                    .get(i_stat)
+                    .ok_or_else(out_of_bounds)?
+                    .clone(),
+            )
+            .build()?;
+        Ok(self.manifest.get(&key))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{ymdh_to_datetime, Level};
+
+    use super::*;
+
+    fn create_manifest() -> Manifest {
+        let mut manifest = Manifest::new(object_store::path::Path::from("gefs"));
+        let key = KeyBuilder::new()
+            .set_reference_datetime(ymdh_to_datetime(2017, 1, 1, 0))
+            .set_ensemble_member("gec00")
+            .set_forecast_step(ForecastStep::Instant(TimeDelta::zero()))
+            .set_parameter("HGT")
+            .set_vertical_level(Level::Isobaric(1_000))
+            .build()
+            .unwrap();
+        let anl_path = object_store::path::Path::from("gefs.20170101/00/gec00.t00z.pgrb2aanl");
+        let f006_path = object_store::path::Path::from("gefs.20170101/00/gec00.t00z.pgrb2af006");
+        manifest.insert(key.clone(), &anl_path, 0, 50487);
+        let tmp_key = KeyBuilder::from(key).set_parameter("TMP").build().unwrap();
+        manifest.insert(tmp_key.clone(), &anl_path, 50487, 20166);
+        manifest.insert(
+            KeyBuilder::from(tmp_key)
+                .set_forecast_step(ForecastStep::Instant(TimeDelta::hours(6)))
+                .build()
+                .unwrap(),
+            &f006_path,
+            100,
+            20000,
+        );
+        manifest
+    }
+
+    #[test]
+    fn test_metadata() -> anyhow::Result<()> {
+        let manifest = create_manifest();
+        let view = ZarrStoreView::new(&manifest, [181, 360])?;
+
+        let group: serde_json::Value =
+            serde_json::from_str(&view.get_metadata("zarr.json").unwrap())?;
+        assert_eq!(group["node_type"], "group");
+
+        let array: serde_json::Value =
+            serde_json::from_str(&view.get_metadata("TMP/zarr.json").unwrap())?;
+        assert_eq!(array["zarr_format"], 3);
+        assert_eq!(array["node_type"], "array");
+        assert_eq!(array["shape"], json!([1, 1, 2, 1, 1, 181, 360]));
+        assert_eq!(
+            array["chunk_grid"]["configuration"]["chunk_shape"],
+            json!([1, 1, 1, 1, 1, 181, 360])
+        );
+        assert_eq!(array["data_type"], "float32");
+        assert_eq!(array["dimension_names"], json!(DIMENSION_NAMES));
+
+        let forecast_step: serde_json::Value =
+            serde_json::from_str(&view.get_metadata("forecast_step/zarr.json").unwrap())?;
+        assert_eq!(forecast_step["shape"], json!([2]));
+        assert_eq!(forecast_step["dimension_names"], json!(["forecast_step"]));
+        assert_eq!(
+            array["attributes"]["coordinates"],
+            "forecast_step_start forecast_step_end"
+        );
+        for name in FORECAST_STEP_BOUNDS {
+            let bounds: serde_json::Value =
+                serde_json::from_str(&view.get_metadata(&format!("{name}/zarr.json")).unwrap())?;
+            assert_eq!(bounds["shape"], json!([2]));
+            assert_eq!(bounds["dimension_names"], json!(["forecast_step"]));
+            assert!(view.get_coord_chunk(&format!("{name}/c/0")).is_some());
+        }
+
+        assert!(view.get_metadata("UGRD/zarr.json").is_none());
+        assert!(view.get_metadata("TMP/c/0/0/0/0/0/0/0").is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_resolve_chunk_key() -> anyhow::Result<()> {
+        let manifest = create_manifest();
+        let view = ZarrStoreView::new(&manifest, [181, 360])?;
+
+        let msg_loc = view.resolve_chunk_key("TMP/c/0/0/1/0/0/0/0")?.unwrap();
+        assert_eq!(
+            msg_loc.path(),
+            &object_store::path::Path::from("gefs.20170101/00/gec00.t00z.pgrb2af006")
+        );
+        assert_eq!(msg_loc.byte_range(), 100..20100);
+
+        let msg_loc = view.resolve_chunk_key("TMP/c/0/0/0/0/0/0/0")?.unwrap();
+        assert_eq!(msg_loc.byte_range(), 50487..70653);
+
+        // There's no HGT message for the second forecast step:
+        assert!(view.resolve_chunk_key("HGT/c/0/0/1/0/0/0/0")?.is_none());
+
+        // Invalid chunk keys:
+        for chunk_key in [
+            "TMP/c/0/0/2/0/0/0/0", // Out of bounds.
+            "TMP/c/0/0/0/0/1/0/0", // Out of bounds (only one statistic).
+            "TMP/c/0/0/0/0/0/0",   // Too few indices.
+            "TMP/c/0/0/0/0/0/0/1", // Only one chunk in the y and x dimensions.
+            "UGRD/c/0/0/0/0/0/0/0",
+            "TMP/0/0/0/0/0/0/0",
+        ] {
+            assert!(view.resolve_chunk_key(chunk_key).is_err(), "{chunk_key}");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_statistic() -> anyhow::Result<()> {
+        let mut manifest = create_manifest();
+        let path = object_store::path::Path::from("blend.20170101/00/blend.t00z.f000");
+        let key = KeyBuilder::new()
+            .set_reference_datetime(ymdh_to_datetime(2017, 1, 1, 0))
+            .set_ensemble_member("gec00")
+            .set_forecast_step(ForecastStep::Instant(TimeDelta::zero()))
+            .set_parameter("TMP")
+            .set_vertical_level(Level::Isobaric(1_000))
+            .set_statistic(Some(Statistic::Percentile(90)))
+            .build()?;
+        manifest.insert(key, &path, 0, 100);
+        let view = ZarrStoreView::new(&manifest, [181, 360])?;
+        assert_eq!(view.shape()[4], 2);
+
+        let coord_array = statistic_coord_array(view.coord_labels());
+        assert_eq!(coord_array.metadata["shape"], json!([2]));
+        assert_eq!(coord_array.chunk, b"\0\0\0\0\0\0\0\0\090% level");
+        assert_eq!(
+            view.get_coord_chunk("statistic/c/0"),
+            Some(&coord_array.chunk[..])
+        );
+
+        // The deterministic field and the 90th percentile are different chunks:
+        let msg_loc = view.resolve_chunk_key("TMP/c/0/0/0/0/0/0/0")?.unwrap();
+        assert_eq!(msg_loc.byte_range(), 50487..70653);
+        let msg_loc = view.resolve_chunk_key("TMP/c/0/0/0/0/1/0/0")?.unwrap();
+        assert_eq!(msg_loc.path(), &path);
+        assert!(view.resolve_chunk_key("HGT/c/0/0/0/0/1/0/0")?.is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_reference_datetime_coord_array_round_trip() -> anyhow::Result<()> {
+        let datetimes = vec![
+            ymdh_to_datetime(2000, 1, 1, 0),
+            ymdh_to_datetime(2017, 1, 1, 6),
+            ymdh_to_datetime(2024, 2, 29, 18),
+        ];
+        let labels = CoordLabels::new(
+            datetimes.clone().into(),
+            Default::default(),
+            Default::default(),
+            Default::default(),
+            Default::default(),
+        );
+        let coord_array = reference_datetime_coord_array(&labels)?;
+        assert_eq!(coord_array.metadata["data_type"], "int64");
+        assert_eq!(
+            coord_array.metadata["attributes"]["units"],
+            REFERENCE_DATETIME_UNITS
+        );
+
+        // Decode the chunk, in the same way that `xarray` would:
+        let decoded: Vec<_> = coord_array
+            .chunk
+            .chunks_exact(8)
+            .map(|bytes| {
+                chrono::DateTime::from_timestamp_nanos(i64::from_le_bytes(
+                    bytes.try_into().unwrap(),
+                ))
+            })
+            .collect();
+        assert_eq!(decoded, datetimes);
+        Ok(())
+    }
+
+    #[test]
+    fn test_forecast_step_coord_array() {
+        let labels = CoordLabels::new(
+            Default::default(),
+            Default::default(),
+            vec![
+                ForecastStep::Instant(TimeDelta::zero()),
+                ForecastStep::Range {
+                    start: TimeDelta::hours(3),
+                    end: TimeDelta::hours(6),
+                },
+                ForecastStep::Range {
+                    start: TimeDelta::zero(),
+                    end: TimeDelta::hours(6),
+                },
+                ForecastStep::Instant(TimeDelta::hours(6)),
+            ]
+            .into(),
+            Default::default(),
+            Default::default(),
+        );
+
+        // Each step has a unique label, even if several steps end at 6 hours:
+        let coord_array = forecast_step_coord_array(&labels);
+        let data_type = &coord_array.metadata["data_type"];
+        assert_eq!(data_type["name"], "null_terminated_bytes");
+        let length_bytes = data_type["configuration"]["length_bytes"].as_u64().unwrap();
+        let decoded: Vec<_> = coord_array
+            .chunk
+            .chunks_exact(length_bytes as usize)
+            .map(|bytes| std::str::from_utf8(bytes).unwrap().trim_end_matches('\0'))
+            .collect();
+        assert_eq!(decoded, ["0", "0-21600", "10800-21600", "21600"]);
+
+        let decode_int64 = |coord_array: &CoordArray| -> Vec<i64> {
+            coord_array
+                .chunk
+                .chunks_exact(8)
+                .map(|bytes| i64::from_le_bytes(bytes.try_into().unwrap()))
+                .collect()
+        };
+        let [start, end] = forecast_step_bounds_coord_arrays(&labels);
+        let bounds: Vec<_> = decode_int64(&end)
+            .into_iter()
+            .zip(decode_int64(&start))
+            .collect();
+        assert_eq!(
+            bounds,
+            [(0, 0), (21_600, 0), (21_600, 10_800), (21_600, 21_600)]
+        );
+        // The (end, start) pairs are strictly increasing, so they're unique:
+        assert!(bounds.windows(2).all(|pair| pair[0] < pair[1]));
+    }
+
+    #[test]
+    fn test_string_coord_array() {
+        let coord_array = string_coord_array("parameter", &["HGT", "TMP", "UGRD"]);
+        assert_eq!(
+            coord_array.metadata["data_type"]["configuration"]["length_bytes"],
+            4
+        );
+        assert_eq!(coord_array.chunk, b"HGT\0TMP\0UGRD");
+    }
+}
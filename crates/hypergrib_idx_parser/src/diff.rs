@@ -0,0 +1,207 @@
+use std::{collections::BTreeMap, fmt};
+
+use crate::{msg_lengths, ForecastStep, IdxRecord, Level};
+
+/// The identity of a message when comparing two `.idx` files. The reference datetime and the
+/// ensemble member are deliberately ignored, so that `.idx` files from different dates or
+/// ensemble members can be compared.
+type MsgId<'a> = (&'a str, &'a Level, &'a ForecastStep);
+
+/// The location of a message within its GRIB file. The length of the final message is `None`
+/// because `.idx` files don't record it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IdxMsgLocation {
+    pub byte_offset: u64,
+    pub msg_length: Option<u64>,
+}
+
+/// The differences between two `.idx` files, `a` and `b`. Messages are identified by their
+/// parameter, vertical level, and forecast step.
+#[derive(Debug, Default, PartialEq)]
+pub struct IdxDiff<'a> {
+    pub only_in_a: Vec<&'a IdxRecord>,
+    pub only_in_b: Vec<&'a IdxRecord>,
+    /// Messages in both `a` and `b` whose byte offset or length differs.
+    pub changed: Vec<(&'a IdxRecord, IdxMsgLocation, IdxMsgLocation)>,
+}
+
+impl IdxDiff<'_> {
+    pub fn is_empty(&self) -> bool {
+        self.only_in_a.is_empty() && self.only_in_b.is_empty() && self.changed.is_empty()
+    }
+}
+
+/// Compare the records of two `.idx` files. Returns an error if the byte offsets of either
+/// `.idx` file are not strictly increasing, or if either `.idx` file contains the same message
+/// twice.
+pub fn diff_idx<'a>(a: &'a [IdxRecord], b: &'a [IdxRecord]) -> anyhow::Result<IdxDiff<'a>> {
+    let a_locations = index_records(a)?;
+    let b_locations = index_records(b)?;
+    let mut diff = IdxDiff::default();
+    for (msg_id, (record, a_location)) in &a_locations {
+        match b_locations.get(msg_id) {
+            None => diff.only_in_a.push(record),
+            Some((_, b_location)) if a_location != b_location => {
+                diff.changed.push((record, *a_location, *b_location))
+            }
+            Some(_) => (),
+        }
+    }
+    diff.only_in_b = b_locations
+        .iter()
+        .filter(|(msg_id, _)| !a_locations.contains_key(msg_id))
+        .map(|(_, (record, _))| *record)
+        .collect();
+    // Report messages in the order they appear in the `.idx` files:
+    diff.only_in_a.sort_by_key(|record| record.msg_id);
+    diff.only_in_b.sort_by_key(|record| record.msg_id);
+    diff.changed.sort_by_key(|(record, _, _)| record.msg_id);
+    Ok(diff)
+}
+
+fn index_records(
+    records: &[IdxRecord],
+) -> anyhow::Result<BTreeMap<MsgId<'_>, (&IdxRecord, IdxMsgLocation)>> {
+    let mut map = BTreeMap::new();
+    for (record, msg_length) in records.iter().zip(msg_lengths(records, None)?) {
+        let msg_id = (
+            record.parameter.as_str(),
+            &record.vertical_level,
+            &record.forecast_step,
+        );
+        let location = IdxMsgLocation {
+            byte_offset: record.byte_offset,
+            msg_length,
+        };
+        if map.insert(msg_id, (record, location)).is_some() {
+            return Err(anyhow::format_err!(
+                "The .idx file contains the message {msg_id:?} more than once"
+            ));
+        }
+    }
+    Ok(map)
+}
+
+fn describe(record: &IdxRecord) -> String {
+    let step = match record.forecast_step {
+        ForecastStep::Instant(step) => format!("{} hour", step.num_hours()),
+        ForecastStep::Range { start, end } => {
+            format!("{}-{} hour", start.num_hours(), end.num_hours())
+        }
+    };
+    format!("{}:{}:{step}", record.parameter, record.vertical_level)
+}
+
+impl fmt::Display for IdxMsgLocation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.msg_length {
+            Some(msg_length) => write!(f, "offset {}, length {msg_length}", self.byte_offset),
+            None => write!(f, "offset {}, length unknown", self.byte_offset),
+        }
+    }
+}
+
+impl fmt::Display for IdxDiff<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{} messages only in A:", self.only_in_a.len())?;
+        for record in &self.only_in_a {
+            writeln!(f, "- {}", describe(record))?;
+        }
+        writeln!(f, "{} messages only in B:", self.only_in_b.len())?;
+        for record in &self.only_in_b {
+            writeln!(f, "- {}", describe(record))?;
+        }
+        write!(f, "{} messages whose location changed:", self.changed.len())?;
+        for (record, a_location, b_location) in &self.changed {
+            write!(f, "\n- {}: {a_location} -> {b_location}", describe(record))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parse_idx;
+
+    use super::*;
+
+    #[test]
+    fn test_diff_idx() -> anyhow::Result<()> {
+        let a = parse_idx(
+            "\
+1:0:d=2017010100:HGT:10 mb:anl:ENS=low-res ctl
+2:50487:d=2017010100:TMP:10 mb:anl:ENS=low-res ctl
+3:70653:d=2017010100:RH:10 mb:anl:ENS=low-res ctl
+4:81565:d=2017010100:UGRD:10 mb:anl:ENS=low-res ctl
+"
+            .as_bytes(),
+        )?;
+        let b = parse_idx(
+            "\
+1:0:d=2020093000:HGT:10 mb:anl:ENS=low-res ctl
+2:50487:d=2020093000:TMP:10 mb:anl:ENS=low-res ctl
+3:70000:d=2020093000:UGRD:10 mb:anl:ENS=low-res ctl
+4:90000:d=2020093000:VGRD:10 mb:anl:ENS=low-res ctl
+"
+            .as_bytes(),
+        )?;
+        assert!(diff_idx(&a, &a)?.is_empty());
+
+        let diff = diff_idx(&a, &b)?;
+        fn params<'a>(records: &[&'a IdxRecord]) -> Vec<&'a str> {
+            records.iter().map(|r| r.parameter.as_str()).collect()
+        }
+        assert_eq!(params(&diff.only_in_a), ["RH"]);
+        assert_eq!(params(&diff.only_in_b), ["VGRD"]);
+        // TMP is in the same place, but its length changed. UGRD moved:
+        let changed: Vec<_> = diff
+            .changed
+            .iter()
+            .map(|(r, a, b)| (r.parameter.as_str(), *a, *b))
+            .collect();
+        assert_eq!(
+            changed,
+            [
+                (
+                    "TMP",
+                    IdxMsgLocation {
+                        byte_offset: 50487,
+                        msg_length: Some(20166)
+                    },
+                    IdxMsgLocation {
+                        byte_offset: 50487,
+                        msg_length: Some(19513)
+                    }
+                ),
+                (
+                    "UGRD",
+                    IdxMsgLocation {
+                        byte_offset: 81565,
+                        msg_length: None
+                    },
+                    IdxMsgLocation {
+                        byte_offset: 70000,
+                        msg_length: Some(20000)
+                    }
+                ),
+            ]
+        );
+        let s = diff.to_string();
+        assert!(s.contains("1 messages only in A:\n- RH:10 mb:0 hour\n"));
+        assert!(s.contains("2 messages whose location changed:"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_diff_idx_with_duplicate_message() -> anyhow::Result<()> {
+        let a = parse_idx(
+            "\
+1:0:d=2017010100:HGT:10 mb:anl:ENS=low-res ctl
+2:50487:d=2017010100:HGT:10 mb:anl:ENS=low-res ctl
+"
+            .as_bytes(),
+        )?;
+        assert!(diff_idx(&a, &a).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,63 @@
+/// Why an `.idx` file couldn't be parsed. Each variant carries the (1-based) line number of the
+/// problematic line. All variants except `Unreadable` also carry the raw content of that line, so
+/// callers can decide (per kind of error) whether to skip the line or abort.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum IdxParseError {
+    /// The line has fewer than the 7 columns of an `.idx` line.
+    #[error("Line {line} has {n_columns} columns, but expected at least 7: '{content}'")]
+    WrongColumnCount {
+        line: u64,
+        content: String,
+        n_columns: usize,
+    },
+
+    /// The first column (the message number) isn't an unsigned integer.
+    #[error("Invalid msg_id on line {line} ({reason}): '{content}'")]
+    BadMsgId {
+        line: u64,
+        content: String,
+        reason: String,
+    },
+
+    /// The second column (the byte offset of the message) isn't an unsigned integer.
+    #[error("Invalid byte offset on line {line} ({reason}): '{content}'")]
+    BadByteOffset {
+        line: u64,
+        content: String,
+        reason: String,
+    },
+
+    /// The third column isn't a valid reference datetime of the form `d=YYYYMMDDHH`.
+    #[error("Invalid reference datetime on line {line} ({reason}): '{content}'")]
+    BadDate {
+        line: u64,
+        content: String,
+        reason: String,
+    },
+
+    /// The sixth column isn't a forecast step which `ForecastStep` can parse.
+    #[error("Invalid forecast step on line {line} ({reason}): '{content}'")]
+    BadStep {
+        line: u64,
+        content: String,
+        reason: String,
+    },
+
+    /// The line couldn't be read at all (e.g. because it isn't valid UTF-8).
+    #[error("Failed to read line {line}: {reason}")]
+    Unreadable { line: u64, reason: String },
+}
+
+impl IdxParseError {
+    /// The (1-based) line number of the line which couldn't be parsed.
+    pub fn line(&self) -> u64 {
+        match *self {
+            Self::WrongColumnCount { line, .. }
+            | Self::BadMsgId { line, .. }
+            | Self::BadByteOffset { line, .. }
+            | Self::BadDate { line, .. }
+            | Self::BadStep { line, .. }
+            | Self::Unreadable { line, .. } => line,
+        }
+    }
+}
@@ -0,0 +1,158 @@
+use std::{cmp::Ordering, str::FromStr};
+
+use chrono::TimeDelta;
+
+/// The forecast step of a GRIB message.
+///
+/// Most fields are valid at an instant. But accumulated and averaged fields (e.g. precipitation
+/// and radiation) are defined over a window of time. For example, a 0-6 hour accumulation and
+/// a 3-6 hour accumulation are valid at the same time, but are different fields.
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug)]
+pub enum ForecastStep {
+    Instant(TimeDelta),
+    Range { start: TimeDelta, end: TimeDelta },
+}
+
+impl ForecastStep {
+    /// The start of the window. For `Instant`, this is the same as `end`.
+    pub fn start(&self) -> TimeDelta {
+        match *self {
+            Self::Instant(step) => step,
+            Self::Range { start, .. } => start,
+        }
+    }
+
+    /// The time (relative to the reference datetime) at which the field is valid. For `Range`,
+    /// this is the end of the window.
+    pub fn end(&self) -> TimeDelta {
+        match *self {
+            Self::Instant(step) => step,
+            Self::Range { end, .. } => end,
+        }
+    }
+
+    fn sort_key(&self) -> (TimeDelta, TimeDelta, bool) {
+        (self.end(), self.start(), matches!(self, Self::Range { .. }))
+    }
+}
+
+impl From<TimeDelta> for ForecastStep {
+    fn from(step: TimeDelta) -> Self {
+        Self::Instant(step)
+    }
+}
+
+/// Sort by the end of the window, and then by the start of the window, so that
+/// `Instant(6 hours)` sorts next to `Range { start: 0 hours, end: 6 hours }`.
+impl Ord for ForecastStep {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.sort_key().cmp(&other.sort_key())
+    }
+}
+
+impl PartialOrd for ForecastStep {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Parse the forecast step strings used in `.idx` files. For example:
+/// - "anl"
+/// - "6 hour fcst"
+/// - "0-6 hour acc fcst" (the same applies to "ave", "max", and "min")
+impl FromStr for ForecastStep {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        if s == "anl" {
+            return Ok(Self::Instant(TimeDelta::zero()));
+        }
+        let words: Vec<&str> = s.split(' ').collect();
+        let parse_hours = |hours: &str| -> anyhow::Result<TimeDelta> {
+            let hours: i64 = hours
+                .parse()
+                .map_err(|e| anyhow::format_err!("Failed to parse hours in '{s}': {e}"))?;
+            Ok(TimeDelta::hours(hours))
+        };
+        match words.as_slice() {
+            [hours, "hour", "fcst"] if !hours.contains('-') => {
+                Ok(Self::Instant(parse_hours(hours)?))
+            }
+            [hours, "hour", "acc" | "ave" | "max" | "min", "fcst"] => match hours.split_once('-') {
+                Some((start, end)) => Ok(Self::Range {
+                    start: parse_hours(start)?,
+                    end: parse_hours(end)?,
+                }),
+                None => Err(anyhow::format_err!("Expected a range of hours in '{s}'")),
+            },
+            _ => Err(anyhow::format_err!("Failed to parse forecast step: '{s}'")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_str() -> anyhow::Result<()> {
+        assert_eq!(
+            "anl".parse::<ForecastStep>()?,
+            ForecastStep::Instant(TimeDelta::zero())
+        );
+        assert_eq!(
+            "6 hour fcst".parse::<ForecastStep>()?,
+            ForecastStep::Instant(TimeDelta::hours(6))
+        );
+        assert_eq!(
+            "0-6 hour acc fcst".parse::<ForecastStep>()?,
+            ForecastStep::Range {
+                start: TimeDelta::zero(),
+                end: TimeDelta::hours(6)
+            }
+        );
+        assert_eq!(
+            "6-12 hour ave fcst".parse::<ForecastStep>()?,
+            ForecastStep::Range {
+                start: TimeDelta::hours(6),
+                end: TimeDelta::hours(12)
+            }
+        );
+        assert!("6-12 hour fcst".parse::<ForecastStep>().is_err());
+        assert!("6 hour acc fcst".parse::<ForecastStep>().is_err());
+        assert!("foo".parse::<ForecastStep>().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_ord() {
+        let mut steps = vec![
+            ForecastStep::Range {
+                start: TimeDelta::hours(3),
+                end: TimeDelta::hours(6),
+            },
+            ForecastStep::Instant(TimeDelta::hours(12)),
+            ForecastStep::Range {
+                start: TimeDelta::zero(),
+                end: TimeDelta::hours(6),
+            },
+            ForecastStep::Instant(TimeDelta::hours(6)),
+        ];
+        steps.sort();
+        assert_eq!(
+            steps,
+            vec![
+                ForecastStep::Range {
+                    start: TimeDelta::zero(),
+                    end: TimeDelta::hours(6),
+                },
+                ForecastStep::Range {
+                    start: TimeDelta::hours(3),
+                    end: TimeDelta::hours(6),
+                },
+                ForecastStep::Instant(TimeDelta::hours(6)),
+                ForecastStep::Instant(TimeDelta::hours(12)),
+            ]
+        );
+    }
+}
@@ -0,0 +1,329 @@
+use std::{cmp::Ordering, fmt, str::FromStr};
+
+/// The vertical level of a GRIB message, parsed from the level strings used in `.idx` files.
+///
+/// `Level` is ordered by physical position, from the bottom up: Depths below ground (deepest
+/// first), then the surface, then heights above ground (lowest first), then isobaric levels
+/// (highest pressure first). Levels in terrain-following or model coordinates can't be compared
+/// with the levels above, so they sort afterwards: Sigma levels (highest sigma first), then hybrid
+/// levels (in order of level number), then potential vorticity surfaces (lowest PV first). All
+/// `Other` levels sort last, in lexicographic order.
+///
+/// Parsing never fails: Strings which aren't recognised become `Level::Other`. And `Display`
+/// reproduces the original string, so `Level` round-trips to and from the `.idx` string.
+#[derive(PartialEq, Eq, Hash, Clone, Debug)]
+pub enum Level {
+    /// e.g. "0.1-0.4 m below ground". In centimetres, so that the depths can be represented
+    /// exactly.
+    DepthBelowGround { top_cm: u32, bottom_cm: u32 },
+
+    /// "surface"
+    Surface,
+
+    /// e.g. "2 m above ground". In metres.
+    HeightAboveGround(u32),
+
+    /// e.g. "10 mb". In pascals, so that fractional millibars (e.g. "0.4 mb") can be represented
+    /// exactly.
+    Isobaric(u32),
+
+    /// e.g. "0.995 sigma level". In units of 1e-4, so that sigma (which is between 0 and 1) can
+    /// be represented exactly.
+    Sigma(u32),
+
+    /// e.g. "1 hybrid level". The model level number.
+    Hybrid(u32),
+
+    /// e.g. "2 PVU surface". In units of 1e-3 PVU.
+    PotentialVorticity(u32),
+
+    /// Any other level, e.g. "entire atmosphere" or "mean sea level".
+    // TODO: Represent more levels using `gribberish::templates::product::tables::FixedSurfaceType`.
+    Other(String),
+}
+
+impl Level {
+    /// The position of each variant in the physical ordering.
+    fn rank(&self) -> u8 {
+        match self {
+            Self::DepthBelowGround { .. } => 0,
+            Self::Surface => 1,
+            Self::HeightAboveGround(_) => 2,
+            Self::Isobaric(_) => 3,
+            Self::Sigma(_) => 4,
+            Self::Hybrid(_) => 5,
+            Self::PotentialVorticity(_) => 6,
+            Self::Other(_) => 7,
+        }
+    }
+
+    /// The type of fixed surface, as defined in WMO GRIB2 Code Table 4.5 (and by
+    /// `gribberish::templates::product::tables::FixedSurfaceType`). Returns `None` for
+    /// `Level::Other`.
+    pub fn fixed_surface_type(&self) -> Option<u8> {
+        match self {
+            Self::Surface => Some(1),
+            Self::Isobaric(_) => Some(100),
+            Self::HeightAboveGround(_) => Some(103),
+            Self::Sigma(_) => Some(104),
+            Self::Hybrid(_) => Some(105),
+            Self::DepthBelowGround { .. } => Some(106),
+            Self::PotentialVorticity(_) => Some(109),
+            Self::Other(_) => None,
+        }
+    }
+
+    fn try_from_physical_level(s: &str) -> Option<Self> {
+        if s == "surface" {
+            return Some(Self::Surface);
+        }
+        if let Some(pressure) = s.strip_suffix(" mb") {
+            return parse_scaled(pressure, 100).map(Self::Isobaric);
+        }
+        if let Some(height) = s.strip_suffix(" m above ground") {
+            return parse_scaled(height, 1).map(Self::HeightAboveGround);
+        }
+        if let Some(sigma) = s.strip_suffix(" sigma level") {
+            return parse_scaled(sigma, 10_000).map(Self::Sigma);
+        }
+        if let Some(level_number) = s.strip_suffix(" hybrid level") {
+            return parse_scaled(level_number, 1).map(Self::Hybrid);
+        }
+        if let Some(pv) = s.strip_suffix(" PVU surface") {
+            return parse_scaled(pv, 1_000).map(Self::PotentialVorticity);
+        }
+        if let Some(depths) = s.strip_suffix(" m below ground") {
+            let (top, bottom) = depths.split_once('-')?;
+            return Some(Self::DepthBelowGround {
+                top_cm: parse_scaled(top, 100)?,
+                bottom_cm: parse_scaled(bottom, 100)?,
+            });
+        }
+        None
+    }
+}
+
+impl From<&str> for Level {
+    fn from(s: &str) -> Self {
+        match Self::try_from_physical_level(s) {
+            // Only use the physical level if it round-trips (e.g. "010 mb" would not).
+            Some(level) if level.to_string() == s => level,
+            _ => Self::Other(s.to_string()),
+        }
+    }
+}
+
+impl FromStr for Level {
+    type Err = std::convert::Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(Self::from(s))
+    }
+}
+
+impl fmt::Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::DepthBelowGround { top_cm, bottom_cm } => write!(
+                f,
+                "{}-{} m below ground",
+                format_scaled(*top_cm, 100),
+                format_scaled(*bottom_cm, 100)
+            ),
+            Self::Surface => write!(f, "surface"),
+            Self::HeightAboveGround(m) => write!(f, "{m} m above ground"),
+            Self::Isobaric(pa) => write!(f, "{} mb", format_scaled(*pa, 100)),
+            Self::Sigma(sigma) => write!(f, "{} sigma level", format_scaled(*sigma, 10_000)),
+            Self::Hybrid(n) => write!(f, "{n} hybrid level"),
+            Self::PotentialVorticity(pv) => {
+                write!(f, "{} PVU surface", format_scaled(*pv, 1_000))
+            }
+            Self::Other(s) => write!(f, "{s}"),
+        }
+    }
+}
+
+impl Ord for Level {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (
+                Self::DepthBelowGround { top_cm, bottom_cm },
+                Self::DepthBelowGround {
+                    top_cm: other_top_cm,
+                    bottom_cm: other_bottom_cm,
+                },
+            ) => (other_bottom_cm, other_top_cm).cmp(&(bottom_cm, top_cm)),
+            (Self::HeightAboveGround(m), Self::HeightAboveGround(other_m)) => m.cmp(other_m),
+            (Self::Isobaric(pa), Self::Isobaric(other_pa)) => other_pa.cmp(pa),
+            (Self::Sigma(sigma), Self::Sigma(other_sigma)) => other_sigma.cmp(sigma),
+            (Self::Hybrid(n), Self::Hybrid(other_n)) => n.cmp(other_n),
+            (Self::PotentialVorticity(pv), Self::PotentialVorticity(other_pv)) => pv.cmp(other_pv),
+            (Self::Other(s), Self::Other(other_s)) => s.cmp(other_s),
+            _ => self.rank().cmp(&other.rank()),
+        }
+    }
+}
+
+impl PartialOrd for Level {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Parse a non-negative decimal string (e.g. "0.4") and multiply by `scale`.
+/// Returns `None` if the result isn't a whole number.
+fn parse_scaled(s: &str, scale: u32) -> Option<u32> {
+    let value: f64 = s.parse().ok()?;
+    let scaled = value * scale as f64;
+    let rounded = scaled.round();
+    ((scaled - rounded).abs() < 1e-6 && (0.0..=u32::MAX as f64).contains(&rounded))
+        .then_some(rounded as u32)
+}
+
+fn format_scaled(value: u32, scale: u32) -> String {
+    if value.is_multiple_of(scale) {
+        (value / scale).to_string()
+    } else {
+        (value as f64 / scale as f64).to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_str_round_trip() {
+        for (s, expected) in [
+            ("surface", Level::Surface),
+            ("10 mb", Level::Isobaric(1_000)),
+            ("0.4 mb", Level::Isobaric(40)),
+            ("2 m above ground", Level::HeightAboveGround(2)),
+            (
+                "0.1-0.4 m below ground",
+                Level::DepthBelowGround {
+                    top_cm: 10,
+                    bottom_cm: 40,
+                },
+            ),
+            (
+                "entire atmosphere",
+                Level::Other(String::from("entire atmosphere")),
+            ),
+            ("010 mb", Level::Other(String::from("010 mb"))),
+            ("1 hybrid level", Level::Hybrid(1)),
+            ("0.995 sigma level", Level::Sigma(9_950)),
+            ("1 sigma level", Level::Sigma(10_000)),
+            ("2 PVU surface", Level::PotentialVorticity(2_000)),
+            ("1.5 PVU surface", Level::PotentialVorticity(1_500)),
+            (
+                "1.5 hybrid level",
+                Level::Other(String::from("1.5 hybrid level")),
+            ),
+        ] {
+            let level = Level::from(s);
+            assert_eq!(level, expected);
+            assert_eq!(level.to_string(), s);
+        }
+    }
+
+    #[test]
+    fn test_isobaric_levels_sort_in_pressure_order() {
+        let mut levels: Vec<Level> = ["10 mb", "850 mb", "1000 mb", "200 mb", "925 mb", "500 mb"]
+            .into_iter()
+            .map(Level::from)
+            .collect();
+        levels.sort();
+        let levels: Vec<String> = levels.iter().map(Level::to_string).collect();
+        assert_eq!(
+            levels,
+            ["1000 mb", "925 mb", "850 mb", "500 mb", "200 mb", "10 mb"]
+        );
+    }
+
+    #[test]
+    fn test_mixed_levels_sort_in_physical_order() {
+        let mut levels: Vec<Level> = [
+            "mean sea level",
+            "500 mb",
+            "10 m above ground",
+            "surface",
+            "0-0.1 m below ground",
+            "2 m above ground",
+            "0.1-0.4 m below ground",
+            "entire atmosphere",
+        ]
+        .into_iter()
+        .map(Level::from)
+        .collect();
+        levels.sort();
+        let levels: Vec<String> = levels.iter().map(Level::to_string).collect();
+        assert_eq!(
+            levels,
+            [
+                "0.1-0.4 m below ground",
+                "0-0.1 m below ground",
+                "surface",
+                "2 m above ground",
+                "10 m above ground",
+                "500 mb",
+                "entire atmosphere",
+                "mean sea level",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_fixed_surface_type() {
+        use gribberish::templates::product::tables::FixedSurfaceType;
+        for (s, expected) in [
+            ("surface", FixedSurfaceType::GroundOrWater),
+            ("10 mb", FixedSurfaceType::IsobaricSurface),
+            (
+                "2 m above ground",
+                FixedSurfaceType::SpecifiedHeightLevelAboveGround,
+            ),
+            ("0.995 sigma level", FixedSurfaceType::SigmaLevel),
+            ("1 hybrid level", FixedSurfaceType::HybridLevel),
+            (
+                "0-0.1 m below ground",
+                FixedSurfaceType::DepthBelowLandSurface,
+            ),
+        ] {
+            assert_eq!(Level::from(s).fixed_surface_type(), Some(expected as u8));
+        }
+        // gribberish doesn't (yet) define potential vorticity surfaces:
+        assert_eq!(Level::from("2 PVU surface").fixed_surface_type(), Some(109));
+        assert_eq!(Level::from("entire atmosphere").fixed_surface_type(), None);
+    }
+
+    #[test]
+    fn test_model_levels_sort_after_isobaric_levels() {
+        let mut levels: Vec<Level> = [
+            "2 PVU surface",
+            "2 hybrid level",
+            "0.995 sigma level",
+            "1 hybrid level",
+            "500 mb",
+            "0.5 sigma level",
+            "entire atmosphere",
+        ]
+        .into_iter()
+        .map(Level::from)
+        .collect();
+        levels.sort();
+        let levels: Vec<String> = levels.iter().map(Level::to_string).collect();
+        assert_eq!(
+            levels,
+            [
+                "500 mb",
+                "0.995 sigma level",
+                "0.5 sigma level",
+                "1 hybrid level",
+                "2 hybrid level",
+                "2 PVU surface",
+                "entire atmosphere",
+            ]
+        );
+    }
+}
@@ -1,87 +1,254 @@
 #[doc = include_str!("../README.md")]
-use anyhow;
-use chrono::{DateTime, NaiveDate, TimeDelta, Utc};
-use serde::Deserialize;
-
-#[derive(PartialEq, Debug, serde::Deserialize)]
-struct IdxRecord {
-    msg_id: u32,
-    byte_offset: u32,
-    #[serde(deserialize_with = "deserialize_init_datetime")]
-    reference_datetime: DateTime<Utc>,
-    parameter: String,
-    vertical_level: String,
-    // TODO: Define `struct Level{
-    //     fixed_surface_type: gribberish::templates::product::tables::FixedSurfaceType,
-    //     value: Option<f32>
-    // }`
-    // e.g. "10 mb" would be `Level{FixedSurfaceType::IsobaricSurface, 10}`
-    #[serde(deserialize_with = "deserialize_step")]
-    forecast_step: TimeDelta,
-    ensemble_member: Option<String>,
+use chrono::{DateTime, NaiveDate, Utc};
+
+mod diff;
+mod error;
+mod forecast_step;
+mod level;
+mod statistic;
+pub use diff::{diff_idx, IdxDiff, IdxMsgLocation};
+pub use error::IdxParseError;
+pub use forecast_step::ForecastStep;
+pub use level::Level;
+pub use statistic::Statistic;
+
+/// A single row of an `.idx` file.
+#[derive(PartialEq, Debug)]
+pub struct IdxRecord {
+    pub msg_id: u32,
+    pub byte_offset: u64,
+    pub reference_datetime: DateTime<Utc>,
+    pub parameter: String,
+    pub vertical_level: Level,
+    pub forecast_step: ForecastStep,
+    pub ensemble_member: Option<String>,
+    /// The percentile or probability of a probabilistic product, parsed from the first column
+    /// (from the 7th column onwards) which holds a statistic. `None` for deterministic fields.
+    pub statistic: Option<Statistic>,
+    /// The filename of the GRIB file which contains this message. `.idx` files don't include
+    /// the GRIB filename, so this is derived from the `.idx` path by `parse_idx_with_path`.
+    /// `None` if the record was parsed by `parse_idx`.
+    pub grib_filename: Option<String>,
 }
 
-// TODO: Return an iterator where each item is a `Result<IdxRecord>`.
-fn parse_idx(b: &[u8]) -> anyhow::Result<Vec<IdxRecord>> {
+impl IdxRecord {
+    /// Returns `true` if the `parameter` or the `vertical_level` of this record is empty. Real
+    /// `.idx` files occasionally contain such rows for placeholder or reserved messages.
+    pub fn has_empty_field(&self) -> bool {
+        self.parameter.trim().is_empty()
+            || matches!(&self.vertical_level, Level::Other(s) if s.trim().is_empty())
+    }
+}
+
+/// The number of columns in each line of an `.idx` file. Any further columns are only searched
+/// for a `Statistic`.
+const N_COLUMNS: usize = 7;
+
+/// Parse the body of an `.idx` file. Empty lines are skipped. Returns an error for the first line
+/// which can't be parsed. See `IdxParseError`.
+// TODO: Return an iterator where each item is a `Result<IdxRecord, IdxParseError>`.
+pub fn parse_idx(b: &[u8]) -> Result<Vec<IdxRecord>, IdxParseError> {
     let mut rdr = csv::ReaderBuilder::new()
         .delimiter(b':')
         .has_headers(false)
+        .flexible(true)
         .from_reader(b);
     let mut records = vec![];
-    for result in rdr.deserialize() {
-        records.push(result?);
+    for result in rdr.records() {
+        let row = result.map_err(|e| IdxParseError::Unreadable {
+            line: e.position().map_or(0, |position| line_number(b, position)),
+            reason: e.to_string(),
+        })?;
+        let line = row
+            .position()
+            .map_or(0, |position| line_number(b, position));
+        records.push(parse_row(&row, line)?);
     }
     Ok(records)
 }
 
-pub fn deserialize_init_datetime<'de, D>(deserializer: D) -> Result<DateTime<Utc>, D::Error>
-where
-    D: serde::Deserializer<'de>,
-{
-    let s = <&str>::deserialize(deserializer)?;
-    // The slightly convoluted approach below is necessary because `NaiveDateTime::parse_str`
-    // requires the input string to include the hour but `.idx` files don't include hours!
-    // So we _could_ implement a hack whereby we append "00" to the end of `s` but that requires
-    // a heap allocation for every row of the `.idx`. The advantage of the approach below
-    // is that it doesn't require any heap allocations.
-    let (date, remainder) = NaiveDate::parse_and_remainder(s, "d=%Y%m%d")
-        .map_err(|e| serde::de::Error::custom(format!("Invalid init date: {e}")))?;
-    let hour: u32 = remainder.parse().map_err(|e| {
-        serde::de::Error::custom(format!(
-            "Hour of the NWP init could not be parsed into a u32: {e}"
-        ))
-    })?;
-    match date.and_hms_opt(hour, 0, 0) {
-        Some(dt) => Ok(dt.and_utc()),
-        None => Err(serde::de::Error::custom(format!(
-            "Invalid init hour: {hour}"
-        ))),
+/// The (1-based) line number of `position` in `b`. `csv::Position::line` doesn't count empty
+/// lines (and the byte position of a record can be the start of the empty lines before the
+/// record), so count the newlines ourselves.
+fn line_number(b: &[u8], position: &csv::Position) -> u64 {
+    let mut byte = usize::try_from(position.byte()).map_or(b.len(), |byte| byte.min(b.len()));
+    while matches!(b.get(byte), Some(b'\n' | b'\r')) {
+        byte += 1;
     }
+    1 + b[..byte].iter().filter(|&&c| c == b'\n').count() as u64
 }
 
-pub fn deserialize_step<'de, D>(deserializer: D) -> Result<TimeDelta, D::Error>
-where
-    D: serde::Deserializer<'de>,
-{
-    let s = <&str>::deserialize(deserializer)?;
-    match s {
-        "anl" => Ok(TimeDelta::zero()),
-        _ => Err(serde::de::Error::custom(format!(
-            "Failed to parse forecast step: {s}"
-        ))),
-    }
-    // TODO: Implement deserialisation for other step strings! See:
+/// Parse line number `line` of an `.idx` file, which has already been split into columns.
+fn parse_row(row: &csv::StringRecord, line: u64) -> Result<IdxRecord, IdxParseError> {
+    let content = || row.iter().collect::<Vec<_>>().join(":");
+    if row.len() < N_COLUMNS {
+        return Err(IdxParseError::WrongColumnCount {
+            line,
+            content: content(),
+            n_columns: row.len(),
+        });
+    }
+    let msg_id = row[0].parse().map_err(|e| IdxParseError::BadMsgId {
+        line,
+        content: content(),
+        reason: format!("{e}"),
+    })?;
+    let byte_offset = row[1].parse().map_err(|e| IdxParseError::BadByteOffset {
+        line,
+        content: content(),
+        reason: format!("{e}"),
+    })?;
+    let reference_datetime =
+        parse_init_datetime(&row[2]).map_err(|reason| IdxParseError::BadDate {
+            line,
+            content: content(),
+            reason,
+        })?;
+    // TODO: Parse more step strings! See:
     // https://github.com/NOAA-EMC/NCEPLIBS-grib_util/blob/develop/src/wgrib/wgrib.c#L2248-L2446
     // Even better, use existing strings from gribberish, although this will require
     // adding `abbrev` annotations to the relevant gribberish enums, and defining
     // a `FromAbbrev` proc macro. The relevant gribberish enums might be
     // `GeneratingProcess` and/or `ReferenceDataSignificance`. Also see:
     // https://github.com/mpiannucci/gribberish/blob/1e35224773d4c174b4db59875a55438921898e2e/gribberish/src/message_metadata.rs#L96
+    let forecast_step = row[5].parse().map_err(|e| IdxParseError::BadStep {
+        line,
+        content: content(),
+        reason: format!("{e}"),
+    })?;
+    Ok(IdxRecord {
+        msg_id,
+        byte_offset,
+        reference_datetime,
+        parameter: row[3].to_string(),
+        vertical_level: Level::from(&row[4]),
+        forecast_step,
+        ensemble_member: Some(&row[6]).filter(|s| !s.is_empty()).map(str::to_string),
+        statistic: row.iter().skip(6).find_map(Statistic::from_idx_str),
+        grib_filename: None,
+    })
+}
+
+/// Parse the body of the `.idx` file at `idx_path`, and set the `grib_filename` of each record to
+/// the filename of `idx_path` without the trailing `.idx` (e.g. `foo/bar.grib2.idx` gives
+/// `bar.grib2`). `idx_path` may also end with `.idx.gz`, but `b` must already be decompressed.
+pub fn parse_idx_with_path(b: &[u8], idx_path: &str) -> anyhow::Result<Vec<IdxRecord>> {
+    let idx_filename = idx_path.rsplit('/').next().unwrap_or(idx_path);
+    let grib_filename = idx_filename
+        .strip_suffix(".gz")
+        .unwrap_or(idx_filename)
+        .strip_suffix(".idx")
+        .filter(|grib_filename| !grib_filename.is_empty())
+        .ok_or_else(|| {
+            anyhow::format_err!("idx path must end with '<filename>.idx': '{idx_path}'")
+        })?;
+    let mut records = parse_idx(b)?;
+    for record in records.iter_mut() {
+        record.grib_filename = Some(grib_filename.to_string());
+    }
+    Ok(records)
+}
+
+/// Remove the records whose `parameter` or `vertical_level` is empty (see
+/// `IdxRecord::has_empty_field`), and return the removed records, so the caller can decide
+/// whether to warn about them.
+///
+/// Note that `msg_lengths` must be called *before* removing records, otherwise the length of the
+/// message before each removed record would include the removed message.
+pub fn remove_records_with_empty_fields(records: &mut Vec<IdxRecord>) -> Vec<IdxRecord> {
+    let (removed, kept) = std::mem::take(records)
+        .into_iter()
+        .partition(IdxRecord::has_empty_field);
+    *records = kept;
+    removed
+}
+
+/// Compute the length of each GRIB message from the byte offsets of consecutive messages.
+///
+/// `.idx` files don't record the length of each message, so the length of message `i` is
+/// the difference between the byte offset of message `i + 1` and message `i`. The length of
+/// the final message can only be computed if we know the total size of the GRIB file, so the
+/// final element is `None` if `file_size` is `None`.
+///
+/// Returns an error if the byte offsets are not strictly increasing, or if `file_size` is not
+/// larger than the byte offset of the final message.
+pub fn msg_lengths(
+    records: &[IdxRecord],
+    file_size: Option<u64>,
+) -> anyhow::Result<Vec<Option<u64>>> {
+    let next_byte_offsets = records
+        .iter()
+        .skip(1)
+        .map(|record| Some(record.byte_offset))
+        .chain(std::iter::once(file_size));
+    records
+        .iter()
+        .zip(next_byte_offsets)
+        .map(|(record, next_byte_offset)| match next_byte_offset {
+            None => Ok(None),
+            Some(next_byte_offset) => match next_byte_offset.checked_sub(record.byte_offset) {
+                Some(msg_length) if msg_length > 0 => Ok(Some(msg_length)),
+                _ => Err(anyhow::format_err!(
+                    "The byte offset of the message after msg_id {} ({next_byte_offset}) \
+                     must be larger than the byte offset of msg_id {} ({})",
+                    record.msg_id,
+                    record.msg_id,
+                    record.byte_offset,
+                )),
+            },
+        })
+        .collect()
+}
+
+/// Parse the reference datetime column of an `.idx` file. Returns a description of the problem
+/// if `s` can't be parsed. Three forms are supported:
+///
+/// - `d=YYYYMMDDHH` (e.g. `d=2017010106`), which is by far the most common.
+/// - `d=YYYYMMDD HH`, where the hour is separated from the date by a space, `T`, or `_`.
+/// - `d=YYYYMMDDHHMM` (e.g. `d=201701010615`), for sub-hourly models like HRRR.
+fn parse_init_datetime(s: &str) -> Result<DateTime<Utc>, String> {
+    // The slightly convoluted approach below is necessary because `NaiveDateTime::parse_str`
+    // requires the input string to include the hour and minute but most `.idx` files don't include
+    // minutes! So we _could_ implement a hack whereby we append "00" to the end of `s` but that
+    // requires a heap allocation for every row of the `.idx`. The advantage of the approach below
+    // is that it doesn't require any heap allocations.
+    // Anything which isn't a valid datetime in UTC (e.g. hour 24, minute 60, month 0, or day 32)
+    // is an error.
+    let (date, remainder) = NaiveDate::parse_and_remainder(s, "d=%Y%m%d")
+        .map_err(|e| format!("Invalid init date in '{s}' (expected 'd=YYYYMMDDHH'): {e}"))?;
+    let remainder = remainder.strip_prefix([' ', 'T', '_']).unwrap_or(remainder);
+    // Dispatch on the length of the time. An empty time falls through to the error below.
+    let (hour, minute) = match (remainder.len(), remainder.split_at_checked(2)) {
+        (0..=2, _) => (remainder, "0"),
+        (4, Some((hour, minute))) => (hour, minute),
+        _ => {
+            return Err(format!(
+                "Invalid init time '{remainder}' in '{s}' (expected 'HH' or 'HHMM')"
+            ))
+        }
+    };
+    let hour: u32 = hour.parse().map_err(|e| {
+        format!("Hour of the NWP init in '{s}' could not be parsed into a u32: {e}")
+    })?;
+    let minute: u32 = minute.parse().map_err(|e| {
+        format!("Minute of the NWP init in '{s}' could not be parsed into a u32: {e}")
+    })?;
+    if hour > 23 {
+        return Err(format!(
+            "Invalid init hour {hour} in '{s}': The hour must be between 0 and 23"
+        ));
+    }
+    match date.and_hms_opt(hour, minute, 0) {
+        Some(dt) => Ok(dt.and_utc()),
+        None => Err(format!(
+            "Invalid init minute {minute} in '{s}': The minute must be between 0 and 59"
+        )),
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use chrono::NaiveDate;
+    use chrono::{NaiveDate, TimeDelta};
     use gribberish::templates::product::parameters::meteorological;
 
     use super::*;
@@ -107,11 +274,245 @@ mod tests {
                     .unwrap()
                     .and_utc(),
                 parameter: String::from("HGT"),
-                vertical_level: String::from("10 mb"),
-                forecast_step: TimeDelta::zero(),
+                vertical_level: Level::Isobaric(1_000),
+                forecast_step: ForecastStep::Instant(TimeDelta::zero()),
                 ensemble_member: Some(String::from("ENS=low-res ctl")),
+                statistic: None,
+                grib_filename: None,
             }
         );
         Ok(())
     }
+
+    #[test]
+    fn test_parse_idx_statistics() -> anyhow::Result<()> {
+        // Rows from the National Blend of Models, whose probability rows have an extra column.
+        let idx_text = "\
+1:0:d=2023100100:TMP:2 m above ground:6 hour fcst:10% level
+2:1000:d=2023100100:TMP:2 m above ground:6 hour fcst:90% level
+3:2000:d=2023100100:APCP:surface:0-6 hour acc fcst:prob >0.254:prob fcst 255/255
+4:3000:d=2023100100:TMP:2 m above ground:6 hour fcst:
+";
+        let statistics: Vec<_> = parse_idx(idx_text.as_bytes())?
+            .into_iter()
+            .map(|record| record.statistic)
+            .collect();
+        assert_eq!(
+            statistics,
+            [
+                Some(Statistic::Percentile(10)),
+                Some(Statistic::Percentile(90)),
+                Some(Statistic::ProbabilityAbove(254_000)),
+                None,
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_idx_rejects_invalid_datetimes() {
+        for (d, expected_error) in [
+            ("d=2017010124", "Invalid init hour 24 in 'd=2017010124'"),
+            ("d=2017000100", "Invalid init date in 'd=2017000100'"),
+            ("d=2017013200", "Invalid init date in 'd=2017013200'"),
+            ("d=2017022900", "Invalid init date in 'd=2017022900'"),
+            ("d=20170101", "could not be parsed into a u32"),
+            (
+                "d=201701010660",
+                "Invalid init minute 60 in 'd=201701010660'",
+            ),
+            ("d=201701012400", "Invalid init hour 24 in 'd=201701012400'"),
+            ("d=20170101061", "Invalid init time '061'"),
+            ("d=2017010106150", "Invalid init time '06150'"),
+        ] {
+            let idx_text = format!("1:0:{d}:HGT:10 mb:anl:ENS=low-res ctl\n");
+            let error = parse_idx(idx_text.as_bytes()).unwrap_err();
+            assert!(
+                error.to_string().contains(expected_error),
+                "{d}: Unexpected error: {error}"
+            );
+        }
+        // 2016 was a leap year:
+        assert!(parse_idx(b"1:0:d=2016022900:HGT:10 mb:anl:ENS=low-res ctl\n").is_ok());
+    }
+
+    #[test]
+    fn test_parse_init_datetime_forms() -> Result<(), String> {
+        let ymdhm = |year, month, day, hour, minute| {
+            NaiveDate::from_ymd_opt(year, month, day)
+                .and_then(|date| date.and_hms_opt(hour, minute, 0))
+                .unwrap()
+                .and_utc()
+        };
+        let six_am = ymdhm(2017, 1, 1, 6, 0);
+        // The hour is embedded in the date:
+        assert_eq!(parse_init_datetime("d=2017010106")?, six_am);
+        // The hour is separate from the date:
+        for d in [
+            "d=20170101 06",
+            "d=20170101T06",
+            "d=20170101_06",
+            "d=20170101 6",
+        ] {
+            assert_eq!(parse_init_datetime(d)?, six_am, "{d}");
+        }
+        // Sub-hourly, with minutes (e.g. HRRR):
+        assert_eq!(
+            parse_init_datetime("d=201701010615")?,
+            ymdhm(2017, 1, 1, 6, 15)
+        );
+        assert_eq!(parse_init_datetime("d=201701010600")?, six_am);
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_idx_errors() {
+        const GOOD_LINE: &str = "1:0:d=2017010100:HGT:10 mb:anl:ENS=low-res ctl";
+        let parse_second_line = |line: &str| {
+            let idx_text = format!("{GOOD_LINE}\n{line}\n");
+            parse_idx(idx_text.as_bytes()).unwrap_err()
+        };
+
+        let line = "2:50487:d=2017010100:TMP:10 mb";
+        assert_eq!(
+            parse_second_line(line),
+            IdxParseError::WrongColumnCount {
+                line: 2,
+                content: line.to_string(),
+                n_columns: 5,
+            }
+        );
+        let line = "x:50487:d=2017010100:TMP:10 mb:anl:ENS=low-res ctl";
+        assert!(matches!(
+            parse_second_line(line),
+            IdxParseError::BadMsgId { line: 2, content, .. } if content == line
+        ));
+        let line = "2:-1:d=2017010100:TMP:10 mb:anl:ENS=low-res ctl";
+        assert!(matches!(
+            parse_second_line(line),
+            IdxParseError::BadByteOffset { line: 2, content, .. } if content == line
+        ));
+        let line = "2:50487:2017010100:TMP:10 mb:anl:ENS=low-res ctl";
+        assert!(matches!(
+            parse_second_line(line),
+            IdxParseError::BadDate { line: 2, content, .. } if content == line
+        ));
+        let line = "2:50487:d=2017010100:TMP:10 mb:not a step:ENS=low-res ctl";
+        assert!(matches!(
+            parse_second_line(line),
+            IdxParseError::BadStep { line: 2, content, .. } if content == line
+        ));
+        let error = parse_idx(b"1:0:d=2017010100:HGT:10 mb:anl:\xff\n").unwrap_err();
+        assert!(matches!(error, IdxParseError::Unreadable { line: 1, .. }));
+
+        // Empty lines are skipped, but still counted:
+        let idx_text = format!("{GOOD_LINE}\n\n2:50487:d=2017010100:TMP\n");
+        assert_eq!(parse_idx(idx_text.as_bytes()).unwrap_err().line(), 3);
+    }
+
+    #[test]
+    fn test_parse_idx_with_path() -> anyhow::Result<()> {
+        let idx_text = "\
+1:0:d=2017010100:HGT:10 mb:anl:ENS=low-res ctl
+2:50487:d=2017010100:TMP:10 mb:anl:ENS=low-res ctl
+";
+        for (idx_path, expected_grib_filename) in [
+            ("foo.grib2.idx", "foo.grib2"),
+            (
+                "gefs.20170101/00/gec00.t00z.pgrb2af006.idx",
+                "gec00.t00z.pgrb2af006",
+            ),
+            ("foo.grib2.idx.gz", "foo.grib2"),
+        ] {
+            let records = parse_idx_with_path(idx_text.as_bytes(), idx_path)?;
+            assert_eq!(records.len(), 2);
+            for record in records {
+                assert_eq!(
+                    record.grib_filename.as_deref(),
+                    Some(expected_grib_filename)
+                );
+            }
+        }
+        for idx_path in ["foo.grib2", "foo/.idx", "foo.gz"] {
+            assert!(parse_idx_with_path(idx_text.as_bytes(), idx_path).is_err());
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_idx_with_forecast_step_ranges() -> anyhow::Result<()> {
+        let idx_text = "\
+1:0:d=2017010100:HGT:10 mb:6 hour fcst:ENS=low-res ctl
+2:50487:d=2017010100:APCP:surface:0-6 hour acc fcst:ENS=low-res ctl
+";
+        let records = parse_idx(idx_text.as_bytes())?;
+        assert_eq!(
+            records[0].forecast_step,
+            ForecastStep::Instant(TimeDelta::hours(6))
+        );
+        assert_eq!(
+            records[1].forecast_step,
+            ForecastStep::Range {
+                start: TimeDelta::zero(),
+                end: TimeDelta::hours(6)
+            }
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_byte_offset_above_u32_max() -> anyhow::Result<()> {
+        let idx_text = "\
+1:0:d=2017010100:HGT:10 mb:anl:ENS=low-res ctl
+2:4294967296:d=2017010100:TMP:10 mb:anl:ENS=low-res ctl
+3:6442450944:d=2017010100:RH:10 mb:anl:ENS=low-res ctl
+";
+        let records = parse_idx(idx_text.as_bytes())?;
+        assert_eq!(records[1].byte_offset, u32::MAX as u64 + 1);
+        assert_eq!(records[2].byte_offset, 6_442_450_944);
+        assert_eq!(
+            msg_lengths(&records, Some(8_589_934_592))?,
+            vec![
+                Some(4_294_967_296),
+                Some(2_147_483_648),
+                Some(2_147_483_648)
+            ]
+        );
+        assert_eq!(
+            msg_lengths(&records, None)?,
+            vec![Some(4_294_967_296), Some(2_147_483_648), None]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_msg_lengths_with_decreasing_byte_offsets() -> anyhow::Result<()> {
+        let idx_text = "\
+1:100:d=2017010100:HGT:10 mb:anl:ENS=low-res ctl
+2:50:d=2017010100:TMP:10 mb:anl:ENS=low-res ctl
+";
+        let records = parse_idx(idx_text.as_bytes())?;
+        assert!(msg_lengths(&records, None).is_err());
+        assert!(msg_lengths(&records[1..], Some(50)).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_remove_records_with_empty_fields() -> anyhow::Result<()> {
+        let idx_text = "\
+1:0:d=2017010100:HGT:10 mb:anl:ENS=low-res ctl
+2:50487:d=2017010100::10 mb:anl:ENS=low-res ctl
+3:70653:d=2017010100:RH::anl:ENS=low-res ctl
+4:81565:d=2017010100:UGRD:10 mb:anl:ENS=low-res ctl
+";
+        let mut records = parse_idx(idx_text.as_bytes())?;
+        assert_eq!(records.len(), 4);
+        assert_eq!(records[1].parameter, "");
+        let removed = remove_records_with_empty_fields(&mut records);
+        let msg_ids = |records: &[IdxRecord]| records.iter().map(|r| r.msg_id).collect::<Vec<_>>();
+        assert_eq!(msg_ids(&removed), [2, 3]);
+        assert_eq!(msg_ids(&records), [1, 4]);
+        assert!(remove_records_with_empty_fields(&mut records).is_empty());
+        Ok(())
+    }
 }
@@ -0,0 +1,134 @@
+use std::fmt;
+
+/// A statistic of an ensemble forecast, parsed from the strings used in the `.idx` files of
+/// probabilistic products (e.g. the National Blend of Models). For example, the 10th percentile
+/// and the 90th percentile of 2 m temperature are different messages, even though they have the
+/// same parameter, vertical level, and forecast step.
+///
+/// Thresholds are in millionths of the parameter's unit (e.g. "prob >0.254" is
+/// `ProbabilityAbove(254_000)`), so that thresholds can be compared (and hashed) exactly.
+///
+/// `Display` reproduces the `.idx` string (for the canonical formatting of each threshold).
+#[derive(PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Debug)]
+pub enum Statistic {
+    /// e.g. "10% level".
+    Percentile(u8),
+
+    /// e.g. "prob >0.254": The probability that the value is above the threshold.
+    ProbabilityAbove(i64),
+
+    /// e.g. "prob <273.15": The probability that the value is below the threshold.
+    ProbabilityBelow(i64),
+
+    /// e.g. "prob >=0.254 <2.54": The probability that the value is at or above `lower` and below
+    /// `upper`.
+    ProbabilityBetween { lower: i64, upper: i64 },
+
+    /// Any other probability, e.g. "prob >1e-07" (whose threshold can't be represented in
+    /// millionths). Holds the whole `.idx` string.
+    OtherProbability(String),
+}
+
+/// The scale of thresholds: Thresholds are in millionths of the parameter's unit.
+const THRESHOLD_SCALE: f64 = 1e6;
+
+impl Statistic {
+    /// Parse one column of an `.idx` file. Returns `None` if `s` isn't a statistic (e.g. if `s`
+    /// describes the ensemble member, like "ENS=low-res ctl").
+    pub fn from_idx_str(s: &str) -> Option<Self> {
+        if let Some(percentile) = s.strip_suffix("% level") {
+            return percentile
+                .parse()
+                .ok()
+                .filter(|percentile| *percentile <= 100)
+                .map(Self::Percentile);
+        }
+        let probability = s.strip_prefix("prob ")?;
+        let statistic = if let Some((lower, upper)) = probability
+            .strip_prefix(">=")
+            .and_then(|thresholds| thresholds.split_once(" <"))
+        {
+            parse_threshold(lower)
+                .zip(parse_threshold(upper))
+                .map(|(lower, upper)| Self::ProbabilityBetween { lower, upper })
+        } else if let Some(threshold) = probability.strip_prefix('>') {
+            parse_threshold(threshold).map(Self::ProbabilityAbove)
+        } else if let Some(threshold) = probability.strip_prefix('<') {
+            parse_threshold(threshold).map(Self::ProbabilityBelow)
+        } else {
+            None
+        };
+        Some(statistic.unwrap_or_else(|| Self::OtherProbability(s.to_string())))
+    }
+}
+
+impl fmt::Display for Statistic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Percentile(percentile) => write!(f, "{percentile}% level"),
+            Self::ProbabilityAbove(threshold) => {
+                write!(f, "prob >{}", format_threshold(*threshold))
+            }
+            Self::ProbabilityBelow(threshold) => {
+                write!(f, "prob <{}", format_threshold(*threshold))
+            }
+            Self::ProbabilityBetween { lower, upper } => write!(
+                f,
+                "prob >={} <{}",
+                format_threshold(*lower),
+                format_threshold(*upper)
+            ),
+            Self::OtherProbability(s) => write!(f, "{s}"),
+        }
+    }
+}
+
+/// Returns `None` if `s` isn't a number, or can't be represented exactly in millionths.
+fn parse_threshold(s: &str) -> Option<i64> {
+    let value: f64 = s.parse().ok()?;
+    let scaled = value * THRESHOLD_SCALE;
+    let rounded = scaled.round();
+    ((scaled - rounded).abs() < 1e-3 && rounded.abs() < i64::MAX as f64).then_some(rounded as i64)
+}
+
+fn format_threshold(threshold: i64) -> String {
+    (threshold as f64 / THRESHOLD_SCALE).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_idx_str_round_trip() {
+        for (s, expected) in [
+            ("10% level", Statistic::Percentile(10)),
+            ("90% level", Statistic::Percentile(90)),
+            ("prob >0.254", Statistic::ProbabilityAbove(254_000)),
+            ("prob <273.15", Statistic::ProbabilityBelow(273_150_000)),
+            ("prob <-10", Statistic::ProbabilityBelow(-10_000_000)),
+            (
+                "prob >=0.254 <2.54",
+                Statistic::ProbabilityBetween {
+                    lower: 254_000,
+                    upper: 2_540_000,
+                },
+            ),
+            (
+                "prob >1e-07",
+                Statistic::OtherProbability(String::from("prob >1e-07")),
+            ),
+        ] {
+            let statistic = Statistic::from_idx_str(s);
+            assert_eq!(statistic, Some(expected), "{s}");
+            assert_eq!(statistic.unwrap().to_string(), s);
+        }
+    }
+
+    #[test]
+    fn test_from_idx_str_not_a_statistic() {
+        for s in ["ENS=low-res ctl", "ENS=+1", "", "101% level", "x% level"] {
+            assert_eq!(Statistic::from_idx_str(s), None, "{s}");
+        }
+    }
+}
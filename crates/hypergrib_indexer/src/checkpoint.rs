@@ -0,0 +1,124 @@
+//! Periodically save the progress of indexing to a local file, so that an interrupted indexing
+//! run can be resumed (using `--resume <checkpoint>`) instead of starting again from scratch.
+//!
+//! The checkpoint file is JSON, of the form:
+//!
+//! ```json
+//! {
+//!   "processed_prefixes": ["gefs.20170101/00", "gefs.20170101/06"],
+//!   "coord_labels": { ... }
+//! }
+//! ```
+//!
+//! where `coord_labels` is in the format written by `CoordLabels::to_json`.
+
+use std::{
+    collections::BTreeSet,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+use hypergrib::CoordLabels;
+
+use crate::coord_labels_builder::CoordLabelsBuilder;
+
+/// By default, save the checkpoint after processing this many prefixes.
+const DEFAULT_SAVE_EVERY_N_PREFIXES: usize = 100;
+
+#[derive(serde::Serialize, serde::Deserialize)]
+struct CheckpointJson {
+    processed_prefixes: BTreeSet<String>,
+    coord_labels: serde_json::Value,
+}
+
+pub struct Checkpoint {
+    /// The local filesystem path of the checkpoint file.
+    path: PathBuf,
+    save_every_n_prefixes: usize,
+    processed_prefixes: BTreeSet<String>,
+    n_prefixes_since_last_save: usize,
+
+    /// The coord labels loaded from the checkpoint file. `None` for a new checkpoint.
+    coord_labels: Option<CoordLabels>,
+}
+
+impl Checkpoint {
+    /// Start a new checkpoint, which will be saved to `path`.
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            save_every_n_prefixes: DEFAULT_SAVE_EVERY_N_PREFIXES,
+            processed_prefixes: BTreeSet::new(),
+            n_prefixes_since_last_save: 0,
+            coord_labels: None,
+        }
+    }
+
+    /// Load an existing checkpoint from `path`. Progress will continue to be saved to `path`.
+    pub fn load(path: impl Into<PathBuf>) -> anyhow::Result<Self> {
+        let path = path.into();
+        let json = std::fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read checkpoint file {path:?}"))?;
+        let checkpoint_json: CheckpointJson = serde_json::from_str(&json)
+            .with_context(|| format!("Failed to parse checkpoint file {path:?}"))?;
+        let coord_labels = CoordLabels::from_json(&checkpoint_json.coord_labels.to_string())?;
+        Ok(Self {
+            processed_prefixes: checkpoint_json.processed_prefixes,
+            coord_labels: Some(coord_labels),
+            ..Self::new(path)
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn with_save_every_n_prefixes(mut self, save_every_n_prefixes: usize) -> Self {
+        self.save_every_n_prefixes = save_every_n_prefixes;
+        self
+    }
+
+    /// The coord labels loaded from the checkpoint file, if any.
+    pub(crate) fn coord_labels(&self) -> Option<&CoordLabels> {
+        self.coord_labels.as_ref()
+    }
+
+    pub fn n_processed_prefixes(&self) -> usize {
+        self.processed_prefixes.len()
+    }
+
+    pub(crate) fn is_processed(&self, prefix: &object_store::path::Path) -> bool {
+        self.processed_prefixes.contains(prefix.as_ref())
+    }
+
+    /// Record that `prefix` has been fully processed, and that its coordinate labels are in
+    /// `coord_labels_builder`. Saves the checkpoint every `save_every_n_prefixes`.
+    pub(crate) fn mark_processed(
+        &mut self,
+        prefix: &object_store::path::Path,
+        coord_labels_builder: &CoordLabelsBuilder,
+    ) -> anyhow::Result<()> {
+        self.processed_prefixes.insert(prefix.to_string());
+        self.n_prefixes_since_last_save += 1;
+        if self.n_prefixes_since_last_save >= self.save_every_n_prefixes {
+            self.save(coord_labels_builder)?;
+        }
+        Ok(())
+    }
+
+    /// Save the checkpoint. To avoid corrupting the checkpoint if we're interrupted whilst
+    /// saving, the checkpoint is written to a temporary file which is then renamed.
+    pub(crate) fn save(&mut self, coord_labels_builder: &CoordLabelsBuilder) -> anyhow::Result<()> {
+        let checkpoint_json = CheckpointJson {
+            processed_prefixes: self.processed_prefixes.clone(),
+            coord_labels: serde_json::from_str(&coord_labels_builder.to_coord_labels().to_json()?)?,
+        };
+        let tmp_path = self.path.with_extension("tmp");
+        std::fs::write(&tmp_path, serde_json::to_string_pretty(&checkpoint_json)?)
+            .with_context(|| format!("Failed to write checkpoint file {tmp_path:?}"))?;
+        std::fs::rename(&tmp_path, &self.path)
+            .with_context(|| format!("Failed to rename {tmp_path:?} to {:?}", self.path))?;
+        self.n_prefixes_since_last_save = 0;
+        Ok(())
+    }
+}
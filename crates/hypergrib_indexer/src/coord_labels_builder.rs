@@ -1,12 +1,12 @@
 use std::{collections::BTreeSet, sync::Arc};
 
-use chrono::{DateTime, TimeDelta, Utc};
-use hypergrib::CoordLabels;
-use object_store::{limit::LimitStore, ObjectStore};
+use chrono::{DateTime, Utc};
+use hypergrib::{store::StoreConfig, CoordLabels, ForecastStep, Level, Statistic};
+use object_store::ObjectStore;
 use url::Url;
 
-/// Set the maximum number of concurrent operations.
-/// Set to `None` for no limit. But beware that, when no limit is set,
+/// The default maximum number of concurrent operations, used if `StoreConfig::concurrency_limit`
+/// is `None`. Set to `None` for no limit. But beware that, when no limit is set,
 /// you may have to increase the number of open file descriptors that your operating
 /// system allows. (Note that file descriptors are used for network connections, as
 /// well as for files!). On Linux, run `ulimit -n 10000` to set the limit to 10,000.
@@ -17,11 +17,15 @@ pub(crate) struct CoordLabelsBuilder {
     grib_base_path: object_store::path::Path,
     idx_store: Arc<dyn ObjectStore>,
     idx_base_path: object_store::path::Path,
+    /// The scheme and bucket of the idx store (e.g. `s3://noaa-gefs-pds`), if known. Only used to
+    /// describe locations in error messages.
+    idx_bucket_url: Option<String>,
     reference_datetime: BTreeSet<DateTime<Utc>>,
     ensemble_member: BTreeSet<String>,
-    forecast_step: BTreeSet<TimeDelta>,
+    forecast_step: BTreeSet<ForecastStep>,
     parameter: BTreeSet<String>,
-    vertical_level: BTreeSet<String>,
+    vertical_level: BTreeSet<Level>,
+    statistic: BTreeSet<Option<Statistic>>,
 }
 
 impl CoordLabelsBuilder {
@@ -36,42 +40,75 @@ impl CoordLabelsBuilder {
             grib_base_path,
             idx_store,
             idx_base_path,
+            idx_bucket_url: None,
             reference_datetime: BTreeSet::new(),
             ensemble_member: BTreeSet::new(),
             forecast_step: BTreeSet::new(),
             parameter: BTreeSet::new(),
             vertical_level: BTreeSet::new(),
+            statistic: BTreeSet::new(),
         }
     }
 
-    pub(crate) fn new_from_url(url: &str, skip_signature: bool) -> anyhow::Result<Self> {
-        let mut opts = vec![];
-        if skip_signature {
-            opts.push(("skip_signature", "true"));
-        }
+    pub(crate) fn new_from_url(
+        url: &str,
+        skip_signature: bool,
+        store_config: &StoreConfig,
+    ) -> anyhow::Result<Self> {
         let bucket_url = Url::try_from(url)?;
-        let (store, base_path) = object_store::parse_url_opts(&bucket_url, opts)?;
-        let store: Arc<dyn ObjectStore> = if let Some(concurrency_limit) = CONCURRENCY_LIMIT {
-            Arc::new(LimitStore::new(store, concurrency_limit))
-        } else {
-            Arc::from(store)
+        let store_config = StoreConfig {
+            concurrency_limit: store_config.concurrency_limit.or(CONCURRENCY_LIMIT),
+            ..store_config.clone()
         };
-        Ok(CoordLabelsBuilder::new(
-            store.clone(),
-            base_path.clone(),
-            store,
-            base_path,
-        ))
+        let (store, base_path) = store_config.make_store(&bucket_url, skip_signature)?;
+        let store: Arc<dyn ObjectStore> = Arc::from(store);
+        let mut builder =
+            CoordLabelsBuilder::new(store.clone(), base_path.clone(), store, base_path);
+        builder.idx_bucket_url = Some(format!(
+            "{}://{}",
+            bucket_url.scheme(),
+            bucket_url.host_str().unwrap_or_default()
+        ));
+        Ok(builder)
     }
 
     pub(crate) fn build(self) -> CoordLabels {
-        CoordLabels {
-            reference_datetime: to_sorted_vec(self.reference_datetime),
-            ensemble_member: to_sorted_vec(self.ensemble_member),
-            forecast_step: to_sorted_vec(self.forecast_step),
-            parameter: to_sorted_vec(self.parameter),
-            vertical_level: to_sorted_vec(self.vertical_level),
-        }
+        let coord_labels = CoordLabels::new(
+            self.reference_datetime.into(),
+            self.ensemble_member.into(),
+            self.forecast_step.into(),
+            self.parameter.into(),
+            self.vertical_level.into(),
+        );
+        with_statistic(coord_labels, self.statistic)
+    }
+
+    /// Like `build`, but doesn't consume `self`.
+    pub(crate) fn to_coord_labels(&self) -> CoordLabels {
+        let coord_labels = CoordLabels::new(
+            self.reference_datetime.clone().into(),
+            self.ensemble_member.clone().into(),
+            self.forecast_step.clone().into(),
+            self.parameter.clone().into(),
+            self.vertical_level.clone().into(),
+        );
+        with_statistic(coord_labels, self.statistic.clone())
+    }
+
+    /// Insert all the labels from `coord_labels` (e.g. when resuming from a checkpoint).
+    pub(crate) fn extend(&mut self, coord_labels: &CoordLabels) {
+        self.reference_datetime
+            .extend(coord_labels.reference_datetime().iter().copied());
+        self.ensemble_member
+            .extend(coord_labels.ensemble_member().iter().cloned());
+        self.forecast_step
+            .extend(coord_labels.forecast_step().iter().copied());
+        self.parameter
+            .extend(coord_labels.parameter().iter().cloned());
+        self.vertical_level
+            .extend(coord_labels.vertical_level().iter().cloned());
+        self.statistic
+            .extend(coord_labels.statistic().iter().cloned());
     }
 
     pub(crate) fn grib_store(&self) -> &Arc<dyn ObjectStore> {
@@ -90,10 +127,39 @@ impl CoordLabelsBuilder {
         &self.idx_base_path
     }
 
+    /// Describe the location of `prefix` in the idx store, for error messages. For example,
+    /// `s3://noaa-gefs-pds/gefs.20241008/00/`.
+    pub(crate) fn idx_location(&self, prefix: &object_store::path::Path) -> String {
+        let prefix = match prefix.as_ref() {
+            "" => String::new(),
+            prefix => format!("{prefix}/"),
+        };
+        match &self.idx_bucket_url {
+            Some(bucket_url) => format!("{bucket_url}/{prefix}"),
+            None => format!("'{prefix}'"),
+        }
+    }
+
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "trace", skip(self), ret)
+    )]
     pub(crate) fn insert_reference_datetime(&mut self, datetime: DateTime<Utc>) -> bool {
         self.reference_datetime.insert(datetime)
     }
 
+    pub(crate) fn insert_parameter(&mut self, parameter: String) -> bool {
+        self.parameter.insert(parameter)
+    }
+
+    pub(crate) fn insert_vertical_level(&mut self, vertical_level: Level) -> bool {
+        self.vertical_level.insert(vertical_level)
+    }
+
+    pub(crate) fn insert_statistic(&mut self, statistic: Option<Statistic>) -> bool {
+        self.statistic.insert(statistic)
+    }
+
     pub(crate) fn reference_datetime(&self) -> &BTreeSet<DateTime<Utc>> {
         &self.reference_datetime
     }
@@ -109,13 +175,15 @@ impl CoordLabelsBuilder {
     }
 }
 
-fn to_sorted_vec<T, S>(set: S) -> Vec<T>
-where
-    T: Ord,
-    S: IntoIterator,
-    Vec<T>: FromIterator<<S as IntoIterator>::Item>,
-{
-    let mut v: Vec<T> = set.into_iter().collect();
-    v.sort();
-    v
+/// If no statistics were found (e.g. if indexing was cancelled before reading any `.idx` files)
+/// then keep the `None` label which `CoordLabels::new` gives the `statistic` dimension.
+fn with_statistic(
+    coord_labels: CoordLabels,
+    statistic: BTreeSet<Option<Statistic>>,
+) -> CoordLabels {
+    if statistic.is_empty() {
+        coord_labels
+    } else {
+        coord_labels.with_statistic(statistic.into())
+    }
 }
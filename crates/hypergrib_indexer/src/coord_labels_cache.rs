@@ -0,0 +1,121 @@
+//! Cache the `CoordLabels` of a dataset on the local filesystem, so that re-indexing an archive
+//! which grows over time only processes the reference datetimes which are new since the last run.
+//!
+//! Each cache file is keyed by the location of the dataset (the bucket URL and prefix), and
+//! records a cheap "freshness token" computed from the listing of reference datetime prefixes:
+//! The number of prefixes and the most recent reference datetime. If the token hasn't changed
+//! since the last run then the cached `CoordLabels` are used without reading any `.idx` files.
+//!
+//! Each cache file is JSON, of the form:
+//!
+//! ```json
+//! {
+//!   "location": "s3://noaa-gefs-pds/",
+//!   "token": { "n_prefixes": 2, "last_reference_datetime": "2017-01-01T06:00:00+00:00" },
+//!   "processed_prefixes": ["gefs.20170101/00", "gefs.20170101/06"],
+//!   "coord_labels": { ... }
+//! }
+//! ```
+//!
+//! where `coord_labels` is in the format written by `CoordLabels::to_json`.
+
+use std::{collections::BTreeSet, path::PathBuf};
+
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use hypergrib::CoordLabels;
+
+/// A cheap summary of the listing of reference datetime prefixes. If the token of a listing
+/// matches the token of the cache then nothing has changed.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub(crate) struct FreshnessToken {
+    n_prefixes: usize,
+    /// In RFC 3339 format.
+    last_reference_datetime: Option<String>,
+}
+
+impl FreshnessToken {
+    pub(crate) fn new(n_prefixes: usize, last_reference_datetime: Option<&DateTime<Utc>>) -> Self {
+        Self {
+            n_prefixes,
+            last_reference_datetime: last_reference_datetime.map(DateTime::to_rfc3339),
+        }
+    }
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+struct CacheJson {
+    location: String,
+    token: FreshnessToken,
+    processed_prefixes: BTreeSet<String>,
+    coord_labels: serde_json::Value,
+}
+
+/// The contents of one cache file.
+pub(crate) struct CachedCoordLabels {
+    pub(crate) token: FreshnessToken,
+    pub(crate) processed_prefixes: BTreeSet<String>,
+    pub(crate) coord_labels: CoordLabels,
+}
+
+/// A directory of cached `CoordLabels`, with one file per dataset location.
+pub struct CoordLabelsCache {
+    dir: PathBuf,
+}
+
+impl CoordLabelsCache {
+    /// `dir` is created when the cache is first saved, if it doesn't already exist.
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    /// The path of the cache file for `location` (e.g. `s3://noaa-gefs-pds/`).
+    fn path(&self, location: &str) -> PathBuf {
+        let file_stem: String = location
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+            .collect();
+        self.dir.join(format!("{file_stem}.json"))
+    }
+
+    /// Returns `None` if there's no cache file for `location`.
+    pub(crate) fn load(&self, location: &str) -> anyhow::Result<Option<CachedCoordLabels>> {
+        let path = self.path(location);
+        if !path.exists() {
+            return Ok(None);
+        }
+        let json = std::fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read cache file {path:?}"))?;
+        let cache_json: CacheJson = serde_json::from_str(&json)
+            .with_context(|| format!("Failed to parse cache file {path:?}"))?;
+        // Different locations can map to the same file name.
+        if cache_json.location != location {
+            return Ok(None);
+        }
+        Ok(Some(CachedCoordLabels {
+            token: cache_json.token,
+            processed_prefixes: cache_json.processed_prefixes,
+            coord_labels: CoordLabels::from_json(&cache_json.coord_labels.to_string())?,
+        }))
+    }
+
+    /// Save the cache file for `location`. To avoid corrupting the cache if we're interrupted
+    /// whilst saving, the cache is written to a temporary file which is then renamed.
+    pub(crate) fn save(&self, location: &str, cached: &CachedCoordLabels) -> anyhow::Result<()> {
+        std::fs::create_dir_all(&self.dir)
+            .with_context(|| format!("Failed to create cache directory {:?}", self.dir))?;
+        let cache_json = CacheJson {
+            location: location.to_string(),
+            token: cached.token.clone(),
+            processed_prefixes: cached.processed_prefixes.clone(),
+            coord_labels: serde_json::from_str(&cached.coord_labels.to_json()?)?,
+        };
+        let path = self.path(location);
+        let tmp_path = path.with_extension("tmp");
+        std::fs::write(&tmp_path, serde_json::to_string_pretty(&cache_json)?)
+            .with_context(|| format!("Failed to write cache file {tmp_path:?}"))?;
+        std::fs::rename(&tmp_path, &path)
+            .with_context(|| format!("Failed to rename {tmp_path:?} to {path:?}"))?;
+        Ok(())
+    }
+}
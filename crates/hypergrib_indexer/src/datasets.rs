@@ -1,2 +1,4 @@
 pub mod gefs;
+pub mod gefs_reforecast;
 pub use gefs::Gefs;
+pub use gefs_reforecast::GefsReforecast;
@@ -1,8 +1,22 @@
+use std::collections::BTreeSet;
+
 use anyhow::Context;
-use chrono::{DateTime, NaiveDate, Utc};
-use hypergrib::{CoordLabels, GetCoordLabels};
+use chrono::{DateTime, NaiveDate, Timelike, Utc};
+use hypergrib::{store::StoreConfig, CoordLabels, EnsembleMember, EnsembleNaming, GetCoordLabels};
+use tokio_util::sync::CancellationToken;
 
-use crate::coord_labels_builder::CoordLabelsBuilder;
+use crate::{
+    checkpoint::Checkpoint,
+    coord_labels_builder::CoordLabelsBuilder,
+    coord_labels_cache::{CachedCoordLabels, CoordLabelsCache, FreshnessToken},
+    empty_listing::EmptyListing,
+    expected_msg_count::ExpectedMsgCount,
+    fetch_and_parse::fetch_and_parse_idx_files,
+    list_skipping_dirs::{list_objects, ListingStrategy},
+    progress::Progress,
+    strict_decoding::StrictDecoding,
+    warnings::{Warning, Warnings},
+};
 use list_with_depth::list_with_depth;
 
 const BUCKET_URL: &str = "s3://noaa-gefs-pds";
@@ -10,55 +24,289 @@ const SKIP_SIGNATURE: bool = true;
 
 pub struct Gefs {
     coord_labels_builder: CoordLabelsBuilder,
+    checkpoint: Option<Checkpoint>,
+    cache: Option<CoordLabelsCache>,
+    expected_msg_count: Option<ExpectedMsgCount>,
+    strict_decoding: Option<StrictDecoding>,
+    listing_strategy: ListingStrategy,
+    cancellation_token: CancellationToken,
+    progress: Progress,
+    warnings: Warnings,
 }
 
 impl Gefs {
-    pub fn new() -> anyhow::Result<Self> {
-        let coord_labels_builder = CoordLabelsBuilder::new_from_url(BUCKET_URL, SKIP_SIGNATURE)?;
+    pub fn new(store_config: &StoreConfig) -> anyhow::Result<Self> {
+        let coord_labels_builder =
+            CoordLabelsBuilder::new_from_url(BUCKET_URL, SKIP_SIGNATURE, store_config)?;
         Ok(Self {
             coord_labels_builder,
+            checkpoint: None,
+            cache: None,
+            expected_msg_count: None,
+            strict_decoding: None,
+            listing_strategy: ListingStrategy::default(),
+            cancellation_token: CancellationToken::new(),
+            progress: Progress::new(),
+            warnings: Warnings::new(),
         })
     }
 
+    /// Periodically save progress to `checkpoint`. If `checkpoint` was loaded from an existing
+    /// checkpoint file then indexing resumes from where that checkpoint left off.
+    pub fn with_checkpoint(mut self, checkpoint: Checkpoint) -> Self {
+        if let Some(coord_labels) = checkpoint.coord_labels() {
+            self.coord_labels_builder.extend(coord_labels);
+        }
+        self.checkpoint = Some(checkpoint);
+        self
+    }
+
+    /// Cache the coord labels in `cache`. Reference datetimes which were processed by previous
+    /// runs aren't processed again, and no `.idx` files are read if the listing of reference
+    /// datetimes hasn't changed since the last run.
+    pub fn with_cache(mut self, cache: CoordLabelsCache) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    /// Report the progress of indexing to `progress`. By default, progress is printed to stdout
+    /// every `progress::DEFAULT_INTERVAL`.
+    pub fn with_progress(mut self, progress: Progress) -> Self {
+        self.progress = progress;
+        self
+    }
+
+    /// Check the number of messages in each `.idx` file which is read.
+    pub fn with_expected_msg_count(mut self, expected_msg_count: ExpectedMsgCount) -> Self {
+        self.expected_msg_count = Some(expected_msg_count);
+        self
+    }
+
+    /// Stop indexing with an error if any `.idx` record which is read has a parameter
+    /// abbreviation or a vertical level which can't be decoded.
+    pub fn with_strict_decoding(mut self, strict_decoding: StrictDecoding) -> Self {
+        self.strict_decoding = Some(strict_decoding);
+        self
+    }
+
+    /// How to list the objects below each reference datetime.
+    pub fn with_listing_strategy(mut self, listing_strategy: ListingStrategy) -> Self {
+        self.listing_strategy = listing_strategy;
+        self
+    }
+
+    /// Stop indexing cleanly when `cancellation_token` is cancelled (e.g. on Ctrl-C). The
+    /// reference datetime which is being processed is finished, the checkpoint (if any) is saved,
+    /// and `get_coord_labels_with_warnings` returns the partial coord labels with a
+    /// `Warning::Cancelled`. The cache isn't updated, and the `.idx` files aren't read.
+    pub fn with_cancellation_token(mut self, cancellation_token: CancellationToken) -> Self {
+        self.cancellation_token = cancellation_token;
+        self
+    }
+
     /// The reference datetimes are extracted from the first two parts of the path, for example:
     /// `gefs.20241204/00/`.
-    async fn get_reference_datetimes(&mut self) -> anyhow::Result<()> {
+    ///
+    /// Different folders can map to the same reference datetime (e.g. during version
+    /// transitions). Each reference datetime is only indexed once, and duplicates are recorded as
+    /// warnings.
+    ///
+    /// Prefixes in `cached_prefixes` are skipped. Stops early (after saving the checkpoint) if
+    /// indexing is cancelled. Returns the freshness token of the listing, and the prefixes which
+    /// were processed.
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all, err))]
+    async fn get_reference_datetimes(
+        &mut self,
+        cached_prefixes: &BTreeSet<String>,
+    ) -> anyhow::Result<(FreshnessToken, Vec<object_store::path::Path>)> {
         let store = self.coord_labels_builder.idx_store().clone();
         let prefix = self.coord_labels_builder.idx_base_path();
         let list = list_with_depth(store, Some(prefix), 1).await?;
+        if list.common_prefixes.is_empty() {
+            return Err(EmptyListing {
+                location: self.coord_labels_builder.idx_location(prefix),
+                extension: None,
+            }
+            .into());
+        }
+        #[cfg(feature = "tracing")]
+        tracing::debug!(
+            n_common_prefixes = list.common_prefixes.len(),
+            "Listed reference datetime prefixes"
+        );
+        self.progress
+            .start_stage("reference datetimes", list.common_prefixes.len());
+        let mut processed_prefixes = Vec::new();
         for prefix in list.common_prefixes.iter() {
+            if self.cancellation_token.is_cancelled() {
+                break;
+            }
+            if cached_prefixes.contains(prefix.as_ref())
+                || self
+                    .checkpoint
+                    .as_ref()
+                    .is_some_and(|checkpoint| checkpoint.is_processed(prefix))
+            {
+                self.progress.inc(1);
+                continue;
+            }
             let datetime = path_to_reference_datetime(prefix)?;
             let datetime_is_unique = self
                 .coord_labels_builder
                 .insert_reference_datetime(datetime);
-            assert!(
-                datetime_is_unique,
-                "Duplicate reference datetime! {datetime}"
-            );
+            if !datetime_is_unique {
+                self.warnings.push(Warning::DuplicateReferenceDatetime {
+                    path: prefix.clone(),
+                    reference_datetime: datetime,
+                });
+            }
+            if let Some(checkpoint) = self.checkpoint.as_mut() {
+                checkpoint.mark_processed(prefix, &self.coord_labels_builder)?;
+            }
+            processed_prefixes.push(prefix.clone());
+            self.progress.inc(1);
+        }
+        if let Some(checkpoint) = self.checkpoint.as_mut() {
+            checkpoint.save(&self.coord_labels_builder)?;
+        }
+        // Every prefix has been parsed successfully (by this run or by a previous run), unless
+        // indexing was cancelled, in which case the caller doesn't use the token.
+        let last_reference_datetime = list
+            .common_prefixes
+            .iter()
+            .filter_map(|prefix| path_to_reference_datetime(prefix).ok())
+            .max();
+        let token =
+            FreshnessToken::new(list.common_prefixes.len(), last_reference_datetime.as_ref());
+        Ok((token, processed_prefixes))
+    }
+
+    /// Get the parameters and vertical levels by reading the bodies of a minimal set of `.idx`
+    /// files: The analysis step of the control member of the most recent reference datetime.
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip(self), err))]
+    async fn get_parameters_and_vertical_levels(&mut self) -> anyhow::Result<()> {
+        let Some(reference_datetime) = self.coord_labels_builder.reference_datetime().last() else {
+            return Ok(());
+        };
+        let store = self.coord_labels_builder.idx_store().clone();
+        let prefix = object_store::path::Path::from_iter(
+            self.coord_labels_builder
+                .idx_base_path()
+                .parts()
+                .chain(reference_datetime_to_path(reference_datetime).parts()),
+        );
+        let control = hypergrib::datasets::gefs::Gefs::format_member(&EnsembleMember::Control)
+            .expect("GEFS has a control member");
+        let control = control.as_str();
+        let paths: Vec<_> = list_objects(
+            store.as_ref(),
+            &prefix,
+            hypergrib::datasets::gefs::Gefs::NON_GRIB_DIRS,
+            self.listing_strategy,
+        )
+        .await?
+        .into_iter()
+        .filter_map(|meta| {
+            let path = meta.location;
+            // `.idx.gz` files are decompressed by `fetch_and_parse_idx_files`:
+            let filename = path.filename()?;
+            let filename = filename.strip_suffix(".gz").unwrap_or(filename);
+            let is_control_analysis = filename.starts_with(control)
+                && (filename.ends_with("anl.idx") || filename.ends_with("f000.idx"));
+            is_control_analysis.then_some(path)
+        })
+        .collect();
+        if paths.is_empty() {
+            return Err(EmptyListing {
+                location: self.coord_labels_builder.idx_location(&prefix),
+                extension: Some("idx"),
+            }
+            .into());
+        }
+        for (_path, records) in fetch_and_parse_idx_files(
+            store,
+            paths,
+            self.expected_msg_count.clone(),
+            self.strict_decoding,
+            &self.warnings,
+            &self.progress,
+        )
+        .await?
+        {
+            for record in records {
+                self.coord_labels_builder.insert_parameter(record.parameter);
+                self.coord_labels_builder
+                    .insert_vertical_level(record.vertical_level);
+                self.coord_labels_builder.insert_statistic(record.statistic);
+            }
         }
         Ok(())
     }
-}
 
-impl GetCoordLabels for Gefs {
-    async fn get_coord_labels(mut self) -> anyhow::Result<CoordLabels> {
-        self.get_reference_datetimes().await?;
+    /// Like `get_coord_labels`, but also returns the non-fatal problems found during indexing.
+    /// Each warning is also printed to stderr as soon as it's found.
+    ///
+    /// Returns an `EmptyListing` error if no reference datetimes or no `.idx` files are found,
+    /// which usually means that the prefix is wrong, or that requests need to be signed.
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip(self), err))]
+    pub async fn get_coord_labels_with_warnings(
+        mut self,
+    ) -> anyhow::Result<(CoordLabels, Vec<Warning>)> {
+        let location = self
+            .coord_labels_builder
+            .idx_location(self.coord_labels_builder.idx_base_path());
+        let cached = match &self.cache {
+            Some(cache) => cache.load(&location)?,
+            None => None,
+        };
+        let mut cached_prefixes = BTreeSet::new();
+        if let Some(cached) = cached.as_ref() {
+            self.coord_labels_builder.extend(&cached.coord_labels);
+            cached_prefixes.clone_from(&cached.processed_prefixes);
+        }
+
+        let (token, processed_prefixes) = self.get_reference_datetimes(&cached_prefixes).await?;
         println!(
             "{}",
             self.coord_labels_builder.describe_reference_datetimes()
         );
+        if self.cancellation_token.is_cancelled() {
+            self.warnings.push(Warning::cancelled(&self.progress));
+            return Ok((self.coord_labels_builder.build(), self.warnings.to_vec()));
+        }
+        if cached.is_some_and(|cached| cached.token == token) {
+            println!("The cached coord labels for {location} are up to date.");
+            return Ok((self.coord_labels_builder.build(), self.warnings.to_vec()));
+        }
+        self.get_parameters_and_vertical_levels().await?;
+
+        if let Some(cache) = &self.cache {
+            cached_prefixes.extend(processed_prefixes.iter().map(ToString::to_string));
+            let cached = CachedCoordLabels {
+                token,
+                processed_prefixes: cached_prefixes,
+                coord_labels: self.coord_labels_builder.to_coord_labels(),
+            };
+            cache.save(&location, &cached)?;
+        }
 
         // TODO: Get list of ensemble members and steps from GEFS .idx filenames.
         //       See issue #23. And use the code snippet in issue #23!
-        // TODO: Get a list of parameters and vertical levels by reading the
-        //       bodies of a minimal set of .idx files. See issue #24.
         // TODO: Get the horizontal spatial coordinates. See issue #25.
-        Ok(self.coord_labels_builder.build())
+        Ok((self.coord_labels_builder.build(), self.warnings.to_vec()))
+    }
+}
+
+impl GetCoordLabels for Gefs {
+    async fn get_coord_labels(self) -> anyhow::Result<CoordLabels> {
+        let (coord_labels, _warnings) = self.get_coord_labels_with_warnings().await?;
+        Ok(coord_labels)
     }
 }
 
 /// Convert the first two parts of a path to a reference datetime.
 /// For example, `gefs.20191122/18` becomes 2019-11-22T18:00.
+#[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", ret, err))]
 fn path_to_reference_datetime(path: &object_store::path::Path) -> anyhow::Result<DateTime<Utc>> {
     let parts: Vec<_> = path.parts().take(2).collect();
     let error_context = |s| format!("{s} when parsing path: '{path}'");
@@ -77,8 +325,20 @@ fn path_to_reference_datetime(path: &object_store::path::Path) -> anyhow::Result
     }
 }
 
+/// The inverse of `path_to_reference_datetime`.
+/// For example, 2019-11-22T18:00 becomes `gefs.20191122/18`.
+fn reference_datetime_to_path(reference_datetime: &DateTime<Utc>) -> object_store::path::Path {
+    object_store::path::Path::from_iter([
+        reference_datetime.format("gefs.%Y%m%d").to_string(),
+        format!("{:02}", reference_datetime.hour()),
+    ])
+}
+
 #[cfg(test)]
 mod tests {
+    use std::sync::Arc;
+
+    use object_store::{memory::InMemory, ObjectStore, PutPayload};
 
     use super::*;
 
@@ -92,4 +352,315 @@ mod tests {
         );
         Ok(())
     }
+
+    #[test]
+    fn test_reference_datetime_to_path() -> anyhow::Result<()> {
+        let path = object_store::path::Path::from("gefs.20191122/18");
+        let datetime = path_to_reference_datetime(&path)?;
+        assert_eq!(reference_datetime_to_path(&datetime), path);
+        Ok(())
+    }
+
+    #[cfg(feature = "tracing")]
+    #[tracing_test::traced_test]
+    #[test]
+    fn test_path_to_reference_datetime_emits_span() -> anyhow::Result<()> {
+        let path = object_store::path::Path::from("gefs.20191122/18");
+        path_to_reference_datetime(&path)?;
+        assert!(logs_contain("path_to_reference_datetime"));
+        assert!(logs_contain("gefs.20191122/18"));
+        assert!(logs_contain("2019-11-22T18:00:00Z"));
+        Ok(())
+    }
+
+    const IDX_TEXT: &str = "\
+1:0:d=2017010100:HGT:10 mb:anl:ENS=low-res ctl
+2:50487:d=2017010100:TMP:2 m above ground:anl:ENS=low-res ctl
+";
+
+    async fn put_idx(store: &InMemory, prefix: &str) -> anyhow::Result<()> {
+        let path = object_store::path::Path::from(format!("{prefix}/gec00.t00z.pgrb2aanl.idx"));
+        store.put(&path, PutPayload::from(IDX_TEXT)).await?;
+        Ok(())
+    }
+
+    fn gefs_from_store(store: Arc<InMemory>) -> Gefs {
+        let base_path = object_store::path::Path::default();
+        Gefs {
+            coord_labels_builder: CoordLabelsBuilder::new(
+                store.clone(),
+                base_path.clone(),
+                store,
+                base_path,
+            ),
+            checkpoint: None,
+            cache: None,
+            expected_msg_count: None,
+            strict_decoding: None,
+            listing_strategy: ListingStrategy::default(),
+            cancellation_token: CancellationToken::new(),
+            progress: Progress::new(),
+            warnings: Warnings::new(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_resume_from_checkpoint() -> anyhow::Result<()> {
+        const PREFIXES: [&str; 6] = [
+            "gefs.20170101/00",
+            "gefs.20170101/06",
+            "gefs.20170101/12",
+            "gefs.20170101/18",
+            "gefs.20170102/00",
+            "gefs.20170102/06",
+        ];
+        let store = Arc::new(InMemory::new());
+        for prefix in &PREFIXES[..4] {
+            put_idx(&store, prefix).await?;
+        }
+        // Simulate a failure after processing 4 prefixes, using a prefix which can't be parsed.
+        put_idx(&store, "gefs.20170101/XX").await?;
+
+        let checkpoint_dir = tempfile::tempdir()?;
+        let checkpoint_path = checkpoint_dir.path().join("checkpoint.json");
+        let checkpoint = Checkpoint::new(&checkpoint_path).with_save_every_n_prefixes(1);
+        let interrupted = gefs_from_store(store.clone()).with_checkpoint(checkpoint);
+        assert!(interrupted.get_coord_labels().await.is_err());
+
+        // Fix the problem, and add more prefixes.
+        store
+            .delete(&object_store::path::Path::from(
+                "gefs.20170101/XX/gec00.t00z.pgrb2aanl.idx",
+            ))
+            .await?;
+        for prefix in &PREFIXES[4..] {
+            put_idx(&store, prefix).await?;
+        }
+
+        let checkpoint = Checkpoint::load(&checkpoint_path)?;
+        assert_eq!(checkpoint.n_processed_prefixes(), 4);
+        let resumed = gefs_from_store(store.clone())
+            .with_checkpoint(checkpoint)
+            .get_coord_labels()
+            .await?;
+        let uninterrupted = gefs_from_store(store).get_coord_labels().await?;
+        assert_eq!(resumed, uninterrupted);
+        assert_eq!(resumed.reference_datetime().len(), PREFIXES.len());
+        assert_eq!(
+            Checkpoint::load(&checkpoint_path)?.n_processed_prefixes(),
+            6
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_cancel_then_resume() -> anyhow::Result<()> {
+        const PREFIXES: [&str; 5] = [
+            "gefs.20170101/00",
+            "gefs.20170101/06",
+            "gefs.20170101/12",
+            "gefs.20170101/18",
+            "gefs.20170102/00",
+        ];
+        let store = Arc::new(InMemory::new());
+        for prefix in PREFIXES {
+            put_idx(&store, prefix).await?;
+        }
+
+        // Cancel (like Ctrl-C would) once 2 reference datetimes have been processed:
+        let cancellation_token = CancellationToken::new();
+        let progress = Progress::new()
+            .with_interval(std::time::Duration::ZERO)
+            .with_callback({
+                let cancellation_token = cancellation_token.clone();
+                move |update| {
+                    if update.n_processed == 2 {
+                        cancellation_token.cancel();
+                    }
+                }
+            });
+        let checkpoint_dir = tempfile::tempdir()?;
+        let checkpoint_path = checkpoint_dir.path().join("checkpoint.json");
+        // Only save at the end, to check that the checkpoint is flushed when cancelled.
+        let checkpoint = Checkpoint::new(&checkpoint_path).with_save_every_n_prefixes(100);
+        let (partial, warnings) = gefs_from_store(store.clone())
+            .with_checkpoint(checkpoint)
+            .with_progress(progress)
+            .with_cancellation_token(cancellation_token)
+            .get_coord_labels_with_warnings()
+            .await?;
+        assert_eq!(partial.reference_datetime().len(), 2);
+        assert!(partial.parameter().is_empty());
+        assert_eq!(
+            warnings,
+            [Warning::Cancelled {
+                stage: String::from("reference datetimes"),
+                n_processed: 2,
+                n_total: 5,
+            }]
+        );
+
+        let checkpoint = Checkpoint::load(&checkpoint_path)?;
+        assert_eq!(checkpoint.n_processed_prefixes(), 2);
+        assert_eq!(checkpoint.coord_labels(), Some(&partial));
+        let resumed = gefs_from_store(store.clone())
+            .with_checkpoint(checkpoint)
+            .get_coord_labels()
+            .await?;
+        assert_eq!(resumed, gefs_from_store(store).get_coord_labels().await?);
+        assert_eq!(resumed.reference_datetime().len(), PREFIXES.len());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_duplicate_reference_datetime_is_a_warning() -> anyhow::Result<()> {
+        let store = Arc::new(InMemory::new());
+        put_idx(&store, "gefs.20170101/00").await?;
+        // "0" and "00" both describe midnight:
+        put_idx(&store, "gefs.20170101/0").await?;
+        put_idx(&store, "gefs.20170101/06").await?;
+
+        let (coord_labels, warnings) = gefs_from_store(store)
+            .get_coord_labels_with_warnings()
+            .await?;
+        assert_eq!(coord_labels.reference_datetime().len(), 2);
+        assert_eq!(**coord_labels.parameter(), ["HGT", "TMP"]);
+        assert_eq!(
+            warnings,
+            [Warning::DuplicateReferenceDatetime {
+                path: object_store::path::Path::from("gefs.20170101/00"),
+                reference_datetime: DateTime::parse_from_rfc3339("2017-01-01T00:00:00Z")?.into(),
+            }]
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_gzip_compressed_idx_files_are_listed() -> anyhow::Result<()> {
+        use std::io::Write;
+
+        let store = Arc::new(InMemory::new());
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(IDX_TEXT.as_bytes())?;
+        let path = object_store::path::Path::from("gefs.20170101/00/gec00.t00z.pgrb2aanl.idx.gz");
+        store
+            .put(&path, PutPayload::from(encoder.finish()?))
+            .await?;
+        let coord_labels = gefs_from_store(store).get_coord_labels().await?;
+        assert_eq!(coord_labels.reference_datetime().len(), 1);
+        assert_eq!(**coord_labels.parameter(), ["HGT", "TMP"]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_empty_listing_is_an_error() -> anyhow::Result<()> {
+        let store = Arc::new(InMemory::new());
+        let error = gefs_from_store(store.clone())
+            .get_coord_labels()
+            .await
+            .unwrap_err();
+        assert_eq!(
+            error.downcast_ref::<EmptyListing>(),
+            Some(&EmptyListing {
+                location: String::from("''"),
+                extension: None,
+            })
+        );
+
+        // A reference datetime without any `.idx` files for the control member's analysis:
+        let path = object_store::path::Path::from("gefs.20170101/00/gep01.t00z.pgrb2aanl.idx");
+        store.put(&path, PutPayload::from(IDX_TEXT)).await?;
+        let error = gefs_from_store(store).get_coord_labels().await.unwrap_err();
+        assert_eq!(
+            error.downcast_ref::<EmptyListing>(),
+            Some(&EmptyListing {
+                location: String::from("'gefs.20170101/00/'"),
+                extension: Some("idx"),
+            })
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_cache_only_processes_new_reference_datetimes() -> anyhow::Result<()> {
+        let store = Arc::new(InMemory::new());
+        for prefix in ["gefs.20170101/00", "gefs.20170101/06"] {
+            put_idx(&store, prefix).await?;
+        }
+        let cache_dir = tempfile::tempdir()?;
+        gefs_from_store(store.clone())
+            .with_cache(CoordLabelsCache::new(cache_dir.path()))
+            .get_coord_labels()
+            .await?;
+
+        // A second run with one new reference datetime only processes that reference datetime:
+        put_idx(&store, "gefs.20170101/12").await?;
+        let mut gefs = gefs_from_store(store.clone());
+        let cached = CoordLabelsCache::new(cache_dir.path()).load("''")?.unwrap();
+        assert_eq!(cached.coord_labels.reference_datetime().len(), 2);
+        let (_token, processed_prefixes) = gefs
+            .get_reference_datetimes(&cached.processed_prefixes)
+            .await?;
+        assert_eq!(
+            processed_prefixes,
+            [object_store::path::Path::from("gefs.20170101/12")]
+        );
+        let coord_labels = gefs_from_store(store.clone())
+            .with_cache(CoordLabelsCache::new(cache_dir.path()))
+            .get_coord_labels()
+            .await?;
+        assert_eq!(coord_labels.reference_datetime().len(), 3);
+        assert_eq!(
+            coord_labels,
+            gefs_from_store(store.clone()).get_coord_labels().await?
+        );
+
+        // If nothing has changed then no `.idx` files are read, so a corrupt `.idx` file in the
+        // most recent reference datetime doesn't matter:
+        let path = object_store::path::Path::from("gefs.20170101/12/gec00.t00z.pgrb2aanl.idx");
+        store
+            .put(&path, PutPayload::from("not an idx file"))
+            .await?;
+        let cached_coord_labels = gefs_from_store(store.clone())
+            .with_cache(CoordLabelsCache::new(cache_dir.path()))
+            .get_coord_labels()
+            .await?;
+        assert_eq!(cached_coord_labels, coord_labels);
+        assert!(gefs_from_store(store).get_coord_labels().await.is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_progress_reaches_totals() -> anyhow::Result<()> {
+        let store = Arc::new(InMemory::new());
+        for prefix in ["gefs.20170101/00", "gefs.20170101/06", "gefs.20170101/12"] {
+            put_idx(&store, prefix).await?;
+        }
+        let updates = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let updates_clone = updates.clone();
+        let progress = Progress::new()
+            .with_interval(std::time::Duration::ZERO)
+            .with_callback(move |update| updates_clone.lock().unwrap().push(update.clone()));
+        gefs_from_store(store)
+            .with_progress(progress.clone())
+            .get_coord_labels()
+            .await?;
+
+        let updates = updates.lock().unwrap();
+        let counts: Vec<_> = updates
+            .iter()
+            .map(|update| (update.stage.as_str(), update.n_processed, update.n_total))
+            .collect();
+        assert_eq!(
+            counts,
+            [
+                ("reference datetimes", 1, 3),
+                ("reference datetimes", 2, 3),
+                ("reference datetimes", 3, 3),
+                ("idx files", 1, 1),
+            ]
+        );
+        assert!(progress.update().is_finished());
+        Ok(())
+    }
 }
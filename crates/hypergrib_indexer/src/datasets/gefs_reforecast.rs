@@ -0,0 +1,358 @@
+use anyhow::Context;
+use chrono::{DateTime, NaiveDateTime, Utc};
+use hypergrib::{
+    datasets::gefs_reforecast::GefsReforecast as GefsReforecastLayout, has_ext, store::StoreConfig,
+    CoordLabels, EnsembleMember, EnsembleNaming, GetCoordLabels,
+};
+use tokio_util::sync::CancellationToken;
+
+use crate::{
+    coord_labels_builder::CoordLabelsBuilder,
+    empty_listing::EmptyListing,
+    expected_msg_count::ExpectedMsgCount,
+    fetch_and_parse::fetch_and_parse_idx_files,
+    progress::Progress,
+    strict_decoding::StrictDecoding,
+    warnings::{Warning, Warnings},
+};
+use list_with_depth::list_with_depth;
+
+const BUCKET_URL: &str = "s3://noaa-gefs-retrospective/GEFSv12/reforecast";
+const SKIP_SIGNATURE: bool = true;
+
+/// The GEFS reforecast. See `hypergrib::datasets::gefs_reforecast` for the layout.
+pub struct GefsReforecast {
+    coord_labels_builder: CoordLabelsBuilder,
+    expected_msg_count: Option<ExpectedMsgCount>,
+    strict_decoding: Option<StrictDecoding>,
+    cancellation_token: CancellationToken,
+    progress: Progress,
+    warnings: Warnings,
+}
+
+impl GefsReforecast {
+    pub fn new(store_config: &StoreConfig) -> anyhow::Result<Self> {
+        let coord_labels_builder =
+            CoordLabelsBuilder::new_from_url(BUCKET_URL, SKIP_SIGNATURE, store_config)?;
+        Ok(Self {
+            coord_labels_builder,
+            expected_msg_count: None,
+            strict_decoding: None,
+            cancellation_token: CancellationToken::new(),
+            progress: Progress::new(),
+            warnings: Warnings::new(),
+        })
+    }
+
+    /// Report the progress of indexing to `progress`. By default, progress is printed to stdout
+    /// every `progress::DEFAULT_INTERVAL`.
+    pub fn with_progress(mut self, progress: Progress) -> Self {
+        self.progress = progress;
+        self
+    }
+
+    /// Check the number of messages in each `.idx` file which is read.
+    pub fn with_expected_msg_count(mut self, expected_msg_count: ExpectedMsgCount) -> Self {
+        self.expected_msg_count = Some(expected_msg_count);
+        self
+    }
+
+    /// Stop indexing with an error if any `.idx` record which is read has a parameter
+    /// abbreviation or a vertical level which can't be decoded.
+    pub fn with_strict_decoding(mut self, strict_decoding: StrictDecoding) -> Self {
+        self.strict_decoding = Some(strict_decoding);
+        self
+    }
+
+    /// Stop indexing cleanly when `cancellation_token` is cancelled (e.g. on Ctrl-C).
+    /// `get_coord_labels_with_warnings` then returns the partial coord labels with a
+    /// `Warning::Cancelled`, without reading any `.idx` files.
+    pub fn with_cancellation_token(mut self, cancellation_token: CancellationToken) -> Self {
+        self.cancellation_token = cancellation_token;
+        self
+    }
+
+    /// The reference datetimes are extracted from the second part of the path, for example:
+    /// `2000/2000010100/`. Duplicate reference datetimes are recorded as warnings. Stops early if
+    /// indexing is cancelled.
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip(self), err))]
+    async fn get_reference_datetimes(&mut self) -> anyhow::Result<()> {
+        let store = self.coord_labels_builder.idx_store().clone();
+        let prefix = self.coord_labels_builder.idx_base_path();
+        let list = list_with_depth(store, Some(prefix), 1).await?;
+        if list.common_prefixes.is_empty() {
+            return Err(EmptyListing {
+                location: self.coord_labels_builder.idx_location(prefix),
+                extension: None,
+            }
+            .into());
+        }
+        self.progress
+            .start_stage("reference datetimes", list.common_prefixes.len());
+        for prefix in list.common_prefixes.iter() {
+            if self.cancellation_token.is_cancelled() {
+                break;
+            }
+            let datetime = path_to_reference_datetime(prefix)?;
+            let datetime_is_unique = self
+                .coord_labels_builder
+                .insert_reference_datetime(datetime);
+            if !datetime_is_unique {
+                self.warnings.push(Warning::DuplicateReferenceDatetime {
+                    path: prefix.clone(),
+                    reference_datetime: datetime,
+                });
+            }
+            self.progress.inc(1);
+        }
+        Ok(())
+    }
+
+    /// Get the parameters and vertical levels by reading the `.idx` files of the control member
+    /// of the most recent reference datetime, for the first 10 days. Each of these `.idx` files
+    /// describes one parameter at one "level type", for all forecast steps.
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip(self), err))]
+    async fn get_parameters_and_vertical_levels(&mut self) -> anyhow::Result<()> {
+        let Some(reference_datetime) = self.coord_labels_builder.reference_datetime().last() else {
+            return Ok(());
+        };
+        let control = GefsReforecastLayout::format_member(&EnsembleMember::Control)
+            .expect("The GEFS reforecast has a control member");
+        let store = self.coord_labels_builder.idx_store().clone();
+        let prefix = object_store::path::Path::from_iter(
+            self.coord_labels_builder.idx_base_path().parts().chain([
+                reference_datetime.format("%Y").to_string().into(),
+                reference_datetime.format("%Y%m%d%H").to_string().into(),
+                control.into(),
+                "Days:1-10".into(),
+            ]),
+        );
+        let list = store.list_with_delimiter(Some(&prefix)).await?;
+        let paths: Vec<_> = list
+            .objects
+            .into_iter()
+            .map(|meta| meta.location)
+            .filter(|path| has_ext(path, "idx"))
+            .collect();
+        if paths.is_empty() {
+            return Err(EmptyListing {
+                location: self.coord_labels_builder.idx_location(&prefix),
+                extension: Some("idx"),
+            }
+            .into());
+        }
+        for (_path, records) in fetch_and_parse_idx_files(
+            store,
+            paths,
+            self.expected_msg_count.clone(),
+            self.strict_decoding,
+            &self.warnings,
+            &self.progress,
+        )
+        .await?
+        {
+            for record in records {
+                self.coord_labels_builder.insert_parameter(record.parameter);
+                self.coord_labels_builder
+                    .insert_vertical_level(record.vertical_level);
+                self.coord_labels_builder.insert_statistic(record.statistic);
+            }
+        }
+        Ok(())
+    }
+
+    /// Like `get_coord_labels`, but also returns the non-fatal problems found during indexing.
+    /// Each warning is also printed to stderr as soon as it's found.
+    ///
+    /// Returns an `EmptyListing` error if no reference datetimes or no `.idx` files are found,
+    /// which usually means that the prefix is wrong, or that requests need to be signed.
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip(self), err))]
+    pub async fn get_coord_labels_with_warnings(
+        mut self,
+    ) -> anyhow::Result<(CoordLabels, Vec<Warning>)> {
+        self.get_reference_datetimes().await?;
+        println!(
+            "{}",
+            self.coord_labels_builder.describe_reference_datetimes()
+        );
+        if self.cancellation_token.is_cancelled() {
+            self.warnings.push(Warning::cancelled(&self.progress));
+            return Ok((self.coord_labels_builder.build(), self.warnings.to_vec()));
+        }
+        self.get_parameters_and_vertical_levels().await?;
+
+        // TODO: Get the ensemble members (which vary by day of the week) and forecast steps.
+        Ok((self.coord_labels_builder.build(), self.warnings.to_vec()))
+    }
+}
+
+impl GetCoordLabels for GefsReforecast {
+    async fn get_coord_labels(self) -> anyhow::Result<CoordLabels> {
+        let (coord_labels, _warnings) = self.get_coord_labels_with_warnings().await?;
+        Ok(coord_labels)
+    }
+}
+
+/// Convert the last part of a path to a reference datetime.
+/// For example, `GEFSv12/reforecast/2019/2019112200` becomes 2019-11-22T00:00.
+fn path_to_reference_datetime(path: &object_store::path::Path) -> anyhow::Result<DateTime<Utc>> {
+    let init = path
+        .filename()
+        .with_context(|| format!("Empty path: '{path}'"))?;
+    let datetime =
+        NaiveDateTime::parse_from_str(&format!("{init}00"), "%Y%m%d%H%M").with_context(|| {
+            format!("Failed to convert NWP reference datetime when parsing path: '{path}'")
+        })?;
+    Ok(datetime.and_utc())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use hypergrib::Level;
+    use object_store::{memory::InMemory, ObjectStore, PutPayload};
+
+    use super::*;
+
+    #[test]
+    fn test_path_to_reference_datetime() -> anyhow::Result<()> {
+        let path = object_store::path::Path::from("GEFSv12/reforecast/2019/2019112200");
+        assert_eq!(
+            path_to_reference_datetime(&path)?,
+            DateTime::parse_from_rfc3339("2019-11-22T00:00:00Z")?
+        );
+        assert!(path_to_reference_datetime(&object_store::path::Path::from("2019/c00")).is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_get_coord_labels() -> anyhow::Result<()> {
+        let store = Arc::new(InMemory::new());
+        let files = [
+            (
+                "2000/2000010100/c00/Days:1-10/tmp_2m_2000010100_c00.grib2.idx",
+                "1:0:d=2000010100:TMP:2 m above ground:3 hour fcst:ENS=low-res ctl\n",
+            ),
+            (
+                "2000/2000010200/c00/Days:1-10/tmp_2m_2000010200_c00.grib2.idx",
+                "1:0:d=2000010200:TMP:2 m above ground:3 hour fcst:ENS=low-res ctl\n",
+            ),
+            (
+                "2000/2000010200/c00/Days:1-10/hgt_pres_2000010200_c00.grib2.idx",
+                "1:0:d=2000010200:HGT:850 mb:3 hour fcst:ENS=low-res ctl\n",
+            ),
+            // Only the first 10 days of the control member are read:
+            (
+                "2000/2000010200/c00/Days:10-16/ugrd_hgt_2000010200_c00.grib2.idx",
+                "1:0:d=2000010200:UGRD:10 m above ground:246 hour fcst:ENS=low-res ctl\n",
+            ),
+        ];
+        for (path, idx_text) in files {
+            store
+                .put(
+                    &object_store::path::Path::from(path),
+                    PutPayload::from(idx_text),
+                )
+                .await?;
+        }
+        let base_path = object_store::path::Path::default();
+        let dataset = GefsReforecast {
+            coord_labels_builder: CoordLabelsBuilder::new(
+                store.clone(),
+                base_path.clone(),
+                store,
+                base_path,
+            ),
+            expected_msg_count: None,
+            strict_decoding: None,
+            cancellation_token: CancellationToken::new(),
+            progress: Progress::new(),
+            warnings: Warnings::new(),
+        };
+        let coord_labels = dataset.get_coord_labels().await?;
+        assert_eq!(coord_labels.reference_datetime().len(), 2);
+        assert_eq!(**coord_labels.parameter(), ["HGT", "TMP"]);
+        assert_eq!(
+            **coord_labels.vertical_level(),
+            [Level::HeightAboveGround(2), Level::Isobaric(85_000)]
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_gzip_compressed_idx_files_are_listed() -> anyhow::Result<()> {
+        use std::io::Write;
+
+        let store = Arc::new(InMemory::new());
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder
+            .write_all(b"1:0:d=2000010100:TMP:2 m above ground:3 hour fcst:ENS=low-res ctl\n")?;
+        store
+            .put(
+                &object_store::path::Path::from(
+                    "2000/2000010100/c00/Days:1-10/tmp_2m_2000010100_c00.grib2.idx.gz",
+                ),
+                PutPayload::from(encoder.finish()?),
+            )
+            .await?;
+        let base_path = object_store::path::Path::default();
+        let dataset = GefsReforecast {
+            coord_labels_builder: CoordLabelsBuilder::new(
+                store.clone(),
+                base_path.clone(),
+                store,
+                base_path,
+            ),
+            expected_msg_count: None,
+            strict_decoding: None,
+            cancellation_token: CancellationToken::new(),
+            progress: Progress::new(),
+            warnings: Warnings::new(),
+        };
+        let coord_labels = dataset.get_coord_labels().await?;
+        assert_eq!(coord_labels.reference_datetime().len(), 1);
+        assert_eq!(**coord_labels.parameter(), ["TMP"]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_duplicate_reference_datetime_is_a_warning() -> anyhow::Result<()> {
+        let store = Arc::new(InMemory::new());
+        let idx_text = "1:0:d=2000010100:TMP:2 m above ground:3 hour fcst:ENS=low-res ctl\n";
+        // The same reference datetime, misfiled under the wrong year:
+        for path in [
+            "2000/2000010100/c00/Days:1-10/tmp_2m_2000010100_c00.grib2.idx",
+            "2001/2000010100/c00/Days:1-10/tmp_2m_2000010100_c00.grib2.idx",
+        ] {
+            store
+                .put(
+                    &object_store::path::Path::from(path),
+                    PutPayload::from(idx_text),
+                )
+                .await?;
+        }
+        let base_path = object_store::path::Path::default();
+        let dataset = GefsReforecast {
+            coord_labels_builder: CoordLabelsBuilder::new(
+                store.clone(),
+                base_path.clone(),
+                store,
+                base_path,
+            ),
+            expected_msg_count: None,
+            strict_decoding: None,
+            cancellation_token: CancellationToken::new(),
+            progress: Progress::new(),
+            warnings: Warnings::new(),
+        };
+        let (coord_labels, warnings) = dataset.get_coord_labels_with_warnings().await?;
+        assert_eq!(coord_labels.reference_datetime().len(), 1);
+        assert_eq!(**coord_labels.parameter(), ["TMP"]);
+        assert!(matches!(
+            &warnings[..],
+            [Warning::DuplicateReferenceDatetime { path, .. }] if path.as_ref() == "2001/2000010100"
+        ));
+        Ok(())
+    }
+}
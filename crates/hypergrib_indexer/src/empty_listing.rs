@@ -0,0 +1,51 @@
+//! Detect listings which find nothing.
+//!
+//! A listing which finds nothing usually means that the prefix is wrong, or that the bucket
+//! requires signed requests. Without this check, indexing would "succeed" and silently produce
+//! empty coordinate labels.
+
+use std::fmt;
+
+/// Listing a location found no matching objects.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EmptyListing {
+    /// The location which was listed, e.g. `s3://noaa-gefs-pds/gefs.20241008/00/`.
+    pub location: String,
+    /// The file extension which was searched for (e.g. `idx`). `None` if the listing was
+    /// searching for directories.
+    pub extension: Option<&'static str>,
+}
+
+impl fmt::Display for EmptyListing {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.extension {
+            Some(extension) => write!(f, "Found 0 .{extension} files")?,
+            None => write!(f, "Found 0 directories")?,
+        }
+        write!(
+            f,
+            " under {}. Check the prefix, and whether requests to this bucket need to be signed.",
+            self.location
+        )
+    }
+}
+
+impl std::error::Error for EmptyListing {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_display() {
+        let error = EmptyListing {
+            location: String::from("s3://noaa-gefs-pds/gefs.20241008/00/"),
+            extension: Some("idx"),
+        };
+        assert_eq!(
+            error.to_string(),
+            "Found 0 .idx files under s3://noaa-gefs-pds/gefs.20241008/00/. Check the prefix, \
+             and whether requests to this bucket need to be signed."
+        );
+    }
+}
@@ -0,0 +1,105 @@
+//! Check that each `.idx` file contains the expected number of messages. An `.idx` file with too
+//! few messages may be the result of a partial upload.
+
+use object_store::path::Path;
+
+use crate::warnings::{Warning, Warnings};
+
+/// What to do when an `.idx` file doesn't contain the expected number of messages.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum OnUnexpectedMsgCount {
+    /// Record a warning, and continue indexing.
+    #[default]
+    Warn,
+    /// Stop indexing and return an error.
+    Error,
+}
+
+/// The expected number of messages in each `.idx` file.
+///
+/// Note that the entry counts in the docs of `hypergrib::datasets::gefs::Version` are the number
+/// of files in each directory, not the number of messages in each `.idx` file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExpectedMsgCount {
+    expected: usize,
+    /// The number of messages may differ from `expected` by up to `tolerance` without
+    /// triggering a warning or error.
+    tolerance: usize,
+    on_unexpected: OnUnexpectedMsgCount,
+}
+
+/// The outcome of a successful `ExpectedMsgCount::check`.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum MsgCountCheck {
+    Ok,
+    /// The number of messages was unexpected, and a warning was recorded.
+    Warned,
+}
+
+impl ExpectedMsgCount {
+    pub fn new(expected: usize, tolerance: usize, on_unexpected: OnUnexpectedMsgCount) -> Self {
+        Self {
+            expected,
+            tolerance,
+            on_unexpected,
+        }
+    }
+
+    /// Check that `n_messages` is within `tolerance` of `expected`. Unexpected counts are either
+    /// recorded in `warnings` or returned as an error, depending on `on_unexpected`.
+    pub(crate) fn check(
+        &self,
+        path: &Path,
+        n_messages: usize,
+        warnings: &Warnings,
+    ) -> anyhow::Result<MsgCountCheck> {
+        if n_messages.abs_diff(self.expected) <= self.tolerance {
+            return Ok(MsgCountCheck::Ok);
+        }
+        let warning = Warning::UnexpectedMsgCount {
+            path: path.clone(),
+            n_messages,
+            expected: self.expected,
+            tolerance: self.tolerance,
+        };
+        match self.on_unexpected {
+            OnUnexpectedMsgCount::Warn => {
+                warnings.push(warning);
+                Ok(MsgCountCheck::Warned)
+            }
+            OnUnexpectedMsgCount::Error => Err(anyhow::format_err!("{warning}")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check() -> anyhow::Result<()> {
+        let path = Path::from("gefs.20241010/00/atmos/pgrb2ap5/gec00.t00z.pgrb2a.0p50.f000.idx");
+        let warnings = Warnings::new();
+        let expected = ExpectedMsgCount::new(80, 2, OnUnexpectedMsgCount::Warn);
+        assert_eq!(expected.check(&path, 80, &warnings)?, MsgCountCheck::Ok);
+        assert_eq!(expected.check(&path, 78, &warnings)?, MsgCountCheck::Ok);
+        assert_eq!(expected.check(&path, 82, &warnings)?, MsgCountCheck::Ok);
+        assert!(warnings.is_empty());
+        assert_eq!(expected.check(&path, 40, &warnings)?, MsgCountCheck::Warned);
+        assert_eq!(
+            warnings.to_vec(),
+            [Warning::UnexpectedMsgCount {
+                path: path.clone(),
+                n_messages: 40,
+                expected: 80,
+                tolerance: 2
+            }]
+        );
+
+        let expected = ExpectedMsgCount::new(80, 2, OnUnexpectedMsgCount::Error);
+        assert_eq!(expected.check(&path, 80, &warnings)?, MsgCountCheck::Ok);
+        assert!(expected.check(&path, 40, &warnings).is_err());
+        assert_eq!(warnings.len(), 1);
+        Ok(())
+    }
+}
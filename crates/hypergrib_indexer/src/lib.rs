@@ -1,2 +1,11 @@
+pub mod checkpoint;
 pub(crate) mod coord_labels_builder;
+pub mod coord_labels_cache;
 pub mod datasets;
+pub mod empty_listing;
+pub mod expected_msg_count;
+pub(crate) mod fetch_and_parse;
+pub mod list_skipping_dirs;
+pub mod progress;
+pub mod strict_decoding;
+pub mod warnings;
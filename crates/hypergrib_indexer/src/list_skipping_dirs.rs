@@ -0,0 +1,239 @@
+//! Recursively list a prefix, without descending into directories which contain no GRIB data.
+//!
+//! Some datasets have subdirectories which contain no GRIB data (e.g. GEFS's `atmos/bufr`,
+//! `atmos/init`, and `wave/station`). Listing these subdirectories wastes requests, and their
+//! files may be mistaken for GRIB files. So each dataset defines the directory names to skip.
+//!
+//! Listing a bucket with hundreds of thousands of objects requires many LIST requests. Which
+//! [`ListingStrategy`] needs the fewest requests depends on the layout of the bucket.
+
+use futures_util::{future::BoxFuture, TryStreamExt};
+use object_store::{path::Path, ObjectMeta, ObjectStore};
+
+/// How to list all the objects below a prefix.
+///
+/// Note that `object_store` doesn't expose the page size of LIST requests. S3 returns at most
+/// 1,000 objects per page (which is also S3's default), so the page size can't be increased.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum ListingStrategy {
+    /// Send (at least) one delimited LIST request per directory. Directories which contain no
+    /// GRIB data aren't listed at all. Best when the skipped directories contain many objects.
+    #[default]
+    Delimited,
+    /// Send paginated LIST requests for every object below the prefix, and then discard the
+    /// objects in skipped directories. Best when there are many directories, each of which
+    /// contains few objects.
+    Flat,
+}
+
+/// List all objects below `prefix` using `strategy`, skipping any directory whose name is in
+/// `skip_dirs`.
+pub(crate) async fn list_objects(
+    store: &dyn ObjectStore,
+    prefix: &Path,
+    skip_dirs: &[&str],
+    strategy: ListingStrategy,
+) -> object_store::Result<Vec<ObjectMeta>> {
+    match strategy {
+        ListingStrategy::Delimited => list_skipping_dirs(store, prefix, skip_dirs).await,
+        ListingStrategy::Flat => {
+            let n_prefix_parts = prefix.parts().count();
+            store
+                .list(Some(prefix))
+                .try_filter(|meta| {
+                    // Only check the directories below `prefix` (and not the filename).
+                    let mut parts: Vec<_> = meta.location.parts().skip(n_prefix_parts).collect();
+                    parts.pop();
+                    let in_skipped_dir =
+                        parts.iter().any(|part| skip_dirs.contains(&part.as_ref()));
+                    futures_util::future::ready(!in_skipped_dir)
+                })
+                .try_collect()
+                .await
+        }
+    }
+}
+
+/// List all objects below `prefix`, skipping any directory whose name is in `skip_dirs`.
+pub(crate) fn list_skipping_dirs<'a>(
+    store: &'a dyn ObjectStore,
+    prefix: &'a Path,
+    skip_dirs: &'a [&'a str],
+) -> BoxFuture<'a, object_store::Result<Vec<ObjectMeta>>> {
+    // Recursive async functions must be boxed.
+    Box::pin(async move {
+        let list_result = store.list_with_delimiter(Some(prefix)).await?;
+        let mut objects = list_result.objects;
+        for common_prefix in list_result.common_prefixes {
+            let dir_name = common_prefix.filename().unwrap_or_default();
+            if skip_dirs.contains(&dir_name) {
+                #[cfg(feature = "tracing")]
+                tracing::debug!(%common_prefix, "Skipping directory which contains no GRIB data");
+                continue;
+            }
+            objects.extend(list_skipping_dirs(store, &common_prefix, skip_dirs).await?);
+        }
+        Ok(objects)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fmt,
+        sync::atomic::{AtomicUsize, Ordering},
+    };
+
+    use async_trait::async_trait;
+    use futures_util::stream::BoxStream;
+    use object_store::{
+        memory::InMemory, GetOptions, GetResult, ListResult, MultipartUpload, PutMultipartOpts,
+        PutOptions, PutPayload, PutResult,
+    };
+
+    use super::*;
+
+    /// An `InMemory` store which counts LIST requests.
+    #[derive(Debug, Default)]
+    struct CountingStore {
+        inner: InMemory,
+        n_list_requests: AtomicUsize,
+    }
+
+    impl fmt::Display for CountingStore {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "CountingStore({})", self.inner)
+        }
+    }
+
+    #[async_trait]
+    impl ObjectStore for CountingStore {
+        async fn put_opts(
+            &self,
+            location: &Path,
+            payload: PutPayload,
+            opts: PutOptions,
+        ) -> object_store::Result<PutResult> {
+            self.inner.put_opts(location, payload, opts).await
+        }
+
+        async fn put_multipart_opts(
+            &self,
+            location: &Path,
+            opts: PutMultipartOpts,
+        ) -> object_store::Result<Box<dyn MultipartUpload>> {
+            self.inner.put_multipart_opts(location, opts).await
+        }
+
+        async fn get_opts(
+            &self,
+            location: &Path,
+            options: GetOptions,
+        ) -> object_store::Result<GetResult> {
+            self.inner.get_opts(location, options).await
+        }
+
+        async fn delete(&self, location: &Path) -> object_store::Result<()> {
+            self.inner.delete(location).await
+        }
+
+        /// Counts one request per (simulated) page of 1,000 objects, like S3.
+        fn list(&self, prefix: Option<&Path>) -> BoxStream<'_, object_store::Result<ObjectMeta>> {
+            self.n_list_requests.fetch_add(1, Ordering::SeqCst);
+            let mut n_objects = 0;
+            Box::pin(self.inner.list(prefix).inspect_ok(move |_| {
+                n_objects += 1;
+                if n_objects % 1_000 == 1 && n_objects > 1 {
+                    self.n_list_requests.fetch_add(1, Ordering::SeqCst);
+                }
+            }))
+        }
+
+        async fn list_with_delimiter(
+            &self,
+            prefix: Option<&Path>,
+        ) -> object_store::Result<ListResult> {
+            self.n_list_requests.fetch_add(1, Ordering::SeqCst);
+            self.inner.list_with_delimiter(prefix).await
+        }
+
+        async fn copy(&self, from: &Path, to: &Path) -> object_store::Result<()> {
+            self.inner.copy(from, to).await
+        }
+
+        async fn copy_if_not_exists(&self, from: &Path, to: &Path) -> object_store::Result<()> {
+            self.inner.copy_if_not_exists(from, to).await
+        }
+    }
+
+    #[tokio::test]
+    async fn test_list_skipping_dirs() -> anyhow::Result<()> {
+        let store = InMemory::new();
+        let grib_paths = [
+            "gefs.20241008/00/atmos/pgrb2ap5/gec00.t00z.pgrb2a.0p50.f000.idx",
+            "gefs.20241008/00/atmos/pgrb2bp5/gec00.t00z.pgrb2b.0p50.f000.idx",
+            "gefs.20241008/00/chem/pgrb2ap25/gefs.chem.t00z.a2d_0p25.f000.grib2.idx",
+            "gefs.20241008/00/wave/gridded/gefs.wave.t00z.c00.global.0p25.f000.grib2.idx",
+        ];
+        let non_grib_paths = [
+            "gefs.20241008/00/atmos/bufr/gefs.t00z.bufrsnd.tar.gz",
+            "gefs.20241008/00/atmos/init/c00/gfs_data.tile1.nc",
+            "gefs.20241008/00/wave/station/gefs.wave.t00z.spec_tar.gz",
+        ];
+        for path in grib_paths.iter().chain(non_grib_paths.iter()) {
+            store.put(&Path::from(*path), PutPayload::new()).await?;
+        }
+
+        let prefix = Path::from("gefs.20241008/00");
+        let mut listed: Vec<String> = list_skipping_dirs(
+            &store,
+            &prefix,
+            hypergrib::datasets::gefs::Gefs::NON_GRIB_DIRS,
+        )
+        .await?
+        .into_iter()
+        .map(|meta| meta.location.to_string())
+        .collect();
+        listed.sort();
+        assert_eq!(listed, grib_paths);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_count_list_requests() -> anyhow::Result<()> {
+        // A few directories of GRIB data, each containing many objects, and a skipped directory
+        // which also contains many objects.
+        let store = CountingStore::default();
+        for dir in ["pgrb2ap5", "pgrb2bp5", "bufr"] {
+            for i in 0..1_500 {
+                let path = Path::from(format!("gefs.20241008/00/atmos/{dir}/{i:04}.idx"));
+                store.put(&path, PutPayload::new()).await?;
+            }
+        }
+        let prefix = Path::from("gefs.20241008/00");
+        let skip_dirs = hypergrib::datasets::gefs::Gefs::NON_GRIB_DIRS;
+
+        let mut delimited: Vec<_> =
+            list_objects(&store, &prefix, skip_dirs, ListingStrategy::Delimited)
+                .await?
+                .into_iter()
+                .map(|meta| meta.location)
+                .collect();
+        // `00/`, `00/atmos/`, `00/atmos/pgrb2ap5/`, and `00/atmos/pgrb2bp5/`:
+        assert_eq!(store.n_list_requests.swap(0, Ordering::SeqCst), 4);
+
+        let mut flat: Vec<_> = list_objects(&store, &prefix, skip_dirs, ListingStrategy::Flat)
+            .await?
+            .into_iter()
+            .map(|meta| meta.location)
+            .collect();
+        // 4,500 objects (including the skipped directory) need 5 pages of 1,000 objects:
+        assert_eq!(store.n_list_requests.swap(0, Ordering::SeqCst), 5);
+
+        delimited.sort();
+        flat.sort();
+        assert_eq!(delimited.len(), 3_000);
+        assert_eq!(delimited, flat);
+        Ok(())
+    }
+}
@@ -1,6 +1,17 @@
-use clap::{Parser, ValueEnum};
-use hypergrib::GetCoordLabels;
-use hypergrib_indexer::datasets::gefs::Gefs;
+use std::{path::PathBuf, time::Duration};
+
+use clap::{error::ErrorKind, CommandFactory, Parser, ValueEnum};
+use hypergrib::{datasets::gefs::Gefs as GefsLayout, store::StoreConfig};
+use hypergrib_indexer::{
+    checkpoint::Checkpoint,
+    coord_labels_cache::CoordLabelsCache,
+    datasets::{Gefs, GefsReforecast},
+    expected_msg_count::{ExpectedMsgCount, OnUnexpectedMsgCount},
+    list_skipping_dirs::ListingStrategy,
+    progress::Progress,
+    strict_decoding::StrictDecoding,
+};
+use tokio_util::sync::CancellationToken;
 
 /// Create a manifest from GRIB `.idx` files.
 #[derive(Parser, Debug)]
@@ -8,6 +19,55 @@ use hypergrib_indexer::datasets::gefs::Gefs;
 struct Args {
     #[arg(value_enum)]
     dataset: DatasetName,
+
+    /// Periodically save the progress of indexing to this local file.
+    #[arg(long, conflicts_with = "resume")]
+    checkpoint: Option<PathBuf>,
+
+    /// Resume indexing from a checkpoint file written by a previous (interrupted) run.
+    /// Progress will continue to be saved to this file.
+    #[arg(long)]
+    resume: Option<PathBuf>,
+
+    /// Cache the coord labels in this local directory. Later runs only process the reference
+    /// datetimes which are new since the last run.
+    #[arg(long)]
+    cache_dir: Option<PathBuf>,
+
+    /// The expected number of messages in each `.idx` file. If set, then each `.idx` file
+    /// which is read is checked, to catch partial uploads.
+    #[arg(long)]
+    expected_msg_count: Option<usize>,
+
+    /// The number of messages in each `.idx` file may differ from `--expected-msg-count` by up
+    /// to this many messages.
+    #[arg(long, default_value_t = 0, requires = "expected_msg_count")]
+    msg_count_tolerance: usize,
+
+    /// What to do when an `.idx` file doesn't contain the expected number of messages.
+    #[arg(long, value_enum, default_value_t, requires = "expected_msg_count")]
+    on_unexpected_msg_count: OnUnexpectedMsgCount,
+
+    /// Stop indexing with an error if any parameter abbreviation or vertical level in the `.idx`
+    /// files can't be decoded, instead of keeping the raw abbreviation or level. Use this to find
+    /// gaps in the GRIB tables.
+    #[arg(long)]
+    strict: bool,
+
+    /// How to list the objects below each reference datetime. `delimited` avoids listing
+    /// directories which contain no GRIB data. `flat` needs fewer LIST requests when there are
+    /// many directories which each contain few objects. Defaults to `delimited`. Only supported
+    /// for `gefs`.
+    #[arg(long, value_enum)]
+    listing_strategy: Option<ListingStrategy>,
+
+    /// Print the progress of indexing (the count, rate, and ETA of each stage) at most once every
+    /// this many seconds.
+    #[arg(long, default_value_t = 10)]
+    progress_interval: u64,
+
+    #[command(flatten)]
+    store_config: StoreConfig,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, ValueEnum)]
@@ -17,19 +77,127 @@ enum DatasetName {
     /// generates 21 separate forecasts (ensemble members). See:
     /// https://www.ncei.noaa.gov/products/weather-climate-models/global-ensemble-forecast
     Gefs,
+    /// The GEFS reforecast: 20 years of GEFSv12 reforecasts. See:
+    /// https://registry.opendata.aws/noaa-gefs-reforecast
+    GefsReforecast,
 }
 
 #[tokio::main]
 pub async fn main() -> anyhow::Result<()> {
     let args = Args::parse();
+    if args.dataset == DatasetName::GefsReforecast && args.listing_strategy.is_some() {
+        Args::command()
+            .error(
+                ErrorKind::ArgumentConflict,
+                "--listing-strategy is not supported for gefs-reforecast",
+            )
+            .exit();
+    }
+
+    #[cfg(feature = "tracing")]
+    tracing_subscriber::fmt()
+        .with_max_level(tracing::Level::DEBUG)
+        .with_span_events(tracing_subscriber::fmt::format::FmtSpan::CLOSE)
+        .init();
 
     println!("Loading dataset {:?}", args.dataset);
 
-    let dataset = match args.dataset {
-        DatasetName::Gefs => Gefs::new()?,
+    let checkpoint = match (args.checkpoint, args.resume) {
+        (Some(path), _) => Some(Checkpoint::new(path)),
+        (None, Some(path)) => {
+            let checkpoint = Checkpoint::load(path)?;
+            println!(
+                "Resuming from {:?}: {} prefixes already processed",
+                checkpoint.path(),
+                checkpoint.n_processed_prefixes()
+            );
+            Some(checkpoint)
+        }
+        (None, None) => None,
     };
 
-    let coord_labels = dataset.get_coord_labels().await.expect("get_coord_labels");
+    let expected_msg_count = args.expected_msg_count.map(|expected_msg_count| {
+        ExpectedMsgCount::new(
+            expected_msg_count,
+            args.msg_count_tolerance,
+            args.on_unexpected_msg_count,
+        )
+    });
+
+    // Both GEFS datasets are produced by NCEP, which only has one local table.
+    let strict_decoding = args
+        .strict
+        .then(|| StrictDecoding::new(GefsLayout::ORIGINATING_CENTER, u8::MAX));
+
+    let progress = Progress::new().with_interval(Duration::from_secs(args.progress_interval));
+
+    // The first Ctrl-C stops indexing cleanly. The second Ctrl-C exits immediately.
+    let cancellation_token = CancellationToken::new();
+    tokio::spawn({
+        let cancellation_token = cancellation_token.clone();
+        async move {
+            if tokio::signal::ctrl_c().await.is_ok() {
+                eprintln!("Stopping cleanly... Press Ctrl-C again to exit immediately.");
+                cancellation_token.cancel();
+                if tokio::signal::ctrl_c().await.is_ok() {
+                    std::process::exit(130);
+                }
+            }
+        }
+    });
+
+    let (coord_labels, warnings) = match args.dataset {
+        DatasetName::Gefs => {
+            let dataset = Gefs::new(&args.store_config)?
+                .with_listing_strategy(args.listing_strategy.unwrap_or_default())
+                .with_cancellation_token(cancellation_token)
+                .with_progress(progress);
+            let dataset = match checkpoint {
+                Some(checkpoint) => dataset.with_checkpoint(checkpoint),
+                None => dataset,
+            };
+            let dataset = match args.cache_dir {
+                Some(cache_dir) => dataset.with_cache(CoordLabelsCache::new(cache_dir)),
+                None => dataset,
+            };
+            let dataset = match expected_msg_count {
+                Some(expected_msg_count) => dataset.with_expected_msg_count(expected_msg_count),
+                None => dataset,
+            };
+            let dataset = match strict_decoding {
+                Some(strict_decoding) => dataset.with_strict_decoding(strict_decoding),
+                None => dataset,
+            };
+            dataset.get_coord_labels_with_warnings().await
+        }
+        DatasetName::GefsReforecast => {
+            if checkpoint.is_some() {
+                anyhow::bail!(
+                    "--checkpoint and --resume are not yet supported for {:?}",
+                    args.dataset
+                );
+            }
+            if args.cache_dir.is_some() {
+                anyhow::bail!("--cache-dir is not yet supported for {:?}", args.dataset);
+            }
+            let dataset = GefsReforecast::new(&args.store_config)?
+                .with_cancellation_token(cancellation_token)
+                .with_progress(progress);
+            let dataset = match expected_msg_count {
+                Some(expected_msg_count) => dataset.with_expected_msg_count(expected_msg_count),
+                None => dataset,
+            };
+            let dataset = match strict_decoding {
+                Some(strict_decoding) => dataset.with_strict_decoding(strict_decoding),
+                None => dataset,
+            };
+            dataset.get_coord_labels_with_warnings().await
+        }
+    }
+    .expect("get_coord_labels");
+    if !warnings.is_empty() {
+        println!("Finished indexing with {} warnings.", warnings.len());
+    }
     // TODO: Write the coord labels to a metadata file. See:
     // https://github.com/JackKelly/hypergrib/discussions/17
 
@@ -0,0 +1,240 @@
+//! Report the progress of long indexing runs.
+//!
+//! Indexing runs in stages (e.g. listing reference datetimes, then reading `.idx` files). At the
+//! start of each stage, the number of items to process is known. [`Progress`] counts the items as
+//! they're processed, and periodically hands a [`ProgressUpdate`] (with the rate and an ETA) to a
+//! callback. By default, updates are printed to stdout.
+
+use std::{
+    fmt,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+/// The default minimum time between two updates of the same stage.
+pub const DEFAULT_INTERVAL: Duration = Duration::from_secs(10);
+
+/// A snapshot of the progress of one stage of indexing.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProgressUpdate {
+    pub stage: String,
+    pub n_processed: usize,
+    pub n_total: usize,
+    /// The time since the start of this stage.
+    pub elapsed: Duration,
+}
+
+impl ProgressUpdate {
+    /// Items processed per second.
+    pub fn rate(&self) -> f64 {
+        let secs = self.elapsed.as_secs_f64();
+        if secs > 0.0 {
+            self.n_processed as f64 / secs
+        } else {
+            0.0
+        }
+    }
+
+    /// The estimated time until this stage finishes, assuming that the rate stays constant.
+    /// Returns `None` if nothing has been processed yet.
+    pub fn eta(&self) -> Option<Duration> {
+        let n_remaining = self.n_total.saturating_sub(self.n_processed);
+        if n_remaining == 0 {
+            return Some(Duration::ZERO);
+        }
+        let rate = self.rate();
+        (rate > 0.0).then(|| Duration::from_secs_f64(n_remaining as f64 / rate))
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.n_processed >= self.n_total
+    }
+}
+
+impl fmt::Display for ProgressUpdate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let percent = if self.n_total > 0 {
+            100.0 * self.n_processed as f64 / self.n_total as f64
+        } else {
+            100.0
+        };
+        write!(
+            f,
+            "{}: {}/{} ({percent:.1}%), {:.1}/s, elapsed {}s, ETA ",
+            self.stage,
+            self.n_processed,
+            self.n_total,
+            self.rate(),
+            self.elapsed.as_secs(),
+        )?;
+        match self.eta() {
+            Some(eta) => write!(f, "{}s", eta.as_secs()),
+            None => write!(f, "unknown"),
+        }
+    }
+}
+
+type Callback = dyn Fn(&ProgressUpdate) + Send + Sync;
+
+#[derive(Debug)]
+struct State {
+    stage: String,
+    n_processed: usize,
+    n_total: usize,
+    stage_start: Instant,
+    last_update: Instant,
+}
+
+/// A progress reporter, which can be cloned and shared between threads. All clones count towards
+/// the same stage.
+#[derive(Clone)]
+pub struct Progress {
+    interval: Duration,
+    callback: Arc<Callback>,
+    state: Arc<Mutex<State>>,
+}
+
+impl Default for Progress {
+    fn default() -> Self {
+        let now = Instant::now();
+        Self {
+            interval: DEFAULT_INTERVAL,
+            callback: Arc::new(|update: &ProgressUpdate| println!("{update}")),
+            state: Arc::new(Mutex::new(State {
+                stage: String::new(),
+                n_processed: 0,
+                n_total: 0,
+                stage_start: now,
+                last_update: now,
+            })),
+        }
+    }
+}
+
+impl fmt::Debug for Progress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Progress")
+            .field("interval", &self.interval)
+            .field("state", &self.state)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Progress {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The minimum time between two updates of the same stage. The last update of each stage is
+    /// always reported, however short the stage.
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Call `callback` with each update, instead of printing updates to stdout.
+    pub fn with_callback(
+        mut self,
+        callback: impl Fn(&ProgressUpdate) + Send + Sync + 'static,
+    ) -> Self {
+        self.callback = Arc::new(callback);
+        self
+    }
+
+    /// Start a new stage, which will process `n_total` items.
+    pub fn start_stage(&self, stage: &str, n_total: usize) {
+        let now = Instant::now();
+        let mut state = self.state.lock().unwrap();
+        *state = State {
+            stage: stage.to_string(),
+            n_processed: 0,
+            n_total,
+            stage_start: now,
+            last_update: now,
+        };
+    }
+
+    /// Record that `n` more items of the current stage have been processed. Calls the callback
+    /// if `interval` has elapsed since the last update, or if the stage has just finished.
+    pub fn inc(&self, n: usize) {
+        let update = {
+            let mut state = self.state.lock().unwrap();
+            state.n_processed += n;
+            let now = Instant::now();
+            let is_finished = state.n_processed >= state.n_total;
+            if !is_finished && now.duration_since(state.last_update) < self.interval {
+                return;
+            }
+            state.last_update = now;
+            snapshot(&state, now)
+        };
+        // Don't hold the lock whilst calling the callback.
+        #[cfg(feature = "tracing")]
+        tracing::info!(
+            stage = update.stage,
+            n_processed = update.n_processed,
+            n_total = update.n_total,
+            "Progress"
+        );
+        (self.callback)(&update);
+    }
+
+    /// The progress of the current stage.
+    pub fn update(&self) -> ProgressUpdate {
+        snapshot(&self.state.lock().unwrap(), Instant::now())
+    }
+}
+
+fn snapshot(state: &State, now: Instant) -> ProgressUpdate {
+    ProgressUpdate {
+        stage: state.stage.clone(),
+        n_processed: state.n_processed,
+        n_total: state.n_total,
+        elapsed: now.duration_since(state.stage_start),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_progress_update() {
+        let update = ProgressUpdate {
+            stage: String::from("idx files"),
+            n_processed: 10,
+            n_total: 40,
+            elapsed: Duration::from_secs(5),
+        };
+        assert_eq!(update.rate(), 2.0);
+        assert_eq!(update.eta(), Some(Duration::from_secs(15)));
+        assert!(!update.is_finished());
+        assert_eq!(
+            update.to_string(),
+            "idx files: 10/40 (25.0%), 2.0/s, elapsed 5s, ETA 15s"
+        );
+        let not_started = ProgressUpdate {
+            n_processed: 0,
+            ..update
+        };
+        assert_eq!(not_started.eta(), None);
+    }
+
+    #[test]
+    fn test_interval() {
+        let updates = Arc::new(Mutex::new(Vec::new()));
+        let updates_clone = updates.clone();
+        let progress = Progress::new()
+            .with_interval(Duration::from_secs(3600))
+            .with_callback(move |update| updates_clone.lock().unwrap().push(update.clone()));
+        progress.start_stage("prefixes", 3);
+        progress.inc(1);
+        progress.inc(1);
+        // Only the last update is reported, because the interval hasn't elapsed.
+        assert!(updates.lock().unwrap().is_empty());
+        progress.inc(1);
+        let updates = updates.lock().unwrap();
+        assert_eq!(updates.len(), 1);
+        assert_eq!((updates[0].n_processed, updates[0].n_total), (3, 3));
+    }
+}
@@ -0,0 +1,86 @@
+//! Optionally check that the parameter abbreviation and the vertical level of every `.idx` record
+//! can be decoded. By default, an abbreviation which isn't in the GRIB tables is kept as-is, and a
+//! vertical level which `Level` can't represent becomes `Level::Other`. That's usually what we
+//! want, but when validating a dataset (e.g. in CI) we want to know about gaps in the tables
+//! straight away.
+
+use anyhow::Context;
+use grib_tables::{DecodePolicy, ParameterDatabase};
+use hypergrib_idx_parser::{IdxRecord, Level};
+use object_store::path::Path;
+
+/// Stop indexing with an error if any `.idx` record has a parameter abbreviation or a vertical
+/// level which can't be decoded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StrictDecoding {
+    originating_center: u16,
+    subcenter: u8,
+}
+
+impl StrictDecoding {
+    /// Abbreviations are decoded using the local table of `originating_center` and `subcenter`,
+    /// and then the master table (see `ParameterDatabase::decode_idx_abbrev_with_fallback`). Use
+    /// `u8::MAX` (the GRIB missing value) for the subcenter if the center only has one local
+    /// table.
+    pub fn new(originating_center: u16, subcenter: u8) -> Self {
+        Self {
+            originating_center,
+            subcenter,
+        }
+    }
+
+    /// Returns an error naming the first record in `path` whose parameter abbreviation or
+    /// vertical level can't be decoded.
+    pub(crate) fn check(&self, path: &Path, records: &[IdxRecord]) -> anyhow::Result<()> {
+        let param_db = ParameterDatabase::shared();
+        for record in records {
+            let context = || format!("msg_id {} in '{path}'", record.msg_id);
+            param_db
+                .decode_idx_abbrev_with_policy(
+                    &record.parameter,
+                    self.originating_center,
+                    self.subcenter,
+                    DecodePolicy::Strict,
+                )
+                .with_context(context)?;
+            if let Level::Other(level) = &record.vertical_level {
+                return Err(anyhow::format_err!("Unknown vertical level '{level}'"))
+                    .with_context(context);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use hypergrib::datasets::gefs::Gefs;
+    use hypergrib_idx_parser::parse_idx;
+
+    use super::*;
+
+    #[test]
+    fn test_check() -> anyhow::Result<()> {
+        let path = Path::from("gefs.20170101/00/gec00.t00z.pgrb2aanl.idx");
+        let strict = StrictDecoding::new(Gefs::ORIGINATING_CENTER, u8::MAX);
+        let records = parse_idx(
+            b"1:0:d=2017010100:HGT:10 mb:anl:ENS=low-res ctl\n\
+              2:50487:d=2017010100:TMP:2 m above ground:anl:ENS=low-res ctl\n",
+        )?;
+        strict.check(&path, &records)?;
+
+        let records = parse_idx(b"1:0:d=2017010100:NOT_AN_ABBREV:10 mb:anl:ENS=low-res ctl\n")?;
+        let error = format!("{:#}", strict.check(&path, &records).unwrap_err());
+        assert!(error.contains("'NOT_AN_ABBREV'"), "{error}");
+        assert!(error.contains(path.as_ref()), "{error}");
+
+        let records = parse_idx(b"1:0:d=2017010100:HGT:not a level:anl:ENS=low-res ctl\n")?;
+        let error = format!("{:#}", strict.check(&path, &records).unwrap_err());
+        assert!(
+            error.contains("Unknown vertical level 'not a level'"),
+            "{error}"
+        );
+        assert!(error.contains(path.as_ref()), "{error}");
+        Ok(())
+    }
+}
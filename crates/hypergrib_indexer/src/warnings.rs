@@ -0,0 +1,153 @@
+//! Collect non-fatal problems found during indexing.
+//!
+//! Indexing a large dataset can take hours, so problems which don't invalidate the whole index
+//! (e.g. a single partially-uploaded `.idx` file) shouldn't stop indexing. Instead, each problem
+//! is recorded as a [`Warning`] (with enough context to act on), and the warnings are returned
+//! alongside the `CoordLabels`.
+
+use std::{
+    fmt,
+    sync::{Arc, Mutex},
+};
+
+use chrono::{DateTime, Utc};
+use object_store::path::Path;
+
+use crate::progress::Progress;
+
+/// A non-fatal problem found during indexing.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Warning {
+    /// Two different paths describe the same reference datetime. The reference datetime is only
+    /// indexed once.
+    DuplicateReferenceDatetime {
+        path: Path,
+        reference_datetime: DateTime<Utc>,
+    },
+    /// An `.idx` file doesn't contain the expected number of messages (see
+    /// `ExpectedMsgCount`). The file may be a partial upload.
+    UnexpectedMsgCount {
+        path: Path,
+        n_messages: usize,
+        expected: usize,
+        tolerance: usize,
+    },
+    /// Records with an empty parameter or vertical level were skipped.
+    RecordsWithEmptyFields { path: Path, msg_ids: Vec<u32> },
+    /// Indexing was cancelled part way through `stage`, so the coord labels are incomplete.
+    Cancelled {
+        stage: String,
+        n_processed: usize,
+        n_total: usize,
+    },
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::DuplicateReferenceDatetime {
+                path,
+                reference_datetime,
+            } => write!(
+                f,
+                "Duplicate reference datetime {reference_datetime} at '{path}'. Skipping."
+            ),
+            Self::UnexpectedMsgCount {
+                path,
+                n_messages,
+                expected,
+                tolerance,
+            } => write!(
+                f,
+                "'{path}' contains {n_messages} messages, but expected {expected} ± {tolerance} \
+                 messages. The file may be a partial upload."
+            ),
+            Self::RecordsWithEmptyFields { path, msg_ids } => write!(
+                f,
+                "Skipping {} records with an empty parameter or vertical level in '{path}'. \
+                 msg_ids: {msg_ids:?}",
+                msg_ids.len()
+            ),
+            Self::Cancelled {
+                stage,
+                n_processed,
+                n_total,
+            } => write!(
+                f,
+                "Cancelled after processing {n_processed}/{n_total} {stage}. The coord labels are \
+                 incomplete."
+            ),
+        }
+    }
+}
+
+impl Warning {
+    /// A `Warning::Cancelled` for the current stage of `progress`.
+    pub(crate) fn cancelled(progress: &Progress) -> Self {
+        let update = progress.update();
+        Self::Cancelled {
+            stage: update.stage,
+            n_processed: update.n_processed,
+            n_total: update.n_total,
+        }
+    }
+}
+
+/// A collector of `Warning`s, which can be cloned and shared between threads. All clones push to
+/// the same collection.
+#[derive(Debug, Clone, Default)]
+pub struct Warnings {
+    warnings: Arc<Mutex<Vec<Warning>>>,
+}
+
+impl Warnings {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record `warning`, and also print it to stderr so that long-running jobs show problems as
+    /// they happen.
+    pub fn push(&self, warning: Warning) {
+        eprintln!("WARNING: {warning}");
+        #[cfg(feature = "tracing")]
+        tracing::warn!(%warning);
+        self.warnings.lock().unwrap().push(warning);
+    }
+
+    pub fn len(&self) -> usize {
+        self.warnings.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// A copy of all the warnings recorded so far, in the order they were recorded.
+    pub fn to_vec(&self) -> Vec<Warning> {
+        self.warnings.lock().unwrap().clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clones_share_warnings() {
+        let warnings = Warnings::new();
+        let clone = warnings.clone();
+        let warning = Warning::RecordsWithEmptyFields {
+            path: Path::from("foo.idx"),
+            msg_ids: vec![2],
+        };
+        std::thread::spawn(move || clone.push(warning.clone()))
+            .join()
+            .unwrap();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(
+            warnings.to_vec()[0].to_string(),
+            "Skipping 1 records with an empty parameter or vertical level in 'foo.idx'. \
+             msg_ids: [2]"
+        );
+    }
+}